@@ -0,0 +1,31 @@
+-- name: admin password, browser sessions and access tokens
+
+BEGIN;
+
+-- A single administrator account; the row is created on first-run setup.
+CREATE TABLE IF NOT EXISTS auth_admin (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    password_hash TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+-- Session and token secrets are only stored as BLAKE3 digests.
+CREATE TABLE IF NOT EXISTS auth_sessions (
+    token_hash BLOB PRIMARY KEY,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS auth_sessions_by_expiry
+ON auth_sessions(expires_at);
+
+CREATE TABLE IF NOT EXISTS access_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    scope TEXT NOT NULL CHECK (scope IN ('read-only', 'upload-only', 'admin')),
+    token_hash BLOB NOT NULL UNIQUE,
+    created_at INTEGER NOT NULL,
+    last_used_at INTEGER NULL
+);
+
+COMMIT;
@@ -0,0 +1,24 @@
+-- name: photo metadata from EXIF
+
+BEGIN;
+
+-- One row per content hash, so copies of a photo share their metadata.
+-- captured_at is in milliseconds; when the camera did not record a time
+-- zone the wall-clock time is stored as if it were UTC.
+CREATE TABLE IF NOT EXISTS photo_metadata (
+    hash BLOB PRIMARY KEY REFERENCES file_entries(hash) ON DELETE CASCADE,
+    captured_at INTEGER NULL,
+    camera_make TEXT NULL,
+    camera_model TEXT NULL,
+    lens_model TEXT NULL,
+    width INTEGER NULL,
+    height INTEGER NULL,
+    orientation INTEGER NULL,
+    latitude REAL NULL,
+    longitude REAL NULL,
+    indexed_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS photo_metadata_by_captured_at
+ON photo_metadata(captured_at);
+
+COMMIT;
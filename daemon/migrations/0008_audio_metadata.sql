@@ -0,0 +1,26 @@
+-- name: audio tags from ID3, Vorbis comments and MP4 atoms
+
+BEGIN;
+
+-- One row per content hash; cover_hash is the BLAKE3 hash of the embedded
+-- front cover image, so albums sharing artwork can be grouped.
+CREATE TABLE IF NOT EXISTS audio_metadata (
+    hash BLOB PRIMARY KEY REFERENCES file_entries(hash) ON DELETE CASCADE,
+    title TEXT NULL,
+    artist TEXT NULL,
+    album TEXT NULL,
+    album_artist TEXT NULL,
+    track_number INTEGER NULL,
+    track_total INTEGER NULL,
+    disc_number INTEGER NULL,
+    disc_total INTEGER NULL,
+    year INTEGER NULL,
+    genre TEXT NULL,
+    duration_ms INTEGER NULL,
+    cover_hash BLOB NULL,
+    indexed_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS audio_metadata_by_album
+ON audio_metadata(album_artist, album, disc_number, track_number);
+
+COMMIT;
@@ -0,0 +1,17 @@
+-- name: full-text search over file contents
+
+BEGIN;
+
+-- One document per content hash; its id is the rowid of the FTS5 row.
+CREATE TABLE IF NOT EXISTS content_documents (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    hash BLOB NOT NULL UNIQUE REFERENCES file_entries(hash) ON DELETE CASCADE,
+    indexed_at INTEGER NOT NULL
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS content_search USING fts5(
+    content,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+COMMIT;
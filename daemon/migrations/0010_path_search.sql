@@ -0,0 +1,46 @@
+-- name: filename and path search
+
+BEGIN;
+
+-- Trigram tokens let any fragment of three or more characters match, so a
+-- search for "port" finds "Reports/2024.pdf". file_locations has no stable
+-- rowid of its own, since a VACUUM may renumber it, so each location gets an
+-- explicit id here that its path_search row shares. The triggers below keep
+-- both in step.
+CREATE TABLE IF NOT EXISTS path_search_rows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    node_id BLOB NOT NULL,
+    path TEXT NOT NULL,
+    UNIQUE (node_id, path)
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS path_search USING fts5(
+    path,
+    tokenize = 'trigram'
+);
+
+INSERT INTO path_search_rows (node_id, path) SELECT node_id, path FROM file_locations;
+INSERT INTO path_search (rowid, path) SELECT id, path FROM path_search_rows;
+
+CREATE TRIGGER IF NOT EXISTS path_search_insert AFTER INSERT ON file_locations BEGIN
+    INSERT INTO path_search_rows (node_id, path) VALUES (new.node_id, new.path);
+    INSERT INTO path_search (rowid, path) VALUES (last_insert_rowid(), new.path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS path_search_delete AFTER DELETE ON file_locations BEGIN
+    DELETE FROM path_search WHERE rowid = (
+        SELECT id FROM path_search_rows WHERE node_id = old.node_id AND path = old.path
+    );
+    DELETE FROM path_search_rows WHERE node_id = old.node_id AND path = old.path;
+END;
+
+CREATE TRIGGER IF NOT EXISTS path_search_update AFTER UPDATE OF node_id, path ON file_locations BEGIN
+    DELETE FROM path_search WHERE rowid = (
+        SELECT id FROM path_search_rows WHERE node_id = old.node_id AND path = old.path
+    );
+    DELETE FROM path_search_rows WHERE node_id = old.node_id AND path = old.path;
+    INSERT INTO path_search_rows (node_id, path) VALUES (new.node_id, new.path);
+    INSERT INTO path_search (rowid, path) VALUES (last_insert_rowid(), new.path);
+END;
+
+COMMIT;
@@ -0,0 +1,14 @@
+-- name: sniffed MIME types
+
+BEGIN;
+
+-- mime_type stays the effective type every query filters on. These record
+-- the two opinions it was chosen from so disagreements can be shown.
+ALTER TABLE file_locations ADD COLUMN extension_mime_type TEXT NULL;
+ALTER TABLE file_locations ADD COLUMN sniffed_mime_type TEXT NULL;
+
+-- Until now the type came from the extension alone; files are sniffed the
+-- next time their folder is scanned.
+UPDATE file_locations SET extension_mime_type = mime_type;
+
+COMMIT;
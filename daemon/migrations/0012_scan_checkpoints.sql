@@ -0,0 +1,20 @@
+-- name: resumable scan checkpoints
+
+BEGIN;
+
+-- One row per Scanned folder with a scan in progress. The indexer rewrites
+-- it as batches commit and deletes it when the scan ends, so a row left at
+-- startup marks a scan the daemon was stopped in the middle of.
+CREATE TABLE IF NOT EXISTS scan_checkpoints (
+    scanned_folder_id INTEGER PRIMARY KEY REFERENCES "ScannedFolder"(id) ON DELETE CASCADE,
+    node_id BLOB NOT NULL,
+    scan_id BLOB NOT NULL,
+    started_at INTEGER NOT NULL,
+    -- JSON array of directories still to traverse, in queue order.
+    pending_directories TEXT NOT NULL,
+    directories_scanned INTEGER NOT NULL,
+    files_indexed INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+COMMIT;
@@ -0,0 +1,10 @@
+-- name: scheduled job runs
+
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS scheduled_jobs (
+    job TEXT PRIMARY KEY,
+    last_run_at INTEGER NOT NULL
+);
+
+COMMIT;
@@ -0,0 +1,29 @@
+-- name: content verification scrubbing
+
+BEGIN;
+
+-- When the content was last read back and found to match `hash`. Set when a
+-- file is hashed and by the scrub job; NULL for locations indexed before
+-- scrubbing existed, which are therefore verified first.
+ALTER TABLE file_locations ADD COLUMN verified_at INTEGER NULL;
+CREATE INDEX IF NOT EXISTS file_locations_by_verified_at
+ON file_locations(node_id, verified_at);
+
+-- Locations whose content no longer matches their indexed hash although
+-- their size and timestamps did not change. A row is removed when the file
+-- verifies again or is legitimately modified.
+CREATE TABLE IF NOT EXISTS scrub_alerts (
+    node_id BLOB NOT NULL,
+    path TEXT NOT NULL,
+    expected_hash BLOB NOT NULL,
+    -- NULL when the file could not be read back at all.
+    actual_hash BLOB NULL,
+    error TEXT NULL,
+    size INTEGER NOT NULL,
+    modified_at INTEGER NULL,
+    detected_at INTEGER NOT NULL,
+    PRIMARY KEY (node_id, path),
+    FOREIGN KEY (node_id, path) REFERENCES file_locations(node_id, path) ON DELETE CASCADE
+);
+
+COMMIT;
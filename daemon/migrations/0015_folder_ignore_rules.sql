@@ -0,0 +1,10 @@
+-- name: per-folder include and exclude globs
+
+BEGIN;
+
+-- JSON arrays of gitignore-style globs. Includes bring back paths the
+-- built-in rules skip, such as hidden directories; excludes leave paths out.
+ALTER TABLE "ScannedFolder" ADD COLUMN include_globs TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE "ScannedFolder" ADD COLUMN exclude_globs TEXT NOT NULL DEFAULT '[]';
+
+COMMIT;
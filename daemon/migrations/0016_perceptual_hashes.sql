@@ -0,0 +1,13 @@
+-- name: perceptual hashes for similar images
+
+BEGIN;
+
+-- One row per content hash. dhash is the 64-bit difference hash stored as
+-- a signed integer; images a few bits apart look alike.
+CREATE TABLE IF NOT EXISTS perceptual_hashes (
+    hash BLOB PRIMARY KEY REFERENCES file_entries(hash) ON DELETE CASCADE,
+    dhash INTEGER NOT NULL,
+    indexed_at INTEGER NOT NULL
+);
+
+COMMIT;
@@ -0,0 +1,57 @@
+-- name: peer devices and index change feed
+
+BEGIN;
+
+-- Other PuppyDrive daemons this one pulls the index from. Their locations
+-- are stored in file_locations under their own node_id. access_token is the
+-- token the peer issued to this daemon, sent as a bearer header on every
+-- pull, so it has to stay recoverable rather than hashed. Tokens peers
+-- present to this daemon are ordinary access_tokens rows and only their
+-- hash is stored.
+CREATE TABLE IF NOT EXISTS peers (
+    node_id BLOB PRIMARY KEY REFERENCES nodes(node_id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    access_token TEXT NOT NULL,
+    last_sequence INTEGER NOT NULL DEFAULT 0,
+    last_synced_at INTEGER NULL,
+    last_error TEXT NULL,
+    paired_at INTEGER NOT NULL
+);
+
+-- Feed of local paths whose indexed state changed, served to peers. Each
+-- path keeps only its latest change; a path without a local location was
+-- removed. Triggers delete and re-insert rather than INSERT OR REPLACE,
+-- because an upsert into file_locations overrides a trigger's OR clause.
+CREATE TABLE IF NOT EXISTS index_changes (
+    sequence INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL UNIQUE
+);
+
+INSERT OR IGNORE INTO index_changes (path)
+SELECT location.path FROM file_locations location
+JOIN nodes node ON node.node_id = location.node_id AND node.is_local = 1
+ORDER BY location.path;
+
+CREATE TRIGGER IF NOT EXISTS index_changes_insert AFTER INSERT ON file_locations
+WHEN new.node_id IN (SELECT node_id FROM nodes WHERE is_local = 1) BEGIN
+    DELETE FROM index_changes WHERE path = new.path;
+    INSERT INTO index_changes (path) VALUES (new.path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS index_changes_update AFTER UPDATE ON file_locations
+WHEN new.node_id IN (SELECT node_id FROM nodes WHERE is_local = 1)
+  AND (old.path != new.path OR old.hash IS NOT new.hash OR old.size != new.size
+       OR old.mime_type IS NOT new.mime_type OR old.modified_at IS NOT new.modified_at) BEGIN
+    DELETE FROM index_changes WHERE path = old.path;
+    INSERT INTO index_changes (path) VALUES (old.path);
+    DELETE FROM index_changes WHERE path = new.path;
+    INSERT INTO index_changes (path) VALUES (new.path);
+END;
+
+CREATE TRIGGER IF NOT EXISTS index_changes_delete AFTER DELETE ON file_locations
+WHEN old.node_id IN (SELECT node_id FROM nodes WHERE is_local = 1) BEGIN
+    DELETE FROM index_changes WHERE path = old.path;
+    INSERT INTO index_changes (path) VALUES (old.path);
+END;
+
+COMMIT;
@@ -1,32 +1,53 @@
-#[cfg(test)]
-use std::collections::VecDeque;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::io::{Cursor, Read, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::net::SocketAddr;
 use std::path::{Component, Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
-use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher, event::ModifyKind};
+use notify::{
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{ModifyKind, RenameMode},
+};
 use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use secrecy::{ExposeSecret, SecretString};
 use wgui::{
     ClientEvent, HttpResponse, Item, StaticAsset, Wgui, button, checkbox, custom_component, hstack,
     link, modal, option, select, slider, text, text_input, vstack,
 };
 
-use crate::config::{self, AppConfig, InboxConfig};
+pub(crate) mod api;
+
+use crate::auth::{self, AccessScope, Auth, ClientSession, SetupError};
+use crate::backup::{
+    BackupEvent, BackupRequest, BackupStore, BackupWorker, ConflictPolicy, Manifest, PruneReport,
+    PruneRequest, RestoreRequest, RestoreSummary, SnapshotSummary, StoreKeys,
+};
+use crate::config::{self, AppConfig, BackupSchedule, InboxConfig, RetentionConfig};
 #[cfg(test)]
 use crate::database::MediaIndexObservation;
 use crate::database::{
-    Database, IndexedFile, IndexedMediaFile, LocalSourceConfig, MediaScanPath, ScanHistoryEntry,
-    ScanOutcome, ScanTrigger, Source, VirtualDirectory, VirtualDirectoryEntry, local_source_path,
-    validate_source_config,
+    AccessTokenRecord, AudioMetadata, ContentSearchHit, Database, DuplicateGroup, FileSearchHit,
+    IndexedFile, IndexedMediaFile, LocalSourceConfig, MediaScanPath, Peer, PhotoMetadata,
+    ScanHistoryEntry, ScanOutcome, ScanTrigger, ScrubAlert, ScrubSummary, SftpAuth,
+    SftpSourceConfig, SimilarImage, Source, VirtualDirectory, VirtualDirectoryEntry,
+    local_source_path, sftp_source_config, validate_source_config,
 };
-use crate::indexer::{IndexerEvent, IndexerWorker};
+use crate::file_query::FileQuery;
+use crate::ignore_rules::{self, IgnoreRules};
+use crate::indexer::{self, DuplicateAction, IndexerEvent, IndexerWorker};
 use crate::managed_folder::ManagedFolder;
+use crate::peers::{self, FetchPolicy, PeerEvent};
+use crate::scheduler::{ConditionProbe, ScheduledJob, Scheduler, SystemConditions};
 use crate::session_secrets::SessionSecretStore;
+use crate::sftp::{
+    self, IndexOptions, PreviewCache, RemoteEntry, RemoteFiles, SftpConnection, SftpCredential,
+    SourceEvent, SourceNode,
+};
+use crate::upload_sessions::{UploadError, UploadStaging};
 
 const THIS_COMPUTER_SOURCE_ID: u32 = 20;
 const LOCAL_PARENT_ID: u32 = 21;
@@ -117,10 +138,87 @@ const SHOW_PICKER_NEW_FOLDER_ID: u32 = 113;
 const AUDIO_PREVIOUS_PAGE_ID: u32 = 114;
 const AUDIO_NEXT_PAGE_ID: u32 = 115;
 const AUDIO_SCANNED_FOLDER_FILTER_ID: u32 = 116;
+const AUTH_TICKET_ID: u32 = 117;
+const SIGN_OUT_ID: u32 = 118;
+const ACCESS_TOKEN_NAME_INPUT_ID: u32 = 119;
+const ACCESS_TOKEN_SCOPE_ID: u32 = 120;
+const CREATE_ACCESS_TOKEN_ID: u32 = 121;
+const REVOKE_ACCESS_TOKEN_ID: u32 = 122;
+const DISMISS_ACCESS_TOKEN_ID: u32 = 123;
+const TOGGLE_FOLDER_INDEXER_ID: u32 = 124;
+const MEDIA_SORT_ID: u32 = 125;
+const MEDIA_CAMERA_FILTER_ID: u32 = 126;
+const MEDIA_PHOTO_FILTER_ID: u32 = 127;
+const AUDIO_VIEW_ID: u32 = 128;
+const OPEN_AUDIO_ALBUM_ID: u32 = 129;
+const OPEN_AUDIO_ARTIST_ID: u32 = 130;
+const CLOSE_AUDIO_GROUP_ID: u32 = 131;
+const FILES_CONTENT_SEARCH_ID: u32 = 132;
+const CLEAR_FILES_CONTENT_SEARCH_ID: u32 = 133;
+const OPEN_CONTENT_SEARCH_HIT_ID: u32 = 134;
+const SIDEBAR_SEARCH_ID: u32 = 135;
+const CLEAR_SIDEBAR_SEARCH_ID: u32 = 136;
+const FILE_SEARCH_VIEW_ID: u32 = 137;
+const BACKUP_SCHEDULE_ID: u32 = 138;
+const METERED_CONNECTIONS_ID: u32 = 139;
+const PAUSE_ON_BATTERY_ID: u32 = 140;
+const VERIFY_FILES_NOW_ID: u32 = 141;
+const FOLDER_INCLUDE_GLOBS_INPUT_ID: u32 = 142;
+const FOLDER_EXCLUDE_GLOBS_INPUT_ID: u32 = 143;
+const SAVE_FOLDER_IGNORE_RULES_ID: u32 = 144;
+const DUPLICATE_SORT_ID: u32 = 145;
+const TRASH_DUPLICATES_ID: u32 = 146;
+const HARDLINK_DUPLICATES_ID: u32 = 147;
+const SIMILAR_IMAGE_DISTANCE_ID: u32 = 148;
+const KEEP_SIMILAR_IMAGE_ID: u32 = 149;
+const PEER_URL_INPUT_ID: u32 = 150;
+const PEER_TOKEN_INPUT_ID: u32 = 151;
+const PAIR_PEER_ID: u32 = 152;
+const SYNC_PEER_ID: u32 = 153;
+const REMOVE_PEER_ID: u32 = 154;
+const BACKUP_DESTINATION_INPUT_ID: u32 = 155;
+const SAVE_BACKUP_DESTINATION_ID: u32 = 156;
+const BACK_UP_NOW_ID: u32 = 157;
+const OPEN_SNAPSHOT_ID: u32 = 158;
+const OPEN_SNAPSHOT_ENTRY_ID: u32 = 159;
+const SNAPSHOT_UP_ID: u32 = 160;
+const CLOSE_SNAPSHOT_ID: u32 = 161;
+const RESTORE_TARGET_INPUT_ID: u32 = 162;
+const RESTORE_CONFLICTS_ID: u32 = 163;
+const RESTORE_SNAPSHOT_ENTRY_ID: u32 = 164;
+const RESTORE_SNAPSHOT_DIRECTORY_ID: u32 = 165;
+const PREVIEW_PRUNE_ID: u32 = 166;
+const PRUNE_NOW_ID: u32 = 167;
+const BACKUP_PASSPHRASE_INPUT_ID: u32 = 168;
+const UNLOCK_BACKUP_ID: u32 = 169;
+const ROTATE_BACKUP_KEY_ID: u32 = 170;
+const LOCK_BACKUP_ID: u32 = 171;
+const SOURCE_SECRET_INPUT_ID: u32 = 172;
+const SOURCE_KEY_PASSPHRASE_INPUT_ID: u32 = 173;
+const CONNECT_SOURCE_ID: u32 = 174;
+const DISCONNECT_SOURCE_ID: u32 = 175;
+const REFRESH_SOURCE_ID: u32 = 176;
+const INDEX_SOURCE_ID: u32 = 177;
+const ADD_SOURCE_TYPE_ID: u32 = 178;
+const ADD_SOURCE_HOST_INPUT_ID: u32 = 179;
+const ADD_SOURCE_PORT_INPUT_ID: u32 = 180;
+const ADD_SOURCE_USER_INPUT_ID: u32 = 181;
+const ADD_SOURCE_AUTH_ID: u32 = 182;
+const SCHEDULER_TICK: Duration = Duration::from_secs(15);
 const MAX_FILE_PREVIEW_BYTES: u64 = 1_048_576;
 const MAX_HEX_PREVIEW_BYTES: usize = 65_536;
 const MAX_UPLOAD_BYTES: usize = 1_073_741_824;
+const MAX_UPLOAD_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+const UPLOAD_SESSION_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const FAILED_LOGIN_DELAY: Duration = Duration::from_secs(1);
 const FILES_PAGE_SIZE: usize = 100;
+const CONTENT_SEARCH_LIMIT: usize = 50;
+const DUPLICATE_GROUPS_SHOWN: usize = 200;
+const SNAPSHOT_ENTRIES_SHOWN: usize = 500;
+const SIMILAR_IMAGE_GROUPS_SHOWN: usize = 50;
+const FILE_SEARCH_LIMIT: usize = 500;
+/// Shorter queries match too many prefixes to be useful while typing.
+const CONTENT_SEARCH_MIN_CHARS: usize = 2;
 const APP_CSS: &str = r#"
 html,
 body {
@@ -211,7 +309,11 @@ pub struct App {
     wgui: Wgui,
     bind_addr: SocketAddr,
     client_ids: HashSet<usize>,
+    /// Signed-in wgui clients and the browser session each signed in with.
+    authenticated_clients: HashMap<usize, ClientSession>,
+    signing_out_clients: HashSet<usize>,
     database: Database,
+    auth: Arc<Auth>,
     config: AppConfig,
     config_path: PathBuf,
     configured_this_computer_root: PathBuf,
@@ -228,6 +330,7 @@ pub struct App {
     file_table_asset: StaticAsset,
     mobile_nav_asset: StaticAsset,
     upload_asset: StaticAsset,
+    auth_gate_asset: StaticAsset,
     media_entries: Vec<LocalEntry>,
     media_index_entries: Vec<IndexedMediaFile>,
     audio_entries: Vec<LocalEntry>,
@@ -244,13 +347,64 @@ pub struct App {
     local_node_id: Vec<u8>,
     indexer: IndexerWorker,
     indexer_events: tokio::sync::mpsc::Receiver<IndexerEvent>,
+    api_requests: tokio::sync::mpsc::Receiver<api::ApiRequest>,
+    peer_events: tokio::sync::mpsc::Receiver<PeerEvent>,
+    peer_event_tx: tokio::sync::mpsc::Sender<PeerEvent>,
+    peers: Vec<Peer>,
+    /// Devices with a pairing or sync run in flight, by node id.
+    syncing_peers: HashSet<Vec<u8>>,
+    pairing_peer: bool,
+    new_peer_url: String,
+    new_peer_token: String,
+    peer_message: Option<String>,
+    peer_cache_dir: PathBuf,
+    peer_fetch: FetchPolicy,
+    /// File shown in the viewer while its content is fetched from a device.
+    fetching_file: Option<FileListingEntry>,
+    backup: BackupWorker,
+    backup_events: tokio::sync::mpsc::Receiver<BackupEvent>,
+    backup_running: bool,
+    /// Files done, files in total and bytes written by the running backup.
+    backup_progress: Option<(usize, usize, u64)>,
+    backup_destination: String,
+    backup_message: Option<String>,
+    /// Keys of the backup store, held only for this session.
+    backup_keys: Option<Arc<StoreKeys>>,
+    /// Passphrase typed on the Backups page, to unlock with or rotate to.
+    backup_passphrase: SecretString,
+    backup_unlocking: bool,
+    backup_key_rotating: bool,
+    snapshots: Vec<SnapshotSummary>,
+    open_snapshot: Option<OpenSnapshot>,
+    /// Folder restores go below; empty restores to the original location.
+    restore_target: String,
+    restore_conflicts: ConflictPolicy,
+    restore_running: bool,
+    /// Files done and files in total while a restore runs.
+    restore_progress: Option<(usize, usize)>,
+    restore_message: Option<String>,
+    last_restore: Option<RestoreSummary>,
+    prune_running: bool,
+    prune_message: Option<String>,
+    last_prune: Option<PruneReport>,
     index_status: HashMap<u32, FolderIndexStatus>,
     last_index_progress_render: Option<Instant>,
+    scheduler: Scheduler,
+    condition_probe: Box<dyn ConditionProbe>,
+    scrub_alerts: Vec<ScrubAlert>,
+    scrub_summary: ScrubSummary,
+    duplicate_groups: Vec<DuplicateGroup>,
+    duplicate_sort: String,
+    duplicate_message: Option<String>,
+    similar_images: Vec<Vec<SimilarImage>>,
+    similar_image_distance: String,
     selected_scanned_folder_id: Option<u32>,
     selected_scanned_folder_history: Vec<ScanHistoryEntry>,
+    folder_include_globs: String,
+    folder_exclude_globs: String,
     media_watcher: RecommendedWatcher,
     watched_media_paths: Vec<PathBuf>,
-    media_change_rx: tokio::sync::mpsc::Receiver<u32>,
+    media_change_rx: tokio::sync::mpsc::Receiver<WatchedChange>,
     new_media_path: String,
     media_path_error: Option<String>,
     new_inbox_name: String,
@@ -271,13 +425,22 @@ pub struct App {
     media_sort_descending: bool,
     media_page: usize,
     media_scanned_folder_filter: String,
+    media_camera_filter: String,
+    media_photo_filter: String,
     audio_page: usize,
     audio_scanned_folder_filter: String,
+    audio_view: String,
+    audio_group: Option<AudioGroup>,
     files_view_mode: String,
     files_mime_filter: String,
     files_scanned_folder_filter: String,
     files_sort: String,
     files_page: usize,
+    files_content_query: String,
+    files_content_hits: Vec<ContentSearchHit>,
+    file_search_query: String,
+    file_search_hits: Vec<FileSearchHit>,
+    file_search_error: Option<String>,
     scan_ignored_directories: String,
     scan_max_file_size_mb: String,
     scan_max_items: String,
@@ -301,9 +464,35 @@ pub struct App {
     show_add_source: bool,
     new_source_name: String,
     new_source_path: String,
+    /// `local` or `sftp`; for SFTP the path field holds the remote root.
+    new_source_type: String,
+    new_source_host: String,
+    new_source_port: String,
+    new_source_user: String,
+    new_source_auth: SftpAuth,
+    new_source_error: Option<String>,
     sources: Vec<Source>,
     active_source_id: Option<u32>,
-    _session_secrets: SessionSecretStore,
+    source_events: tokio::sync::mpsc::Receiver<SourceEvent>,
+    source_event_tx: tokio::sync::mpsc::Sender<SourceEvent>,
+    /// Open SFTP connections, by source key.
+    source_connections: HashMap<String, sftp::Connection>,
+    connecting_sources: HashSet<String>,
+    /// Sources being indexed, with the flag that stops their index.
+    indexing_sources: HashMap<String, Arc<AtomicBool>>,
+    /// Listings of the open SFTP source's expanded folders; `None` while the
+    /// listing is in flight.
+    remote_listings: HashMap<PathBuf, Option<Vec<LocalEntry>>>,
+    source_secret: SecretString,
+    source_key_passphrase: SecretString,
+    /// Last connection or indexing outcome of each SFTP source, by source key.
+    source_messages: HashMap<String, String>,
+    access_tokens: Vec<AccessTokenRecord>,
+    new_access_token_name: String,
+    new_access_token_scope: AccessScope,
+    created_access_token: Option<String>,
+    access_token_error: Option<String>,
+    session_secrets: SessionSecretStore,
     viewing_this_computer: bool,
     active_page: AppPage,
 }
@@ -333,6 +522,11 @@ struct FileListingEntry {
     hash: Option<Vec<u8>>,
     replica_count: usize,
     media_root_id: Option<u32>,
+    photo: Option<PhotoMetadata>,
+    audio: Option<AudioMetadata>,
+    /// Set for files found on another node, whose content is fetched from a
+    /// paired device when opened.
+    remote_node: Option<String>,
 }
 
 impl FileListingEntry {
@@ -346,6 +540,9 @@ impl FileListingEntry {
             hash: indexed.hash.clone(),
             replica_count: indexed.replica_count,
             media_root_id: entry.media_root_id,
+            photo: indexed.photo.clone(),
+            audio: indexed.audio.clone(),
+            remote_node: None,
         }
     }
 
@@ -366,6 +563,9 @@ impl FileListingEntry {
             hash: Some(entry.hash.clone()),
             replica_count: entry.replica_count,
             media_root_id: entry.scanned_folder_id,
+            photo: None,
+            audio: None,
+            remote_node: None,
         }
     }
 
@@ -382,19 +582,72 @@ impl FileListingEntry {
             hash: entry.hash.clone(),
             replica_count: entry.replica_count,
             media_root_id: entry.scanned_folder_id,
+            photo: None,
+            audio: None,
+            remote_node: None,
+        }
+    }
+
+    fn from_content_hit(hit: &ContentSearchHit) -> Self {
+        Self {
+            path: Some(hit.path.clone()),
+            name: hit.path.file_name().map_or_else(
+                || hit.path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            size: hit.size,
+            mime_type: hit.mime_type.clone(),
+            modified_at: hit.modified_at.and_then(system_time_from_millis),
+            hash: Some(hit.hash.clone()),
+            replica_count: hit.replica_count,
+            media_root_id: hit.scanned_folder_id,
+            photo: None,
+            audio: None,
+            remote_node: None,
+        }
+    }
+
+    fn from_file_search_hit(hit: &FileSearchHit) -> Self {
+        Self {
+            path: Some(hit.path.clone()),
+            name: hit.path.file_name().map_or_else(
+                || hit.path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            size: hit.size,
+            mime_type: hit.mime_type.clone(),
+            modified_at: hit.modified_at.and_then(system_time_from_millis),
+            hash: hit.hash.clone(),
+            replica_count: hit.replica_count,
+            media_root_id: hit.scanned_folder_id,
+            photo: None,
+            audio: None,
+            remote_node: (!hit.is_local).then(|| hit.node_name.clone()),
         }
     }
 
+    fn captured_at(&self) -> Option<i64> {
+        self.photo.as_ref().and_then(|photo| photo.captured_at)
+    }
+
     fn is_image(&self) -> bool {
-        self.path
+        self.mime_type
             .as_deref()
-            .is_some_and(|path| image_content_type(path).is_some())
+            .is_some_and(|mime_type| mime_type.starts_with("image/"))
+            || self
+                .path
+                .as_deref()
+                .is_some_and(|path| image_content_type(path).is_some())
     }
 
     fn is_video(&self) -> bool {
-        self.path
+        self.mime_type
             .as_deref()
-            .is_some_and(|path| video_content_type(path).is_some())
+            .is_some_and(|mime_type| mime_type.starts_with("video/"))
+            || self
+                .path
+                .as_deref()
+                .is_some_and(|path| video_content_type(path).is_some())
     }
 
     fn file_type(&self) -> &str {
@@ -409,6 +662,9 @@ impl FileListingEntry {
     }
 
     fn as_local_entry(&self) -> Option<LocalEntry> {
+        if self.remote_node.is_some() {
+            return None;
+        }
         let path = self.path.clone()?;
         Some(LocalEntry {
             name: self.name.clone(),
@@ -476,6 +732,8 @@ struct ImageFile {
 
 enum FileViewerEntries {
     Local(Vec<LocalEntry>),
+    /// Files listed from the open SFTP source, downloaded when shown.
+    Remote(Vec<LocalEntry>),
     Indexed,
     Media(Vec<usize>),
 }
@@ -483,7 +741,7 @@ enum FileViewerEntries {
 impl FileViewerEntries {
     fn len(&self, indexed_entries: &[FileListingEntry]) -> usize {
         match self {
-            Self::Local(entries) => entries.len(),
+            Self::Local(entries) | Self::Remote(entries) => entries.len(),
             Self::Indexed => indexed_entries.len(),
             Self::Media(indices) => indices.len(),
         }
@@ -513,6 +771,15 @@ struct FolderContext {
     path: PathBuf,
 }
 
+/// A snapshot open in the browser on the Backups page.
+struct OpenSnapshot {
+    manifest: Manifest,
+    /// Index into `manifest.folders`, or `None` while listing the folders.
+    folder: Option<usize>,
+    /// Relative to the folder root.
+    directory: PathBuf,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum AppPage {
     Files,
@@ -521,6 +788,9 @@ enum AppPage {
     ScannedFolder,
     VirtualDirectories,
     Transfers,
+    Integrity,
+    Duplicates,
+    Backups,
     Settings,
 }
 
@@ -531,6 +801,35 @@ enum MediaSortKey {
     Size,
     Replicas,
     Modified,
+    Captured,
+    Camera,
+    Lens,
+    Resolution,
+    Title,
+    Artist,
+    Album,
+    Duration,
+}
+
+/// An album or artist opened from the Audio page's grouped views.
+#[derive(Clone, PartialEq, Eq)]
+enum AudioGroup {
+    Album { artist: String, album: String },
+    Artist(String),
+}
+
+struct AudioAlbumSummary {
+    artist: String,
+    album: String,
+    year: Option<i32>,
+    tracks: usize,
+    duration_ms: i64,
+}
+
+struct AudioArtistSummary {
+    artist: String,
+    albums: usize,
+    tracks: usize,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -568,6 +867,21 @@ impl App {
         })?;
 
         let database = Database::open(&paths.database_file)?;
+        let auth = Arc::new(Auth::new(
+            database.auth_store(),
+            !bind_addr.ip().is_loopback(),
+        ));
+        if !auth.is_configured()? {
+            match auth.setup_code() {
+                Some(code) => log::warn!(
+                    "no admin password is set; open http://{bind_addr} and enter setup code {code}"
+                ),
+                None => {
+                    log::info!("no admin password is set; open http://{bind_addr} to choose one")
+                }
+            }
+        }
+        let access_tokens = auth.access_tokens()?;
         let existing_media_paths = database.media_scan_paths()?;
         if !config.media.paths_initialized {
             if existing_media_paths.is_empty() {
@@ -583,6 +897,17 @@ impl App {
         let virtual_directories = database.virtual_directories()?;
         let virtual_directory_entries = database.virtual_directory_entries(&local_node_id)?;
         let (indexer_event_tx, indexer_events) = tokio::sync::mpsc::channel(256);
+        let (peer_event_tx, peer_events) = tokio::sync::mpsc::channel(32);
+        let (source_event_tx, source_events) = tokio::sync::mpsc::channel(32);
+        let (backup_event_tx, backup_events) = tokio::sync::mpsc::channel(32);
+        let backup = BackupWorker::start(database.path().to_path_buf(), backup_event_tx);
+        let backup_destination = config
+            .backup
+            .destination
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        let peers = database.peers()?;
         let indexer = IndexerWorker::start(database.path().to_path_buf(), indexer_event_tx);
         let managed_folders = managed_folders(&media_paths);
         let sources = database.sources()?;
@@ -626,6 +951,10 @@ impl App {
             "/file-upload.js",
             concat!(env!("CARGO_MANIFEST_DIR"), "/ui/file-upload.js"),
         );
+        let auth_gate_asset = wgui.mount_static_file(
+            "/auth-gate.js",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/ui/auth-gate.js"),
+        );
         let active_files_root = Arc::new(RwLock::new(this_computer_root.clone()));
         let served_media_paths = Arc::new(RwLock::new(media_paths.clone()));
         let served_managed_folders = Arc::new(RwLock::new(managed_folders.clone()));
@@ -633,11 +962,25 @@ impl App {
         let thumbnail_database_path = database.path().to_path_buf();
         let thumbnail_cache_dir = paths.thumbnail_cache_dir.clone();
         let thumbnail_node_id = local_node_id.clone();
+        let peer_cache_dir = paths.peer_cache_dir.clone();
+        let peer_fetch = FetchPolicy {
+            cache_limit_bytes: config.peers.cache_mb.saturating_mul(1024 * 1024),
+            allow_cleartext: config.peers.allow_cleartext,
+        };
+        let handler_peer_cache_dir = peer_cache_dir.clone();
         let handler_files_root = active_files_root.clone();
         let handler_media_paths = served_media_paths.clone();
         let handler_managed_folders = served_managed_folders.clone();
         let handler_upload_root = this_computer_root.clone();
         let handler_inboxes = served_inboxes.clone();
+        let (api_request_tx, api_requests) = tokio::sync::mpsc::channel(32);
+        let handler_auth = auth.clone();
+        let upload_staging = Arc::new(UploadStaging::new(&paths.upload_staging_dir));
+        match upload_staging.prune(UPLOAD_SESSION_MAX_AGE) {
+            Ok(0) => {}
+            Ok(removed) => log::info!("removed {removed} abandoned upload sessions"),
+            Err(error) => log::warn!("unable to prune upload sessions: {error:#}"),
+        }
         wgui.set_http_handler(move |request| {
             let files_root = handler_files_root.clone();
             let media_paths = handler_media_paths.clone();
@@ -645,9 +988,32 @@ impl App {
             let thumbnail_database_path = thumbnail_database_path.clone();
             let thumbnail_cache_dir = thumbnail_cache_dir.clone();
             let thumbnail_node_id = thumbnail_node_id.clone();
+            let peer_cache_dir = handler_peer_cache_dir.clone();
             let upload_root = handler_upload_root.clone();
             let inboxes = handler_inboxes.clone();
+            let upload_staging = upload_staging.clone();
+            let api_request_tx = api_request_tx.clone();
+            let auth = handler_auth.clone();
             async move {
+                if let Some(action) = request.path.strip_prefix("/auth/") {
+                    let action = action.to_owned();
+                    let response = tokio::task::spawn_blocking(move || {
+                        auth_response(&auth, &request, &action)
+                    })
+                    .await
+                    .ok()?;
+                    if response.status == 401 {
+                        // Slow down password guessing without blocking other requests.
+                        tokio::time::sleep(FAILED_LOGIN_DELAY).await;
+                    }
+                    return Some(response);
+                }
+                if let Some(response) = authorize_request(&auth, &request) {
+                    return Some(response);
+                }
+                if request.path.starts_with(api::API_PREFIX) {
+                    return Some(api::forward(&api_request_tx, request).await);
+                }
                 if request.path == "/favicon.ico" {
                     return Some(
                         HttpResponse::new(200, FAVICON_BYTES.to_vec())
@@ -657,9 +1023,19 @@ impl App {
                 if request.path == "/uploads" {
                     return Some(upload_response(&request, &upload_root, &inboxes));
                 }
+                if let Some(session_path) = upload_session_path(&request.path) {
+                    return Some(upload_session_response(
+                        &request,
+                        session_path,
+                        &upload_root,
+                        &inboxes,
+                        &upload_staging,
+                    ));
+                }
                 if let Some(relative_path) = request.path.strip_prefix("/source-files/") {
                     let root = files_root.read().ok()?.clone();
-                    return local_media_response(relative_path, &root);
+                    return local_media_response(relative_path, &root, &request.headers)
+                        .map(MediaResponse::into_http);
                 }
                 if let Some(thumbnail_request) = request.path.strip_prefix("/media-thumbnails/") {
                     let folders = managed_folders.read().ok()?.clone();
@@ -682,6 +1058,29 @@ impl App {
                     .ok()
                     .flatten();
                 }
+                if let Some(hash) = request.path.strip_prefix("/content/") {
+                    let hash = parse_content_hash(hash)?;
+                    let folders = managed_folders.read().ok()?.clone();
+                    // Peers ask with `local=1` and must not trigger a fetch in turn.
+                    let fetch = request
+                        .query
+                        .get("local")
+                        .is_none_or(|value| value != "1")
+                        .then_some(peer_fetch);
+                    return tokio::task::spawn_blocking(move || {
+                        content_response(
+                            &hash,
+                            &thumbnail_database_path,
+                            &thumbnail_node_id,
+                            &folders,
+                            &peer_cache_dir,
+                            fetch,
+                            &request.headers,
+                        )
+                    })
+                    .await
+                    .ok();
+                }
                 request.path.strip_prefix("/media-files/").and_then(|path| {
                     let (id, relative_path) = path.split_once('/')?;
                     let id = id.parse::<u32>().ok()?;
@@ -691,7 +1090,8 @@ impl App {
                         return None;
                     }
                     let folders = managed_folders.read().ok()?;
-                    managed_media_response(relative_path, folders.get(&id)?)
+                    managed_media_response(relative_path, folders.get(&id)?, &request.headers)
+                        .map(MediaResponse::into_http)
                 })
             }
         });
@@ -713,12 +1113,16 @@ impl App {
             &config.media.ignored_directory_names,
         )?;
         let media_folder_picker_path = media_folder_picker_start_path(&this_computer_root);
+        let scheduler = load_scheduler(&database)?;
 
         let app = Self {
             wgui,
             bind_addr,
             client_ids: HashSet::new(),
+            authenticated_clients: HashMap::new(),
+            signing_out_clients: HashSet::new(),
             database,
+            auth,
             config_path: paths.config_file,
             config,
             configured_this_computer_root: this_computer_root.clone(),
@@ -734,6 +1138,7 @@ impl App {
             file_table_asset,
             mobile_nav_asset,
             upload_asset,
+            auth_gate_asset,
             media_entries: local_entries_from_index(cached_media.clone()),
             media_index_entries: cached_media,
             audio_entries: local_entries_from_index(cached_audio.clone()),
@@ -750,10 +1155,54 @@ impl App {
             local_node_id,
             indexer,
             indexer_events,
+            api_requests,
+            peer_events,
+            peer_event_tx,
+            peers,
+            syncing_peers: HashSet::new(),
+            pairing_peer: false,
+            new_peer_url: String::new(),
+            new_peer_token: String::new(),
+            peer_message: None,
+            peer_cache_dir,
+            peer_fetch,
+            fetching_file: None,
+            backup,
+            backup_events,
+            backup_running: false,
+            backup_progress: None,
+            backup_destination,
+            backup_message: None,
+            backup_keys: None,
+            backup_passphrase: SecretString::default(),
+            backup_unlocking: false,
+            backup_key_rotating: false,
+            snapshots: Vec::new(),
+            open_snapshot: None,
+            restore_target: String::new(),
+            restore_conflicts: ConflictPolicy::Skip,
+            restore_running: false,
+            restore_progress: None,
+            restore_message: None,
+            last_restore: None,
+            prune_running: false,
+            prune_message: None,
+            last_prune: None,
             index_status: HashMap::new(),
             last_index_progress_render: None,
+            scheduler,
+            condition_probe: Box::new(SystemConditions),
+            scrub_alerts: Vec::new(),
+            scrub_summary: ScrubSummary::default(),
+            duplicate_groups: Vec::new(),
+            duplicate_sort: "waste".to_owned(),
+            duplicate_message: None,
+            similar_images: Vec::new(),
+            similar_image_distance: "6".to_owned(),
             selected_scanned_folder_id: None,
             selected_scanned_folder_history: Vec::new(),
+            folder_include_globs: String::new(),
+            folder_exclude_globs: String::new(),
             media_watcher,
             watched_media_paths,
             media_change_rx,
@@ -777,13 +1226,22 @@ impl App {
             media_sort_descending: false,
             media_page: 0,
             media_scanned_folder_filter: "all".to_owned(),
+            media_camera_filter: "all".to_owned(),
+            media_photo_filter: "all".to_owned(),
             audio_page: 0,
             audio_scanned_folder_filter: "all".to_owned(),
+            audio_view: "tracks".to_owned(),
+            audio_group: None,
             files_view_mode: "table".to_owned(),
             files_mime_filter: "all".to_owned(),
             files_scanned_folder_filter: "all".to_owned(),
             files_sort: "modified".to_owned(),
             files_page: 0,
+            files_content_query: String::new(),
+            files_content_hits: Vec::new(),
+            file_search_query: String::new(),
+            file_search_hits: Vec::new(),
+            file_search_error: None,
             scan_ignored_directories,
             scan_max_file_size_mb,
             scan_max_items,
@@ -807,14 +1265,36 @@ impl App {
             show_add_source: false,
             new_source_name: String::new(),
             new_source_path: String::new(),
+            new_source_type: "local".to_owned(),
+            new_source_host: String::new(),
+            new_source_port: "22".to_owned(),
+            new_source_user: String::new(),
+            new_source_auth: SftpAuth::Password,
+            new_source_error: None,
             sources,
             active_source_id: None,
-            _session_secrets: SessionSecretStore::default(),
+            source_events,
+            source_event_tx,
+            source_connections: HashMap::new(),
+            connecting_sources: HashSet::new(),
+            indexing_sources: HashMap::new(),
+            remote_listings: HashMap::new(),
+            source_secret: SecretString::default(),
+            source_key_passphrase: SecretString::default(),
+            source_messages: HashMap::new(),
+            access_tokens,
+            new_access_token_name: String::new(),
+            new_access_token_scope: AccessScope::ReadOnly,
+            created_access_token: None,
+            access_token_error: None,
+            session_secrets: SessionSecretStore::default(),
             viewing_this_computer: true,
             active_page: AppPage::Files,
         };
         let mut app = app;
         app.refresh_filtered_files(true);
+        app.reload_scrub_report();
+        app.resume_interrupted_scans();
         Ok(app)
     }
 
@@ -836,6 +1316,10 @@ impl App {
                     .color("#0f6175"),
             ])
             .padding(6),
+            text_input()
+                .id(SIDEBAR_SEARCH_ID)
+                .svalue(&self.file_search_query)
+                .placeholder("Search all files"),
             nav_link("□  Files", "/", self.active_page == AppPage::Files),
             nav_link("▧  Media", "/media", self.active_page == AppPage::Media),
             nav_link("♫  Audio", "/audio", self.active_page == AppPage::Audio),
@@ -849,6 +1333,21 @@ impl App {
                 "/transfers",
                 self.active_page == AppPage::Transfers,
             ),
+            nav_link(
+                &integrity_nav_label(self.scrub_alerts.len()),
+                "/integrity",
+                self.active_page == AppPage::Integrity,
+            ),
+            nav_link(
+                "⧉  Duplicates",
+                "/duplicates",
+                self.active_page == AppPage::Duplicates,
+            ),
+            nav_link(
+                "⛁  Backups",
+                "/backups",
+                self.active_page == AppPage::Backups,
+            ),
             hstack([
                 text("Sources").grow(1).color("#6b7280"),
                 button("+")
@@ -865,8 +1364,9 @@ impl App {
             ),
         ];
         sidebar_items.extend(self.sources.iter().map(|source| {
-            let available =
-                source.enabled && local_source_path(source).is_some_and(|path| path.is_dir());
+            let available = source.enabled
+                && (self.source_connections.contains_key(&source.source_key)
+                    || local_source_path(source).is_some_and(|path| path.is_dir()));
             source_nav_button(
                 source,
                 self.active_source_id == Some(source.id) && self.active_page == AppPage::Files,
@@ -905,7 +1405,13 @@ impl App {
             .padding_right(6)
             .overflow("hidden");
 
+        let searching = !self.file_search_query.trim().is_empty();
         let workspace = match self.active_page {
+            _ if searching => self
+                .file_search_panel()
+                .grow(1)
+                .padding(6)
+                .overflow("hidden"),
             AppPage::Settings => self.settings_panel().grow(1).padding(6).overflow("auto"),
             AppPage::Media => self.media_panel().grow(1).padding(6).overflow("hidden"),
             AppPage::Audio => self.audio_panel().grow(1).padding(6).overflow("hidden"),
@@ -920,16 +1426,23 @@ impl App {
                 .padding(6)
                 .overflow("hidden"),
             AppPage::Transfers => self.transfers_panel().grow(1).padding(6).overflow("hidden"),
+            AppPage::Integrity => self.integrity_panel().grow(1).padding(6).overflow("auto"),
+            AppPage::Duplicates => self.duplicates_panel().grow(1).padding(6).overflow("auto"),
+            AppPage::Backups => self.backups_panel().grow(1).padding(6).overflow("auto"),
             AppPage::Files => content,
         };
 
         let page_title = match self.active_page {
+            _ if searching => "Search",
             AppPage::Files => "Files",
             AppPage::Media => "Media",
             AppPage::Audio => "Audio",
             AppPage::ScannedFolder => "Scanned folder",
             AppPage::VirtualDirectories => "Virtual directories",
             AppPage::Transfers => "Transfers",
+            AppPage::Integrity => "Integrity",
+            AppPage::Duplicates => "Duplicates",
+            AppPage::Backups => "Backups",
             AppPage::Settings => "Settings",
         };
         let mobile_nav_bar = hstack([
@@ -984,17 +1497,65 @@ impl App {
     }
 
     pub async fn run(&mut self) {
+        let mut scheduler_tick = tokio::time::interval(SCHEDULER_TICK);
+        scheduler_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
         loop {
             let message = tokio::select! {
                 message = self.wgui.next() => message,
+                _ = scheduler_tick.tick() => {
+                    // Idle tabs whose session ended fall back to the sign-in gate.
+                    let clients = self.authenticated_clients.keys().copied().collect::<Vec<_>>();
+                    for client_id in clients {
+                        if !self.client_session_is_active(client_id) {
+                            self.wgui
+                                .render(client_id, self.render_for(client_id))
+                                .await;
+                        }
+                    }
+                    if self.run_scheduled_jobs() {
+                        self.render_all_clients().await;
+                    }
+                    continue;
+                }
                 changed = self.media_change_rx.recv() => {
-                    let Some(folder_id) = changed else {
+                    let Some(change) = changed else {
                         break;
                     };
-                    self.queue_media_index_for(folder_id, ScanTrigger::FilesystemChange);
+                    self.apply_watched_change(change);
                     self.render_all_clients().await;
                     continue;
                 }
+                request = self.api_requests.recv() => {
+                    let Some(request) = request else {
+                        continue;
+                    };
+                    if self.handle_api_request(request).await {
+                        self.render_all_clients().await;
+                    }
+                    continue;
+                }
+                event = self.peer_events.recv() => {
+                    if let Some(event) = event {
+                        self.handle_peer_event(event);
+                        self.render_all_clients().await;
+                    }
+                    continue;
+                }
+                event = self.backup_events.recv() => {
+                    if let Some(event) = event {
+                        self.handle_backup_event(event);
+                        self.render_all_clients().await;
+                    }
+                    continue;
+                }
+                event = self.source_events.recv() => {
+                    if let Some(event) = event {
+                        self.handle_source_event(event).await;
+                        self.request_remote_listings();
+                        self.render_all_clients().await;
+                    }
+                    continue;
+                }
                 event = self.indexer_events.recv() => {
                     if let Some(event) = event {
                         let is_progress = matches!(&event, IndexerEvent::Progress { .. });
@@ -1019,14 +1580,42 @@ impl App {
             match message.event {
                 ClientEvent::Connected { id: _ } => {
                     self.client_ids.insert(client_id);
-                    self.wgui.render(client_id, self.render()).await;
+                    self.wgui
+                        .render(client_id, self.render_for(client_id))
+                        .await;
                     log::info!("wgui client {client_id} connected");
                 }
                 ClientEvent::Disconnected { id: _ } => {
                     self.client_ids.remove(&client_id);
+                    self.authenticated_clients.remove(&client_id);
+                    self.signing_out_clients.remove(&client_id);
                     log::info!("wgui client {client_id} disconnected");
                 }
+                ClientEvent::OnCustom(event) if event.id == AUTH_TICKET_ID => {
+                    let ticket = event
+                        .payload
+                        .get("ticket")
+                        .and_then(|ticket| ticket.as_str())
+                        .unwrap_or_default();
+                    let Some(session) = self.auth.redeem_client_ticket(ticket) else {
+                        log::warn!("wgui client {client_id} presented an invalid session ticket");
+                        continue;
+                    };
+                    self.signing_out_clients.remove(&client_id);
+                    self.authenticated_clients.insert(client_id, session);
+                    self.wgui.render(client_id, self.render()).await;
+                    continue;
+                }
+                // Everything else requires a signed-in browser session.
+                _ if !self.authenticated_clients.contains_key(&client_id) => continue,
+                _ if !self.client_session_is_active(client_id) => {
+                    self.wgui
+                        .render(client_id, self.render_for(client_id))
+                        .await;
+                    continue;
+                }
                 ClientEvent::PathChanged(change) => {
+                    self.clear_file_search();
                     let path = change.path.trim_end_matches('/');
                     let scanned_folder_id = path
                         .strip_prefix("/scanned-folders/")
@@ -1038,6 +1627,7 @@ impl App {
                         _ if scanned_folder_id.is_some() => {
                             self.selected_scanned_folder_id = scanned_folder_id;
                             self.refresh_selected_scanned_folder_history();
+                            self.reset_folder_ignore_rule_drafts();
                             AppPage::ScannedFolder
                         }
                         "/virtual-directories" => {
@@ -1045,6 +1635,16 @@ impl App {
                             AppPage::VirtualDirectories
                         }
                         "/transfers" => AppPage::Transfers,
+                        "/integrity" => AppPage::Integrity,
+                        "/duplicates" => {
+                            self.duplicate_message = None;
+                            self.reload_duplicates();
+                            AppPage::Duplicates
+                        }
+                        "/backups" => {
+                            self.reload_snapshots();
+                            AppPage::Backups
+                        }
                         "/settings" => AppPage::Settings,
                         _ if path
                             .strip_prefix("/virtual-directories/")
@@ -1086,15 +1686,46 @@ impl App {
                 ClientEvent::OnSelect(change) if change.id == MEDIA_VIEW_MODE_ID => {
                     self.media_view_mode = change.value;
                     self.media_page = 0;
+                    self.duplicate_message = None;
+                    self.reload_similar_images();
+                }
+                ClientEvent::OnSelect(change) if change.id == SIMILAR_IMAGE_DISTANCE_ID => {
+                    self.similar_image_distance = change.value;
+                    self.reload_similar_images();
                 }
                 ClientEvent::OnSelect(change) if change.id == MEDIA_SCANNED_FOLDER_FILTER_ID => {
                     self.media_scanned_folder_filter = change.value;
                     self.media_page = 0;
                 }
+                ClientEvent::OnSelect(change) if change.id == DUPLICATE_SORT_ID => {
+                    self.duplicate_sort = change.value;
+                    sort_duplicate_groups(&mut self.duplicate_groups, &self.duplicate_sort);
+                }
+                ClientEvent::OnSelect(change) if change.id == MEDIA_SORT_ID => {
+                    if let Some(key) = media_sort_key_from_name(&change.value) {
+                        self.media_sort_key = key;
+                        self.media_sort_descending =
+                            matches!(key, MediaSortKey::Captured | MediaSortKey::Modified);
+                        self.media_page = 0;
+                    }
+                }
+                ClientEvent::OnSelect(change) if change.id == MEDIA_CAMERA_FILTER_ID => {
+                    self.media_camera_filter = change.value;
+                    self.media_page = 0;
+                }
+                ClientEvent::OnSelect(change) if change.id == MEDIA_PHOTO_FILTER_ID => {
+                    self.media_photo_filter = change.value;
+                    self.media_page = 0;
+                }
                 ClientEvent::OnSelect(change) if change.id == AUDIO_SCANNED_FOLDER_FILTER_ID => {
                     self.audio_scanned_folder_filter = change.value;
                     self.audio_page = 0;
                 }
+                ClientEvent::OnSelect(change) if change.id == AUDIO_VIEW_ID => {
+                    self.audio_view = change.value;
+                    self.audio_group = None;
+                    self.audio_page = 0;
+                }
                 ClientEvent::OnSelect(change) if change.id == VIRTUAL_DIRECTORY_VIEW_MODE_ID => {
                     self.virtual_directory_view_mode = change.value;
                 }
@@ -1109,6 +1740,33 @@ impl App {
                     self.files_scanned_folder_filter = change.value;
                     self.refresh_filtered_files(true);
                 }
+                ClientEvent::OnSelect(change) if change.id == ADD_SOURCE_TYPE_ID => {
+                    self.new_source_type = change.value;
+                    self.new_source_error = None;
+                }
+                ClientEvent::OnSelect(change) if change.id == ADD_SOURCE_AUTH_ID => {
+                    self.new_source_auth = if change.value == "private_key" {
+                        SftpAuth::PrivateKey
+                    } else {
+                        SftpAuth::Password
+                    };
+                }
+                ClientEvent::OnSelect(change) if change.id == RESTORE_CONFLICTS_ID => {
+                    if let Some(conflicts) = ConflictPolicy::from_str(&change.value) {
+                        self.restore_conflicts = conflicts;
+                    }
+                }
+                ClientEvent::OnSelect(change) if change.id == BACKUP_SCHEDULE_ID => {
+                    if let Some(schedule) = BackupSchedule::from_str(&change.value) {
+                        self.config.backup.schedule = schedule;
+                        self.save_config();
+                    }
+                }
+                ClientEvent::OnSelect(change) if change.id == ACCESS_TOKEN_SCOPE_ID => {
+                    if let Some(scope) = AccessScope::from_str(&change.value) {
+                        self.new_access_token_scope = scope;
+                    }
+                }
                 ClientEvent::OnSelect(change) if change.id == FILES_SORT_ID => {
                     self.files_sort = change.value;
                     self.refresh_filtered_files(true);
@@ -1116,12 +1774,37 @@ impl App {
                 ClientEvent::OnSliderChange(change) if change.id == MEDIA_THUMBNAIL_SIZE_ID => {
                     self.media_thumbnail_size = change.value.clamp(140, 320);
                 }
+                ClientEvent::OnTextChanged(change) if change.id == SIDEBAR_SEARCH_ID => {
+                    self.file_search_query = change.value;
+                    self.refresh_file_search();
+                }
+                ClientEvent::OnTextChanged(change) if change.id == FILES_CONTENT_SEARCH_ID => {
+                    self.files_content_query = change.value;
+                    self.refresh_content_search();
+                }
                 ClientEvent::OnTextChanged(change) if change.id == ADD_SOURCE_NAME_INPUT_ID => {
                     self.new_source_name = change.value;
                 }
                 ClientEvent::OnTextChanged(change) if change.id == ADD_SOURCE_PATH_INPUT_ID => {
                     self.new_source_path = change.value;
                 }
+                ClientEvent::OnTextChanged(change) if change.id == ADD_SOURCE_HOST_INPUT_ID => {
+                    self.new_source_host = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == ADD_SOURCE_PORT_INPUT_ID => {
+                    self.new_source_port = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == ADD_SOURCE_USER_INPUT_ID => {
+                    self.new_source_user = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == SOURCE_SECRET_INPUT_ID => {
+                    self.source_secret = SecretString::from(change.value);
+                }
+                ClientEvent::OnTextChanged(change)
+                    if change.id == SOURCE_KEY_PASSPHRASE_INPUT_ID =>
+                {
+                    self.source_key_passphrase = SecretString::from(change.value);
+                }
                 ClientEvent::OnTextChanged(change) if change.id == ADD_MEDIA_PATH_INPUT_ID => {
                     self.new_media_path = change.value;
                     self.media_path_error = None;
@@ -1134,6 +1817,34 @@ impl App {
                     self.new_inbox_folder = change.value;
                     self.inbox_error = None;
                 }
+                ClientEvent::OnTextChanged(change) if change.id == ACCESS_TOKEN_NAME_INPUT_ID => {
+                    self.new_access_token_name = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == PEER_URL_INPUT_ID => {
+                    self.new_peer_url = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == PEER_TOKEN_INPUT_ID => {
+                    self.new_peer_token = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == BACKUP_DESTINATION_INPUT_ID => {
+                    self.backup_destination = change.value;
+                }
+                ClientEvent::OnTextChanged(change) if change.id == BACKUP_PASSPHRASE_INPUT_ID => {
+                    self.backup_passphrase = SecretString::from(change.value);
+                }
+                ClientEvent::OnTextChanged(change) if change.id == RESTORE_TARGET_INPUT_ID => {
+                    self.restore_target = change.value;
+                }
+                ClientEvent::OnTextChanged(change)
+                    if change.id == FOLDER_INCLUDE_GLOBS_INPUT_ID =>
+                {
+                    self.folder_include_globs = change.value;
+                }
+                ClientEvent::OnTextChanged(change)
+                    if change.id == FOLDER_EXCLUDE_GLOBS_INPUT_ID =>
+                {
+                    self.folder_exclude_globs = change.value;
+                }
                 ClientEvent::OnTextChanged(change) if change.id == NEW_FOLDER_NAME_INPUT_ID => {
                     self.new_folder_name = change.value;
                     self.new_folder_error = None;
@@ -1236,12 +1947,81 @@ impl App {
                         self.open_virtual_file(directory_id, index);
                     }
                 }
+                ClientEvent::OnCustom(event) if event.id == FILE_SEARCH_VIEW_ID => {
+                    if let Some(index) = custom_event_index(&event.payload) {
+                        self.open_file_search_hit(index);
+                    }
+                }
                 ClientEvent::OnCustom(event) if event.id == INDEXED_FILE_VIEW_ID => {
                     if let Some(index) = custom_event_index(&event.payload) {
                         self.open_indexed_file(index);
                     }
                 }
                 ClientEvent::OnClick(click) => match click.id {
+                    SIGN_OUT_ID => {
+                        self.authenticated_clients.remove(&client_id);
+                        self.signing_out_clients.insert(client_id);
+                        self.created_access_token = None;
+                    }
+                    CREATE_ACCESS_TOKEN_ID => self.create_access_token(),
+                    REVOKE_ACCESS_TOKEN_ID => {
+                        if let Some(id) = click.inx {
+                            self.revoke_access_token(id);
+                        }
+                    }
+                    DISMISS_ACCESS_TOKEN_ID => self.created_access_token = None,
+                    PAIR_PEER_ID => self.pair_peer(),
+                    SYNC_PEER_ID => {
+                        if let Some(peer) = click
+                            .inx
+                            .and_then(|inx| self.peers.get(inx as usize))
+                            .cloned()
+                        {
+                            self.sync_peer(peer);
+                        }
+                    }
+                    REMOVE_PEER_ID => {
+                        if let Some(inx) = click.inx {
+                            self.remove_peer(inx as usize);
+                        }
+                    }
+                    SAVE_BACKUP_DESTINATION_ID => self.save_backup_destination(),
+                    UNLOCK_BACKUP_ID | ROTATE_BACKUP_KEY_ID => {
+                        let passphrase = std::mem::take(&mut self.backup_passphrase);
+                        let requested = if click.id == UNLOCK_BACKUP_ID {
+                            self.unlock_backup(passphrase)
+                        } else {
+                            self.rotate_backup_key(passphrase)
+                        };
+                        if let Err(error) = requested {
+                            self.backup_message = Some(format!("{error:#}"));
+                        }
+                    }
+                    LOCK_BACKUP_ID => self.lock_backup(),
+                    BACK_UP_NOW_ID => self.start_backup(),
+                    PREVIEW_PRUNE_ID | PRUNE_NOW_ID => {
+                        if let Err(error) = self.start_prune(click.id == PREVIEW_PRUNE_ID) {
+                            self.prune_message = Some(format!("{error:#}"));
+                        }
+                    }
+                    OPEN_SNAPSHOT_ID => {
+                        if let Some(inx) = click.inx {
+                            self.open_snapshot(inx as usize);
+                        }
+                    }
+                    OPEN_SNAPSHOT_ENTRY_ID => {
+                        if let Some(inx) = click.inx {
+                            self.open_snapshot_entry(inx as usize);
+                        }
+                    }
+                    SNAPSHOT_UP_ID => self.snapshot_up(),
+                    CLOSE_SNAPSHOT_ID => self.open_snapshot = None,
+                    RESTORE_SNAPSHOT_ENTRY_ID => {
+                        if let Some(inx) = click.inx {
+                            self.restore_from_browser(Some(inx as usize));
+                        }
+                    }
+                    RESTORE_SNAPSHOT_DIRECTORY_ID => self.restore_from_browser(None),
                     THIS_COMPUTER_SOURCE_ID => {
                         self.activate_files_root(self.configured_this_computer_root.clone(), None);
                         self.wgui.handle().push_state(client_id, "/").await;
@@ -1302,6 +2082,41 @@ impl App {
                             self.media_page += 1;
                         }
                     }
+                    OPEN_AUDIO_ALBUM_ID => {
+                        if let Some(album) = click
+                            .inx
+                            .and_then(|index| self.audio_albums().into_iter().nth(index as usize))
+                        {
+                            self.audio_group = Some(AudioGroup::Album {
+                                artist: album.artist,
+                                album: album.album,
+                            });
+                            self.audio_page = 0;
+                        }
+                    }
+                    OPEN_AUDIO_ARTIST_ID => {
+                        if let Some(artist) = click
+                            .inx
+                            .and_then(|index| self.audio_artists().into_iter().nth(index as usize))
+                        {
+                            self.audio_group = Some(AudioGroup::Artist(artist.artist));
+                            self.audio_page = 0;
+                        }
+                    }
+                    CLEAR_SIDEBAR_SEARCH_ID => self.clear_file_search(),
+                    CLEAR_FILES_CONTENT_SEARCH_ID => {
+                        self.files_content_query.clear();
+                        self.files_content_hits.clear();
+                    }
+                    OPEN_CONTENT_SEARCH_HIT_ID => {
+                        if let Some(index) = click.inx {
+                            self.open_content_search_hit(index as usize);
+                        }
+                    }
+                    CLOSE_AUDIO_GROUP_ID => {
+                        self.audio_group = None;
+                        self.audio_page = 0;
+                    }
                     AUDIO_PREVIOUS_PAGE_ID => {
                         self.audio_page = self.audio_page.saturating_sub(1);
                     }
@@ -1390,10 +2205,46 @@ impl App {
                     CLOSE_FOLDER_CONTEXT_ID => self.folder_context = None,
                     OPEN_FOLDER_CONTEXT_ID => self.open_context_folder(),
                     TOGGLE_FOLDER_CONTEXT_ID => self.toggle_context_folder(),
-                    INCLUDE_FOLDER_MEDIA_ID => self.include_context_folder_in_media().await,
+                    INCLUDE_FOLDER_MEDIA_ID if self.active_remote_source().is_none() => {
+                        self.include_context_folder_in_media().await
+                    }
                     SHOW_ADD_SOURCE_ID => self.show_add_source = true,
-                    CLOSE_ADD_SOURCE_ID => self.show_add_source = false,
+                    CLOSE_ADD_SOURCE_ID => {
+                        self.show_add_source = false;
+                        self.new_source_error = None;
+                    }
                     SAVE_ADD_SOURCE_ID => self.save_added_source().await,
+                    CONNECT_SOURCE_ID => {
+                        if let Some(source) = self.active_remote_source().cloned() {
+                            let secret = std::mem::take(&mut self.source_secret);
+                            let passphrase = std::mem::take(&mut self.source_key_passphrase);
+                            if let Err(error) = self.connect_source(source.id, secret, passphrase) {
+                                self.source_messages
+                                    .insert(source.source_key, format!("{error:#}"));
+                            }
+                        }
+                    }
+                    DISCONNECT_SOURCE_ID => {
+                        if let Some(source) = self.active_remote_source() {
+                            let source_key = source.source_key.clone();
+                            self.disconnect_source(&source_key);
+                        }
+                    }
+                    REFRESH_SOURCE_ID => self.remote_listings.clear(),
+                    INDEX_SOURCE_ID => {
+                        if let Some(source) = self.active_remote_source().cloned() {
+                            let result = if self.indexing_sources.contains_key(&source.source_key) {
+                                self.stop_source_index(&source.source_key);
+                                Ok(())
+                            } else {
+                                self.index_source(source.id)
+                            };
+                            if let Err(error) = result {
+                                self.source_messages
+                                    .insert(source.source_key, format!("{error:#}"));
+                            }
+                        }
+                    }
                     SHOW_MEDIA_FOLDER_PICKER_ID => {
                         self.open_folder_picker(FolderPickerTarget::Media)
                     }
@@ -1413,6 +2264,43 @@ impl App {
                             self.toggle_media_path(id).await;
                         }
                     }
+                    TOGGLE_FOLDER_INDEXER_ID => {
+                        if let (Some(folder_id), Some(index)) =
+                            (self.selected_scanned_folder_id, click.inx)
+                        {
+                            self.toggle_folder_indexer(folder_id, index as usize).await;
+                        }
+                    }
+                    SAVE_FOLDER_IGNORE_RULES_ID => {
+                        if let Some(folder_id) = self.selected_scanned_folder_id {
+                            self.save_folder_ignore_rules(folder_id).await;
+                        }
+                    }
+                    METERED_CONNECTIONS_ID => {
+                        self.config.backup.metered_connections =
+                            !self.config.backup.metered_connections;
+                        self.save_config();
+                    }
+                    VERIFY_FILES_NOW_ID => self.request_scrub(),
+                    TRASH_DUPLICATES_ID => {
+                        if let Some(inx) = click.inx {
+                            self.resolve_duplicates(inx as usize, DuplicateAction::Trash);
+                        }
+                    }
+                    KEEP_SIMILAR_IMAGE_ID => {
+                        if let Some(inx) = click.inx {
+                            self.keep_similar_image(inx as usize);
+                        }
+                    }
+                    HARDLINK_DUPLICATES_ID => {
+                        if let Some(inx) = click.inx {
+                            self.resolve_duplicates(inx as usize, DuplicateAction::Hardlink);
+                        }
+                    }
+                    PAUSE_ON_BATTERY_ID => {
+                        self.config.backup.pause_on_battery = !self.config.backup.pause_on_battery;
+                        self.save_config();
+                    }
                     REMOVE_MEDIA_PATH_ID => {
                         if let Some(id) = click.inx {
                             self.remove_media_path(id);
@@ -1438,7 +2326,9 @@ impl App {
                             self.remove_inbox(index as usize);
                         }
                     }
-                    SHOW_NEW_FOLDER_ID if self.viewing_this_computer => {
+                    SHOW_NEW_FOLDER_ID
+                        if self.viewing_this_computer && self.active_remote_source().is_none() =>
+                    {
                         self.open_new_folder(
                             self.this_computer_path.clone(),
                             self.this_computer_root.clone(),
@@ -1466,17 +2356,69 @@ impl App {
                 }
             }
 
+            self.request_remote_listings();
             self.render_all_clients().await;
         }
     }
 
     async fn render_all_clients(&self) {
         for client_id in &self.client_ids {
-            self.wgui.render(*client_id, self.render()).await;
+            self.wgui
+                .render(*client_id, self.render_for(*client_id))
+                .await;
+        }
+    }
+
+    /// Whether the browser session a client signed in with is still active.
+    /// Signing out elsewhere, a password change or expiry ends it, and the
+    /// client is then signed out too.
+    fn client_session_is_active(&mut self, client_id: usize) -> bool {
+        let Some(session) = self.authenticated_clients.get(&client_id) else {
+            return false;
+        };
+        let active = self
+            .auth
+            .client_session_is_active(session)
+            .unwrap_or_else(|error| {
+                log::warn!("unable to check the session of wgui client {client_id}: {error:#}");
+                false
+            });
+        if !active {
+            self.authenticated_clients.remove(&client_id);
         }
+        active
+    }
+
+    fn render_for(&self, client_id: usize) -> Item {
+        if self.authenticated_clients.contains_key(&client_id) {
+            return self.render();
+        }
+        let configured = self.auth.is_configured().unwrap_or_else(|error| {
+            log::warn!("unable to read admin password state: {error:#}");
+            true
+        });
+        custom_component(
+            "auth-gate",
+            self.auth_gate_asset.url(),
+            serde_json::json!({
+                "configured": configured,
+                "setupCodeRequired": self.auth.setup_code().is_some(),
+                "signOut": self.signing_out_clients.contains(&client_id),
+            }),
+        )
+        .custom_event("authenticated", AUTH_TICKET_ID)
+        .grow(1)
     }
 
     fn local_entries_at(&self, directory: &Path) -> Vec<LocalEntry> {
+        if self.active_remote_source().is_some() {
+            return self
+                .remote_listings
+                .get(directory)
+                .cloned()
+                .flatten()
+                .unwrap_or_default();
+        }
         let Ok(entries) = fs::read_dir(directory) else {
             return Vec::new();
         };
@@ -1556,8 +2498,14 @@ impl App {
             destination.push(component.as_os_str());
         }
 
-        let Ok(destination) = fs::canonicalize(destination) else {
-            return;
+        // Remote paths are taken as listed; the server resolves them.
+        let destination = if self.active_remote_source().is_some() {
+            destination
+        } else {
+            let Ok(destination) = fs::canonicalize(destination) else {
+                return;
+            };
+            destination
         };
         if destination.starts_with(&self.this_computer_root) {
             self.this_computer_path = destination.clone();
@@ -1594,8 +2542,9 @@ impl App {
         nodes.push(TreeNode { entry, depth });
 
         if expanded {
+            let remote = self.active_remote_source().is_some();
             for mut child in self.local_entries_at(&path) {
-                if child.is_directory && !child.is_symlink {
+                if child.is_directory && !child.is_symlink && !remote {
                     let Ok(child_path) = fs::canonicalize(&child.path) else {
                         continue;
                     };
@@ -1698,7 +2647,11 @@ impl App {
         else {
             return;
         };
-        self.file_viewer_entries = FileViewerEntries::Local(entries);
+        self.file_viewer_entries = if self.active_remote_source().is_some() {
+            FileViewerEntries::Remote(entries)
+        } else {
+            FileViewerEntries::Local(entries)
+        };
         self.file_viewer_index = None;
         self.file_viewer_expanded = false;
         if self.select_viewer_entry(&entry) {
@@ -1745,20 +2698,56 @@ impl App {
         }
     }
 
-    fn virtual_listing_entries(&self, directory_id: u32) -> Vec<FileListingEntry> {
-        self.virtual_directory_entries
+    fn refresh_content_search(&mut self) {
+        let query = self.files_content_query.trim();
+        if query.chars().count() < CONTENT_SEARCH_MIN_CHARS {
+            self.files_content_hits.clear();
+            return;
+        }
+        self.files_content_hits = self
+            .database
+            .search_contents(&self.local_node_id, query, CONTENT_SEARCH_LIMIT)
+            .unwrap_or_else(|error| {
+                log::error!("content search failed: {error:#}");
+                Vec::new()
+            });
+    }
+
+    fn refresh_file_search(&mut self) {
+        self.file_search_hits.clear();
+        self.file_search_error = None;
+        match FileQuery::parse(
+            &self.file_search_query,
+            system_time_millis(SystemTime::now()),
+        ) {
+            Ok(query) => match self.database.search_files(&query, FILE_SEARCH_LIMIT) {
+                Ok(hits) => self.file_search_hits = hits,
+                Err(error) => log::error!("file search failed: {error:#}"),
+            },
+            Err(error) => self.file_search_error = Some(format!("{error:#}")),
+        }
+    }
+
+    fn clear_file_search(&mut self) {
+        self.file_search_query.clear();
+        self.file_search_hits.clear();
+        self.file_search_error = None;
+    }
+
+    fn file_search_entries(&self) -> Vec<FileListingEntry> {
+        self.file_search_hits
             .iter()
-            .filter(|entry| entry.virtual_directory_id == directory_id)
-            .map(FileListingEntry::from_virtual)
+            .map(FileListingEntry::from_file_search_hit)
             .collect()
     }
 
-    fn open_virtual_file(&mut self, directory_id: u32, index: usize) {
-        let entries = self.virtual_listing_entries(directory_id);
-        let Some(entry) = entries
-            .get(index)
-            .and_then(FileListingEntry::as_local_entry)
-        else {
+    fn open_file_search_hit(&mut self, index: usize) {
+        let entries = self.file_search_entries();
+        let Some(listing) = entries.get(index) else {
+            return;
+        };
+        let Some(entry) = listing.as_local_entry() else {
+            self.open_remote_entry(listing);
             return;
         };
         let viewer_entries = entries
@@ -1776,14 +2765,73 @@ impl App {
         self.file_viewer_expanded = false;
         if self.select_viewer_entry(&entry) {
             self.file_viewer_index = Some(viewer_index);
+        } else {
+            self.open_remote_entry(listing);
         }
     }
 
-    fn sorted_media_indices(&self) -> Vec<usize> {
-        let mut indices = (0..self.media_entries.len()).collect::<Vec<_>>();
-        indices.sort_by(|left_index, right_index| {
-            let left = &self.media_entries[*left_index];
+    fn open_content_search_hit(&mut self, index: usize) {
+        let entries = self
+            .files_content_hits
+            .iter()
+            .map(FileListingEntry::from_content_hit)
+            .filter_map(|entry| entry.as_local_entry())
+            .collect::<Vec<_>>();
+        let Some(entry) = entries.get(index).cloned() else {
+            return;
+        };
+        self.file_viewer_entries = FileViewerEntries::Local(entries);
+        self.file_viewer_index = None;
+        self.file_viewer_expanded = false;
+        if self.select_viewer_entry(&entry) {
+            self.file_viewer_index = Some(index);
+        }
+    }
+
+    fn virtual_listing_entries(&self, directory_id: u32) -> Vec<FileListingEntry> {
+        self.virtual_directory_entries
+            .iter()
+            .filter(|entry| entry.virtual_directory_id == directory_id)
+            .map(FileListingEntry::from_virtual)
+            .collect()
+    }
+
+    fn open_virtual_file(&mut self, directory_id: u32, index: usize) {
+        let entries = self.virtual_listing_entries(directory_id);
+        let Some(listing) = entries.get(index) else {
+            return;
+        };
+        let Some(entry) = listing.as_local_entry() else {
+            self.open_remote_entry(listing);
+            return;
+        };
+        let viewer_entries = entries
+            .iter()
+            .filter_map(FileListingEntry::as_local_entry)
+            .collect::<Vec<_>>();
+        let Some(viewer_index) = viewer_entries
+            .iter()
+            .position(|candidate| candidate.path == entry.path)
+        else {
+            return;
+        };
+        self.file_viewer_entries = FileViewerEntries::Local(viewer_entries);
+        self.file_viewer_index = None;
+        self.file_viewer_expanded = false;
+        if self.select_viewer_entry(&entry) {
+            self.file_viewer_index = Some(viewer_index);
+        } else {
+            self.open_remote_entry(listing);
+        }
+    }
+
+    fn sorted_media_indices(&self) -> Vec<usize> {
+        let mut indices = (0..self.media_entries.len()).collect::<Vec<_>>();
+        indices.sort_by(|left_index, right_index| {
+            let left = &self.media_entries[*left_index];
             let right = &self.media_entries[*right_index];
+            let left_photo = self.media_index_entries[*left_index].photo.as_ref();
+            let right_photo = self.media_index_entries[*right_index].photo.as_ref();
             let ordering = match self.media_sort_key {
                 MediaSortKey::Name => left.name.to_lowercase().cmp(&right.name.to_lowercase()),
                 MediaSortKey::Type => media_kind(left).cmp(media_kind(right)),
@@ -1792,6 +2840,24 @@ impl App {
                     .replica_count
                     .cmp(&self.media_index_entries[*right_index].replica_count),
                 MediaSortKey::Modified => left.modified_at.cmp(&right.modified_at),
+                MediaSortKey::Captured => media_timeline_millis(left, left_photo)
+                    .cmp(&media_timeline_millis(right, right_photo)),
+                MediaSortKey::Camera => photo_sort_text(left_photo.and_then(PhotoMetadata::camera))
+                    .cmp(&photo_sort_text(
+                        right_photo.and_then(PhotoMetadata::camera),
+                    )),
+                MediaSortKey::Lens => {
+                    photo_sort_text(left_photo.and_then(|photo| photo.lens_model.clone())).cmp(
+                        &photo_sort_text(right_photo.and_then(|photo| photo.lens_model.clone())),
+                    )
+                }
+                MediaSortKey::Resolution => {
+                    photo_pixels(left_photo).cmp(&photo_pixels(right_photo))
+                }
+                MediaSortKey::Title
+                | MediaSortKey::Artist
+                | MediaSortKey::Album
+                | MediaSortKey::Duration => std::cmp::Ordering::Equal,
             }
             .then_with(|| left.name.to_lowercase().cmp(&right.name.to_lowercase()));
             if self.media_sort_descending {
@@ -1814,14 +2880,72 @@ impl App {
                         .and_then(|entry| entry.media_root_id)
                         .is_some_and(|id| id.to_string() == self.media_scanned_folder_filter)
             })
+            .filter(|index| {
+                let photo = self
+                    .media_index_entries
+                    .get(*index)
+                    .and_then(|indexed| indexed.photo.as_ref());
+                (self.media_camera_filter == "all"
+                    || photo
+                        .and_then(PhotoMetadata::camera)
+                        .is_some_and(|camera| camera == self.media_camera_filter))
+                    && photo_filter_matches(&self.media_photo_filter, photo)
+            })
             .collect()
     }
 
+    /// Distinct cameras among indexed photos, for the Media camera filter.
+    fn media_cameras(&self) -> Vec<String> {
+        let mut cameras = self
+            .media_index_entries
+            .iter()
+            .filter_map(|indexed| indexed.photo.as_ref().and_then(PhotoMetadata::camera))
+            .collect::<Vec<_>>();
+        cameras.sort_by_key(|camera| camera.to_lowercase());
+        cameras.dedup();
+        cameras
+    }
+
     fn filtered_audio_indices(&self) -> Vec<usize> {
+        let mut indices = self
+            .audio_folder_indices()
+            .into_iter()
+            .filter(|index| {
+                let Some(group) = &self.audio_group else {
+                    return true;
+                };
+                let Some(tags) = self.audio_index_entries[*index].audio.as_ref() else {
+                    return false;
+                };
+                match group {
+                    AudioGroup::Album { artist, album } => {
+                        tags.album.as_deref() == Some(album.as_str())
+                            && tags.album_credit().unwrap_or_default() == artist
+                    }
+                    AudioGroup::Artist(artist) => {
+                        tags.album_credit() == Some(artist.as_str())
+                            || tags.artist.as_deref() == Some(artist.as_str())
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        if matches!(self.audio_group, Some(AudioGroup::Album { .. })) {
+            // An opened album plays in disc and track order.
+            indices.sort_by_key(|index| {
+                album_track_order(self.audio_index_entries[*index].audio.as_ref())
+            });
+        }
+        indices
+    }
+
+    /// Audio files in sort order, narrowed by the Scanned folder filter only.
+    fn audio_folder_indices(&self) -> Vec<usize> {
         let mut indices = (0..self.audio_entries.len()).collect::<Vec<_>>();
         indices.sort_by(|left_index, right_index| {
             let left = &self.audio_entries[*left_index];
             let right = &self.audio_entries[*right_index];
+            let left_tags = self.audio_index_entries[*left_index].audio.as_ref();
+            let right_tags = self.audio_index_entries[*right_index].audio.as_ref();
             let ordering = match self.media_sort_key {
                 MediaSortKey::Name => left.name.to_lowercase().cmp(&right.name.to_lowercase()),
                 MediaSortKey::Type => self.audio_index_entries[*left_index]
@@ -1831,7 +2955,27 @@ impl App {
                 MediaSortKey::Replicas => self.audio_index_entries[*left_index]
                     .replica_count
                     .cmp(&self.audio_index_entries[*right_index].replica_count),
-                MediaSortKey::Modified => left.modified_at.cmp(&right.modified_at),
+                MediaSortKey::Modified
+                | MediaSortKey::Captured
+                | MediaSortKey::Camera
+                | MediaSortKey::Lens
+                | MediaSortKey::Resolution => left.modified_at.cmp(&right.modified_at),
+                MediaSortKey::Title => {
+                    photo_sort_text(left_tags.and_then(|tags| tags.title.clone())).cmp(
+                        &photo_sort_text(right_tags.and_then(|tags| tags.title.clone())),
+                    )
+                }
+                MediaSortKey::Artist => {
+                    photo_sort_text(left_tags.and_then(|tags| tags.artist.clone())).cmp(
+                        &photo_sort_text(right_tags.and_then(|tags| tags.artist.clone())),
+                    )
+                }
+                MediaSortKey::Album => {
+                    album_track_order(left_tags).cmp(&album_track_order(right_tags))
+                }
+                MediaSortKey::Duration => left_tags
+                    .and_then(|tags| tags.duration_ms)
+                    .cmp(&right_tags.and_then(|tags| tags.duration_ms)),
             }
             .then_with(|| left.name.to_lowercase().cmp(&right.name.to_lowercase()));
             if self.media_sort_descending {
@@ -1853,6 +2997,71 @@ impl App {
             .collect()
     }
 
+    /// Tagged albums in the current Scanned folder filter, by artist then title.
+    fn audio_albums(&self) -> Vec<AudioAlbumSummary> {
+        let mut albums: Vec<AudioAlbumSummary> = Vec::new();
+        for index in self.audio_folder_indices() {
+            let Some(tags) = self.audio_index_entries[index].audio.as_ref() else {
+                continue;
+            };
+            let Some(album) = tags.album.as_deref() else {
+                continue;
+            };
+            let artist = tags.album_credit().unwrap_or_default();
+            let duration_ms = tags.duration_ms.unwrap_or(0);
+            match albums
+                .iter_mut()
+                .find(|summary| summary.album == album && summary.artist == artist)
+            {
+                Some(summary) => {
+                    summary.tracks += 1;
+                    summary.duration_ms += duration_ms;
+                    summary.year = summary.year.or(tags.year);
+                }
+                None => albums.push(AudioAlbumSummary {
+                    artist: artist.to_owned(),
+                    album: album.to_owned(),
+                    year: tags.year,
+                    tracks: 1,
+                    duration_ms,
+                }),
+            }
+        }
+        albums.sort_by_key(|summary| (summary.artist.to_lowercase(), summary.album.to_lowercase()));
+        albums
+    }
+
+    /// Artists credited on tagged tracks, with album and track counts.
+    fn audio_artists(&self) -> Vec<AudioArtistSummary> {
+        let mut artists: Vec<AudioArtistSummary> = Vec::new();
+        for index in self.audio_folder_indices() {
+            let Some(tags) = self.audio_index_entries[index].audio.as_ref() else {
+                continue;
+            };
+            let Some(artist) = tags.album_credit() else {
+                continue;
+            };
+            match artists.iter_mut().find(|summary| summary.artist == artist) {
+                Some(summary) => summary.tracks += 1,
+                None => artists.push(AudioArtistSummary {
+                    artist: artist.to_owned(),
+                    albums: 0,
+                    tracks: 1,
+                }),
+            }
+        }
+        for album in self.audio_albums() {
+            if let Some(summary) = artists
+                .iter_mut()
+                .find(|summary| summary.artist == album.artist)
+            {
+                summary.albums += 1;
+            }
+        }
+        artists.sort_by_key(|summary| summary.artist.to_lowercase());
+        artists
+    }
+
     fn toggle_media_sort(&mut self, key: MediaSortKey) {
         if self.media_sort_key == key {
             self.media_sort_descending = !self.media_sort_descending;
@@ -2004,8 +3213,130 @@ impl App {
         }
     }
 
+    /// Opens a file that has no readable local replica, using the peer cache
+    /// or fetching the content from a paired device that holds it.
+    fn open_remote_entry(&mut self, entry: &FileListingEntry) {
+        let Some(hash) = entry.hash.clone() else {
+            return;
+        };
+        if let Some(path) = peers::cached_content(&self.peer_cache_dir, &hash) {
+            self.file_viewer_entries.clear();
+            self.file_viewer_index = None;
+            self.file_viewer_expanded = false;
+            self.select_fetched(entry, hash, &path);
+            return;
+        }
+        let device = match self.database.peers_holding(&hash) {
+            Ok(peers) if !peers.is_empty() => peers[0].name.clone(),
+            Ok(_) => {
+                self.open_source_replica(entry, &hash);
+                return;
+            }
+            Err(error) => {
+                log::warn!(
+                    "could not look up devices holding {}: {error:#}",
+                    entry.name
+                );
+                return;
+            }
+        };
+        self.file_viewer_entries.clear();
+        self.file_viewer_index = None;
+        self.file_viewer_expanded = false;
+        self.show_remote_message(
+            entry,
+            Some(hash.clone()),
+            format!("Fetching from {device}…"),
+        );
+        self.fetching_file = Some(entry.clone());
+        let database_path = self.database.path().to_path_buf();
+        let cache_dir = self.peer_cache_dir.clone();
+        let policy = self.peer_fetch;
+        let events = self.peer_event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = Database::open(&database_path)
+                .and_then(|database| peers::fetch(&database, &cache_dir, &hash, policy))
+                .map_err(|error| format!("{error:#}"));
+            let _ = events.blocking_send(PeerEvent::Fetched { hash, result });
+        });
+    }
+
+    fn show_remote_message(
+        &mut self,
+        entry: &FileListingEntry,
+        hash: Option<Vec<u8>>,
+        message: String,
+    ) {
+        self.selected_file_hash = hash;
+        self.selected_file = Some(FileViewer {
+            name: entry.name.clone(),
+            size: format_size(entry.size),
+            bytes: Vec::new(),
+            text: Some(message),
+            mode: FileViewMode::Text,
+            truncated: false,
+        });
+        self.selected_video = None;
+        self.selected_image = None;
+    }
+
+    /// Shows content fetched from another device. Images and videos stream
+    /// from `/content/`, which serves the cached copy.
+    fn select_fetched(&mut self, entry: &FileListingEntry, hash: Vec<u8>, path: &Path) {
+        let size = format_size(entry.size);
+        let modified = entry
+            .modified_at
+            .map_or_else(|| "—".to_owned(), format_modified);
+        let source_url = format!("/content/{}", hex_encode(&hash));
+        self.selected_video = None;
+        self.selected_image = None;
+        self.selected_file = None;
+        if entry.is_video() {
+            self.selected_video = Some(VideoFile {
+                name: entry.name.clone(),
+                size,
+                modified,
+                source_url,
+            });
+        } else if entry.is_image() {
+            self.selected_image = Some(ImageFile {
+                name: entry.name.clone(),
+                size,
+                modified,
+                source_url,
+                preview_url: None,
+            });
+        } else {
+            let mut bytes = Vec::new();
+            if let Err(error) = fs::File::open(path)
+                .and_then(|file| file.take(MAX_FILE_PREVIEW_BYTES).read_to_end(&mut bytes))
+            {
+                let message = format!("Could not read the fetched copy: {error}");
+                self.show_remote_message(entry, Some(hash), message);
+                return;
+            }
+            let text = String::from_utf8(bytes.clone()).ok();
+            self.selected_file = Some(FileViewer {
+                name: entry.name.clone(),
+                size,
+                mode: if text.is_some() {
+                    FileViewMode::Text
+                } else {
+                    FileViewMode::Hex
+                },
+                bytes,
+                text,
+                truncated: entry.size > MAX_FILE_PREVIEW_BYTES,
+            });
+        }
+        self.selected_file_hash = Some(hash);
+    }
+
     fn select_viewer_entry(&mut self, entry: &LocalEntry) -> bool {
-        if is_video_file(entry) {
+        self.fetching_file = None;
+        if matches!(self.file_viewer_entries, FileViewerEntries::Remote(_)) {
+            self.select_source_entry(entry)
+        } else if is_video_file(entry) {
             self.select_video(entry)
         } else if is_image_file(entry) {
             self.select_image(entry)
@@ -2034,6 +3365,7 @@ impl App {
     }
 
     fn close_file_viewer(&mut self) {
+        self.fetching_file = None;
         self.selected_video = None;
         self.selected_image = None;
         self.selected_file = None;
@@ -2045,6 +3377,146 @@ impl App {
         self.virtual_directory_error = None;
     }
 
+    fn reload_access_tokens(&mut self) {
+        match self.auth.access_tokens() {
+            Ok(tokens) => self.access_tokens = tokens,
+            Err(error) => log::warn!("could not load access tokens: {error:#}"),
+        }
+    }
+
+    fn create_access_token(&mut self) {
+        match self
+            .auth
+            .create_access_token(&self.new_access_token_name, self.new_access_token_scope)
+        {
+            Ok((_, secret)) => {
+                self.created_access_token = Some(secret);
+                self.access_token_error = None;
+                self.new_access_token_name.clear();
+                self.reload_access_tokens();
+            }
+            Err(error) => self.access_token_error = Some(format!("{error:#}")),
+        }
+    }
+
+    fn revoke_access_token(&mut self, id: u32) {
+        if let Err(error) = self.auth.revoke_access_token(id) {
+            self.access_token_error = Some(format!("{error:#}"));
+        }
+        self.reload_access_tokens();
+    }
+
+    fn reload_peers(&mut self) {
+        match self.database.peers() {
+            Ok(peers) => self.peers = peers,
+            Err(error) => log::warn!("could not load paired devices: {error:#}"),
+        }
+    }
+
+    /// Contacts the device entered on the Settings page, pairs with it and
+    /// pulls its index, off the event loop.
+    fn pair_peer(&mut self) {
+        if self.pairing_peer {
+            return;
+        }
+        self.pairing_peer = true;
+        self.peer_message = Some("Contacting the device…".to_owned());
+        let database_path = self.database.path().to_path_buf();
+        let url = self.new_peer_url.clone();
+        let access_token = self.new_peer_token.trim().to_owned();
+        let allow_cleartext = self.config.peers.allow_cleartext;
+        let events = self.peer_event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = Database::open(&database_path)
+                .and_then(|database| peers::pair(&database, &url, &access_token, allow_cleartext))
+                .map_err(|error| format!("{error:#}"));
+            let _ = events.blocking_send(PeerEvent::Paired(result));
+        });
+    }
+
+    fn sync_peer(&mut self, peer: Peer) {
+        if !self.syncing_peers.insert(peer.node_id.clone()) {
+            return;
+        }
+        let database_path = self.database.path().to_path_buf();
+        let allow_cleartext = self.config.peers.allow_cleartext;
+        let events = self.peer_event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = Database::open(&database_path)
+                .and_then(|database| {
+                    peers::sync(&database, &peer, allow_cleartext).inspect_err(|error| {
+                        if let Err(error) =
+                            database.record_peer_error(&peer.node_id, &format!("{error:#}"))
+                        {
+                            log::warn!("failed recording sync error: {error:#}");
+                        }
+                    })
+                })
+                .map_err(|error| format!("{error:#}"));
+            let _ = events.blocking_send(PeerEvent::Synced {
+                node_id: peer.node_id,
+                name: peer.name,
+                result,
+            });
+        });
+    }
+
+    fn remove_peer(&mut self, index: usize) {
+        let Some(peer) = self.peers.get(index) else {
+            return;
+        };
+        match self.database.remove_peer(&peer.node_id) {
+            Ok(_) => self.peer_message = Some(format!("Unpaired {}.", peer.name)),
+            Err(error) => self.peer_message = Some(format!("{error:#}")),
+        }
+        self.reload_peers();
+    }
+
+    fn handle_peer_event(&mut self, event: PeerEvent) {
+        match event {
+            PeerEvent::Paired(Ok(peer)) => {
+                self.pairing_peer = false;
+                self.peer_message = Some(format!("Paired with {}.", peer.name));
+                self.new_peer_url.clear();
+                self.new_peer_token.clear();
+                self.reload_peers();
+                self.sync_peer(peer);
+            }
+            PeerEvent::Paired(Err(error)) => {
+                self.pairing_peer = false;
+                self.peer_message = Some(error);
+            }
+            PeerEvent::Synced {
+                node_id,
+                name,
+                result,
+            } => {
+                self.syncing_peers.remove(&node_id);
+                match result {
+                    Ok(changes) => log::info!("synced {changes} index changes from {name}"),
+                    Err(error) => log::warn!("syncing with {name} failed: {error}"),
+                }
+                self.reload_peers();
+            }
+            PeerEvent::Fetched { hash, result } => {
+                // Ignore fetches for a file the viewer has moved on from.
+                let Some(entry) = self
+                    .fetching_file
+                    .take_if(|entry| entry.hash.as_deref() == Some(hash.as_slice()))
+                else {
+                    return;
+                };
+                match result {
+                    Ok(path) => self.select_fetched(&entry, hash, &path),
+                    Err(error) => {
+                        let message = format!("Could not fetch: {error}");
+                        self.show_remote_message(&entry, Some(hash), message)
+                    }
+                }
+            }
+        }
+    }
+
     fn reload_virtual_directories(&mut self) {
         match self.database.virtual_directories() {
             Ok(directories) => self.virtual_directories = directories,
@@ -2118,74 +3590,581 @@ impl App {
     }
 
     async fn save_added_source(&mut self) {
-        let name = self.new_source_name.trim();
+        let name = self.new_source_name.trim().to_owned();
         if name.is_empty() {
             return;
         }
-        let Ok(path) = fs::canonicalize(self.new_source_path.trim()) else {
-            log::warn!("source path '{}' is not accessible", self.new_source_path);
-            return;
+        let result = if self.new_source_type == "sftp" {
+            match self.new_source_port.trim().parse() {
+                Ok(port) => {
+                    let config = SftpSourceConfig {
+                        host: self.new_source_host.trim().to_owned(),
+                        port,
+                        user: self.new_source_user.trim().to_owned(),
+                        root: self.new_source_path.trim().to_owned(),
+                        auth: self.new_source_auth,
+                        host_key: None,
+                    };
+                    self.create_sftp_source(&name, config).await
+                }
+                Err(_) => Err(anyhow::anyhow!("port must be a number from 1 to 65535")),
+            }
+        } else {
+            let path = PathBuf::from(self.new_source_path.trim());
+            self.create_local_source(&name, &path).await
         };
-        if !path.is_dir() {
-            log::warn!("source path '{}' is not a directory", path.display());
+        if let Err(error) = result {
+            log::warn!("unable to add source: {error:#}");
+            self.new_source_error = Some(format!("{error:#}"));
             return;
         }
+        self.new_source_name.clear();
+        self.new_source_path.clear();
+        self.new_source_host.clear();
+        self.new_source_port = "22".to_owned();
+        self.new_source_user.clear();
+        self.new_source_error = None;
+        self.show_add_source = false;
+    }
+
+    async fn create_local_source(&mut self, name: &str, path: &Path) -> Result<Source> {
+        let path = fs::canonicalize(path)
+            .with_context(|| format!("source path '{}' is not accessible", path.display()))?;
+        if !path.is_dir() {
+            anyhow::bail!("source path '{}' is not a directory", path.display());
+        }
         let config = serde_json::to_string(&LocalSourceConfig {
             path: path.to_string_lossy().into_owned(),
         })
         .expect("serialize local source config");
-        if let Err(error) = validate_source_config("local", 1, &config) {
-            log::warn!("invalid local source: {error}");
-            return;
-        }
+        validate_source_config("local", 1, &config).context("invalid local source")?;
+        self.insert_source(name, "local", config).await
+    }
+
+    /// Adds an SFTP source. Its host key is pinned on the first connection.
+    async fn create_sftp_source(&mut self, name: &str, config: SftpSourceConfig) -> Result<Source> {
+        let config = serde_json::to_string(&config).expect("serialize SFTP source config");
+        validate_source_config("sftp", 1, &config).context("invalid SFTP source")?;
+        self.insert_source(name, "sftp", config).await
+    }
+
+    async fn insert_source(
+        &mut self,
+        name: &str,
+        source_type: &str,
+        config: String,
+    ) -> Result<Source> {
         let source = Source {
             id: 0,
             source_key: uuid::Uuid::new_v4().to_string(),
             name: name.to_owned(),
-            source_type: "local".to_owned(),
+            source_type: source_type.to_owned(),
             config_schema_version: 1,
             config,
             enabled: true,
         };
-        let source = match self.database.save_source(source).await {
-            Ok(source) => source,
-            Err(error) => {
-                log::error!("failed saving source: {error:#}");
-                return;
-            }
-        };
-        self.sources.push(source);
-        self.new_source_name.clear();
-        self.new_source_path.clear();
-        self.show_add_source = false;
+        let source = self
+            .database
+            .save_source(source)
+            .await
+            .context("failed saving source")?;
+        self.sources.push(source.clone());
+        Ok(source)
     }
 
-    fn save_config(&self) {
-        if let Err(error) = config::save(&self.config, &self.config_path) {
-            log::error!("failed saving PuppyDrive settings: {error:#}");
+    async fn update_source(&mut self, source: Source) -> Result<Source> {
+        let index = self
+            .sources
+            .iter()
+            .position(|stored| stored.id == source.id)
+            .context("source not found")?;
+        validate_source_config(
+            &source.source_type,
+            source.config_schema_version,
+            &source.config,
+        )?;
+        let source = self.database.save_source(source).await?;
+        self.sources[index] = source.clone();
+        if !source.enabled {
+            self.disconnect_source(&source.source_key);
         }
+        if self.active_source_id == Some(source.id) && !source.enabled {
+            self.activate_files_root(self.configured_this_computer_root.clone(), None);
+        }
+        Ok(source)
     }
 
-    fn add_inbox(&mut self) -> bool {
-        let name = self.new_inbox_name.trim();
-        if name.is_empty() {
-            self.inbox_error = Some("Give the Inbox a name.".to_owned());
-            return false;
+    fn remove_source(&mut self, id: u32) -> Result<bool> {
+        let source = self.sources.iter().find(|source| source.id == id).cloned();
+        if !self.database.delete_source(id)? {
+            return Ok(false);
         }
-        if self
-            .config
-            .inboxes
+        if let Some(source) = source.filter(|source| source.source_type == "sftp") {
+            self.disconnect_source(&source.source_key);
+            self.source_messages.remove(&source.source_key);
+            self.database
+                .remove_source_node(&sftp::node_id(&source.source_key))?;
+        }
+        self.sources.retain(|source| source.id != id);
+        if self.active_source_id == Some(id) {
+            self.activate_files_root(self.configured_this_computer_root.clone(), None);
+        }
+        Ok(true)
+    }
+
+    /// The active source, when it is an SFTP source.
+    fn active_remote_source(&self) -> Option<&Source> {
+        let id = self.active_source_id?;
+        self.sources
             .iter()
-            .any(|inbox| inbox.name.eq_ignore_ascii_case(name))
+            .find(|source| source.id == id && source.source_type == "sftp")
+    }
+
+    /// Keeps the secrets entered for SFTP source `id` in the session store
+    /// and opens a connection with them off the event loop. Empty fields
+    /// leave what the session already holds.
+    fn connect_source(
+        &mut self,
+        id: u32,
+        secret: SecretString,
+        passphrase: SecretString,
+    ) -> Result<()> {
+        let source = self
+            .sources
+            .iter()
+            .find(|source| source.id == id)
+            .context("source not found")?;
+        let config = sftp_source_config(source).context("not an SFTP source")?;
+        if !source.enabled {
+            anyhow::bail!("{} is disabled", source.name);
+        }
+        let source_key = source.source_key.clone();
+        if self.source_connections.contains_key(&source_key)
+            || self.connecting_sources.contains(&source_key)
         {
-            self.inbox_error = Some("An Inbox with that name already exists.".to_owned());
-            return false;
+            return Ok(());
         }
-        let folder = match inbox_folder_path(&self.new_inbox_folder) {
-            Ok(folder) => folder,
-            Err(error) => {
-                self.inbox_error = Some(format!("Invalid Inbox folder: {error}"));
-                return false;
+        let (slot, missing) = match config.auth {
+            SftpAuth::Password => (sftp::PASSWORD_SLOT, "enter the password"),
+            SftpAuth::PrivateKey => (sftp::PRIVATE_KEY_SLOT, "paste the private key"),
+        };
+        if !secret.expose_secret().is_empty() {
+            self.session_secrets.set(&source_key, slot, secret);
+        }
+        if !passphrase.expose_secret().is_empty() {
+            self.session_secrets
+                .set(&source_key, sftp::KEY_PASSPHRASE_SLOT, passphrase);
+        }
+        let credential = self
+            .source_credential(&source_key, config.auth)
+            .context(missing)?;
+        self.connecting_sources.insert(source_key.clone());
+        self.source_messages.remove(&source_key);
+        let events = self.source_event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = SftpConnection::connect(&config, &credential)
+                .map(|(connection, host_key)| {
+                    let connection: Box<dyn RemoteFiles> = Box::new(connection);
+                    (Arc::new(Mutex::new(connection)), host_key)
+                })
+                .map_err(|error| format!("{error:#}"));
+            let _ = events.blocking_send(SourceEvent::Connected { source_key, result });
+        });
+        Ok(())
+    }
+
+    fn source_credential(&self, source_key: &str, auth: SftpAuth) -> Option<SftpCredential> {
+        let secret = |slot| self.session_secrets.get(source_key, slot).cloned();
+        match auth {
+            SftpAuth::Password => secret(sftp::PASSWORD_SLOT).map(SftpCredential::Password),
+            SftpAuth::PrivateKey => Some(SftpCredential::PrivateKey {
+                key: secret(sftp::PRIVATE_KEY_SLOT)?,
+                passphrase: secret(sftp::KEY_PASSPHRASE_SLOT),
+            }),
+        }
+    }
+
+    /// Closes the connection to a source and forgets its session secrets. A
+    /// connection still being opened is dropped when it arrives.
+    fn disconnect_source(&mut self, source_key: &str) {
+        self.source_connections.remove(source_key);
+        self.connecting_sources.remove(source_key);
+        self.stop_source_index(source_key);
+        self.session_secrets.clear_source(source_key);
+        if self
+            .active_remote_source()
+            .is_some_and(|source| source.source_key == source_key)
+        {
+            self.remote_listings.clear();
+        }
+    }
+
+    /// Lists the expanded folders of the open SFTP source not listed yet, off
+    /// the event loop.
+    fn request_remote_listings(&mut self) {
+        let Some(source) = self.active_remote_source() else {
+            return;
+        };
+        let Some(connection) = self.source_connections.get(&source.source_key).cloned() else {
+            return;
+        };
+        let source_key = source.source_key.clone();
+        let directories = self
+            .expanded_local_dirs
+            .iter()
+            .filter(|directory| !self.remote_listings.contains_key(*directory))
+            .cloned()
+            .collect::<Vec<_>>();
+        for directory in directories {
+            self.remote_listings.insert(directory.clone(), None);
+            let connection = connection.clone();
+            let source_key = source_key.clone();
+            let events = self.source_event_tx.clone();
+            tokio::task::spawn_blocking(move || {
+                let result = {
+                    let mut files = connection.lock().unwrap_or_else(|error| error.into_inner());
+                    sftp::list(files.as_mut(), &directory)
+                }
+                .map_err(|error| format!("{error:#}"));
+                let _ = events.blocking_send(SourceEvent::Listed {
+                    source_key,
+                    directory,
+                    result,
+                });
+            });
+        }
+    }
+
+    /// Shows a file of the open SFTP source once it has been downloaded.
+    fn select_source_entry(&mut self, entry: &LocalEntry) -> bool {
+        let Some(source) = self.active_remote_source() else {
+            return false;
+        };
+        let source_key = source.source_key.clone();
+        let listing = FileListingEntry {
+            path: Some(entry.path.clone()),
+            name: entry.name.clone(),
+            size: entry.size_bytes,
+            mime_type: None,
+            modified_at: entry.modified_at,
+            hash: None,
+            replica_count: 0,
+            media_root_id: None,
+            photo: None,
+            audio: None,
+            remote_node: None,
+        };
+        self.fetch_source_file(&source_key, listing)
+    }
+
+    /// Opens an indexed file that no paired device holds through a connected
+    /// SFTP source that indexed it.
+    fn open_source_replica(&mut self, entry: &FileListingEntry, hash: &[u8]) {
+        let replica = self.source_connections.keys().find_map(|source_key| {
+            let paths = self
+                .database
+                .file_replica_paths(&sftp::node_id(source_key), hash)
+                .ok()?;
+            Some((source_key.clone(), paths.into_iter().next()?))
+        });
+        let Some((source_key, path)) = replica else {
+            return;
+        };
+        self.file_viewer_entries.clear();
+        self.file_viewer_index = None;
+        self.file_viewer_expanded = false;
+        let entry = FileListingEntry {
+            path: Some(path),
+            ..entry.clone()
+        };
+        self.fetch_source_file(&source_key, entry);
+    }
+
+    /// Downloads a file of a connected SFTP source into the content cache for
+    /// the viewer; it is shown when `SourceEvent::Fetched` arrives.
+    fn fetch_source_file(&mut self, source_key: &str, mut entry: FileListingEntry) -> bool {
+        let Some(connection) = self.source_connections.get(source_key).cloned() else {
+            return false;
+        };
+        let Some(source) = self
+            .sources
+            .iter()
+            .find(|source| source.source_key == source_key)
+        else {
+            return false;
+        };
+        let Some(path) = entry.path.clone() else {
+            return false;
+        };
+        let node = SourceNode::new(source);
+        entry.remote_node = Some(node.name.clone());
+        let message = format!("Downloading from {}…", node.name);
+        self.show_remote_message(&entry, entry.hash.clone(), message);
+        let modified_at = entry.modified_at.map(system_time_millis);
+        self.fetching_file = Some(entry);
+        let database_path = self.database.path().to_path_buf();
+        let cache = PreviewCache {
+            dir: self.peer_cache_dir.clone(),
+            limit_bytes: self.peer_fetch.cache_limit_bytes,
+            max_file_bytes: sftp::PREVIEW_LIMIT_BYTES,
+        };
+        let source_key = source_key.to_owned();
+        let events = self.source_event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = Database::open(&database_path)
+                .and_then(|database| {
+                    let mut files = connection.lock().unwrap_or_else(|error| error.into_inner());
+                    sftp::fetch(files.as_mut(), &database, &node, &path, modified_at, &cache)
+                })
+                .map_err(|error| format!("{error:#}"));
+            let _ = events.blocking_send(SourceEvent::Fetched {
+                source_key,
+                path,
+                result,
+            });
+        });
+        true
+    }
+
+    /// Indexes a connected SFTP source over a connection of its own, so
+    /// browsing it stays responsive meanwhile. The scan settings' size limit
+    /// and ignored directory names apply to it too.
+    fn index_source(&mut self, id: u32) -> Result<()> {
+        let source = self
+            .sources
+            .iter()
+            .find(|source| source.id == id)
+            .cloned()
+            .context("source not found")?;
+        let config = sftp_source_config(&source).context("not an SFTP source")?;
+        if !self.source_connections.contains_key(&source.source_key) {
+            anyhow::bail!("connect to {} first", source.name);
+        }
+        let credential = self
+            .source_credential(&source.source_key, config.auth)
+            .context("this session holds no credentials for the source")?;
+        if self.indexing_sources.contains_key(&source.source_key) {
+            return Ok(());
+        }
+        let options = IndexOptions {
+            max_file_size_bytes: self
+                .config
+                .media
+                .max_file_size_mb
+                .saturating_mul(1_024 * 1_024),
+            ignored_directory_names: self.config.media.ignored_directory_names.clone(),
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+        self.indexing_sources
+            .insert(source.source_key.clone(), options.cancel.clone());
+        self.source_messages.insert(
+            source.source_key.clone(),
+            format!("Indexing {}…", source.name),
+        );
+        let node = SourceNode::new(&source);
+        let database_path = self.database.path().to_path_buf();
+        let events = self.source_event_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut progress = |report: &sftp::IndexReport| {
+                let _ = events.blocking_send(SourceEvent::IndexProgress {
+                    source_key: source.source_key.clone(),
+                    files: report.files,
+                });
+            };
+            let result = Database::open(&database_path)
+                .and_then(|database| {
+                    let (mut connection, _) = SftpConnection::connect(&config, &credential)?;
+                    let root = Path::new(&config.root);
+                    sftp::index(
+                        &mut connection,
+                        &database,
+                        &node,
+                        root,
+                        &options,
+                        &mut progress,
+                    )
+                })
+                .map_err(|error| format!("{error:#}"));
+            let _ = events.blocking_send(SourceEvent::Indexed {
+                source_key: source.source_key,
+                result,
+            });
+        });
+        Ok(())
+    }
+
+    /// Asks a running index of a source to stop; what it wrote is kept.
+    fn stop_source_index(&mut self, source_key: &str) {
+        if let Some(cancel) = self.indexing_sources.get(source_key) {
+            cancel.store(true, Ordering::Relaxed);
+            self.source_messages
+                .insert(source_key.to_owned(), "Stopping the index…".to_owned());
+        }
+    }
+
+    async fn handle_source_event(&mut self, event: SourceEvent) {
+        match event {
+            SourceEvent::Connected { source_key, result } => {
+                // Dropped when the source was disconnected meanwhile.
+                if !self.connecting_sources.remove(&source_key) {
+                    return;
+                }
+                match result {
+                    Ok((connection, host_key)) => {
+                        if let Err(error) = self.pin_host_key(&source_key, host_key).await {
+                            log::warn!("could not pin the source's host key: {error:#}");
+                        }
+                        self.source_connections.insert(source_key, connection);
+                    }
+                    Err(error) => {
+                        self.session_secrets.clear_source(&source_key);
+                        self.source_messages
+                            .insert(source_key, format!("Could not connect: {error}"));
+                    }
+                }
+            }
+            SourceEvent::Listed {
+                source_key,
+                directory,
+                result,
+            } => {
+                if self
+                    .active_remote_source()
+                    .is_none_or(|source| source.source_key != source_key)
+                {
+                    return;
+                }
+                let entries = match result {
+                    Ok(entries) => entries.into_iter().map(remote_local_entry).collect(),
+                    Err(error) => {
+                        let message = format!("Could not list {}: {error}", directory.display());
+                        self.source_messages.insert(source_key, message);
+                        Vec::new()
+                    }
+                };
+                self.remote_listings.insert(directory, Some(entries));
+            }
+            SourceEvent::Fetched {
+                source_key,
+                path,
+                result,
+            } => {
+                let Some(name) = self
+                    .sources
+                    .iter()
+                    .find(|source| source.source_key == source_key)
+                    .map(|source| source.name.clone())
+                else {
+                    return;
+                };
+                // Ignore fetches for a file the viewer has moved on from.
+                let Some(entry) = self.fetching_file.take_if(|entry| {
+                    entry.path.as_deref() == Some(path.as_path())
+                        && entry.remote_node.as_deref() == Some(name.as_str())
+                }) else {
+                    return;
+                };
+                match result {
+                    Ok((hash, cached)) => self.select_fetched(&entry, hash, &cached),
+                    Err(error) => {
+                        let message = format!("Could not download: {error}");
+                        self.show_remote_message(&entry, None, message)
+                    }
+                }
+            }
+            SourceEvent::IndexProgress { source_key, files } => {
+                let stopping = self
+                    .indexing_sources
+                    .get(&source_key)
+                    .is_none_or(|cancel| cancel.load(Ordering::Relaxed));
+                if stopping {
+                    return;
+                }
+                let name = self
+                    .sources
+                    .iter()
+                    .find(|source| source.source_key == source_key)
+                    .map_or("the source", |source| source.name.as_str());
+                let message = format!("Indexing {name}… {files} files so far.");
+                self.source_messages.insert(source_key, message);
+            }
+            SourceEvent::Indexed { source_key, result } => {
+                self.indexing_sources.remove(&source_key);
+                let name = self
+                    .sources
+                    .iter()
+                    .find(|source| source.source_key == source_key)
+                    .map_or("the source", |source| source.name.as_str());
+                let message = match result {
+                    Ok(report) if report.cancelled => format!(
+                        "Stopped indexing {name} after {} files; nothing was removed.",
+                        report.files
+                    ),
+                    Ok(report) if report.errors.is_empty() => format!(
+                        "Indexed {} files on {name}: {} read, {} removed.",
+                        report.files, report.hashed, report.removed
+                    ),
+                    Ok(report) => format!(
+                        "Indexed {} files on {name}; {} could not be read, first: {}",
+                        report.files,
+                        report.errors.len(),
+                        report.errors[0]
+                    ),
+                    Err(error) => format!("Indexing {name} failed: {error}"),
+                };
+                self.source_messages.insert(source_key, message);
+            }
+        }
+    }
+
+    /// Records the host key seen on a source's first connection. Later
+    /// connections refuse a server presenting another key.
+    async fn pin_host_key(&mut self, source_key: &str, host_key: String) -> Result<()> {
+        let Some(mut source) = self
+            .sources
+            .iter()
+            .find(|source| source.source_key == source_key)
+            .cloned()
+        else {
+            return Ok(());
+        };
+        let Some(mut config) =
+            sftp_source_config(&source).filter(|config| config.host_key.is_none())
+        else {
+            return Ok(());
+        };
+        config.host_key = Some(host_key);
+        source.config = serde_json::to_string(&config)?;
+        self.update_source(source).await?;
+        Ok(())
+    }
+
+    fn save_config(&self) {
+        if let Err(error) = config::save(&self.config, &self.config_path) {
+            log::error!("failed saving PuppyDrive settings: {error:#}");
+        }
+    }
+
+    fn add_inbox(&mut self) -> bool {
+        let name = self.new_inbox_name.trim();
+        if name.is_empty() {
+            self.inbox_error = Some("Give the Inbox a name.".to_owned());
+            return false;
+        }
+        if self
+            .config
+            .inboxes
+            .iter()
+            .any(|inbox| inbox.name.eq_ignore_ascii_case(name))
+        {
+            self.inbox_error = Some("An Inbox with that name already exists.".to_owned());
+            return false;
+        }
+        let folder = match inbox_folder_path(&self.new_inbox_folder) {
+            Ok(folder) => folder,
+            Err(error) => {
+                self.inbox_error = Some(format!("Invalid Inbox folder: {error}"));
+                return false;
             }
         };
         if let Err(error) = resolve_upload_folder(&self.configured_this_computer_root, &folder) {
@@ -2296,31 +4275,51 @@ impl App {
         );
     }
 
-    fn queue_media_index_for(&mut self, folder_id: u32, trigger: ScanTrigger) {
-        if self
-            .index_status
-            .get(&folder_id)
-            .is_some_and(|status| status.scanning || status.queued)
-        {
-            log::info!(
-                "scan requested for scanned folder {folder_id}, but it is already queued or scanning"
-            );
-            return;
+    /// Continues scans the daemon was stopped in the middle of. Checkpoints
+    /// for folders that were since disabled or removed are dropped.
+    fn resume_interrupted_scans(&mut self) {
+        let checkpoints = match self.database.scan_checkpoints() {
+            Ok(checkpoints) => checkpoints,
+            Err(error) => {
+                log::error!("failed loading scan checkpoints: {error:#}");
+                return;
+            }
+        };
+        let mut folders = Vec::new();
+        for checkpoint in checkpoints {
+            let folder = self.media_paths.iter().find(|folder| {
+                folder.id == checkpoint.scanned_folder_id
+                    && folder.enabled
+                    && checkpoint.node_id == self.local_node_id
+            });
+            match folder {
+                Some(folder) => folders.push(folder.clone()),
+                None => {
+                    if let Err(error) = self
+                        .database
+                        .delete_scan_checkpoint(checkpoint.scanned_folder_id)
+                    {
+                        log::warn!("failed dropping stale scan checkpoint: {error:#}");
+                    }
+                }
+            }
         }
-        let folders: Vec<MediaScanPath> = self
-            .media_paths
-            .iter()
-            .filter(|folder| folder.id == folder_id)
-            .cloned()
-            .collect();
         if folders.is_empty() {
-            log::warn!("scan requested for unknown scanned folder id {folder_id}");
             return;
         }
         log::info!(
-            "queueing scan for scanned folder {folder_id}: {}",
-            folders[0].path
+            "resuming interrupted scans for scanned folders {:?}",
+            folders.iter().map(|folder| folder.id).collect::<Vec<_>>()
         );
+        for folder in &folders {
+            self.index_status.insert(
+                folder.id,
+                FolderIndexStatus {
+                    queued: true,
+                    ..Default::default()
+                },
+            );
+        }
         self.indexer.request_scan(
             folders,
             self.local_node_id.clone(),
@@ -2331,52 +4330,630 @@ impl App {
                 .media
                 .max_file_size_mb
                 .saturating_mul(1_024 * 1_024),
-            trigger,
-        );
-        self.index_status.insert(
-            folder_id,
-            FolderIndexStatus {
-                queued: true,
-                ..Default::default()
-            },
+            ScanTrigger::Resumed,
         );
     }
 
-    fn stop_media_index_for(&mut self, folder_id: u32) {
-        if !self.indexer.cancel_scan(folder_id) {
-            log::debug!("stop requested for scanned folder {folder_id}, but no scan is active");
+    /// Starts the scheduled jobs that are due and returns whether any ran,
+    /// or whether one is being held back and the Settings page should say so.
+    fn run_scheduled_jobs(&mut self) -> bool {
+        let was_blocked = ScheduledJob::ALL
+            .iter()
+            .any(|job| self.scheduler.blocked_reason(*job).is_some());
+        let now = system_time_millis(SystemTime::now());
+        let conditions = self.condition_probe.conditions();
+        let due = self.scheduler.take_due(&self.config, now, conditions);
+        let is_blocked = ScheduledJob::ALL
+            .iter()
+            .any(|job| self.scheduler.blocked_reason(*job).is_some());
+        for job in &due {
+            log::info!("running scheduled job {}", job.as_str());
+            if let Err(error) = self.database.record_scheduled_job_run(job.as_str(), now) {
+                log::warn!("failed recording scheduled job run: {error:#}");
+            }
+            match job {
+                ScheduledJob::Rescan => self.queue_scheduled_rescans(|_| true),
+                ScheduledJob::FallbackRescan => {
+                    let watched = self.watched_media_paths.clone();
+                    self.queue_scheduled_rescans(|path| !watched.contains(&path.to_path_buf()));
+                }
+                ScheduledJob::Maintenance => self.indexer.request_maintenance(),
+                ScheduledJob::Scrub => self.request_scrub(),
+                ScheduledJob::PeerSync => {
+                    for peer in self.peers.clone() {
+                        self.sync_peer(peer);
+                    }
+                }
+                ScheduledJob::Backup => self.start_backup(),
+                ScheduledJob::Prune => {
+                    if let Err(error) = self.start_prune(false) {
+                        log::info!("not pruning snapshots: {error:#}");
+                    }
+                }
+            }
+        }
+        !due.is_empty() || was_blocked != is_blocked
+    }
+
+    fn save_backup_destination(&mut self) {
+        let destination = self.backup_destination.trim();
+        let destination = if destination.is_empty() {
+            None
+        } else {
+            let path = PathBuf::from(destination);
+            if !path.is_absolute() || !path.is_dir() {
+                self.backup_message = Some(format!("{destination} is not a folder."));
+                return;
+            }
+            Some(path)
+        };
+        let mut updated = self.config.clone();
+        updated.backup.destination = destination;
+        if let Err(error) = config::save(&updated, &self.config_path) {
+            log::error!("failed saving backup destination: {error:#}");
+            self.backup_message = Some("Could not save the backup destination.".to_owned());
             return;
         }
-        let status = self.index_status.entry(folder_id).or_default();
-        status.stopping = true;
-        status.message = None;
-        log::info!("stop requested for scanned folder {folder_id}");
+        self.config = updated;
+        self.backup_message = None;
+        self.lock_backup();
     }
 
-    fn handle_indexer_event(&mut self, event: IndexerEvent) {
+    /// Unlocks the store at the backup destination with `passphrase` on the
+    /// backup worker, setting up a new store protected by it if there is
+    /// none yet.
+    fn unlock_backup(&mut self, passphrase: SecretString) -> Result<()> {
+        if self.backup_unlocking {
+            anyhow::bail!("the backup is already being unlocked");
+        }
+        if passphrase.expose_secret().is_empty() {
+            anyhow::bail!("enter the backup passphrase");
+        }
+        let destination = self
+            .config
+            .backup
+            .destination
+            .clone()
+            .context("no backup destination is set")?;
+        self.backup.request_unlock(destination, passphrase);
+        self.backup_unlocking = true;
+        self.backup_message = None;
+        Ok(())
+    }
+
+    /// Re-encrypts the unlocked store under a fresh key, which `passphrase`
+    /// unlocks from then on.
+    fn rotate_backup_key(&mut self, passphrase: SecretString) -> Result<()> {
+        if self.backup_key_rotating {
+            anyhow::bail!("the backup key is already being rotated");
+        }
+        if passphrase.expose_secret().is_empty() {
+            anyhow::bail!("enter the new backup passphrase");
+        }
+        let destination = self
+            .config
+            .backup
+            .destination
+            .clone()
+            .context("no backup destination is set")?;
+        let keys = self
+            .backup_keys
+            .clone()
+            .context("unlock the backup before rotating its key")?;
+        self.backup
+            .request_key_rotation(destination, keys, passphrase);
+        self.backup_key_rotating = true;
+        self.backup_message = None;
+        Ok(())
+    }
+
+    /// Forgets the backup keys; runs already under way keep theirs.
+    fn lock_backup(&mut self) {
+        self.backup_keys = None;
+        self.open_snapshot = None;
+        self.snapshots.clear();
+    }
+
+    fn start_backup(&mut self) {
+        if self.backup_running {
+            return;
+        }
+        let Some(destination) = self.config.backup.destination.clone() else {
+            self.backup_message = Some("Choose a backup destination first.".to_owned());
+            return;
+        };
+        let Some(keys) = self.backup_keys.clone() else {
+            self.backup_message = Some("Unlock the backup with its passphrase first.".to_owned());
+            return;
+        };
+        self.backup.request_backup(BackupRequest {
+            destination,
+            keys,
+            folders: self.media_paths.clone(),
+            node_id: self.local_node_id.clone(),
+            device_name: self.config.general.device_name.clone(),
+        });
+        self.backup_running = true;
+        self.backup_progress = Some((0, 0, 0));
+        self.backup_message = None;
+    }
+
+    fn handle_backup_event(&mut self, event: BackupEvent) {
         match event {
-            IndexerEvent::Started { folder_ids } => {
-                log::info!("indexer started scan for folders {folder_ids:?}");
-                for folder_id in folder_ids {
-                    let stopping = self
-                        .index_status
-                        .get(&folder_id)
-                        .is_some_and(|status| status.stopping);
-                    self.index_status.insert(
-                        folder_id,
-                        FolderIndexStatus {
-                            scanning: true,
-                            queued: false,
-                            stopping,
-                            ..Default::default()
-                        },
-                    );
+            BackupEvent::Progress {
+                files_done,
+                files_total,
+                bytes_written,
+            } => self.backup_progress = Some((files_done, files_total, bytes_written)),
+            BackupEvent::Finished { snapshot } => {
+                self.backup_running = false;
+                self.backup_progress = None;
+                self.backup_message = Some(format!(
+                    "Backed up {} files, adding {}.",
+                    snapshot.file_count,
+                    format_size(snapshot.bytes_written)
+                ));
+                self.reload_snapshots();
+            }
+            BackupEvent::Failed { message } => {
+                self.backup_running = false;
+                self.backup_progress = None;
+                self.backup_message = Some(format!("Backup failed: {message}"));
+            }
+            BackupEvent::RestoreProgress {
+                files_done,
+                files_total,
+            } => self.restore_progress = Some((files_done, files_total)),
+            BackupEvent::Restored { summary } => {
+                self.restore_running = false;
+                self.restore_progress = None;
+                let mut message = format!(
+                    "Restored {} files to {}.",
+                    summary.restored,
+                    summary.target.display()
+                );
+                for (count, label) in [
+                    (summary.renamed, "restored as copies"),
+                    (summary.skipped, "kept as they were"),
+                    (summary.failed, "could not be restored"),
+                ] {
+                    if count > 0 {
+                        message.push_str(&format!(" {count} {label}."));
+                    }
                 }
+                self.restore_message = Some(message);
+                self.last_restore = Some(summary);
             }
-            IndexerEvent::Progress {
-                folder_id,
-                directories_scanned,
-                media_files_indexed,
+            BackupEvent::RestoreFailed { message } => {
+                self.restore_running = false;
+                self.restore_progress = None;
+                self.restore_message = Some(format!("Restore failed: {message}"));
+            }
+            BackupEvent::Pruned { report } => {
+                self.prune_running = false;
+                self.prune_message = Some(format!(
+                    "{} {} snapshots and {} stored contents, reclaiming {}.",
+                    if report.dry_run {
+                        "Pruning would remove"
+                    } else {
+                        "Removed"
+                    },
+                    report.snapshots.len(),
+                    report.objects.len(),
+                    format_size(report.bytes)
+                ));
+                if !report.dry_run {
+                    self.reload_snapshots();
+                    let removed = |snapshot: &OpenSnapshot| {
+                        report
+                            .snapshots
+                            .iter()
+                            .any(|pruned| pruned.id == snapshot.manifest.id)
+                    };
+                    if self.open_snapshot.as_ref().is_some_and(removed) {
+                        self.open_snapshot = None;
+                    }
+                }
+                self.last_prune = Some(report);
+            }
+            BackupEvent::PruneFailed { message } => {
+                self.prune_running = false;
+                self.prune_message = Some(format!("Prune failed: {message}"));
+            }
+            BackupEvent::Unlocked { keys } => {
+                self.backup_unlocking = false;
+                self.backup_keys = Some(keys);
+                self.backup_message = Some("Backup unlocked for this session.".to_owned());
+                self.reload_snapshots();
+            }
+            BackupEvent::UnlockFailed { message } => {
+                self.backup_unlocking = false;
+                self.backup_message = Some(format!("Could not unlock the backup: {message}"));
+            }
+            BackupEvent::KeyRotated { keys } => {
+                self.backup_key_rotating = false;
+                self.backup_keys = Some(keys);
+                self.backup_message = Some(
+                    "Backup re-encrypted under a new key; use the new passphrase from now on."
+                        .to_owned(),
+                );
+                self.reload_snapshots();
+            }
+            BackupEvent::KeyRotationFailed { message } => {
+                self.backup_key_rotating = false;
+                self.backup_message = Some(format!("Key rotation failed: {message}"));
+            }
+        }
+    }
+
+    /// Prunes snapshots past `BackupConfig.retention` on the backup worker,
+    /// or with `dry_run` only reports what a prune would remove.
+    fn start_prune(&mut self, dry_run: bool) -> Result<()> {
+        if self.prune_running {
+            anyhow::bail!("a prune is already running");
+        }
+        let destination = self
+            .config
+            .backup
+            .destination
+            .clone()
+            .context("no backup destination is set")?;
+        let keys = self
+            .backup_keys
+            .clone()
+            .context("unlock the backup with its passphrase first")?;
+        self.backup.request_prune(PruneRequest {
+            destination,
+            keys,
+            retention: self.config.backup.retention,
+            dry_run,
+        });
+        self.prune_running = true;
+        self.prune_message = None;
+        Ok(())
+    }
+
+    fn backup_store(&self) -> Result<BackupStore> {
+        let destination = self
+            .config
+            .backup
+            .destination
+            .as_ref()
+            .context("no backup destination is set")?;
+        let keys = self
+            .backup_keys
+            .clone()
+            .context("unlock the backup with its passphrase first")?;
+        BackupStore::open(destination, keys)
+    }
+
+    /// Restores `subtree` of one folder in a snapshot on the backup worker.
+    /// `target` is the folder to restore below, or `None` for the original
+    /// location.
+    fn start_restore(
+        &mut self,
+        snapshot_id: String,
+        folder: usize,
+        subtree: PathBuf,
+        target: Option<PathBuf>,
+        conflicts: ConflictPolicy,
+    ) -> Result<()> {
+        if self.restore_running {
+            anyhow::bail!("a restore is already running");
+        }
+        let destination = self
+            .config
+            .backup
+            .destination
+            .clone()
+            .context("no backup destination is set")?;
+        if target.as_ref().is_some_and(|target| !target.is_absolute()) {
+            anyhow::bail!("restore to an absolute path");
+        }
+        let keys = self
+            .backup_keys
+            .clone()
+            .context("unlock the backup with its passphrase first")?;
+        self.backup.request_restore(RestoreRequest {
+            destination,
+            keys,
+            snapshot_id,
+            folder,
+            subtree,
+            target,
+            conflicts,
+        });
+        self.restore_running = true;
+        self.restore_progress = Some((0, 0));
+        self.restore_message = None;
+        Ok(())
+    }
+
+    /// Restores an entry of the directory open in the snapshot browser, or
+    /// the directory itself when `index` is `None`.
+    fn restore_from_browser(&mut self, index: Option<usize>) {
+        let Some(snapshot) = &self.open_snapshot else {
+            return;
+        };
+        let selection = match (snapshot.folder, index) {
+            (None, Some(folder)) => Some((folder, PathBuf::new())),
+            (None, None) => None,
+            (Some(folder), None) => Some((folder, snapshot.directory.clone())),
+            (Some(folder), Some(index)) => snapshot
+                .manifest
+                .entries(folder, &snapshot.directory)
+                .into_iter()
+                .nth(index)
+                .map(|entry| (folder, snapshot.directory.join(entry.name))),
+        };
+        let Some((folder, subtree)) = selection else {
+            return;
+        };
+        let snapshot_id = snapshot.manifest.id.clone();
+        let target = Some(self.restore_target.trim())
+            .filter(|target| !target.is_empty())
+            .map(PathBuf::from);
+        if let Err(error) =
+            self.start_restore(snapshot_id, folder, subtree, target, self.restore_conflicts)
+        {
+            self.restore_message = Some(format!("{error:#}"));
+        }
+    }
+
+    fn reload_snapshots(&mut self) {
+        self.snapshots = self
+            .backup_store()
+            .and_then(|store| store.snapshots())
+            .unwrap_or_else(|error| {
+                log::debug!("no snapshots to list: {error:#}");
+                Vec::new()
+            });
+    }
+
+    fn open_snapshot(&mut self, index: usize) {
+        let Some(snapshot) = self.snapshots.get(index) else {
+            return;
+        };
+        match self
+            .backup_store()
+            .and_then(|store| store.manifest(&snapshot.id))
+        {
+            Ok(manifest) => {
+                self.open_snapshot = Some(OpenSnapshot {
+                    folder: (manifest.folders.len() == 1).then_some(0),
+                    manifest,
+                    directory: PathBuf::new(),
+                });
+            }
+            Err(error) => self.backup_message = Some(format!("{error:#}")),
+        }
+    }
+
+    fn snapshot_up(&mut self) {
+        let Some(snapshot) = &mut self.open_snapshot else {
+            return;
+        };
+        if !snapshot.directory.pop() {
+            snapshot.folder = None;
+        }
+    }
+
+    fn open_snapshot_entry(&mut self, index: usize) {
+        let Some(snapshot) = &mut self.open_snapshot else {
+            return;
+        };
+        let Some(folder) = snapshot.folder else {
+            if index < snapshot.manifest.folders.len() {
+                snapshot.folder = Some(index);
+            }
+            return;
+        };
+        if let Some(entry) = snapshot
+            .manifest
+            .entries(folder, &snapshot.directory)
+            .into_iter()
+            .nth(index)
+            .filter(|entry| entry.is_directory)
+        {
+            snapshot.directory.push(entry.name);
+        }
+    }
+
+    /// Verifies the next slice of files that are due to be read back.
+    fn request_scrub(&mut self) {
+        let interval = self.config.media.scrub_interval_days.max(1) as i64 * 86_400_000;
+        let now = system_time_millis(SystemTime::now());
+        self.indexer.request_scrub(
+            self.media_paths.clone(),
+            self.local_node_id.clone(),
+            now - interval,
+        );
+    }
+
+    fn reload_scrub_report(&mut self) {
+        match self.database.scrub_alerts(&self.local_node_id) {
+            Ok(alerts) => self.scrub_alerts = alerts,
+            Err(error) => log::error!("failed loading scrub alerts: {error:#}"),
+        }
+        let interval = self.config.media.scrub_interval_days.max(1) as i64 * 86_400_000;
+        let since = system_time_millis(SystemTime::now()) - interval;
+        match self.database.scrub_summary(&self.local_node_id, since) {
+            Ok(summary) => self.scrub_summary = summary,
+            Err(error) => log::error!("failed loading scrub summary: {error:#}"),
+        }
+    }
+
+    fn reload_duplicates(&mut self) {
+        self.indexer.request_duplicates(self.local_node_id.clone());
+    }
+
+    /// Keeps the copy at `copy_index`, counted across the listed groups, and
+    /// applies `action` to the other copies of its group.
+    fn resolve_duplicates(&mut self, copy_index: usize, action: DuplicateAction) {
+        let mut remaining = copy_index;
+        let Some(group) = self
+            .duplicate_groups
+            .iter()
+            .take(DUPLICATE_GROUPS_SHOWN)
+            .find(|group| {
+                if remaining < group.copies.len() {
+                    return true;
+                }
+                remaining -= group.copies.len();
+                false
+            })
+        else {
+            return;
+        };
+        self.indexer
+            .request_dedupe(self.media_paths.clone(), group, remaining, action);
+        self.duplicate_message = Some("Verifying copies…".to_owned());
+    }
+
+    /// Queues a scheduled scan of every enabled Scanned folder that is
+    /// reachable and matches `include`.
+    fn queue_scheduled_rescans(&mut self, include: impl Fn(&Path) -> bool) {
+        let folder_ids = self
+            .media_paths
+            .iter()
+            .filter(|folder| folder.enabled)
+            .filter(|folder| {
+                let path = Path::new(&folder.path);
+                path.is_dir() && include(path)
+            })
+            .map(|folder| folder.id)
+            .collect::<Vec<_>>();
+        for folder_id in folder_ids {
+            self.queue_media_index_for(folder_id, ScanTrigger::Scheduled);
+        }
+    }
+
+    fn queue_media_index_for(&mut self, folder_id: u32, trigger: ScanTrigger) -> bool {
+        if self
+            .index_status
+            .get(&folder_id)
+            .is_some_and(|status| status.scanning || status.queued)
+        {
+            log::info!(
+                "scan requested for scanned folder {folder_id}, but it is already queued or scanning"
+            );
+            return false;
+        }
+        let folders: Vec<MediaScanPath> = self
+            .media_paths
+            .iter()
+            .filter(|folder| folder.id == folder_id)
+            .cloned()
+            .collect();
+        if folders.is_empty() {
+            log::warn!("scan requested for unknown scanned folder id {folder_id}");
+            return false;
+        }
+        log::info!(
+            "queueing scan for scanned folder {folder_id}: {}",
+            folders[0].path
+        );
+        self.indexer.request_scan(
+            folders,
+            self.local_node_id.clone(),
+            self.config.media.max_items,
+            self.config.media.max_directories,
+            self.config.media.ignored_directory_names.clone(),
+            self.config
+                .media
+                .max_file_size_mb
+                .saturating_mul(1_024 * 1_024),
+            trigger,
+        );
+        self.index_status.insert(
+            folder_id,
+            FolderIndexStatus {
+                queued: true,
+                ..Default::default()
+            },
+        );
+        true
+    }
+
+    fn apply_watched_change(&mut self, change: WatchedChange) {
+        let (folder_id, paths) = match change {
+            WatchedChange::Rescan { folder_id } => {
+                self.queue_media_index_for(folder_id, ScanTrigger::FilesystemChange);
+                return;
+            }
+            WatchedChange::Paths { folder_id, paths } => (folder_id, paths),
+        };
+        if self
+            .index_status
+            .get(&folder_id)
+            .is_some_and(|status| status.queued)
+        {
+            log::debug!(
+                "{} changed paths in scanned folder {folder_id} are covered by its queued scan",
+                paths.len()
+            );
+            return;
+        }
+        let Some(folder) = self
+            .media_paths
+            .iter()
+            .find(|folder| folder.id == folder_id)
+            .cloned()
+        else {
+            return;
+        };
+        log::debug!(
+            "queueing {} changed paths in scanned folder {folder_id}",
+            paths.len()
+        );
+        self.indexer.request_paths(
+            folder,
+            self.local_node_id.clone(),
+            paths,
+            self.config.media.ignored_directory_names.clone(),
+            self.config
+                .media
+                .max_file_size_mb
+                .saturating_mul(1_024 * 1_024),
+        );
+    }
+
+    fn stop_media_index_for(&mut self, folder_id: u32) -> bool {
+        if !self.indexer.cancel_scan(folder_id) {
+            log::debug!("stop requested for scanned folder {folder_id}, but no scan is active");
+            return false;
+        }
+        let status = self.index_status.entry(folder_id).or_default();
+        status.stopping = true;
+        status.message = None;
+        log::info!("stop requested for scanned folder {folder_id}");
+        true
+    }
+
+    fn handle_indexer_event(&mut self, event: IndexerEvent) {
+        match event {
+            IndexerEvent::Started { folder_ids } => {
+                log::info!("indexer started scan for folders {folder_ids:?}");
+                for folder_id in folder_ids {
+                    let stopping = self
+                        .index_status
+                        .get(&folder_id)
+                        .is_some_and(|status| status.stopping);
+                    self.index_status.insert(
+                        folder_id,
+                        FolderIndexStatus {
+                            scanning: true,
+                            queued: false,
+                            stopping,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+            IndexerEvent::Progress {
+                folder_id,
+                directories_scanned,
+                media_files_indexed,
                 current_path,
             } => {
                 let status = self.index_status.entry(folder_id).or_default();
@@ -2388,6 +4965,7 @@ impl App {
             IndexerEvent::FolderFinished { history } => {
                 let folder_id = history.scanned_folder_id;
                 log::info!("indexer finished scanned folder {folder_id}");
+                self.scheduler.note_index_change();
                 if let Some(status) = self.index_status.get_mut(&folder_id) {
                     status.scanning = false;
                     status.stopping = false;
@@ -2424,6 +5002,65 @@ impl App {
                 }
                 self.reload_media_cache();
             }
+            IndexerEvent::PathsIndexed {
+                folder_id,
+                updated,
+                removed,
+            } => {
+                log::info!(
+                    "indexer updated {updated} and removed {removed} paths in scanned folder {folder_id}"
+                );
+                self.scheduler.note_index_change();
+                self.reload_media_cache();
+                if self.active_page == AppPage::Duplicates {
+                    self.reload_duplicates();
+                }
+            }
+            IndexerEvent::RescanNeeded { folder_id } => {
+                self.queue_media_index_for(folder_id, ScanTrigger::FilesystemChange);
+            }
+            IndexerEvent::ScrubFinished { verified, failed } => {
+                log::info!("scrub verified {verified} files, {failed} failed verification");
+                self.reload_scrub_report();
+            }
+            IndexerEvent::DuplicatesResolved {
+                action,
+                changed,
+                reclaimed_bytes,
+                errors,
+            } => {
+                let mut message = match action {
+                    DuplicateAction::Trash => format!(
+                        "Moved {} copies to the trash. {} is freed once the trash is emptied.",
+                        changed.len(),
+                        format_size(reclaimed_bytes)
+                    ),
+                    DuplicateAction::Hardlink => format!(
+                        "Replaced {} copies with hardlinks, freeing {}.",
+                        changed.len(),
+                        format_size(reclaimed_bytes)
+                    ),
+                };
+                if !errors.is_empty() {
+                    message.push_str(&format!(
+                        " {} copies were left alone: {}",
+                        errors.len(),
+                        errors.join("; ")
+                    ));
+                }
+                self.duplicate_message = Some(message);
+                let mut by_folder = HashMap::<u32, Vec<PathBuf>>::new();
+                for (folder_id, path) in changed {
+                    by_folder.entry(folder_id).or_default().push(path);
+                }
+                for (folder_id, paths) in by_folder {
+                    self.apply_watched_change(WatchedChange::Paths { folder_id, paths });
+                }
+            }
+            IndexerEvent::DuplicatesLoaded { mut groups } => {
+                sort_duplicate_groups(&mut groups, &self.duplicate_sort);
+                self.duplicate_groups = groups;
+            }
             IndexerEvent::Failed { message } => {
                 log::error!("Media indexer: {message}");
                 for status in self
@@ -2472,6 +5109,9 @@ impl App {
                         Vec::new()
                     });
                 self.refresh_filtered_files(false);
+                self.refresh_content_search();
+                self.refresh_file_search();
+                self.reload_similar_images();
             }
             (Err(error), _) | (_, Err(error)) => {
                 log::error!("failed loading persistent Media index: {error:#}")
@@ -2479,6 +5119,59 @@ impl App {
         }
     }
 
+    /// Groups look-alike images while the Media page shows them; other views
+    /// skip the work.
+    fn reload_similar_images(&mut self) {
+        if self.media_view_mode != "similar" {
+            self.similar_images.clear();
+            return;
+        }
+        let max_distance = self.similar_image_distance.parse().unwrap_or(6);
+        match self
+            .database
+            .similar_images(&self.local_node_id, max_distance)
+        {
+            Ok(groups) => self.similar_images = groups,
+            Err(error) => log::error!("failed loading similar images: {error:#}"),
+        }
+    }
+
+    /// Keeps the image at `image_index`, counted across the listed groups,
+    /// and moves every copy of the other images in its group to the trash.
+    fn keep_similar_image(&mut self, image_index: usize) {
+        let mut remaining = image_index;
+        let Some(group) = self
+            .similar_images
+            .iter()
+            .take(SIMILAR_IMAGE_GROUPS_SHOWN)
+            .find(|group| {
+                if remaining < group.len() {
+                    return true;
+                }
+                remaining -= group.len();
+                false
+            })
+        else {
+            return;
+        };
+        let Some(keep) = group[remaining].copies.first().cloned() else {
+            return;
+        };
+        let others = group
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != remaining)
+            .flat_map(|(_, image)| image.copies.iter().cloned())
+            .collect();
+        self.indexer.request_dedupe_copies(
+            self.media_paths.clone(),
+            keep,
+            others,
+            DuplicateAction::Trash,
+        );
+        self.duplicate_message = Some("Verifying copies…".to_owned());
+    }
+
     fn refresh_selected_scanned_folder_history(&mut self) {
         let Some(folder_id) = self.selected_scanned_folder_id else {
             return;
@@ -2526,8 +5219,16 @@ impl App {
         self.tree_view_root = root.clone();
         self.expanded_local_dirs.clear();
         self.expanded_local_dirs.insert(root.clone());
+        self.remote_listings.clear();
+        // `/source-files/` only ever serves a local folder; remote files are
+        // shown from the content cache.
+        let served = if self.active_remote_source().is_some() {
+            self.configured_this_computer_root.clone()
+        } else {
+            root
+        };
         if let Ok(mut served_root) = self.active_files_root.write() {
-            *served_root = root;
+            *served_root = served;
         }
     }
 
@@ -2539,6 +5240,10 @@ impl App {
         else {
             return;
         };
+        if let Some(config) = sftp_source_config(source) {
+            self.activate_files_root(PathBuf::from(config.root), Some(id));
+            return;
+        }
         let Some(path) = local_source_path(source) else {
             return;
         };
@@ -2661,13 +5366,28 @@ impl App {
     }
 
     async fn add_media_path(&mut self, path: PathBuf) {
-        let path = match fs::canonicalize(&path) {
-            Ok(path) if path.is_dir() => path,
-            _ => {
-                self.media_path_error =
-                    Some(format!("{} is not an accessible directory", path.display()));
-                return;
+        match self
+            .insert_scanned_folder(path, true, MediaScanPath::default_indexers())
+            .await
+        {
+            Ok(_) => {
+                self.new_media_path.clear();
+                self.media_path_error = None;
             }
+            Err(error) => self.media_path_error = Some(error),
+        }
+    }
+
+    /// Adds a Scanned folder, returning a user-facing message when it cannot be saved.
+    async fn insert_scanned_folder(
+        &mut self,
+        path: PathBuf,
+        enabled: bool,
+        indexers: String,
+    ) -> std::result::Result<MediaScanPath, String> {
+        let path = match fs::canonicalize(&path) {
+            Ok(path) if path.is_dir() => path,
+            _ => return Err(format!("{} is not an accessible directory", path.display())),
         };
         let path_string = path.to_string_lossy().into_owned();
         if self
@@ -2675,53 +5395,126 @@ impl App {
             .iter()
             .any(|stored| stored.path == path_string)
         {
-            self.media_path_error = Some("That folder is already included.".to_owned());
-            return;
+            return Err("That folder is already included.".to_owned());
         }
         let row = MediaScanPath {
             id: 0,
             path: path_string,
-            enabled: true,
-            indexers: r#"["media"]"#.to_owned(),
+            enabled,
+            indexers,
+            include_globs: "[]".to_owned(),
+            exclude_globs: "[]".to_owned(),
         };
         match self.database.save_media_path(row).await {
             Ok(row) => {
-                self.media_paths.push(row);
-                self.new_media_path.clear();
-                self.media_path_error = None;
+                self.media_paths.push(row.clone());
                 self.media_paths_changed();
+                Ok(row)
             }
             Err(error) => {
                 log::error!("failed saving Scanned folder: {error:#}");
-                self.media_path_error = Some("Could not save the Scanned folder.".to_owned());
+                Err("Could not save the Scanned folder.".to_owned())
             }
         }
     }
 
     async fn toggle_media_path(&mut self, id: u32) {
-        let Some(index) = self.media_paths.iter().position(|path| path.id == id) else {
+        let Some(mut updated) = self.media_paths.iter().find(|path| path.id == id).cloned() else {
             return;
         };
-        let mut updated = self.media_paths[index].clone();
         updated.enabled = !updated.enabled;
-        match self.database.save_media_path(updated).await {
-            Ok(updated) => {
-                self.media_paths[index] = updated;
-                self.media_paths_changed();
-            }
-            Err(error) => log::error!("failed updating Scanned folder: {error:#}"),
+        if let Err(error) = self.update_scanned_folder(updated).await {
+            log::error!("failed updating Scanned folder: {error:#}");
+        }
+    }
+
+    async fn toggle_folder_indexer(&mut self, folder_id: u32, index: usize) {
+        let Some(indexer) = indexer::registered_indexers().get(index) else {
+            return;
+        };
+        let Some(mut updated) = self
+            .media_paths
+            .iter()
+            .find(|path| path.id == folder_id)
+            .cloned()
+        else {
+            return;
+        };
+        let mut names = updated.indexer_names();
+        if names.iter().any(|name| name == indexer.name()) {
+            names.retain(|name| name != indexer.name());
+        } else {
+            names.push(indexer.name().to_owned());
+        }
+        updated.indexers = serde_json::to_string(&names).expect("serialize indexers");
+        if let Err(error) = self.update_scanned_folder(updated).await {
+            log::error!("failed updating Scanned folder indexers: {error:#}");
+        }
+    }
+
+    fn reset_folder_ignore_rule_drafts(&mut self) {
+        let folder = self
+            .selected_scanned_folder_id
+            .and_then(|id| self.media_paths.iter().find(|folder| folder.id == id));
+        self.folder_include_globs = folder
+            .map(|folder| folder.include_glob_list().join(", "))
+            .unwrap_or_default();
+        self.folder_exclude_globs = folder
+            .map(|folder| folder.exclude_glob_list().join(", "))
+            .unwrap_or_default();
+    }
+
+    /// Saves the drafted include and exclude globs and rescans the folder,
+    /// since files the new rules ignore or let through are only picked up by
+    /// a scan.
+    async fn save_folder_ignore_rules(&mut self, folder_id: u32) {
+        let Some(mut updated) = self
+            .media_paths
+            .iter()
+            .find(|path| path.id == folder_id)
+            .cloned()
+        else {
+            return;
+        };
+        updated.include_globs =
+            serde_json::to_string(&ignore_rules::parse_glob_list(&self.folder_include_globs))
+                .expect("serialize include globs");
+        updated.exclude_globs =
+            serde_json::to_string(&ignore_rules::parse_glob_list(&self.folder_exclude_globs))
+                .expect("serialize exclude globs");
+        if let Err(error) = self.update_scanned_folder(updated).await {
+            log::error!("failed updating Scanned folder ignore rules: {error:#}");
+            return;
         }
+        self.reset_folder_ignore_rule_drafts();
+        self.queue_media_index_for(folder_id, ScanTrigger::ManualFolder);
+    }
+
+    async fn update_scanned_folder(&mut self, folder: MediaScanPath) -> Result<MediaScanPath> {
+        let index = self
+            .media_paths
+            .iter()
+            .position(|path| path.id == folder.id)
+            .context("Scanned folder not found")?;
+        let updated = self.database.save_media_path(folder).await?;
+        self.media_paths[index] = updated.clone();
+        self.media_paths_changed();
+        Ok(updated)
     }
 
     fn remove_media_path(&mut self, id: u32) {
-        match self.database.delete_media_path(id) {
-            Ok(true) => {
-                self.media_paths.retain(|path| path.id != id);
-                self.media_paths_changed();
-            }
-            Ok(false) => {}
-            Err(error) => log::error!("failed forgetting Scanned folder: {error:#}"),
+        if let Err(error) = self.forget_scanned_folder(id) {
+            log::error!("failed forgetting Scanned folder: {error:#}");
+        }
+    }
+
+    fn forget_scanned_folder(&mut self, id: u32) -> Result<bool> {
+        if !self.database.delete_media_path(id)? {
+            return Ok(false);
         }
+        self.media_paths.retain(|path| path.id != id);
+        self.media_paths_changed();
+        Ok(true)
     }
 
     fn media_paths_changed(&mut self) {
@@ -2842,20 +5635,95 @@ fn media_sort_key_name(key: MediaSortKey) -> &'static str {
         MediaSortKey::Size => "sizeValue",
         MediaSortKey::Replicas => "replicaCount",
         MediaSortKey::Modified => "modifiedValue",
+        MediaSortKey::Captured => "capturedValue",
+        MediaSortKey::Camera => "camera",
+        MediaSortKey::Lens => "lens",
+        MediaSortKey::Resolution => "pixelsValue",
+        MediaSortKey::Title => "title",
+        MediaSortKey::Artist => "artist",
+        MediaSortKey::Album => "albumValue",
+        MediaSortKey::Duration => "durationValue",
     }
 }
 
 fn media_sort_key_from_payload(payload: &serde_json::Value) -> Option<MediaSortKey> {
-    let key = payload.get("key")?.as_str()?;
+    media_sort_key_from_name(payload.get("key")?.as_str()?)
+}
+
+fn media_sort_key_from_name(key: &str) -> Option<MediaSortKey> {
     match key {
         "name" => Some(MediaSortKey::Name),
         "sizeValue" => Some(MediaSortKey::Size),
         "replicaCount" => Some(MediaSortKey::Replicas),
         "modifiedValue" => Some(MediaSortKey::Modified),
+        "capturedValue" => Some(MediaSortKey::Captured),
+        "camera" => Some(MediaSortKey::Camera),
+        "lens" => Some(MediaSortKey::Lens),
+        "pixelsValue" => Some(MediaSortKey::Resolution),
+        "title" => Some(MediaSortKey::Title),
+        "artist" => Some(MediaSortKey::Artist),
+        "albumValue" => Some(MediaSortKey::Album),
+        "durationValue" => Some(MediaSortKey::Duration),
         _ => None,
     }
 }
 
+/// Capture time for photos that have one, otherwise the file's modified time,
+/// so a capture-date sort still places everything on one timeline.
+fn media_timeline_millis(entry: &LocalEntry, photo: Option<&PhotoMetadata>) -> Option<i64> {
+    photo
+        .and_then(|photo| photo.captured_at)
+        .or_else(|| entry.modified_at.map(system_time_millis))
+}
+
+/// Track position such as `3` or `2-07` on multi-disc albums.
+fn audio_track_label(tags: &AudioMetadata) -> String {
+    match (tags.disc_total.or(tags.disc_number), tags.track_number) {
+        (Some(discs), Some(track)) if discs > 1 => {
+            format!("{}-{track:02}", tags.disc_number.unwrap_or(1))
+        }
+        (_, Some(track)) => track.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Album credit, album, disc and track, with untagged files last.
+fn album_track_order(tags: Option<&AudioMetadata>) -> (bool, String, String, u32, u32) {
+    let Some(tags) = tags else {
+        return (true, String::new(), String::new(), 0, 0);
+    };
+    (
+        tags.album.is_none(),
+        tags.album_credit().unwrap_or_default().to_lowercase(),
+        tags.album.as_deref().unwrap_or_default().to_lowercase(),
+        tags.disc_number.unwrap_or(0),
+        tags.track_number.unwrap_or(0),
+    )
+}
+
+/// Sorts missing values after present ones, case-insensitively.
+fn photo_sort_text(value: Option<String>) -> (bool, Option<String>) {
+    (value.is_none(), value.map(|value| value.to_lowercase()))
+}
+
+fn photo_pixels(photo: Option<&PhotoMetadata>) -> u64 {
+    photo
+        .and_then(PhotoMetadata::display_dimensions)
+        .map_or(0, |(width, height)| u64::from(width) * u64::from(height))
+}
+
+fn photo_filter_matches(filter: &str, photo: Option<&PhotoMetadata>) -> bool {
+    let dimensions = photo.and_then(PhotoMetadata::display_dimensions);
+    match filter {
+        "dated" => photo.is_some_and(|photo| photo.captured_at.is_some()),
+        "located" => photo.and_then(PhotoMetadata::location).is_some(),
+        "unlocated" => photo.and_then(PhotoMetadata::location).is_none(),
+        "landscape" => dimensions.is_some_and(|(width, height)| width > height),
+        "portrait" => dimensions.is_some_and(|(width, height)| height > width),
+        _ => true,
+    }
+}
+
 fn settings_section(title: &str, rows: impl IntoIterator<Item = Item>) -> Item {
     vstack([
         text(title).color("#0f6175").padding_bottom(2),
@@ -2875,6 +5743,7 @@ impl App {
                         .scanned_folder_id
                         .is_some_and(|id| id.to_string() == self.files_scanned_folder_filter)
             })
+            .filter(|entry| self.files_mime_filter != "mismatched" || entry.mime_type_mismatch())
             .map(FileListingEntry::from_indexed)
             .filter(|entry| match self.files_mime_filter.as_str() {
                 "images" => entry.is_image(),
@@ -2899,7 +5768,9 @@ impl App {
 
     fn file_viewer_entry_at(&self, index: usize) -> Option<LocalEntry> {
         match &self.file_viewer_entries {
-            FileViewerEntries::Local(entries) => entries.get(index).cloned(),
+            FileViewerEntries::Local(entries) | FileViewerEntries::Remote(entries) => {
+                entries.get(index).cloned()
+            }
             FileViewerEntries::Indexed => self
                 .filtered_files
                 .get(index)
@@ -2912,11 +5783,11 @@ impl App {
     }
 
     fn open_indexed_file(&mut self, index: usize) {
-        let Some(entry) = self
-            .filtered_files
-            .get(index)
-            .and_then(FileListingEntry::as_local_entry)
-        else {
+        let Some(listing) = self.filtered_files.get(index).cloned() else {
+            return;
+        };
+        let Some(entry) = listing.as_local_entry() else {
+            self.open_remote_entry(&listing);
             return;
         };
         self.file_viewer_entries = FileViewerEntries::Indexed;
@@ -2924,6 +5795,8 @@ impl App {
         self.file_viewer_expanded = false;
         if self.select_viewer_entry(&entry) {
             self.file_viewer_index = Some(index);
+        } else {
+            self.open_remote_entry(&listing);
         }
     }
 
@@ -2973,6 +5846,26 @@ impl App {
             locations.push(format!("{status} — {source}: {}", path.display()));
         }
 
+        if let Some(hash) = entry.hash.as_deref() {
+            match self.database.file_replica_nodes(hash) {
+                Ok(replicas) => locations.extend(
+                    replicas
+                        .into_iter()
+                        .map(|(device, path)| format!("◇ Device — {device}: {}", path.display())),
+                ),
+                Err(error) => log::debug!("could not load replicas on other devices: {error:#}"),
+            }
+        }
+
+        if let Some(path) = entry
+            .hash
+            .as_deref()
+            .and_then(|hash| peers::cached_content(&self.peer_cache_dir, hash))
+        {
+            online = true;
+            locations.push(format!("● Online — Fetched copy: {}", path.display()));
+        }
+
         if locations.is_empty() {
             locations.push("○ Offline — No current source location".to_owned());
         }
@@ -3001,6 +5894,7 @@ impl App {
         if view_mode == "table" {
             let table_component_name = match open_id {
                 INDEXED_FILE_VIEW_ID => "indexed-file-table",
+                FILE_SEARCH_VIEW_ID => "file-search-table",
                 LOCAL_MEDIA_VIEW_ID => "media-file-table",
                 VIRTUAL_FILE_VIEW_ID => "virtual-file-table",
                 _ => "file-table",
@@ -3022,6 +5916,24 @@ impl App {
                     "replicaCount": entry.replica_count,
                     "modified": entry.modified_at.map_or_else(|| "—".to_owned(), format_modified),
                     "modifiedValue": entry.modified_at.and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok()).map(|duration| duration.as_millis()).unwrap_or(0),
+                    "captured": entry.captured_at().map_or_else(|| "—".to_owned(), format_capture_date),
+                    "capturedValue": entry.captured_at().unwrap_or(0),
+                    "camera": entry.photo.as_ref().and_then(PhotoMetadata::camera).unwrap_or_default(),
+                    "lens": entry.photo.as_ref().and_then(|photo| photo.lens_model.clone()).unwrap_or_default(),
+                    "dimensions": entry.photo.as_ref().and_then(PhotoMetadata::display_dimensions).map_or_else(String::new, |(width, height)| format!("{width} × {height}")),
+                    "pixelsValue": photo_pixels(entry.photo.as_ref()),
+                    "location": entry.photo.as_ref().and_then(PhotoMetadata::location).map_or_else(String::new, |(latitude, longitude)| format!("{latitude:.5}, {longitude:.5}")),
+                    "title": entry.audio.as_ref().and_then(|tags| tags.title.clone()).unwrap_or_default(),
+                    "artist": entry.audio.as_ref().and_then(|tags| tags.artist.clone()).unwrap_or_default(),
+                    "album": entry.audio.as_ref().and_then(|tags| tags.album.clone()).unwrap_or_default(),
+                    "albumValue": entry.audio.as_ref().and_then(|tags| tags.album.clone()).unwrap_or_default().to_lowercase(),
+                    "track": entry.audio.as_ref().map(audio_track_label).unwrap_or_default(),
+                    "duration": entry.audio.as_ref().and_then(|tags| tags.duration_ms).map(format_play_time).unwrap_or_default(),
+                    "durationValue": entry.audio.as_ref().and_then(|tags| tags.duration_ms).unwrap_or(0),
+                    "year": entry.audio.as_ref().and_then(|tags| tags.year).map(|year| year.to_string()).unwrap_or_default(),
+                    "genre": entry.audio.as_ref().and_then(|tags| tags.genre.clone()).unwrap_or_default(),
+                    "folder": entry.path.as_deref().and_then(Path::parent).map(|folder| folder.display().to_string()).unwrap_or_default(),
+                    "node": entry.remote_node.as_deref().unwrap_or("This device"),
                 }))
             }).collect::<Vec<_>>();
             return custom_component(
@@ -3030,6 +5942,12 @@ impl App {
                 serde_json::json!({
                     "rows": rows,
                     "serverSort": media_sort.is_some(),
+                    "columns": match self.active_page {
+                        _ if open_id == FILE_SEARCH_VIEW_ID => "search",
+                        AppPage::Media => "photo",
+                        AppPage::Audio => "audio",
+                        _ => "files",
+                    },
                     "sortKey": media_sort.map(|(key, _)| media_sort_key_name(key)),
                     "sortDescending": media_sort.is_some_and(|(_, descending)| descending),
                 }),
@@ -3045,17 +5963,24 @@ impl App {
         let tiles = indices.into_iter().filter_map(|index| {
             let entry = entries.get(index)?;
             let source_url = entry.path.as_ref().and_then(|path| {
-                let root = self.media_paths.iter().find(|root| Some(root.id) == entry.media_root_id)?;
+                let root = self
+                    .media_paths
+                    .iter()
+                    .find(|root| Some(root.id) == entry.media_root_id)?;
                 media_source_url(root, path)
             });
-            let thumbnail_url = entry.is_image().then(|| media_thumbnail_url(entry)).flatten();
+            let thumbnail_url = entry
+                .is_image()
+                .then(|| media_thumbnail_url(entry))
+                .flatten();
             let preview_url = if entry.is_image() {
                 thumbnail_url
             } else {
                 source_url
             };
-            Some(if (entry.is_image() || entry.is_video()) && preview_url.is_some() {
-                custom_component(
+            Some(
+                if (entry.is_image() || entry.is_video()) && preview_url.is_some() {
+                    custom_component(
                     "media-tile",
                     self.media_tile_asset.url(),
                     serde_json::json!({
@@ -3064,25 +5989,29 @@ impl App {
                         "kind": if entry.is_image() { "image" } else { "video" },
                         "src": preview_url,
                         "size": format_size(entry.size),
-                        "modified": entry.modified_at.map_or_else(|| "—".to_owned(), format_modified),
+                        "modified": entry.captured_at().map_or_else(
+                            || entry.modified_at.map_or_else(|| "—".to_owned(), format_modified),
+                            format_capture_date,
+                        ),
                         "thumbnailSize": thumbnail_size,
                     }),
                 )
                 .custom_event("open", open_id)
                 .width(thumbnail_size)
                 .height(tile_height)
-            } else {
-                button(&format!("□\n{}\n{}", entry.name, format_size(entry.size)))
-                    .id(open_id)
-                    .inx(index as u32)
-                    .width(thumbnail_size)
-                    .height(tile_height)
-                    .padding(10)
-                    .border("1px solid #dce5e8")
-                    .background_color("#ffffff")
-                    .color("#0f6175")
-                    .text_align("left")
-            })
+                } else {
+                    button(&format!("□\n{}\n{}", entry.name, format_size(entry.size)))
+                        .id(open_id)
+                        .inx(index as u32)
+                        .width(thumbnail_size)
+                        .height(tile_height)
+                        .padding(10)
+                        .border("1px solid #dce5e8")
+                        .background_color("#ffffff")
+                        .color("#0f6175")
+                        .text_align("left")
+                },
+            )
         });
         hstack(tiles)
             .wrap(true)
@@ -3104,7 +6033,10 @@ impl App {
             "{image_count} images  •  {video_count} videos from {} folders",
             self.media_paths.iter().filter(|path| path.enabled).count()
         );
-        if self.media_scanned_folder_filter != "all" {
+        if self.media_scanned_folder_filter != "all"
+            || self.media_camera_filter != "all"
+            || self.media_photo_filter != "all"
+        {
             media_summary.push_str("  •  filtered");
         }
         if self.media_scan_truncated {
@@ -3123,7 +6055,9 @@ impl App {
         let page = self.media_page.min(page_count.saturating_sub(1));
         let page_start = page.saturating_mul(FILES_PAGE_SIZE);
         let page_end = (page_start + FILES_PAGE_SIZE).min(media_indices.len());
-        let media_content = if media_indices.is_empty() {
+        let media_content = if self.media_view_mode == "similar" {
+            self.similar_images_view(thumbnail_size)
+        } else if media_indices.is_empty() {
             vstack([
                 text("No media found").color("#374151"),
                 text("Images and videos from active Scanned folders will appear here.")
@@ -3147,7 +6081,7 @@ impl App {
                 Some((self.media_sort_key, self.media_sort_descending)),
             )
         };
-        let pagination = if media_indices.is_empty() {
+        let pagination = if media_indices.is_empty() || self.media_view_mode == "similar" {
             hstack(Vec::<Item>::new())
         } else {
             hstack([
@@ -3204,6 +6138,32 @@ impl App {
             let id = folder.id.to_string();
             option(&id, &folder.path)
         }));
+        let mut camera_options = vec![option("all", "All cameras")];
+        camera_options.extend(
+            self.media_cameras()
+                .iter()
+                .map(|camera| option(camera, camera)),
+        );
+        let sort_options = [
+            ("name", "Sort: Name"),
+            ("capturedValue", "Sort: Date taken"),
+            ("modifiedValue", "Sort: Modified"),
+            ("camera", "Sort: Camera"),
+            ("lens", "Sort: Lens"),
+            ("pixelsValue", "Sort: Resolution"),
+            ("sizeValue", "Sort: Size"),
+            ("replicaCount", "Sort: Replicas"),
+        ]
+        .map(|(value, label)| option(value, label));
+        let photo_filter_options = [
+            ("all", "All photos"),
+            ("dated", "With date taken"),
+            ("located", "With location"),
+            ("unlocated", "Without location"),
+            ("landscape", "Landscape"),
+            ("portrait", "Portrait"),
+        ]
+        .map(|(value, label)| option(value, label));
 
         card(vstack([
             hstack([
@@ -3224,14 +6184,39 @@ impl App {
                     .padding(7)
                     .border("1px solid #dce5e8")
                     .background_color("#ffffff"),
-                size_control,
-                select([option("thumbnails", "Thumbnails"), option("table", "Table")])
-                    .id(MEDIA_VIEW_MODE_ID)
-                    .svalue(&self.media_view_mode)
-                    .width(130)
+                select(camera_options)
+                    .id(MEDIA_CAMERA_FILTER_ID)
+                    .svalue(&self.media_camera_filter)
+                    .width(150)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff"),
+                select(photo_filter_options)
+                    .id(MEDIA_PHOTO_FILTER_ID)
+                    .svalue(&self.media_photo_filter)
+                    .width(150)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff"),
+                select(sort_options)
+                    .id(MEDIA_SORT_ID)
+                    .svalue(media_sort_key_name(self.media_sort_key))
+                    .width(160)
                     .padding(7)
                     .border("1px solid #dce5e8")
                     .background_color("#ffffff"),
+                size_control,
+                select([
+                    option("thumbnails", "Thumbnails"),
+                    option("table", "Table"),
+                    option("similar", "Similar images"),
+                ])
+                .id(MEDIA_VIEW_MODE_ID)
+                .svalue(&self.media_view_mode)
+                .width(130)
+                .padding(7)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff"),
                 button("↻  Refresh view")
                     .id(REFRESH_MEDIA_ID)
                     .padding(7)
@@ -3249,6 +6234,127 @@ impl App {
         .overflow("hidden")
     }
 
+    /// Look-alike images side by side, each with a button to keep it and
+    /// trash the rest of its group.
+    fn similar_images_view(&self, thumbnail_size: u32) -> Item {
+        let distance = hstack([
+            text("Match").color("#6b7280"),
+            select([
+                option("2", "Nearly identical"),
+                option("6", "Resized or re-encoded"),
+                option("12", "Loosely similar"),
+            ])
+            .id(SIMILAR_IMAGE_DISTANCE_ID)
+            .svalue(&self.similar_image_distance)
+            .width(190)
+            .padding(7)
+            .border("1px solid #dce5e8")
+            .background_color("#ffffff"),
+        ])
+        .spacing(8);
+        let mut body = vec![distance];
+        if let Some(message) = &self.duplicate_message {
+            body.push(text(message).color("#374151"));
+        }
+        if self.similar_images.is_empty() {
+            body.push(
+                vstack([
+                    text("No similar images found").color("#374151"),
+                    text("Enable the similar-images indexer on a Scanned folder to compare resized and re-exported photos.")
+                        .color("#6b7280"),
+                ])
+                .spacing(4)
+                .padding(24)
+                .background_color("#f8fafb"),
+            );
+            return vstack(body).grow(1).spacing(8).overflow("auto");
+        }
+        let mut image_index = 0_u32;
+        for group in self.similar_images.iter().take(SIMILAR_IMAGE_GROUPS_SHOWN) {
+            let images = group.iter().map(|image| {
+                let dimensions = match (image.width, image.height) {
+                    (Some(width), Some(height)) => format!("{width} × {height}"),
+                    _ => "Unknown size".to_owned(),
+                };
+                let mut details = format!("{dimensions}  •  {}", format_size(image.size));
+                if image.copies.len() > 1 {
+                    details.push_str(&format!("  •  {} copies", image.copies.len()));
+                }
+                let path = image
+                    .copies
+                    .first()
+                    .map(|copy| copy.path.display().to_string())
+                    .unwrap_or_default();
+                let tile = vstack([
+                    self.similar_image_tile(image, thumbnail_size),
+                    text(&details).color("#374151"),
+                    text(&path).break_words(true).color("#6b7280"),
+                    button("Keep this one")
+                        .id(KEEP_SIMILAR_IMAGE_ID)
+                        .inx(image_index)
+                        .padding(6)
+                        .border("1px solid #0f7892")
+                        .background_color("#ffffff")
+                        .color("#0f6175"),
+                ])
+                .spacing(4)
+                .width(thumbnail_size);
+                image_index += 1;
+                tile
+            });
+            body.push(
+                hstack(images.collect::<Vec<_>>())
+                    .wrap(true)
+                    .spacing(12)
+                    .padding(10)
+                    .border("1px solid #e4ebed")
+                    .background_color("#ffffff"),
+            );
+        }
+        vstack(body).grow(1).spacing(8).overflow("auto")
+    }
+
+    fn similar_image_tile(&self, image: &SimilarImage, thumbnail_size: u32) -> Item {
+        let tile_height = thumbnail_size.saturating_mul(3) / 4 + 60;
+        let index = self
+            .media_index_entries
+            .iter()
+            .position(|entry| entry.hash.as_deref() == Some(image.hash.as_slice()));
+        let thumbnail = index.and_then(|index| {
+            let entry = FileListingEntry::from_media(
+                &self.media_entries[index],
+                &self.media_index_entries[index],
+            );
+            Some((index, media_thumbnail_url(&entry)?, entry))
+        });
+        let Some((index, thumbnail_url, entry)) = thumbnail else {
+            return vstack([text("No preview").color("#6b7280")])
+                .width(thumbnail_size)
+                .height(tile_height)
+                .padding(10)
+                .background_color("#f8fafb");
+        };
+        custom_component(
+            "media-tile",
+            self.media_tile_asset.url(),
+            serde_json::json!({
+                "index": index,
+                "name": entry.name,
+                "kind": "image",
+                "src": thumbnail_url,
+                "size": format_size(entry.size),
+                "modified": entry.captured_at().map_or_else(
+                    || entry.modified_at.map_or_else(|| "—".to_owned(), format_modified),
+                    format_capture_date,
+                ),
+                "thumbnailSize": thumbnail_size,
+            }),
+        )
+        .custom_event("open", LOCAL_MEDIA_VIEW_ID)
+        .width(thumbnail_size)
+        .height(tile_height)
+    }
+
     fn audio_listing_entries(&self) -> Vec<FileListingEntry> {
         self.audio_entries
             .iter()
@@ -3257,6 +6363,105 @@ impl App {
             .collect()
     }
 
+    /// Album or artist tiles for the grouped Audio views.
+    fn audio_group_listing(&self) -> Item {
+        let tiles = if self.audio_view == "artists" {
+            self.audio_artists()
+                .into_iter()
+                .enumerate()
+                .map(|(index, artist)| {
+                    let artist_name = if artist.artist.is_empty() {
+                        "Unknown artist"
+                    } else {
+                        artist.artist.as_str()
+                    };
+                    button(&format!(
+                        "{artist_name}\n{} albums  •  {} tracks",
+                        artist.albums, artist.tracks
+                    ))
+                    .id(OPEN_AUDIO_ARTIST_ID)
+                    .inx(index as u32)
+                    .width(220)
+                    .padding(10)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175")
+                    .text_align("left")
+                })
+                .collect::<Vec<_>>()
+        } else {
+            self.audio_albums()
+                .into_iter()
+                .enumerate()
+                .map(|(index, album)| {
+                    let mut details = format!(
+                        "{} tracks  •  {}",
+                        album.tracks,
+                        format_play_time(album.duration_ms)
+                    );
+                    if let Some(year) = album.year {
+                        details = format!("{year}  •  {details}");
+                    }
+                    let artist = if album.artist.is_empty() {
+                        "Unknown artist"
+                    } else {
+                        album.artist.as_str()
+                    };
+                    button(&format!("{}\n{artist}\n{details}", album.album))
+                        .id(OPEN_AUDIO_ALBUM_ID)
+                        .inx(index as u32)
+                        .width(220)
+                        .padding(10)
+                        .border("1px solid #dce5e8")
+                        .background_color("#ffffff")
+                        .color("#0f6175")
+                        .text_align("left")
+                })
+                .collect::<Vec<_>>()
+        };
+        if tiles.is_empty() {
+            return vstack([
+                text("No tagged audio found").color("#374151"),
+                text("Enable the audio-tags indexer on a Scanned folder to group tracks by album and artist.")
+                    .color("#6b7280"),
+            ])
+            .grow(1)
+            .spacing(4)
+            .padding(24)
+            .background_color("#f8fafb");
+        }
+        hstack(tiles)
+            .wrap(true)
+            .spacing(12)
+            .grow(1)
+            .padding(4)
+            .overflow("auto")
+    }
+
+    fn audio_group_header(&self) -> Item {
+        let Some(group) = &self.audio_group else {
+            return hstack(Vec::<Item>::new());
+        };
+        let (back_label, title) = match group {
+            AudioGroup::Album { artist, album } if artist.is_empty() => {
+                ("← All albums", album.clone())
+            }
+            AudioGroup::Album { artist, album } => ("← All albums", format!("{album} — {artist}")),
+            AudioGroup::Artist(artist) => ("← All artists", artist.clone()),
+        };
+        hstack([
+            button(back_label)
+                .id(CLOSE_AUDIO_GROUP_ID)
+                .padding(6)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+            text(&title).color("#1f2937"),
+        ])
+        .spacing(10)
+        .padding_bottom(8)
+    }
+
     fn audio_panel(&self) -> Item {
         let audio_indices = self.filtered_audio_indices();
         let mut audio_summary = format!(
@@ -3277,12 +6482,15 @@ impl App {
             ));
         }
 
+        let grouped_view = self.audio_group.is_none() && self.audio_view != "tracks";
         let listing_entries = self.audio_listing_entries();
         let page_count = audio_indices.len().div_ceil(FILES_PAGE_SIZE);
         let page = self.audio_page.min(page_count.saturating_sub(1));
         let page_start = page.saturating_mul(FILES_PAGE_SIZE);
         let page_end = (page_start + FILES_PAGE_SIZE).min(audio_indices.len());
-        let audio_content = if audio_indices.is_empty() {
+        let audio_content = if grouped_view {
+            self.audio_group_listing()
+        } else if audio_indices.is_empty() {
             vstack([
                 text("No audio found").color("#374151"),
                 text("Audio from active Scanned folders will appear here after the next scan.")
@@ -3306,7 +6514,7 @@ impl App {
                 Some((self.media_sort_key, self.media_sort_descending)),
             )
         };
-        let pagination = if audio_indices.is_empty() {
+        let pagination = if grouped_view || audio_indices.is_empty() {
             hstack(Vec::<Item>::new())
         } else {
             hstack([
@@ -3367,6 +6575,17 @@ impl App {
                     .padding(7)
                     .border("1px solid #dce5e8")
                     .background_color("#ffffff"),
+                select([
+                    option("tracks", "Tracks"),
+                    option("albums", "Albums"),
+                    option("artists", "Artists"),
+                ])
+                .id(AUDIO_VIEW_ID)
+                .svalue(&self.audio_view)
+                .width(120)
+                .padding(7)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff"),
                 button("↻  Refresh view")
                     .id(REFRESH_MEDIA_ID)
                     .padding(7)
@@ -3376,6 +6595,7 @@ impl App {
             ])
             .spacing(10)
             .padding_bottom(10),
+            self.audio_group_header(),
             audio_content,
             pagination,
         ]))
@@ -3576,15 +6796,31 @@ impl App {
     fn settings_panel(&self) -> Item {
         let media_folders = self.media_folders_settings();
         let inboxes = self.inboxes_settings();
+        let access_tokens = self.access_tokens_settings();
+        let schedule = self.schedule_settings();
+        let devices = self.devices_settings();
 
         card(vstack([
-            vstack([
-                text("Settings").color("#1f2937"),
-                text("Changes are applied immediately and saved for this user.").color("#6b7280"),
+            hstack([
+                vstack([
+                    text("Settings").color("#1f2937"),
+                    text("Changes are applied immediately and saved for this user.")
+                        .color("#6b7280"),
+                ])
+                .grow(1)
+                .spacing(3),
+                button("Sign out")
+                    .id(SIGN_OUT_ID)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
             ])
-            .spacing(3)
+            .spacing(8)
             .padding_bottom(8),
-            vstack([inboxes, media_folders]).grow(1).spacing(14),
+            vstack([inboxes, media_folders, schedule, devices, access_tokens])
+                .grow(1)
+                .spacing(14),
         ]))
         .grow(1)
         .padding(18)
@@ -3638,29 +6874,255 @@ impl App {
         settings_section("Inboxes", body)
     }
 
-    fn new_inbox_modal(&self) -> Item {
+    fn schedule_settings(&self) -> Item {
+        let now = system_time_millis(SystemTime::now());
         let mut body = vec![
+            text("How often the Scanned folders are rescanned and backed up. Continuous relies on the filesystem watcher and backs up soon after files change.")
+                .color("#6b7280"),
+            select([
+                option(BackupSchedule::Continuous.as_str(), "Continuous"),
+                option(BackupSchedule::Hourly.as_str(), "Hourly"),
+                option(BackupSchedule::Daily.as_str(), "Daily"),
+            ])
+            .id(BACKUP_SCHEDULE_ID)
+            .svalue(self.config.backup.schedule.as_str())
+            .width(160)
+            .padding(7)
+            .border("1px solid #dce5e8")
+            .background_color("#ffffff"),
             hstack([
-                vstack([
-                    text("New Inbox"),
-                    text("Choose an existing server folder that will receive uploads.")
-                        .color("#6b7280"),
-                ])
-                .grow(1)
-                .spacing(2),
-                button("×")
-                    .id(CLOSE_NEW_INBOX_ID)
-                    .width(40)
-                    .padding(6)
-                    .border("1px solid #dce5e8")
-                    .background_color("#ffffff"),
-            ]),
-            text("Inbox name").color("#4b5563"),
-            text_input()
-                .id(INBOX_NAME_INPUT_ID)
-                .svalue(&self.new_inbox_name)
-                .placeholder("Family photos"),
-            text("Folder relative to the server root").color("#4b5563"),
+                checkbox()
+                    .id(PAUSE_ON_BATTERY_ID)
+                    .checked(self.config.backup.pause_on_battery)
+                    .width(22),
+                text("Pause scheduled work while on battery").grow(1),
+            ])
+            .spacing(8),
+            hstack([
+                checkbox()
+                    .id(METERED_CONNECTIONS_ID)
+                    .checked(self.config.backup.metered_connections)
+                    .width(22),
+                text("Allow network transfers on metered connections").grow(1),
+            ])
+            .spacing(8),
+        ];
+        body.extend(ScheduledJob::ALL.into_iter().map(|job| {
+            let next_run = match (
+                self.scheduler.blocked_reason(job),
+                self.scheduler.next_run(job, &self.config),
+            ) {
+                (_, None) if self.scheduler.waiting_for_changes(job, &self.config) => {
+                    "After the next change".to_owned()
+                }
+                (_, None) => "Off".to_owned(),
+                (Some(reason), Some(_)) => reason.to_owned(),
+                (None, Some(at)) => format_next_run(at, now),
+            };
+            hstack([text(job.label()).grow(1), text(&next_run).color("#6b7280")])
+                .spacing(8)
+                .padding(8)
+                .border("1px solid #e4ebed")
+                .background_color("#ffffff")
+        }));
+        settings_section("Schedule", body)
+    }
+
+    fn devices_settings(&self) -> Item {
+        let allow_cleartext = self.config.peers.allow_cleartext;
+        let mut body = vec![
+            text("Pair with another PuppyDrive to see which of its files this computer also has. Create a read-only access token on the other device and enter its address and token here; pair from both devices to see each other.")
+                .color("#6b7280"),
+            text(if allow_cleartext {
+                "Access tokens are sent unencrypted to devices on the network."
+            } else {
+                "Access tokens travel unencrypted, so only addresses on this computer, such as an SSH tunnel to the other device, are contacted. Set peers.allow_cleartext in the config to pair across the network."
+            })
+            .color("#6b7280"),
+            hstack([
+                text_input()
+                    .id(PEER_URL_INPUT_ID)
+                    .svalue(&self.new_peer_url)
+                    .placeholder(if allow_cleartext {
+                        "http://192.168.1.20:8080"
+                    } else {
+                        "http://127.0.0.1:5778"
+                    })
+                    .grow(1),
+                text_input()
+                    .id(PEER_TOKEN_INPUT_ID)
+                    .svalue(&self.new_peer_token)
+                    .placeholder("Access token")
+                    .grow(1),
+                button(if self.pairing_peer { "Pairing…" } else { "Pair" })
+                    .id(PAIR_PEER_ID)
+                    .padding(7)
+                    .border("1px solid #0f7892")
+                    .background_color("#0f7892")
+                    .color("#ffffff"),
+            ])
+            .spacing(8),
+        ];
+        if let Some(message) = &self.peer_message {
+            body.push(text(message).color("#0f6175"));
+        }
+        body.extend(self.peers.iter().enumerate().map(|(index, peer)| {
+            let status = if self.syncing_peers.contains(&peer.node_id) {
+                "syncing…".to_owned()
+            } else if let Some(error) = &peer.last_error {
+                format!("sync failed: {error}")
+            } else {
+                peer.last_synced_at
+                    .and_then(system_time_from_millis)
+                    .map_or_else(
+                        || "not synced yet".to_owned(),
+                        |at| format!("synced {}", format_modified(at).to_lowercase()),
+                    )
+            };
+            hstack([
+                vstack([
+                    text(&peer.name),
+                    text(&format!(
+                        "{} · {} files · {status}",
+                        peer.url, peer.file_count
+                    ))
+                    .color(if peer.last_error.is_some() {
+                        "#b42318"
+                    } else {
+                        "#6b7280"
+                    }),
+                ])
+                .grow(1)
+                .spacing(2),
+                button("Sync now")
+                    .id(SYNC_PEER_ID)
+                    .inx(index as u32)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff"),
+                button("Unpair")
+                    .id(REMOVE_PEER_ID)
+                    .inx(index as u32)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff"),
+            ])
+            .spacing(8)
+            .padding(8)
+            .border("1px solid #e4ebed")
+            .background_color("#ffffff")
+        }));
+        settings_section("Devices", body)
+    }
+
+    fn access_tokens_settings(&self) -> Item {
+        let mut body = vec![
+            text("Tokens let scripts and other devices use the HTTP API without the admin password. Read-only tokens can browse and download, upload-only tokens can only upload to Inboxes.")
+                .color("#6b7280"),
+            hstack([
+                text_input()
+                    .id(ACCESS_TOKEN_NAME_INPUT_ID)
+                    .svalue(&self.new_access_token_name)
+                    .placeholder("Token name")
+                    .grow(1),
+                select(
+                    AccessScope::ALL
+                        .iter()
+                        .map(|scope| option(scope.as_str(), scope.as_str())),
+                )
+                .id(ACCESS_TOKEN_SCOPE_ID)
+                .svalue(self.new_access_token_scope.as_str())
+                .width(140)
+                .padding(7)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff"),
+                button("Create token")
+                    .id(CREATE_ACCESS_TOKEN_ID)
+                    .padding(7)
+                    .border("1px solid #0f7892")
+                    .background_color("#0f7892")
+                    .color("#ffffff"),
+            ])
+            .spacing(8),
+        ];
+        if let Some(error) = &self.access_token_error {
+            body.push(text(error).color("#b42318"));
+        }
+        if let Some(secret) = &self.created_access_token {
+            body.push(
+                hstack([
+                    vstack([
+                        text("Copy the new token now. It will not be shown again.")
+                            .color("#0f6175"),
+                        text(secret),
+                    ])
+                    .grow(1)
+                    .spacing(2),
+                    button("Done")
+                        .id(DISMISS_ACCESS_TOKEN_ID)
+                        .padding(6)
+                        .border("1px solid #dce5e8")
+                        .background_color("#ffffff"),
+                ])
+                .spacing(8)
+                .padding(8)
+                .border("1px solid #0f7892")
+                .background_color("#ffffff"),
+            );
+        }
+        body.extend(self.access_tokens.iter().map(|token| {
+            let last_used = token
+                .last_used_at
+                .and_then(system_time_from_millis)
+                .map_or_else(
+                    || "never used".to_owned(),
+                    |used_at| format!("last used {}", format_modified(used_at).to_lowercase()),
+                );
+            hstack([
+                vstack([
+                    text(&token.name),
+                    text(&format!("{} · {last_used}", token.scope)).color("#6b7280"),
+                ])
+                .grow(1)
+                .spacing(2),
+                button("Revoke")
+                    .id(REVOKE_ACCESS_TOKEN_ID)
+                    .inx(token.id)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff"),
+            ])
+            .spacing(8)
+            .padding(8)
+            .border("1px solid #e4ebed")
+            .background_color("#ffffff")
+        }));
+        settings_section("Access tokens", body)
+    }
+
+    fn new_inbox_modal(&self) -> Item {
+        let mut body = vec![
+            hstack([
+                vstack([
+                    text("New Inbox"),
+                    text("Choose an existing server folder that will receive uploads.")
+                        .color("#6b7280"),
+                ])
+                .grow(1)
+                .spacing(2),
+                button("×")
+                    .id(CLOSE_NEW_INBOX_ID)
+                    .width(40)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff"),
+            ]),
+            text("Inbox name").color("#4b5563"),
+            text_input()
+                .id(INBOX_NAME_INPUT_ID)
+                .svalue(&self.new_inbox_name)
+                .placeholder("Family photos"),
+            text("Folder relative to the server root").color("#4b5563"),
             hstack([
                 text_input()
                     .id(INBOX_FOLDER_INPUT_ID)
@@ -3878,121 +7340,637 @@ impl App {
         settings_section("Scanned folders", body)
     }
 
-    fn scanned_folder_detail_panel(&self) -> Item {
-        let Some(folder) = self
-            .selected_scanned_folder_id
-            .and_then(|id| self.media_paths.iter().find(|folder| folder.id == id))
-        else {
-            return card(
-                vstack([
-                    text("Scanned folder not found").color("#374151"),
-                    link("/settings", "Back to Settings")
-                        .color("#0f6175")
-                        .cursor("pointer"),
-                ])
-                .spacing(8)
-                .padding(18),
-            )
-            .grow(1)
-            .padding(14);
-        };
-        let history = if self.selected_scanned_folder_history.is_empty() {
+    fn integrity_panel(&self) -> Item {
+        let summary = self.scrub_summary;
+        let coverage = format!(
+            "{} of {} indexed files verified in the last {} days.",
+            summary.verified_files, summary.hashed_files, self.config.media.scrub_interval_days
+        );
+        let report = if self.scrub_alerts.is_empty() {
             vstack([
-                text("No scans recorded yet.").color("#374151"),
-                text("Completed scans, including automatic rescans, will appear here.")
+                text("No damaged files found.").color("#374151"),
+                text("Files whose content changes without a modification time change, or that can no longer be read, will appear here.")
                     .color("#6b7280"),
             ])
             .spacing(4)
             .padding(18)
             .background_color("#f8fafb")
         } else {
-            let header = hstack([
-                text("Started").width(125).color("#6b7280"),
-                text("Trigger").width(125).color("#6b7280"),
-                text("Result").width(95).color("#6b7280"),
-                text("Duration").width(90).color("#6b7280"),
-                text("Folders").width(70).color("#6b7280"),
-                text("Files").width(70).color("#6b7280"),
-                text("Details").grow(1).color("#6b7280"),
-            ])
-            .spacing(8)
-            .padding(8)
-            .background_color("#f8fafb");
-            let rows = self.selected_scanned_folder_history.iter().map(|entry| {
-                let (result, result_color) = scan_outcome_label(entry.outcome);
-                let started = system_time_from_millis(entry.started_at)
+            vstack(self.scrub_alerts.iter().map(|alert| {
+                let problem = match &alert.error {
+                    Some(error) => format!("Could not be read back: {error}"),
+                    None => "Content changed without a modification time change".to_owned(),
+                };
+                let detected = system_time_from_millis(alert.detected_at)
                     .map_or_else(|| "—".to_owned(), format_modified);
-                let detail = entry.error_message.as_deref().unwrap_or("—");
-                hstack([
-                    text(&started).width(125).color("#374151"),
-                    text(scan_trigger_label(entry.trigger))
-                        .width(125)
-                        .color("#374151"),
-                    text(result).width(95).color(result_color),
-                    text(&format_scan_duration(entry.finished_at - entry.started_at))
-                        .width(90)
-                        .color("#374151"),
-                    text(&entry.directories_scanned.to_string())
-                        .width(70)
-                        .color("#374151"),
-                    text(&entry.files_indexed.to_string())
-                        .width(70)
-                        .color("#374151"),
-                    text(detail).grow(1).break_words(true).color("#6b7280"),
-                ])
-                .spacing(8)
-                .padding(8)
-                .border("1px solid #e4ebed")
-                .background_color("#ffffff")
-            });
-            vstack([header, vstack(rows).spacing(2)]).spacing(2)
-        };
-        let scan_active = self
-            .index_status
-            .get(&folder.id)
-            .is_some_and(|status| status.scanning || status.queued);
-        let scan_action = if scan_active {
-            button(
-                self.index_status
-                    .get(&folder.id)
-                    .is_some_and(|status| status.stopping)
-                    .then_some("Stopping…")
-                    .unwrap_or("Stop"),
-            )
-            .id(STOP_MEDIA_SCAN_ID)
-            .inx(folder.id)
-            .padding(7)
-            .border("1px solid #b54708")
-            .background_color("#ffffff")
-            .color("#9a3412")
-        } else {
-            button("Scan")
-                .id(START_MEDIA_SCAN_ID)
-                .inx(folder.id)
-                .padding(7)
-                .border("1px solid #0f7892")
-                .background_color("#ffffff")
-                .color("#0f6175")
-        };
-        card(
-            vstack([
-                hstack([
-                    vstack([
-                        text("Scanned folder").color("#1f2937"),
-                        text(&folder.path).color("#6b7280").break_words(true),
-                        text(&self.scan_status_text(folder)).color("#6b7280"),
+                let actual = alert
+                    .actual_hash
+                    .as_deref()
+                    .map_or_else(|| "—".to_owned(), hex_encode);
+                let mut rows = vec![
+                    hstack([
+                        text(&alert.path.display().to_string())
+                            .grow(1)
+                            .break_words(true)
+                            .color("#1f2937"),
+                        text(&format_size(alert.size)).width(90).color("#6b7280"),
+                        text(&detected).width(110).color("#6b7280"),
                     ])
-                    .grow(1)
-                    .spacing(3),
-                    scan_action,
-                    link("/settings", "Back to Settings")
-                        .padding(7)
-                        .border("1px solid #dce5e8")
-                        .background_color("#ffffff")
-                        .color("#0f6175")
-                        .cursor("pointer"),
+                    .spacing(8),
+                    text(&problem).color("#b42318"),
+                    text(&format!(
+                        "Expected {}, read {actual}",
+                        hex_encode(&alert.expected_hash)
+                    ))
+                    .break_words(true)
+                    .color("#6b7280"),
+                ];
+                if alert.healthy_replicas.is_empty() {
+                    rows.push(text("No healthy replica is indexed.").color("#b54708"));
+                } else {
+                    rows.push(text("Healthy replicas").color("#16794b"));
+                    rows.extend(alert.healthy_replicas.iter().map(|replica| {
+                        text(&replica.display().to_string())
+                            .break_words(true)
+                            .color("#374151")
+                    }));
+                }
+                vstack(rows)
+                    .spacing(4)
+                    .padding(10)
+                    .border("1px solid #e4ebed")
+                    .background_color("#ffffff")
+            }))
+            .spacing(6)
+        };
+        card(vstack([
+            hstack([
+                vstack([
+                    text("Integrity").color("#1f2937"),
+                    text(&coverage).color("#6b7280"),
                 ])
-                .spacing(10)
+                .grow(1)
+                .spacing(3),
+                button("Verify now")
+                    .id(VERIFY_FILES_NOW_ID)
+                    .padding(7)
+                    .border("1px solid #0f7892")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
+            ])
+            .spacing(8)
+            .padding_bottom(12),
+            report,
+        ]))
+        .grow(1)
+        .padding(18)
+        .overflow("auto")
+    }
+
+    fn duplicates_panel(&self) -> Item {
+        let reclaimable = self
+            .duplicate_groups
+            .iter()
+            .map(DuplicateGroup::wasted_bytes)
+            .sum::<u64>();
+        let summary = format!(
+            "{} files stored more than once. {} reclaimable by keeping one copy of each.",
+            self.duplicate_groups.len(),
+            format_size(reclaimable)
+        );
+        let list = if self.duplicate_groups.is_empty() {
+            vstack([
+                text("No duplicates found.").color("#374151"),
+                text("Files with identical content in your Scanned folders will appear here after they are scanned.")
+                    .color("#6b7280"),
+            ])
+            .spacing(4)
+            .padding(18)
+            .background_color("#f8fafb")
+        } else {
+            let mut copy_index = 0_u32;
+            let groups = self
+                .duplicate_groups
+                .iter()
+                .take(DUPLICATE_GROUPS_SHOWN)
+                .map(|group| {
+                    let mut rows = vec![
+                        hstack([
+                            text(&format!(
+                                "{} copies of {}",
+                                group.distinct_files,
+                                format_size(group.size)
+                            ))
+                            .grow(1)
+                            .color("#1f2937"),
+                            text(&format!("{} wasted", format_size(group.wasted_bytes())))
+                                .color("#b54708"),
+                        ])
+                        .spacing(8),
+                    ];
+                    rows.extend(group.copies.iter().map(|copy| {
+                        let modified = copy
+                            .modified_at
+                            .and_then(system_time_from_millis)
+                            .map_or_else(|| "—".to_owned(), format_modified);
+                        let row = hstack([
+                            text(&copy.path.display().to_string())
+                                .grow(1)
+                                .break_words(true)
+                                .color("#374151"),
+                            text(&modified).width(110).color("#6b7280"),
+                            button("Keep, trash others")
+                                .id(TRASH_DUPLICATES_ID)
+                                .inx(copy_index)
+                                .padding(5)
+                                .border("1px solid #dce5e8")
+                                .background_color("#ffffff")
+                                .color("#0f6175"),
+                            button("Keep, link others")
+                                .id(HARDLINK_DUPLICATES_ID)
+                                .inx(copy_index)
+                                .padding(5)
+                                .border("1px solid #dce5e8")
+                                .background_color("#ffffff")
+                                .color("#0f6175"),
+                        ])
+                        .spacing(8);
+                        copy_index += 1;
+                        row
+                    }));
+                    vstack(rows)
+                        .spacing(4)
+                        .padding(10)
+                        .border("1px solid #e4ebed")
+                        .background_color("#ffffff")
+                })
+                .collect::<Vec<_>>();
+            vstack(groups).spacing(6)
+        };
+        let mut body = vec![
+            hstack([
+                vstack([
+                    text("Duplicates").color("#1f2937"),
+                    text(&summary).color("#6b7280"),
+                ])
+                .grow(1)
+                .spacing(3),
+                select([
+                    option("waste", "Most space wasted"),
+                    option("copies", "Most copies"),
+                    option("size", "Largest files"),
+                ])
+                .id(DUPLICATE_SORT_ID)
+                .svalue(&self.duplicate_sort)
+                .width(170)
+                .padding(7)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff"),
+            ])
+            .spacing(8),
+            text(&format!("Trashed copies are moved to {} in their Scanned folder and can be put back by hand. Hardlinked copies share one file, so they also share its permissions, and editing one edits all of them.", indexer::TRASH_DIRECTORY_NAME))
+                .color("#6b7280")
+                .padding_bottom(8),
+        ];
+        if let Some(message) = &self.duplicate_message {
+            body.push(text(message).color("#374151").padding_bottom(8));
+        }
+        body.push(list);
+        card(vstack(body).spacing(4))
+            .grow(1)
+            .padding(18)
+            .overflow("auto")
+    }
+
+    fn backups_panel(&self) -> Item {
+        let destination = self.config.backup.destination.as_ref().map_or_else(
+            || "No destination chosen, so nothing is backed up yet.".to_owned(),
+            |path| format!("Backing up to {}.", path.display()),
+        );
+        let mut body = vec![
+            hstack([
+                vstack([
+                    text("Backups").color("#1f2937"),
+                    text(&destination).color("#6b7280").break_words(true),
+                ])
+                .grow(1)
+                .spacing(3),
+                button(if self.backup_running {
+                    "Backing up…"
+                } else {
+                    "Back up now"
+                })
+                .id(BACK_UP_NOW_ID)
+                .padding(7)
+                .border("1px solid #0f7892")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+            ])
+            .spacing(8),
+            text("Every file content is stored once, so later runs only copy what changed. Each run keeps a snapshot of the Scanned folders as they were. Contents and snapshots are encrypted with your passphrase before they reach the drive.")
+                .color("#6b7280"),
+            hstack([
+                text_input()
+                    .id(BACKUP_DESTINATION_INPUT_ID)
+                    .svalue(&self.backup_destination)
+                    .placeholder("/media/backup-drive")
+                    .grow(1),
+                button("Save")
+                    .id(SAVE_BACKUP_DESTINATION_ID)
+                    .padding(7)
+                    .border("1px solid #0f7892")
+                    .background_color("#0f7892")
+                    .color("#ffffff"),
+            ])
+            .spacing(8),
+            self.backup_key_controls(),
+        ];
+        if let Some((files_done, files_total, bytes_written)) = self.backup_progress {
+            body.push(
+                text(&format!(
+                    "Checked {files_done} of {files_total} files, {} written so far.",
+                    format_size(bytes_written)
+                ))
+                .color("#0f6175"),
+            );
+        }
+        if let Some(message) = &self.backup_message {
+            body.push(text(message).color("#374151"));
+        }
+        body.push(self.retention_panel());
+        body.push(match &self.open_snapshot {
+            Some(snapshot) => {
+                vstack([self.restore_options(snapshot), snapshot_browser(snapshot)]).spacing(8)
+            }
+            None => self.snapshot_list(),
+        });
+        card(vstack(body).spacing(4))
+            .grow(1)
+            .padding(18)
+            .overflow("auto")
+    }
+
+    fn backup_key_controls(&self) -> Item {
+        let (placeholder, status) = if self.backup_keys.is_some() {
+            (
+                "New passphrase to rotate the key to",
+                "Unlocked for this session. Rotating re-encrypts the whole backup under a new key.",
+            )
+        } else {
+            (
+                "Backup passphrase",
+                "Locked. Enter the passphrase to back up, browse or restore; a new destination is protected by the first passphrase entered.",
+            )
+        };
+        let mut controls = vec![
+            text_input()
+                .id(BACKUP_PASSPHRASE_INPUT_ID)
+                .svalue(self.backup_passphrase.expose_secret())
+                .placeholder(placeholder)
+                .grow(1),
+        ];
+        if self.backup_keys.is_some() {
+            controls.push(
+                button(if self.backup_key_rotating {
+                    "Rotating…"
+                } else {
+                    "Rotate key"
+                })
+                .id(ROTATE_BACKUP_KEY_ID)
+                .padding(7)
+                .border("1px solid #0f7892")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+            );
+            controls.push(
+                button("Lock")
+                    .id(LOCK_BACKUP_ID)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#374151"),
+            );
+        } else {
+            controls.push(
+                button(if self.backup_unlocking {
+                    "Unlocking…"
+                } else {
+                    "Unlock"
+                })
+                .id(UNLOCK_BACKUP_ID)
+                .padding(7)
+                .border("1px solid #0f7892")
+                .background_color("#0f7892")
+                .color("#ffffff"),
+            );
+        }
+        vstack([hstack(controls).spacing(8), text(status).color("#6b7280")])
+            .spacing(4)
+            .padding_bottom(8)
+    }
+
+    fn retention_panel(&self) -> Item {
+        let mut rows = vec![
+            hstack([
+                text(&retention_description(&self.config.backup.retention))
+                    .color("#6b7280")
+                    .grow(1),
+                button("Preview prune")
+                    .id(PREVIEW_PRUNE_ID)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
+                button(if self.prune_running {
+                    "Pruning…"
+                } else {
+                    "Prune now"
+                })
+                .id(PRUNE_NOW_ID)
+                .padding(7)
+                .border("1px solid #0f7892")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+            ])
+            .spacing(8),
+        ];
+        if let Some(message) = &self.prune_message {
+            rows.push(text(message).color("#374151"));
+        }
+        if let Some(report) = self.last_prune.as_ref().filter(|report| report.dry_run) {
+            for snapshot in report.snapshots.iter().take(SNAPSHOT_ENTRIES_SHOWN) {
+                let started = system_time_from_millis(snapshot.started_at)
+                    .map(format_modified)
+                    .unwrap_or_default();
+                rows.push(
+                    text(&format!(
+                        "Would remove {started} from {}, {} files",
+                        snapshot.device, snapshot.file_count
+                    ))
+                    .color("#6b7280"),
+                );
+            }
+        }
+        vstack(rows).spacing(4).padding_bottom(8)
+    }
+
+    fn restore_options(&self, snapshot: &OpenSnapshot) -> Item {
+        let mut controls = vec![
+            text_input()
+                .id(RESTORE_TARGET_INPUT_ID)
+                .svalue(&self.restore_target)
+                .placeholder("Restore to the original location")
+                .grow(1),
+            select(
+                ConflictPolicy::ALL
+                    .into_iter()
+                    .map(|policy| option(policy.as_str(), policy.label())),
+            )
+            .id(RESTORE_CONFLICTS_ID)
+            .svalue(self.restore_conflicts.as_str())
+            .width(190)
+            .padding(7)
+            .border("1px solid #dce5e8")
+            .background_color("#ffffff"),
+        ];
+        if snapshot.folder.is_some() {
+            controls.push(
+                button(if self.restore_running {
+                    "Restoring…"
+                } else {
+                    "Restore this folder"
+                })
+                .id(RESTORE_SNAPSHOT_DIRECTORY_ID)
+                .padding(7)
+                .border("1px solid #0f7892")
+                .background_color("#0f7892")
+                .color("#ffffff"),
+            );
+        }
+        let mut rows = vec![
+            hstack(controls).spacing(8),
+            text("Leave the folder empty to put files back where they were, or enter a folder to restore below it. Every restored file is checked against its hash.")
+                .color("#6b7280"),
+        ];
+        if let Some((files_done, files_total)) = self.restore_progress {
+            rows.push(
+                text(&format!("Restored {files_done} of {files_total} files…")).color("#0f6175"),
+            );
+        }
+        if let Some(message) = &self.restore_message {
+            rows.push(text(message).color("#374151"));
+        }
+        vstack(rows).spacing(4)
+    }
+
+    fn snapshot_list(&self) -> Item {
+        if self.snapshots.is_empty() {
+            let hint = if self.backup_keys.is_some() {
+                "Back up to create the first one."
+            } else {
+                "Choose a destination and unlock it to see its snapshots."
+            };
+            return vstack([
+                text("No snapshots yet.").color("#374151"),
+                text(hint).color("#6b7280"),
+            ])
+            .spacing(4)
+            .padding(18)
+            .background_color("#f8fafb");
+        }
+        vstack(self.snapshots.iter().enumerate().map(|(index, snapshot)| {
+            let started = system_time_from_millis(snapshot.started_at)
+                .map_or_else(|| "—".to_owned(), format_modified);
+            let mut detail = format!(
+                "{} · {} files · {} · {} added",
+                snapshot.device,
+                snapshot.file_count,
+                format_size(snapshot.total_bytes),
+                format_size(snapshot.bytes_written)
+            );
+            if snapshot.error_count > 0 {
+                detail.push_str(&format!(" · {} not backed up", snapshot.error_count));
+            }
+            hstack([
+                vstack([
+                    text(&started).color("#1f2937"),
+                    text(&detail).color("#6b7280"),
+                ])
+                .grow(1)
+                .spacing(2),
+                button("Browse")
+                    .id(OPEN_SNAPSHOT_ID)
+                    .inx(index as u32)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
+            ])
+            .spacing(8)
+            .padding(8)
+            .border("1px solid #e4ebed")
+            .background_color("#ffffff")
+        }))
+        .spacing(4)
+    }
+
+    fn scanned_folder_detail_panel(&self) -> Item {
+        let Some(folder) = self
+            .selected_scanned_folder_id
+            .and_then(|id| self.media_paths.iter().find(|folder| folder.id == id))
+        else {
+            return card(
+                vstack([
+                    text("Scanned folder not found").color("#374151"),
+                    link("/settings", "Back to Settings")
+                        .color("#0f6175")
+                        .cursor("pointer"),
+                ])
+                .spacing(8)
+                .padding(18),
+            )
+            .grow(1)
+            .padding(14);
+        };
+        let history = if self.selected_scanned_folder_history.is_empty() {
+            vstack([
+                text("No scans recorded yet.").color("#374151"),
+                text("Completed scans, including automatic rescans, will appear here.")
+                    .color("#6b7280"),
+            ])
+            .spacing(4)
+            .padding(18)
+            .background_color("#f8fafb")
+        } else {
+            let header = hstack([
+                text("Started").width(125).color("#6b7280"),
+                text("Trigger").width(125).color("#6b7280"),
+                text("Result").width(95).color("#6b7280"),
+                text("Duration").width(90).color("#6b7280"),
+                text("Folders").width(70).color("#6b7280"),
+                text("Files").width(70).color("#6b7280"),
+                text("Details").grow(1).color("#6b7280"),
+            ])
+            .spacing(8)
+            .padding(8)
+            .background_color("#f8fafb");
+            let rows = self.selected_scanned_folder_history.iter().map(|entry| {
+                let (result, result_color) = scan_outcome_label(entry.outcome);
+                let started = system_time_from_millis(entry.started_at)
+                    .map_or_else(|| "—".to_owned(), format_modified);
+                let detail = entry.error_message.as_deref().unwrap_or("—");
+                hstack([
+                    text(&started).width(125).color("#374151"),
+                    text(scan_trigger_label(entry.trigger))
+                        .width(125)
+                        .color("#374151"),
+                    text(result).width(95).color(result_color),
+                    text(&format_scan_duration(entry.finished_at - entry.started_at))
+                        .width(90)
+                        .color("#374151"),
+                    text(&entry.directories_scanned.to_string())
+                        .width(70)
+                        .color("#374151"),
+                    text(&entry.files_indexed.to_string())
+                        .width(70)
+                        .color("#374151"),
+                    text(detail).grow(1).break_words(true).color("#6b7280"),
+                ])
+                .spacing(8)
+                .padding(8)
+                .border("1px solid #e4ebed")
+                .background_color("#ffffff")
+            });
+            vstack([header, vstack(rows).spacing(2)]).spacing(2)
+        };
+        let scan_active = self
+            .index_status
+            .get(&folder.id)
+            .is_some_and(|status| status.scanning || status.queued);
+        let scan_action = if scan_active {
+            button(
+                self.index_status
+                    .get(&folder.id)
+                    .is_some_and(|status| status.stopping)
+                    .then_some("Stopping…")
+                    .unwrap_or("Stop"),
+            )
+            .id(STOP_MEDIA_SCAN_ID)
+            .inx(folder.id)
+            .padding(7)
+            .border("1px solid #b54708")
+            .background_color("#ffffff")
+            .color("#9a3412")
+        } else {
+            button("Scan")
+                .id(START_MEDIA_SCAN_ID)
+                .inx(folder.id)
+                .padding(7)
+                .border("1px solid #0f7892")
+                .background_color("#ffffff")
+                .color("#0f6175")
+        };
+        card(
+            vstack([
+                hstack([
+                    vstack([
+                        text("Scanned folder").color("#1f2937"),
+                        text(&folder.path).color("#6b7280").break_words(true),
+                        text(&self.scan_status_text(folder)).color("#6b7280"),
+                    ])
+                    .grow(1)
+                    .spacing(3),
+                    scan_action,
+                    link("/settings", "Back to Settings")
+                        .padding(7)
+                        .border("1px solid #dce5e8")
+                        .background_color("#ffffff")
+                        .color("#0f6175")
+                        .cursor("pointer"),
+                ])
+                .spacing(10)
+                .padding_bottom(14),
+                text("Indexers").color("#1f2937").padding_bottom(6),
+                vstack(indexer::registered_indexers().iter().enumerate().map(
+                    |(index, indexer)| {
+                        hstack([
+                            checkbox()
+                                .id(TOGGLE_FOLDER_INDEXER_ID)
+                                .inx(index as u32)
+                                .checked(folder.indexes(indexer.name()))
+                                .width(22),
+                            text(indexer.name()).width(90).color("#374151"),
+                            text(indexer.description()).grow(1).color("#6b7280"),
+                        ])
+                        .spacing(8)
+                    },
+                ))
+                .spacing(4)
+                .padding_bottom(14),
+                text("Ignore rules").color("#1f2937").padding_bottom(6),
+                vstack([
+                    text("Hidden and skipped directories are ignored by default. Include globs index them anyway; exclude globs skip more. A .puppyignore file in any directory adds gitignore-style patterns for that directory.")
+                        .color("#6b7280"),
+                    text("Include (comma-separated)").color("#4b5563"),
+                    text_input()
+                        .id(FOLDER_INCLUDE_GLOBS_INPUT_ID)
+                        .svalue(&self.folder_include_globs)
+                        .placeholder(".config/, .notes/*.md"),
+                    text("Exclude (comma-separated)").color("#4b5563"),
+                    text_input()
+                        .id(FOLDER_EXCLUDE_GLOBS_INPUT_ID)
+                        .svalue(&self.folder_exclude_globs)
+                        .placeholder("*.tmp, cache/"),
+                    hstack([
+                        button("Save rules")
+                            .id(SAVE_FOLDER_IGNORE_RULES_ID)
+                            .padding(7)
+                            .border("1px solid #0f7892")
+                            .background_color("#ffffff")
+                            .color("#0f6175"),
+                    ]),
+                ])
+                .spacing(6)
                 .padding_bottom(14),
                 text("Scan history").color("#1f2937").padding_bottom(6),
                 history.grow(1).overflow("auto"),
@@ -4016,15 +7994,42 @@ impl App {
             let id = folder.id.to_string();
             option(&id, &folder.path)
         }));
-        let file_content = self.file_listing(
-            &entries,
-            &self.files_view_mode,
-            self.media_thumbnail_size.clamp(140, 320) as u32,
-            INDEXED_FILE_VIEW_ID,
-            page_start..page_end,
-            None,
-        );
-        let pagination = if entries.is_empty() {
+        let searching = self.files_content_query.trim().chars().count() >= CONTENT_SEARCH_MIN_CHARS;
+        let file_content = if searching {
+            self.content_search_listing()
+        } else {
+            self.file_listing(
+                &entries,
+                &self.files_view_mode,
+                self.media_thumbnail_size.clamp(140, 320) as u32,
+                INDEXED_FILE_VIEW_ID,
+                page_start..page_end,
+                None,
+            )
+        };
+        let summary = if searching {
+            format!("{} matching files", self.files_content_hits.len())
+        } else {
+            format!("{} indexed files", entries.len())
+        };
+        let mut search = vec![
+            text_input()
+                .id(FILES_CONTENT_SEARCH_ID)
+                .svalue(&self.files_content_query)
+                .placeholder("Search file contents")
+                .width(220),
+        ];
+        if !self.files_content_query.is_empty() {
+            search.push(
+                button("Clear")
+                    .id(CLEAR_FILES_CONTENT_SEARCH_ID)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
+            );
+        }
+        let pagination = if entries.is_empty() || searching {
             hstack(Vec::<Item>::new())
         } else {
             hstack([
@@ -4064,10 +8069,11 @@ impl App {
             hstack([
                 vstack([
                     text("Files").color("#1f2937"),
-                    text(&format!("{} indexed files", entries.len())).color("#6b7280"),
+                    text(&summary).color("#6b7280"),
                 ])
                 .grow(1)
                 .spacing(3),
+                hstack(search).spacing(4),
                 button("New folder")
                     .id(SHOW_NEW_FOLDER_ID)
                     .padding(7)
@@ -4086,6 +8092,7 @@ impl App {
                     option("images", "Images"),
                     option("videos", "Videos"),
                     option("other", "Other"),
+                    option("mismatched", "Type mismatch"),
                 ])
                 .id(FILES_MIME_FILTER_ID)
                 .svalue(&self.files_mime_filter)
@@ -4122,6 +8129,86 @@ impl App {
         .overflow("hidden")
     }
 
+    fn file_search_panel(&self) -> Item {
+        let entries = self.file_search_entries();
+        let summary = if let Some(error) = &self.file_search_error {
+            text(error).color("#b42318")
+        } else if entries.len() >= FILE_SEARCH_LIMIT {
+            text(&format!("Showing the first {FILE_SEARCH_LIMIT} matches")).color("#6b7280")
+        } else {
+            text(&format!("{} matching files", entries.len())).color("#6b7280")
+        };
+        card(vstack([
+            hstack([
+                vstack([text("Search").color("#1f2937"), summary])
+                    .grow(1)
+                    .spacing(3),
+                button("Clear search")
+                    .id(CLEAR_SIDEBAR_SEARCH_ID)
+                    .padding(7)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
+            ])
+            .spacing(8)
+            .padding_bottom(4),
+            text("Filters: ext:pdf  type:image  size>10mb  modified:2024-05  folder:holiday")
+                .color("#6b7280")
+                .padding_bottom(8),
+            self.file_listing(
+                &entries,
+                "table",
+                self.media_thumbnail_size.clamp(140, 320) as u32,
+                FILE_SEARCH_VIEW_ID,
+                0..entries.len(),
+                None,
+            ),
+        ]))
+        .grow(1)
+        .padding(14)
+        .overflow("hidden")
+    }
+
+    fn content_search_listing(&self) -> Item {
+        if self.files_content_hits.is_empty() {
+            return vstack([
+                text("No file contents match this search").color("#374151"),
+                text("Text, Markdown, JSON and source files are searchable once a Scanned folder with the content indexer has been scanned.")
+                    .color("#6b7280"),
+            ])
+            .spacing(4)
+            .padding(20)
+            .grow(1);
+        }
+        vstack(
+            self.files_content_hits
+                .iter()
+                .enumerate()
+                .map(|(index, hit)| {
+                    let name = hit
+                        .path
+                        .file_name()
+                        .map_or_else(|| hit.path.to_string_lossy(), |name| name.to_string_lossy());
+                    button(&format!(
+                        "{name}\n{}\n{}",
+                        hit.path.display(),
+                        content_snippet_line(&hit.snippet)
+                    ))
+                    .id(OPEN_CONTENT_SEARCH_HIT_ID)
+                    .inx(index as u32)
+                    .padding(10)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175")
+                    .text_align("left")
+                })
+                .collect::<Vec<_>>(),
+        )
+        .spacing(6)
+        .grow(1)
+        .overflow("auto")
+    }
+
     #[allow(dead_code)]
     fn local_files_panel(&self) -> Item {
         let at_root = self.this_computer_path == self.this_computer_root;
@@ -4133,6 +8220,7 @@ impl App {
             .active_source_id
             .and_then(|id| self.sources.iter().find(|source| source.id == id))
             .map_or("This Computer", |source| source.name.as_str());
+        let remote_source_bar = self.remote_source_bar();
         let mut location = vec![
             source_name.to_owned(),
             self.this_computer_root.display().to_string(),
@@ -4176,14 +8264,86 @@ impl App {
             )
         });
 
-        card(vstack([
-            toolbar,
-            columns,
-            vstack(rows).grow(1).overflow("auto"),
-        ]))
-        .spacing(1)
-        .padding(6)
-        .overflow("hidden")
+        let mut panel = vec![toolbar];
+        panel.extend(remote_source_bar);
+        panel.extend([columns, vstack(rows).grow(1).overflow("auto")]);
+        card(vstack(panel)).spacing(1).padding(6).overflow("hidden")
+    }
+
+    /// Connection controls shown above the tree while an SFTP source is open.
+    fn remote_source_bar(&self) -> Option<Item> {
+        let source = self.active_remote_source()?;
+        let config = sftp_source_config(source)?;
+        let address = format!("{}@{}:{}", config.user, config.host, config.port);
+        let mut controls = vec![text(&address).grow(1).color("#4b5563")];
+        let slot = match config.auth {
+            SftpAuth::Password => sftp::PASSWORD_SLOT,
+            SftpAuth::PrivateKey => sftp::PRIVATE_KEY_SLOT,
+        };
+        if self.session_secrets.is_available(&source.source_key, slot) {
+            controls.push(text("Credentials entered for this session").color("#6b7280"));
+        }
+        if self.source_connections.contains_key(&source.source_key) {
+            controls.extend([
+                button("Refresh")
+                    .id(REFRESH_SOURCE_ID)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#0f6175"),
+                button(if self.indexing_sources.contains_key(&source.source_key) {
+                    "Stop indexing"
+                } else {
+                    "Index"
+                })
+                .id(INDEX_SOURCE_ID)
+                .padding(6)
+                .border("1px solid #0f7892")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+                button("Disconnect")
+                    .id(DISCONNECT_SOURCE_ID)
+                    .padding(6)
+                    .border("1px solid #dce5e8")
+                    .background_color("#ffffff")
+                    .color("#374151"),
+            ]);
+        } else if self.connecting_sources.contains(&source.source_key) {
+            controls.push(text("Connecting…").color("#6b7280"));
+        } else {
+            controls.push(
+                text_input()
+                    .id(SOURCE_SECRET_INPUT_ID)
+                    .svalue(self.source_secret.expose_secret())
+                    .placeholder(match config.auth {
+                        SftpAuth::Password => "Password",
+                        SftpAuth::PrivateKey => "Private key (PEM)",
+                    })
+                    .width(220),
+            );
+            if config.auth == SftpAuth::PrivateKey {
+                controls.push(
+                    text_input()
+                        .id(SOURCE_KEY_PASSPHRASE_INPUT_ID)
+                        .svalue(self.source_key_passphrase.expose_secret())
+                        .placeholder("Key passphrase, if any")
+                        .width(180),
+                );
+            }
+            controls.push(
+                button("Connect")
+                    .id(CONNECT_SOURCE_ID)
+                    .padding(6)
+                    .border("1px solid #0f7892")
+                    .background_color("#0f7892")
+                    .color("#ffffff"),
+            );
+        }
+        let mut rows = vec![hstack(controls).spacing(6)];
+        if let Some(message) = self.source_messages.get(&source.source_key) {
+            rows.push(text(message).color("#6b7280"));
+        }
+        Some(vstack(rows).spacing(4).padding_bottom(6))
     }
 
     fn file_viewer_modal(&self) -> Option<Item> {
@@ -4415,7 +8575,9 @@ impl App {
             .border("1px solid #dce5e8")
             .background_color("#ffffff")
             .text_align("left"),
-            if included_in_media {
+            if self.active_remote_source().is_some() {
+                vstack(Vec::<Item>::new())
+            } else if included_in_media {
                 text("Included in Scanned folders")
                     .padding(8)
                     .color("#16803a")
@@ -4644,7 +8806,7 @@ impl App {
     }
 
     fn add_source_modal(&self) -> Item {
-        modal([card(vstack([
+        let mut fields = vec![
             hstack([
                 text("Add source").grow(1),
                 button("×")
@@ -4659,23 +8821,86 @@ impl App {
                 .id(ADD_SOURCE_NAME_INPUT_ID)
                 .svalue(&self.new_source_name)
                 .placeholder("e.g. Archive drive"),
-            text("Folder path").color("#4b5563"),
-            hstack([
+            text("Type").color("#4b5563"),
+            select([
+                option("local", "Folder on this server"),
+                option("sftp", "SFTP server"),
+            ])
+            .id(ADD_SOURCE_TYPE_ID)
+            .svalue(&self.new_source_type)
+            .padding(6)
+            .border("1px solid #dce5e8")
+            .background_color("#ffffff"),
+        ];
+        if self.new_source_type == "sftp" {
+            fields.extend([
+                text("Host and port").color("#4b5563"),
+                hstack([
+                    text_input()
+                        .id(ADD_SOURCE_HOST_INPUT_ID)
+                        .svalue(&self.new_source_host)
+                        .placeholder("e.g. nas.local")
+                        .grow(1),
+                    text_input()
+                        .id(ADD_SOURCE_PORT_INPUT_ID)
+                        .svalue(&self.new_source_port)
+                        .width(70),
+                ])
+                .spacing(8),
+                text("User").color("#4b5563"),
+                text_input()
+                    .id(ADD_SOURCE_USER_INPUT_ID)
+                    .svalue(&self.new_source_user)
+                    .placeholder("e.g. photos"),
+                text("Remote folder").color("#4b5563"),
                 text_input()
                     .id(ADD_SOURCE_PATH_INPUT_ID)
                     .svalue(&self.new_source_path)
-                    .placeholder("e.g. /Volumes/Archive")
-                    .grow(1),
-                button("Browse server…")
-                    .id(BROWSE_SOURCE_PATH_ID)
-                    .padding(6)
-                    .border("1px solid #dce5e8")
-                    .background_color("#ffffff")
-                    .color("#0f6175"),
-            ])
-            .spacing(8),
-            text("The local source is validated and saved to this user's database.")
+                    .placeholder("e.g. /srv/photos"),
+                text("Sign in with").color("#4b5563"),
+                select([
+                    option("password", "Password"),
+                    option("private_key", "Private key"),
+                ])
+                .id(ADD_SOURCE_AUTH_ID)
+                .svalue(match self.new_source_auth {
+                    SftpAuth::Password => "password",
+                    SftpAuth::PrivateKey => "private_key",
+                })
+                .padding(6)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff"),
+                text(
+                    "The password or key is asked for when connecting and kept only until \
+                     the daemon stops. The server's host key is pinned on first connection.",
+                )
                 .color("#6b7280"),
+            ]);
+        } else {
+            fields.extend([
+                text("Folder path").color("#4b5563"),
+                hstack([
+                    text_input()
+                        .id(ADD_SOURCE_PATH_INPUT_ID)
+                        .svalue(&self.new_source_path)
+                        .placeholder("e.g. /Volumes/Archive")
+                        .grow(1),
+                    button("Browse server…")
+                        .id(BROWSE_SOURCE_PATH_ID)
+                        .padding(6)
+                        .border("1px solid #dce5e8")
+                        .background_color("#ffffff")
+                        .color("#0f6175"),
+                ])
+                .spacing(8),
+                text("The local source is validated and saved to this user's database.")
+                    .color("#6b7280"),
+            ]);
+        }
+        if let Some(error) = &self.new_source_error {
+            fields.push(text(error).color("#b42318"));
+        }
+        fields.push(
             hstack([
                 button("Cancel")
                     .id(CLOSE_ADD_SOURCE_ID)
@@ -4690,10 +8915,8 @@ impl App {
                     .color("#ffffff"),
             ])
             .spacing(8),
-        ]))
-        .width(440)
-        .spacing(8)
-        .padding(14)])
+        );
+        modal([card(vstack(fields)).width(440).spacing(8).padding(14)])
     }
 }
 
@@ -5023,7 +9246,19 @@ fn local_entries_from_index(entries: Vec<IndexedMediaFile>) -> Vec<LocalEntry> {
         .collect()
 }
 
-#[cfg(test)]
+/// Restores scheduled job runs, recording `now` for jobs that never ran so a
+/// fresh install waits a full interval before its first scheduled run.
+fn load_scheduler(database: &Database) -> Result<Scheduler> {
+    let now = system_time_millis(SystemTime::now());
+    let runs = database.scheduled_job_runs()?;
+    for job in ScheduledJob::ALL {
+        if !runs.contains_key(job.as_str()) {
+            database.record_scheduled_job_run(job.as_str(), now)?;
+        }
+    }
+    Ok(Scheduler::new(&runs, now))
+}
+
 fn system_time_millis(time: SystemTime) -> i64 {
     time.duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
@@ -5031,6 +9266,33 @@ fn system_time_millis(time: SystemTime) -> i64 {
         .min(i64::MAX as u128) as i64
 }
 
+fn remote_local_entry(entry: RemoteEntry) -> LocalEntry {
+    let is_file = !entry.is_directory && !entry.is_symlink;
+    let modified_at = entry.modified_at.and_then(system_time_from_millis);
+    LocalEntry {
+        kind: if entry.is_directory {
+            "Folder"
+        } else if is_file {
+            "File"
+        } else {
+            "Other"
+        },
+        size: if is_file {
+            format_size(entry.size)
+        } else {
+            "—".to_owned()
+        },
+        size_bytes: entry.size,
+        modified: modified_at.map_or_else(|| "—".to_owned(), format_modified),
+        modified_at,
+        path: entry.path,
+        name: entry.name,
+        is_directory: entry.is_directory,
+        is_symlink: entry.is_symlink,
+        media_root_id: None,
+    }
+}
+
 fn system_time_from_millis(millis: i64) -> Option<SystemTime> {
     u64::try_from(millis)
         .ok()
@@ -5138,7 +9400,9 @@ fn scan_media_entries(
                     hash: folder.blake3(&path).ok(),
                     path,
                     size: metadata.len(),
-                    mime_type,
+                    mime_type: mime_type.clone(),
+                    extension_mime_type: mime_type,
+                    sniffed_mime_type: None,
                     created_at: metadata.created().ok().map(system_time_millis),
                     modified_at: metadata.modified().ok().map(system_time_millis),
                     accessed_at: metadata.accessed().ok().map(system_time_millis),
@@ -5276,7 +9540,7 @@ fn media_thumbnail_response(
         return Some(
             HttpResponse::new(200, bytes)
                 .header("content-type", "image/png")
-                .header("cache-control", "public, max-age=31536000, immutable"),
+                .header("cache-control", "private, max-age=31536000, immutable"),
         );
     }
     if cache_only {
@@ -5303,56 +9567,131 @@ fn media_thumbnail_response(
     Some(
         HttpResponse::new(200, bytes)
             .header("content-type", "image/png")
-            .header("cache-control", "public, max-age=31536000, immutable"),
+            .header("cache-control", "private, max-age=31536000, immutable"),
     )
 }
 
+/// Serves the content with `hash` from a local replica or the peer cache,
+/// fetching it from a paired device when a `fetch` policy is given.
+fn content_response(
+    hash: &[u8],
+    database_path: &Path,
+    node_id: &[u8],
+    folders: &HashMap<u32, ManagedFolder>,
+    cache_dir: &Path,
+    fetch: Option<FetchPolicy>,
+    headers: &HashMap<String, String>,
+) -> HttpResponse {
+    let Ok(database) = Database::open(database_path) else {
+        return HttpResponse::new(503, "index unavailable");
+    };
+    let Some((_, mime_type)) = database.file_entry_metadata(hash).ok().flatten() else {
+        return HttpResponse::new(404, "content not found");
+    };
+    let content_type = mime_type.as_deref().unwrap_or("application/octet-stream");
+    let local_replica = database
+        .file_replica_paths(node_id, hash)
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|path| {
+            let folder = folders
+                .values()
+                .filter(|folder| folder.contains(&path))
+                .max_by_key(|folder| folder.root().components().count())?;
+            folder
+                .canonicalize(&path)
+                .ok()
+                .filter(|path| path.is_file())
+        });
+    let path = match (local_replica, fetch) {
+        (Some(path), _) => path,
+        (None, Some(policy)) => match peers::fetch(&database, cache_dir, hash, policy) {
+            Ok(path) => path,
+            Err(error) => return HttpResponse::new(502, format!("{error:#}")),
+        },
+        (None, None) => match peers::cached_content(cache_dir, hash) {
+            Some(path) => path,
+            None => return HttpResponse::new(404, "content not found"),
+        },
+    };
+    media_response(&path, content_type, 404, headers).into_http()
+}
+
+fn parse_content_hash(hash: &str) -> Option<Vec<u8>> {
+    if hash.len() != 64 {
+        return None;
+    }
+    hex_decode(hash)
+}
+
 fn parse_thumbnail_request(request: &str) -> Option<(u32, Vec<u8>)> {
     let (folder_id, hash) = request.split_once('/')?;
     let folder_id = folder_id.parse().ok()?;
     if hash.len() != 64 || hash.contains('/') {
         return None;
     }
-    let mut bytes = Vec::with_capacity(32);
-    for offset in (0..hash.len()).step_by(2) {
-        bytes.push(u8::from_str_radix(&hash[offset..offset + 2], 16).ok()?);
-    }
-    Some((folder_id, bytes))
+    Some((folder_id, hex_decode(hash)?))
 }
 
-fn hex_encode(bytes: &[u8]) -> String {
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
     bytes.iter().map(|byte| format!("{byte:02x}")).collect()
 }
 
+pub(crate) fn hex_decode(value: &str) -> Option<Vec<u8>> {
+    value
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// What the Media watcher saw in one Scanned folder during a debounce window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WatchedChange {
+    Paths {
+        folder_id: u32,
+        paths: Vec<PathBuf>,
+    },
+    /// Events were dropped or a rename crossed directories, so only a full
+    /// scan can tell what changed.
+    Rescan {
+        folder_id: u32,
+    },
+}
+
+/// More changed paths than this in one debounce window are cheaper to
+/// pick up with a single traversal.
+const MAX_WATCHED_PATHS: usize = 1_024;
+
 fn build_media_watcher(
     roots: &[MediaScanPath],
     debounce: Duration,
     ignored_directory_names: &[String],
 ) -> Result<(
     RecommendedWatcher,
-    tokio::sync::mpsc::Receiver<u32>,
+    tokio::sync::mpsc::Receiver<WatchedChange>,
     Vec<PathBuf>,
 )> {
     let (raw_tx, raw_rx) = std::sync::mpsc::channel();
     let watched_roots = roots.to_vec();
-    let ignored_directory_names = ignored_directory_names.to_vec();
+    let rules = folder_ignore_rules(roots, ignored_directory_names);
     let mut watcher =
         notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
             Ok(event) => {
-                if let Some(folder_id) =
-                    watched_folder_for_event(&event, &watched_roots, &ignored_directory_names)
-                {
-                    let _ = raw_tx.send(folder_id);
+                for change in watched_changes_for_event(&event, &watched_roots, &rules) {
+                    let _ = raw_tx.send(change);
                 }
             }
             Err(error) => log::warn!("Media watcher error: {error}"),
         })
         .context("failed creating Media filesystem watcher")?;
     let mut watched = Vec::new();
-    for root in roots
-        .iter()
-        .filter(|root| root.enabled && root.indexes_media())
-    {
+    for root in roots.iter().filter(|root| indexer::is_indexed(root)) {
         let path = PathBuf::from(&root.path);
         if !path.is_dir() {
             continue;
@@ -5366,13 +9705,38 @@ fn build_media_watcher(
     std::thread::Builder::new()
         .name("puppydrive-media-debounce".to_owned())
         .spawn(move || {
-            while let Ok(folder_id) = raw_rx.recv() {
-                let mut changed_folders = HashSet::from([folder_id]);
-                while let Ok(folder_id) = raw_rx.recv_timeout(debounce) {
-                    changed_folders.insert(folder_id);
+            while let Ok(change) = raw_rx.recv() {
+                // `None` marks a folder that needs a full scan.
+                let mut changed_folders = HashMap::<u32, Option<HashSet<PathBuf>>>::new();
+                let mut pending = Some(change);
+                while let Some(change) = pending {
+                    match change {
+                        WatchedChange::Rescan { folder_id } => {
+                            changed_folders.insert(folder_id, None);
+                        }
+                        WatchedChange::Paths { folder_id, paths } => {
+                            let entry = changed_folders
+                                .entry(folder_id)
+                                .or_insert_with(|| Some(HashSet::new()));
+                            if let Some(changed_paths) = entry {
+                                changed_paths.extend(paths);
+                                if changed_paths.len() > MAX_WATCHED_PATHS {
+                                    *entry = None;
+                                }
+                            }
+                        }
+                    }
+                    pending = raw_rx.recv_timeout(debounce).ok();
                 }
-                for folder_id in changed_folders {
-                    if change_tx.blocking_send(folder_id).is_err() {
+                for (folder_id, paths) in changed_folders {
+                    let change = match paths {
+                        Some(paths) => WatchedChange::Paths {
+                            folder_id,
+                            paths: paths.into_iter().collect(),
+                        },
+                        None => WatchedChange::Rescan { folder_id },
+                    };
+                    if change_tx.blocking_send(change).is_err() {
                         return;
                     }
                 }
@@ -5382,11 +9746,32 @@ fn build_media_watcher(
     Ok((watcher, change_rx, watched))
 }
 
-fn watched_folder_for_event(
-    event: &notify::Event,
+/// Ignore rules for each Scanned folder, matched against its configured path
+/// as the watcher reports it.
+fn folder_ignore_rules(
     roots: &[MediaScanPath],
     ignored_directory_names: &[String],
-) -> Option<u32> {
+) -> HashMap<u32, IgnoreRules> {
+    roots
+        .iter()
+        .map(|root| {
+            let rules = IgnoreRules::for_folder(root, &root.path, ignored_directory_names);
+            (root.id, rules)
+        })
+        .collect()
+}
+
+fn watched_changes_for_event(
+    event: &notify::Event,
+    roots: &[MediaScanPath],
+    rules: &HashMap<u32, IgnoreRules>,
+) -> Vec<WatchedChange> {
+    let watched_roots = roots.iter().filter(|root| indexer::is_indexed(root));
+    if event.need_rescan() {
+        return watched_roots
+            .map(|root| WatchedChange::Rescan { folder_id: root.id })
+            .collect();
+    }
     if !matches!(
         event.kind,
         EventKind::Create(_)
@@ -5394,40 +9779,51 @@ fn watched_folder_for_event(
             | EventKind::Modify(ModifyKind::Data(_))
             | EventKind::Modify(ModifyKind::Name(_))
     ) {
-        return None;
+        return Vec::new();
     }
-    event
+    let owners = event
         .paths
         .iter()
-        .flat_map(|path| {
-            roots.iter().filter_map(move |root| {
-                let root_path = Path::new(&root.path);
-                (root.enabled
-                    && root.indexes_media()
-                    && path.starts_with(root_path)
-                    && !is_ignored_watched_path(path, root_path, ignored_directory_names))
-                .then_some((root.id, root_path.components().count()))
-            })
-        })
-        .max_by_key(|(_, depth)| *depth)
-        .map(|(id, _)| id)
-}
-
-fn is_ignored_watched_path(path: &Path, root: &Path, ignored_directory_names: &[String]) -> bool {
-    path.strip_prefix(root)
-        .ok()
-        .into_iter()
-        .flat_map(Path::components)
-        .filter_map(|component| match component {
-            Component::Normal(name) => name.to_str(),
-            _ => None,
-        })
-        .any(|name| {
-            name.starts_with('.')
-                || ignored_directory_names
-                    .iter()
-                    .any(|ignored| ignored.eq_ignore_ascii_case(name))
+        .filter_map(|path| {
+            watched_roots
+                .clone()
+                .filter(|root| path.starts_with(&root.path))
+                .max_by_key(|root| Path::new(&root.path).components().count())
+                .map(|root| (root, path))
         })
+        .collect::<Vec<_>>();
+    let crosses_directories = matches!(
+        event.kind,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+    ) && event
+        .paths
+        .windows(2)
+        .any(|pair| pair[0].parent() != pair[1].parent());
+    let mut changes = Vec::<WatchedChange>::new();
+    for (root, path) in owners {
+        let rules = rules.get(&root.id);
+        let change = if crosses_directories {
+            WatchedChange::Rescan { folder_id: root.id }
+        } else if ignore_rules::is_ignore_file(path) {
+            // Changed rules can add or drop anything below, so only a full
+            // scan brings the index in line.
+            if let (Some(rules), Some(directory)) = (rules, path.parent()) {
+                rules.forget_ignore_file(directory);
+            }
+            WatchedChange::Rescan { folder_id: root.id }
+        } else if rules.is_some_and(|rules| rules.is_ignored(path, path.is_dir())) {
+            continue;
+        } else {
+            WatchedChange::Paths {
+                folder_id: root.id,
+                paths: vec![path.clone()],
+            }
+        };
+        if !changes.contains(&change) {
+            changes.push(change);
+        }
+    }
+    changes
 }
 
 fn create_folder_at(root: &Path, parent: &Path, name: &str) -> Result<PathBuf> {
@@ -5451,7 +9847,156 @@ fn create_folder_at(root: &Path, parent: &Path, name: &str) -> Result<PathBuf> {
     if !folder.starts_with(&root) {
         anyhow::bail!("created folder is outside the active file root");
     }
-    Ok(folder)
+    Ok(folder)
+}
+
+/// Rejects requests to protected routes that lack a session cookie or a
+/// bearer token with the required scope.
+fn authorize_request(auth: &Auth, request: &wgui::HttpRequest) -> Option<HttpResponse> {
+    let required = auth::required_scope(&request.method, &request.path)?;
+    let (status, message) = match auth.authenticate(&request.headers) {
+        Ok(Some(scope)) if scope.allows(required) => return None,
+        Ok(Some(scope)) => (
+            403,
+            format!("a {} token cannot access this resource", scope.as_str()),
+        ),
+        Ok(None) => (401, "authentication required".to_owned()),
+        Err(error) => {
+            log::error!("unable to authenticate request: {error:#}");
+            (500, "unable to authenticate request".to_owned())
+        }
+    };
+    let response = if request.path.starts_with(api::API_PREFIX) {
+        HttpResponse::new(status, serde_json::json!({ "error": message }).to_string())
+            .header("content-type", "application/json")
+    } else {
+        HttpResponse::new(status, message)
+    };
+    let response = response.header("cache-control", "no-store");
+    if status == 401 {
+        return Some(response.header("www-authenticate", "Bearer realm=\"PuppyDrive\""));
+    }
+    Some(response)
+}
+
+#[derive(serde::Deserialize)]
+struct AuthBody {
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    setup_code: Option<String>,
+    #[serde(default)]
+    new_password: String,
+}
+
+/// Browser sign-in routes under `/auth/`.
+///
+/// - `GET /auth/status` reports whether setup is done and the caller is signed in.
+/// - `POST /auth/setup` sets the first admin password.
+/// - `POST /auth/login` and `POST /auth/logout` manage the session cookie.
+/// - `POST /auth/password` changes the admin password and ends every session.
+/// - `POST /auth/ticket` issues a one-time ticket that signs in the wgui connection.
+fn auth_response(auth: &Auth, request: &wgui::HttpRequest, action: &str) -> HttpResponse {
+    let json = |status: u16, value: serde_json::Value| {
+        HttpResponse::new(status, value.to_string())
+            .header("content-type", "application/json")
+            .header("cache-control", "no-store")
+    };
+    let error = |status: u16, message: &str| json(status, serde_json::json!({ "error": message }));
+    let internal = |error: anyhow::Error| {
+        log::error!("authentication request failed: {error:#}");
+        json(
+            500,
+            serde_json::json!({ "error": "unable to process the request" }),
+        )
+    };
+    let body = || serde_json::from_slice::<AuthBody>(&request.body);
+
+    match (request.method.as_str(), action) {
+        ("GET", "status") => {
+            let configured = match auth.is_configured() {
+                Ok(configured) => configured,
+                Err(error) => return internal(error),
+            };
+            let authenticated = match auth.has_session(&request.headers) {
+                Ok(authenticated) => authenticated,
+                Err(error) => return internal(error),
+            };
+            json(
+                200,
+                serde_json::json!({
+                    "configured": configured,
+                    "authenticated": authenticated,
+                    "setup_code_required": !configured && auth.setup_code().is_some(),
+                }),
+            )
+        }
+        ("POST", "setup") => {
+            let Ok(body) = body() else {
+                return error(400, "invalid JSON body");
+            };
+            match auth.set_up_admin(&body.password, body.setup_code.as_deref()) {
+                Ok(token) => json(201, serde_json::json!({ "configured": true }))
+                    .header("set-cookie", auth::session_cookie(&token)),
+                Err(SetupError::Failed(setup_error)) => internal(setup_error),
+                Err(setup_error @ SetupError::AlreadyConfigured) => {
+                    error(409, &setup_error.to_string())
+                }
+                Err(setup_error @ SetupError::InvalidSetupCode) => {
+                    error(401, &setup_error.to_string())
+                }
+                Err(setup_error @ SetupError::WeakPassword) => error(400, &setup_error.to_string()),
+            }
+        }
+        ("POST", "login") => {
+            let Ok(body) = body() else {
+                return error(400, "invalid JSON body");
+            };
+            match auth.log_in(&body.password) {
+                Ok(Some(token)) => json(200, serde_json::json!({ "authenticated": true }))
+                    .header("set-cookie", auth::session_cookie(&token)),
+                Ok(None) => error(401, "incorrect password"),
+                Err(login_error) => internal(login_error),
+            }
+        }
+        ("POST", "logout") => match auth.log_out(&request.headers) {
+            Ok(()) => HttpResponse::new(204, Vec::new())
+                .header("set-cookie", auth::expired_session_cookie())
+                .header("cache-control", "no-store"),
+            Err(logout_error) => internal(logout_error),
+        },
+        ("POST", "password") => {
+            let Ok(body) = body() else {
+                return error(400, "invalid JSON body");
+            };
+            match auth.has_session(&request.headers) {
+                Ok(true) => {}
+                Ok(false) => return error(403, "not signed in"),
+                Err(password_error) => return internal(password_error),
+            }
+            if body.new_password.chars().count() < auth::MIN_PASSWORD_LENGTH {
+                return error(400, &SetupError::WeakPassword.to_string());
+            }
+            match auth.change_password(&body.password, &body.new_password) {
+                Ok(true) => HttpResponse::new(204, Vec::new())
+                    .header("set-cookie", auth::expired_session_cookie())
+                    .header("cache-control", "no-store"),
+                Ok(false) => error(401, "incorrect password"),
+                Err(password_error) => internal(password_error),
+            }
+        }
+        ("POST", "ticket") => match auth.issue_client_ticket(&request.headers) {
+            Ok(Some(ticket)) => json(200, serde_json::json!({ "ticket": ticket })),
+            // A missing session is expected on first load; 403 avoids the
+            // failed-login delay that applies to 401 responses.
+            Ok(None) => error(403, "not signed in"),
+            Err(ticket_error) => internal(ticket_error),
+        },
+        (_, "status" | "setup" | "login" | "logout" | "password" | "ticket") => {
+            error(405, "method not allowed")
+        }
+        _ => error(404, "not found"),
+    }
 }
 
 fn upload_response(
@@ -5466,34 +10011,10 @@ fn upload_response(
         return HttpResponse::new(413, "uploaded file exceeds the 1 GiB limit");
     }
 
-    let Some(inbox_id) = request.query.get("inbox") else {
-        return HttpResponse::new(400, "missing Inbox");
-    };
-    let Some(filename) = request.headers.get("x-puppydrive-filename") else {
-        return HttpResponse::new(400, "missing upload filename");
-    };
-    let inbox_id = match percent_decode_str(inbox_id).decode_utf8() {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::new(400, "invalid Inbox"),
-    };
-    let inbox = inboxes
-        .read()
-        .ok()
-        .and_then(|inboxes| inboxes.iter().find(|inbox| inbox.id == inbox_id).cloned());
-    let Some(inbox) = inbox else {
-        return HttpResponse::new(404, "Inbox not found");
-    };
-    let destination = match resolve_upload_folder(root, &inbox.folder) {
-        Ok(destination) => destination,
-        Err(error) => {
-            return HttpResponse::new(409, format!("Inbox folder is unavailable: {error}"));
-        }
-    };
-    let filename = match upload_filename(filename) {
-        Ok(filename) => filename,
-        Err(error) => return HttpResponse::new(400, format!("invalid upload filename: {error}")),
+    let (_, target) = match upload_target(request, root, inboxes) {
+        Ok(target) => target,
+        Err(response) => return response,
     };
-    let target = destination.join(&filename);
 
     let mut file = match fs::OpenOptions::new()
         .write(true)
@@ -5516,7 +10037,219 @@ fn upload_response(
         return HttpResponse::new(500, "unable to write upload");
     }
 
-    HttpResponse::new(201, format!("uploaded {}", filename.display()))
+    HttpResponse::new(
+        201,
+        format!(
+            "uploaded {}",
+            target.file_name().unwrap_or_default().display()
+        ),
+    )
+}
+
+/// Resolves the Inbox and filename of an upload request to its destination path.
+fn upload_target(
+    request: &wgui::HttpRequest,
+    root: &Path,
+    inboxes: &Arc<RwLock<Vec<InboxConfig>>>,
+) -> Result<(String, PathBuf), HttpResponse> {
+    let Some(inbox_id) = request.query.get("inbox") else {
+        return Err(HttpResponse::new(400, "missing Inbox"));
+    };
+    let Some(filename) = request.headers.get("x-puppydrive-filename") else {
+        return Err(HttpResponse::new(400, "missing upload filename"));
+    };
+    let inbox_id = match percent_decode_str(inbox_id).decode_utf8() {
+        Ok(id) => id.into_owned(),
+        Err(_) => return Err(HttpResponse::new(400, "invalid Inbox")),
+    };
+    let filename = match upload_filename(filename) {
+        Ok(filename) => filename,
+        Err(error) => {
+            return Err(HttpResponse::new(
+                400,
+                format!("invalid upload filename: {error}"),
+            ));
+        }
+    };
+    let destination = inbox_destination(&inbox_id, root, inboxes)?;
+    Ok((inbox_id, destination.join(filename)))
+}
+
+fn inbox_destination(
+    inbox_id: &str,
+    root: &Path,
+    inboxes: &Arc<RwLock<Vec<InboxConfig>>>,
+) -> Result<PathBuf, HttpResponse> {
+    let inbox = inboxes
+        .read()
+        .ok()
+        .and_then(|inboxes| inboxes.iter().find(|inbox| inbox.id == inbox_id).cloned());
+    let Some(inbox) = inbox else {
+        return Err(HttpResponse::new(404, "Inbox not found"));
+    };
+    resolve_upload_folder(root, &inbox.folder)
+        .map_err(|error| HttpResponse::new(409, format!("Inbox folder is unavailable: {error}")))
+}
+
+/// The part of an upload session URL after `/uploads/sessions`, or `None` for
+/// any other path.
+fn upload_session_path(path: &str) -> Option<&str> {
+    if path == "/uploads/sessions" {
+        return Some("");
+    }
+    path.strip_prefix("/uploads/sessions/")
+}
+
+/// Resumable Inbox uploads.
+///
+/// - `POST /uploads/sessions?inbox=<id>` with `x-puppydrive-filename` and
+///   `upload-length` creates a session.
+/// - `HEAD`/`GET /uploads/sessions/<id>` reports the staged `upload-offset`.
+/// - `PATCH /uploads/sessions/<id>` appends the body at `upload-offset`.
+/// - `POST /uploads/sessions/<id>/finish` moves the completed file into the Inbox.
+/// - `DELETE /uploads/sessions/<id>` discards the session.
+fn upload_session_response(
+    request: &wgui::HttpRequest,
+    session_path: &str,
+    root: &Path,
+    inboxes: &Arc<RwLock<Vec<InboxConfig>>>,
+    staging: &UploadStaging,
+) -> HttpResponse {
+    let session_path = session_path.trim_matches('/');
+    if session_path.is_empty() {
+        if request.method != "POST" {
+            return HttpResponse::new(405, "upload sessions are created with POST")
+                .header("allow", "POST");
+        }
+        return create_upload_session(request, root, inboxes, staging);
+    }
+
+    let (session_id, action) = match session_path.split_once('/') {
+        Some((session_id, action)) => (session_id, Some(action)),
+        None => (session_path, None),
+    };
+    let session = match staging.session(session_id) {
+        Ok(session) => session,
+        Err(error) => return upload_error_response(error),
+    };
+    match (request.method.as_str(), action) {
+        ("HEAD" | "GET", None) => match staging.offset(&session) {
+            Ok(offset) => HttpResponse::new(
+                200,
+                serde_json::json!({
+                    "id": session.id,
+                    "offset": offset,
+                    "length": session.length,
+                })
+                .to_string(),
+            )
+            .header("content-type", "application/json")
+            .header("cache-control", "no-store")
+            .header("upload-offset", offset.to_string())
+            .header("upload-length", session.length.to_string()),
+            Err(error) => upload_error_response(error),
+        },
+        ("PATCH", None) => {
+            if request.body.len() > MAX_UPLOAD_CHUNK_BYTES {
+                return HttpResponse::new(413, "upload chunk exceeds the 64 MiB limit");
+            }
+            let Some(offset) = request
+                .headers
+                .get("upload-offset")
+                .and_then(|offset| offset.trim().parse::<u64>().ok())
+            else {
+                return HttpResponse::new(400, "missing or invalid upload-offset");
+            };
+            match staging.append(&session, offset, &request.body) {
+                Ok(offset) => {
+                    HttpResponse::new(204, Vec::new()).header("upload-offset", offset.to_string())
+                }
+                Err(error) => upload_error_response(error),
+            }
+        }
+        ("POST", Some("finish")) => {
+            let destination = match inbox_destination(&session.inbox_id, root, inboxes) {
+                Ok(destination) => destination,
+                Err(response) => return response,
+            };
+            match staging.finish(&session, &destination.join(&session.filename)) {
+                Ok(()) => {
+                    HttpResponse::new(201, format!("uploaded {}", session.filename.display()))
+                }
+                Err(error) => upload_error_response(error),
+            }
+        }
+        ("DELETE", None) => match staging.abort(&session) {
+            Ok(()) => HttpResponse::new(204, Vec::new()),
+            Err(error) => upload_error_response(error),
+        },
+        _ => HttpResponse::new(405, "unsupported upload session request"),
+    }
+}
+
+fn create_upload_session(
+    request: &wgui::HttpRequest,
+    root: &Path,
+    inboxes: &Arc<RwLock<Vec<InboxConfig>>>,
+    staging: &UploadStaging,
+) -> HttpResponse {
+    let Some(length) = request
+        .headers
+        .get("upload-length")
+        .and_then(|length| length.trim().parse::<u64>().ok())
+    else {
+        return HttpResponse::new(400, "missing or invalid upload-length");
+    };
+    let (inbox_id, target) = match upload_target(request, root, inboxes) {
+        Ok(target) => target,
+        Err(response) => return response,
+    };
+    // Only an early answer for the client; finishing never replaces a file
+    // that appears at the target in the meantime.
+    if fs::symlink_metadata(&target).is_ok() {
+        return HttpResponse::new(409, "a file with that name already exists");
+    }
+    let filename = PathBuf::from(target.file_name().unwrap_or_default());
+    match staging.create(&inbox_id, &filename, length) {
+        Ok(session) => HttpResponse::new(
+            201,
+            serde_json::json!({
+                "id": session.id,
+                "offset": 0,
+                "length": session.length,
+            })
+            .to_string(),
+        )
+        .header("content-type", "application/json")
+        .header("location", format!("/uploads/sessions/{}", session.id))
+        .header("upload-offset", "0"),
+        Err(error) => {
+            log::warn!("unable to create upload session: {error:#}");
+            HttpResponse::new(500, "unable to create upload session")
+        }
+    }
+}
+
+fn upload_error_response(error: UploadError) -> HttpResponse {
+    let status = match &error {
+        UploadError::NotFound => 404,
+        UploadError::Busy => 423,
+        UploadError::OffsetMismatch { .. }
+        | UploadError::Incomplete { .. }
+        | UploadError::AlreadyExists => 409,
+        UploadError::ExceedsLength => 413,
+        UploadError::Io(io_error) => {
+            log::warn!("upload session failed: {io_error}");
+            500
+        }
+    };
+    let response = HttpResponse::new(status, error.to_string());
+    match error {
+        UploadError::OffsetMismatch { expected: offset } | UploadError::Incomplete { offset } => {
+            response.header("upload-offset", offset.to_string())
+        }
+        _ => response,
+    }
 }
 
 fn inbox_folder_path(folder: &str) -> Result<PathBuf> {
@@ -5568,21 +10301,25 @@ fn upload_filename(encoded_filename: &str) -> Result<PathBuf> {
     Ok(PathBuf::from(filename))
 }
 
-fn local_media_response(relative_path: &str, root: &Path) -> Option<HttpResponse> {
+fn local_media_response(
+    relative_path: &str,
+    root: &Path,
+    headers: &HashMap<String, String>,
+) -> Option<MediaResponse> {
     let relative_path = percent_decode_str(relative_path).decode_utf8().ok()?;
     let mut path = root.to_path_buf();
     for component in Path::new(relative_path.as_ref()).components() {
         let Component::Normal(segment) = component else {
-            return Some(HttpResponse::new(400, "invalid media path"));
+            return Some(MediaResponse::message(400, "invalid media path"));
         };
         path.push(segment);
     }
 
     let Ok(path) = fs::canonicalize(path) else {
-        return Some(HttpResponse::new(404, "media not found"));
+        return Some(MediaResponse::message(404, "media not found"));
     };
     if !path.starts_with(root) || !path.is_file() {
-        return Some(HttpResponse::new(404, "media not found"));
+        return Some(MediaResponse::message(404, "media not found"));
     }
 
     let content_type = if let Some(content_type) = video_content_type(&path) {
@@ -5590,56 +10327,481 @@ fn local_media_response(relative_path: &str, root: &Path) -> Option<HttpResponse
     } else if let Some(content_type) = image_content_type(&path) {
         content_type
     } else {
-        return Some(HttpResponse::new(404, "media not found"));
+        return Some(MediaResponse::message(404, "media not found"));
     };
 
-    Some(stream_media_response(&path, content_type, 403))
+    Some(media_response(&path, content_type, 403, headers))
 }
 
-fn managed_media_response(relative_path: &str, folder: &ManagedFolder) -> Option<HttpResponse> {
+fn managed_media_response(
+    relative_path: &str,
+    folder: &ManagedFolder,
+    headers: &HashMap<String, String>,
+) -> Option<MediaResponse> {
     let relative_path = percent_decode_str(relative_path).decode_utf8().ok()?;
     let path = folder
         .resolve_relative(Path::new(relative_path.as_ref()))
         .ok()?;
     if !path.is_file() {
-        return Some(HttpResponse::new(404, "media not found"));
+        return Some(MediaResponse::message(404, "media not found"));
+    }
+    let content_type = video_content_type(&path).or_else(|| image_content_type(&path))?;
+    Some(media_response(&path, content_type, 404, headers))
+}
+
+/// Byte ranges requested by a `Range` header, resolved against the file length.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRangeRequest {
+    /// No usable `Range` header, so the whole file is served.
+    Full,
+    /// Inclusive `(start, end)` pairs, sorted and with overlaps merged.
+    Partial(Vec<(u64, u64)>),
+    Unsatisfiable,
+}
+
+const MAX_BYTE_RANGES: usize = 16;
+
+fn parse_byte_ranges(header: &str, length: u64) -> ByteRangeRequest {
+    let Some(specs) = header.trim().strip_prefix("bytes=") else {
+        return ByteRangeRequest::Full;
+    };
+    let mut ranges = Vec::new();
+    let mut any_spec = false;
+    for spec in specs.split(',') {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            continue;
+        }
+        any_spec = true;
+        let Some((start, end)) = spec.split_once('-') else {
+            return ByteRangeRequest::Full;
+        };
+        let (start, end) = (start.trim(), end.trim());
+        let range = if start.is_empty() {
+            let Ok(suffix) = end.parse::<u64>() else {
+                return ByteRangeRequest::Full;
+            };
+            (suffix > 0 && length > 0).then(|| (length.saturating_sub(suffix), length - 1))
+        } else {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRangeRequest::Full;
+            };
+            let end = if end.is_empty() {
+                u64::MAX
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end,
+                    _ => return ByteRangeRequest::Full,
+                }
+            };
+            (start < length).then(|| (start, end.min(length - 1)))
+        };
+        ranges.extend(range);
+    }
+    // A header without any range is malformed rather than unsatisfiable, and
+    // RFC 9110 has malformed headers ignored.
+    if !any_spec {
+        return ByteRangeRequest::Full;
+    }
+    if ranges.is_empty() {
+        return ByteRangeRequest::Unsatisfiable;
+    }
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    if merged.len() > MAX_BYTE_RANGES {
+        return ByteRangeRequest::Full;
+    }
+    ByteRangeRequest::Partial(merged)
+}
+
+fn media_etag(metadata: &fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{modified:x}\"", metadata.len())
+}
+
+fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let days = seconds / 86_400;
+    let seconds_of_day = seconds % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        MONTHS[(month - 1) as usize],
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+    )
+}
+
+fn retention_description(retention: &RetentionConfig) -> String {
+    if !retention.prunes() {
+        return "Every snapshot is kept.".to_owned();
+    }
+    let rules = [
+        (retention.keep_last, "latest"),
+        (retention.hourly, "hourly"),
+        (retention.daily, "daily"),
+        (retention.weekly, "weekly"),
+        (retention.monthly, "monthly"),
+    ]
+    .into_iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, label)| format!("{count} {label}"))
+    .collect::<Vec<_>>();
+    format!(
+        "Keeps {} snapshots; older ones are pruned daily.",
+        rules.join(", ")
+    )
+}
+
+/// Year, month and day of a day count since 1970-01-01.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // Civil-from-days conversion, see https://howardhinnant.github.io/date_algorithms.html
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// A piece of a media response body: either literal bytes (multipart headers)
+/// or `length` bytes of the file starting at `offset`.
+enum MediaBodyPart {
+    Bytes(Vec<u8>),
+    File { offset: u64, length: u64 },
+}
+
+/// A media response before it is handed to wgui, so its status, headers and
+/// streamed body can be checked without a server.
+struct MediaResponse {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: MediaBody,
+}
+
+enum MediaBody {
+    Message(&'static str),
+    Parts(fs::File, Vec<MediaBodyPart>),
+}
+
+impl MediaResponse {
+    fn message(status: u16, message: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: MediaBody::Message(message),
+        }
+    }
+
+    fn into_http(self) -> HttpResponse {
+        let response = match self.body {
+            MediaBody::Message(message) => HttpResponse::new(self.status, message),
+            MediaBody::Parts(file, parts) => {
+                HttpResponse::stream(self.status, media_body_stream(file, parts))
+            }
+        };
+        self.headers
+            .into_iter()
+            .fold(response, |response, (name, value)| {
+                response.header(name, value)
+            })
+    }
+}
+
+fn media_response(
+    path: &Path,
+    content_type: &str,
+    error_status: u16,
+    headers: &HashMap<String, String>,
+) -> MediaResponse {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return MediaResponse::message(error_status, "media cannot be read"),
+    };
+    let Ok(metadata) = file.metadata() else {
+        return MediaResponse::message(error_status, "media cannot be read");
+    };
+    let length = metadata.len();
+    let etag = media_etag(&metadata);
+    let last_modified = metadata.modified().ok().map(http_date);
+
+    let validator_matches = headers.get("if-range").is_none_or(|validator| {
+        let validator = validator.trim();
+        validator == etag || last_modified.as_deref() == Some(validator)
+    });
+    let ranges = match headers.get("range") {
+        Some(range) if validator_matches => parse_byte_ranges(range, length),
+        _ => ByteRangeRequest::Full,
+    };
+
+    let (status, parts, response_type) = match ranges {
+        ByteRangeRequest::Full => (
+            200,
+            vec![MediaBodyPart::File { offset: 0, length }],
+            content_type.to_owned(),
+        ),
+        ByteRangeRequest::Unsatisfiable => {
+            return MediaResponse {
+                headers: vec![
+                    ("content-range", format!("bytes */{length}")),
+                    ("accept-ranges", "bytes".to_owned()),
+                ],
+                ..MediaResponse::message(416, "requested range not satisfiable")
+            };
+        }
+        ByteRangeRequest::Partial(ranges) if ranges.len() == 1 => {
+            let (start, end) = ranges[0];
+            let mut headers = media_headers(content_type, &etag, last_modified);
+            headers.push(("content-range", format!("bytes {start}-{end}/{length}")));
+            headers.push(("content-length", (end - start + 1).to_string()));
+            return MediaResponse {
+                status: 206,
+                headers,
+                body: MediaBody::Parts(
+                    file,
+                    vec![MediaBodyPart::File {
+                        offset: start,
+                        length: end - start + 1,
+                    }],
+                ),
+            };
+        }
+        ByteRangeRequest::Partial(ranges) => {
+            let boundary = uuid::Uuid::new_v4().simple().to_string();
+            let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+            for (start, end) in ranges {
+                parts.push(MediaBodyPart::Bytes(
+                    format!(
+                        "\r\n--{boundary}\r\ncontent-type: {content_type}\r\ncontent-range: bytes {start}-{end}/{length}\r\n\r\n"
+                    )
+                    .into_bytes(),
+                ));
+                parts.push(MediaBodyPart::File {
+                    offset: start,
+                    length: end - start + 1,
+                });
+            }
+            parts.push(MediaBodyPart::Bytes(
+                format!("\r\n--{boundary}--\r\n").into_bytes(),
+            ));
+            (
+                206,
+                parts,
+                format!("multipart/byteranges; boundary={boundary}"),
+            )
+        }
+    };
+    MediaResponse {
+        status,
+        headers: media_headers(&response_type, &etag, last_modified),
+        body: MediaBody::Parts(file, parts),
+    }
+}
+
+fn media_headers(
+    content_type: &str,
+    etag: &str,
+    last_modified: Option<String>,
+) -> Vec<(&'static str, String)> {
+    let mut headers = vec![
+        ("content-type", content_type.to_owned()),
+        ("accept-ranges", "bytes".to_owned()),
+        ("etag", etag.to_owned()),
+    ];
+    headers.extend(last_modified.map(|last_modified| ("last-modified", last_modified)));
+    headers
+}
+
+fn media_body_stream(
+    file: fs::File,
+    parts: Vec<MediaBodyPart>,
+) -> impl futures_util::Stream<Item = std::io::Result<Vec<u8>>> + Send + 'static {
+    let parts = VecDeque::from(parts);
+    futures_util::stream::unfold((file, parts), |(mut file, mut parts)| async move {
+        loop {
+            match parts.pop_front()? {
+                MediaBodyPart::Bytes(bytes) => return Some((Ok(bytes), (file, parts))),
+                MediaBodyPart::File { length: 0, .. } => continue,
+                MediaBodyPart::File { offset, length } => {
+                    let mut bytes = vec![0; length.min(64 * 1024) as usize];
+                    let read = file
+                        .seek(SeekFrom::Start(offset))
+                        .and_then(|_| file.read(&mut bytes));
+                    return match read {
+                        Ok(0) | Err(_) => None,
+                        Ok(read) => {
+                            bytes.truncate(read);
+                            parts.push_front(MediaBodyPart::File {
+                                offset: offset + read as u64,
+                                length: length - read as u64,
+                            });
+                            Some((Ok(bytes), (file, parts)))
+                        }
+                    };
+                }
+            }
+        }
+    })
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn sort_duplicate_groups(groups: &mut [DuplicateGroup], sort: &str) {
+    match sort {
+        "copies" => groups.sort_by_key(|group| std::cmp::Reverse(group.distinct_files)),
+        "size" => groups.sort_by_key(|group| std::cmp::Reverse(group.size)),
+        _ => groups.sort_by_key(|group| std::cmp::Reverse(group.wasted_bytes())),
+    }
+}
+
+fn snapshot_browser(snapshot: &OpenSnapshot) -> Item {
+    let manifest = &snapshot.manifest;
+    let started = system_time_from_millis(manifest.started_at)
+        .map_or_else(|| "—".to_owned(), format_modified);
+    let location = match snapshot.folder {
+        Some(folder) => manifest.folders[folder]
+            .join(&snapshot.directory)
+            .display()
+            .to_string(),
+        None => "Scanned folders".to_owned(),
+    };
+    let entries = match snapshot.folder {
+        Some(folder) => manifest.entries(folder, &snapshot.directory),
+        None => (0..manifest.folders.len())
+            .map(|folder| {
+                let files = manifest.files.iter().filter(|file| file.folder == folder);
+                crate::backup::SnapshotEntry {
+                    name: manifest.folders[folder].display().to_string(),
+                    is_directory: true,
+                    size: files.clone().map(|file| file.size).sum(),
+                    file_count: files.count(),
+                }
+            })
+            .collect(),
+    };
+    let mut rows = vec![
+        hstack([
+            vstack([
+                text(&format!("Snapshot of {started}")).color("#1f2937"),
+                text(&location).color("#6b7280").break_words(true),
+            ])
+            .grow(1)
+            .spacing(2),
+            button("Up")
+                .id(SNAPSHOT_UP_ID)
+                .padding(6)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+            button("Close")
+                .id(CLOSE_SNAPSHOT_ID)
+                .padding(6)
+                .border("1px solid #dce5e8")
+                .background_color("#ffffff")
+                .color("#0f6175"),
+        ])
+        .spacing(8)
+        .padding_bottom(6),
+    ];
+    rows.extend(
+        entries
+            .iter()
+            .take(SNAPSHOT_ENTRIES_SHOWN)
+            .enumerate()
+            .map(|(index, entry)| {
+                let name: Item = if entry.is_directory {
+                    button(&format!("▸ {}", entry.name))
+                        .id(OPEN_SNAPSHOT_ENTRY_ID)
+                        .inx(index as u32)
+                        .padding(4)
+                        .border("none")
+                        .background_color("transparent")
+                        .color("#0f6175")
+                        .cursor("pointer")
+                } else {
+                    text(&entry.name).color("#374151")
+                };
+                let files = if entry.is_directory {
+                    format!("{} files", entry.file_count)
+                } else {
+                    String::new()
+                };
+                hstack([
+                    name.grow(1),
+                    text(&files).width(90).color("#6b7280"),
+                    text(&format_size(entry.size)).width(90).color("#6b7280"),
+                    button("Restore")
+                        .id(RESTORE_SNAPSHOT_ENTRY_ID)
+                        .inx(index as u32)
+                        .padding(4)
+                        .border("1px solid #dce5e8")
+                        .background_color("#ffffff")
+                        .color("#0f6175"),
+                ])
+                .spacing(8)
+                .padding(4)
+                .border("1px solid #e4ebed")
+                .background_color("#ffffff")
+            }),
+    );
+    if entries.len() > SNAPSHOT_ENTRIES_SHOWN {
+        rows.push(
+            text(&format!(
+                "and {} more",
+                entries.len() - SNAPSHOT_ENTRIES_SHOWN
+            ))
+            .color("#6b7280"),
+        );
     }
-    let content_type = video_content_type(&path).or_else(|| image_content_type(&path))?;
-    Some(stream_media_response(&path, content_type, 404))
-}
-
-fn stream_media_response(path: &Path, content_type: &str, error_status: u16) -> HttpResponse {
-    let file = match fs::File::open(path) {
-        Ok(file) => file,
-        Err(_) => return HttpResponse::new(error_status, "media cannot be read"),
-    };
-    let stream = futures_util::stream::unfold(file, |mut file| async move {
-        let mut bytes = vec![0; 64 * 1024];
-        match file.read(&mut bytes) {
-            Ok(0) | Err(_) => None,
-            Ok(read) => {
-                bytes.truncate(read);
-                Some((Ok(bytes), file))
-            }
-        }
-    });
-    HttpResponse::stream(200, stream)
-        .header("content-type", content_type)
-        .header("accept-ranges", "bytes")
+    vstack(rows).spacing(2)
 }
 
-fn format_size(bytes: u64) -> String {
-    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
-    let mut value = bytes as f64;
-    let mut unit = 0;
-    while value >= 1024.0 && unit < UNITS.len() - 1 {
-        value /= 1024.0;
-        unit += 1;
-    }
-    if unit == 0 {
-        format!("{bytes} B")
+fn integrity_nav_label(alerts: usize) -> String {
+    if alerts == 0 {
+        "✓  Integrity".to_owned()
     } else {
-        format!("{value:.1} {}", UNITS[unit])
+        format!("⚠  Integrity ({alerts})")
     }
 }
 
@@ -5656,6 +10818,8 @@ fn scan_trigger_label(trigger: ScanTrigger) -> &'static str {
         ScanTrigger::ManualFolder => "Manual folder scan",
         ScanTrigger::ManualRefresh => "Manual refresh",
         ScanTrigger::FilesystemChange => "Filesystem change",
+        ScanTrigger::Resumed => "Resumed after restart",
+        ScanTrigger::Scheduled => "Scheduled rescan",
     }
 }
 
@@ -5678,6 +10842,26 @@ fn format_scan_duration(millis: i64) -> String {
     }
 }
 
+/// Track length as a player shows it, e.g. `3:07` or `1:02:03`.
+fn format_play_time(millis: i64) -> String {
+    let seconds = millis.max(0) / 1_000;
+    if seconds >= 3_600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3_600,
+            (seconds / 60) % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Search snippets keep the file's line breaks; hit tiles show them on one line.
+fn content_snippet_line(snippet: &str) -> String {
+    snippet.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 fn format_modified(time: SystemTime) -> String {
     let Ok(elapsed) = SystemTime::now().duration_since(time) else {
         return "Just now".to_owned();
@@ -5701,10 +10885,31 @@ fn format_modified(time: SystemTime) -> String {
     }
 }
 
+/// Time until a scheduled job next runs, e.g. `in 3 hrs`.
+fn format_next_run(at: i64, now: i64) -> String {
+    let seconds = (at - now).max(0) / 1_000;
+    let (value, unit) = if seconds < 60 {
+        return "Due now".to_owned();
+    } else if seconds < 3_600 {
+        (seconds / 60, "min")
+    } else if seconds < 86_400 {
+        (seconds / 3_600, "hr")
+    } else {
+        (seconds / 86_400, "day")
+    };
+    format!("in {value} {unit}{}", if value == 1 { "" } else { "s" })
+}
+
 fn relative_age(value: u64, unit: &str) -> String {
     format!("{value} {unit}{} ago", if value == 1 { "" } else { "s" })
 }
 
+/// Calendar date of a photo capture time, e.g. `2021-06-03`.
+fn format_capture_date(millis: i64) -> String {
+    let (year, month, day) = civil_from_days(millis.div_euclid(86_400_000));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
 #[allow(dead_code)]
 fn file_row(
     icon: &str,
@@ -5748,12 +10953,16 @@ mod tests {
                 path: first.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             },
             MediaScanPath {
                 id: 2,
                 path: second.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             },
         ];
         let folders = managed_folders(&roots);
@@ -5795,28 +11004,33 @@ mod tests {
                 path: directory.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
         let node_id = database.local_node_id("PuppyDrive").unwrap();
         let hash = vec![9; 32];
+        let managed = ManagedFolder::open(folder.id, &folder.path).unwrap();
         database
-            .sync_media_scan(
+            .sync_scan(
                 &node_id,
-                folder.id,
+                &managed,
                 &[MediaIndexObservation {
                     path: source_path.clone(),
                     hash: Some(hash.clone()),
                     size: fs::metadata(&source_path).unwrap().len(),
                     mime_type: Some("image/png".to_owned()),
+                    extension_mime_type: Some("image/png".to_owned()),
+                    sniffed_mime_type: None,
                     created_at: None,
                     modified_at: None,
                     accessed_at: None,
                 }],
+                &crate::indexer::folder_indexers(&folder),
                 true,
             )
             .unwrap();
-        let managed = ManagedFolder::open(folder.id, &folder.path).unwrap();
         let folders = HashMap::from([(folder.id, managed)]);
         let cache_dir = directory.join("thumbnails");
         let request = format!("{}/{}", folder.id, hex_encode(&hash));
@@ -5847,16 +11061,372 @@ mod tests {
         let root = temporary_directory("media-response");
         fs::write(root.join("photo.jpg"), b"photo").unwrap();
         assert_eq!(
-            local_media_response("../photo.jpg", &root).unwrap().status,
+            local_media_response("../photo.jpg", &root, &HashMap::new())
+                .unwrap()
+                .status,
             400
         );
         assert_eq!(
-            local_media_response("photo.jpg", &root).unwrap().status,
+            local_media_response("photo.jpg", &root, &HashMap::new())
+                .unwrap()
+                .status,
+            200
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn watcher_forwards_changed_paths_and_falls_back_to_rescans() {
+        let roots = vec![
+            MediaScanPath {
+                id: 1,
+                path: "/photos".to_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: r#"[".drafts/"]"#.to_owned(),
+                exclude_globs: r#"["*.tmp"]"#.to_owned(),
+            },
+            MediaScanPath {
+                id: 2,
+                path: "/photos/phone".to_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            },
+        ];
+        let ignored = folder_ignore_rules(&roots, &["node_modules".to_owned()]);
+        let event = |kind, paths: &[&str]| {
+            paths.iter().fold(notify::Event::new(kind), |event, path| {
+                event.add_path(PathBuf::from(path))
+            })
+        };
+        let created = event(
+            EventKind::Create(notify::event::CreateKind::File),
+            &["/photos/phone/new.jpg"],
+        );
+        assert_eq!(
+            watched_changes_for_event(&created, &roots, &ignored),
+            [WatchedChange::Paths {
+                folder_id: 2,
+                paths: vec![PathBuf::from("/photos/phone/new.jpg")]
+            }]
+        );
+        let hidden = event(
+            EventKind::Remove(notify::event::RemoveKind::File),
+            &[
+                "/photos/node_modules/skip.jpg",
+                "/photos/.cache/a.jpg",
+                "/photos/upload.tmp",
+            ],
+        );
+        assert!(watched_changes_for_event(&hidden, &roots, &ignored).is_empty());
+        let included = event(
+            EventKind::Create(notify::event::CreateKind::File),
+            &["/photos/.drafts/a.jpg"],
+        );
+        assert_eq!(
+            watched_changes_for_event(&included, &roots, &ignored),
+            [WatchedChange::Paths {
+                folder_id: 1,
+                paths: vec![PathBuf::from("/photos/.drafts/a.jpg")]
+            }]
+        );
+        let rules_changed = event(
+            EventKind::Modify(ModifyKind::Data(notify::event::DataChange::Any)),
+            &["/photos/phone/.puppyignore"],
+        );
+        assert_eq!(
+            watched_changes_for_event(&rules_changed, &roots, &ignored),
+            [WatchedChange::Rescan { folder_id: 2 }]
+        );
+        let renamed = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        assert_eq!(
+            watched_changes_for_event(
+                &event(renamed, &["/photos/a.jpg", "/photos/b.jpg"]),
+                &roots,
+                &ignored
+            ),
+            [
+                WatchedChange::Paths {
+                    folder_id: 1,
+                    paths: vec![PathBuf::from("/photos/a.jpg")]
+                },
+                WatchedChange::Paths {
+                    folder_id: 1,
+                    paths: vec![PathBuf::from("/photos/b.jpg")]
+                }
+            ]
+        );
+        assert_eq!(
+            watched_changes_for_event(
+                &event(renamed, &["/photos/a.jpg", "/photos/phone/a.jpg"]),
+                &roots,
+                &ignored
+            ),
+            [
+                WatchedChange::Rescan { folder_id: 1 },
+                WatchedChange::Rescan { folder_id: 2 }
+            ]
+        );
+        let overflow = notify::Event::new(EventKind::Other).set_flag(notify::event::Flag::Rescan);
+        assert_eq!(
+            watched_changes_for_event(&overflow, &roots, &ignored).len(),
+            2
+        );
+        let metadata = event(
+            EventKind::Modify(ModifyKind::Metadata(notify::event::MetadataKind::Any)),
+            &["/photos/a.jpg"],
+        );
+        assert!(watched_changes_for_event(&metadata, &roots, &ignored).is_empty());
+    }
+
+    #[test]
+    fn watcher_covers_folders_without_the_media_indexer() {
+        let notes = temporary_directory("watch-notes");
+        let unindexed = temporary_directory("watch-unindexed");
+        let roots = vec![
+            MediaScanPath {
+                id: 1,
+                path: notes.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: r#"["content"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            },
+            MediaScanPath {
+                id: 2,
+                path: unindexed.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: "[]".to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            },
+        ];
+        let (_watcher, _changes, watched) =
+            build_media_watcher(&roots, Duration::from_millis(10), &[]).unwrap();
+        assert_eq!(watched, std::slice::from_ref(&notes));
+
+        let ignored = folder_ignore_rules(&roots, &[]);
+        let created = |path: PathBuf| {
+            notify::Event::new(EventKind::Create(notify::event::CreateKind::File)).add_path(path)
+        };
+        assert_eq!(
+            watched_changes_for_event(&created(notes.join("todo.md")), &roots, &ignored),
+            [WatchedChange::Paths {
+                folder_id: 1,
+                paths: vec![notes.join("todo.md")]
+            }]
+        );
+        assert!(
+            watched_changes_for_event(&created(unindexed.join("a.md")), &roots, &ignored)
+                .is_empty()
+        );
+        let overflow = notify::Event::new(EventKind::Other).set_flag(notify::event::Flag::Rescan);
+        assert_eq!(
+            watched_changes_for_event(&overflow, &roots, &ignored),
+            [WatchedChange::Rescan { folder_id: 1 }]
+        );
+        let _ = fs::remove_dir_all(notes);
+        let _ = fs::remove_dir_all(unindexed);
+    }
+
+    #[test]
+    fn parses_byte_ranges() {
+        assert_eq!(
+            parse_byte_ranges("bytes=0-99", 1_000),
+            ByteRangeRequest::Partial(vec![(0, 99)])
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=900-", 1_000),
+            ByteRangeRequest::Partial(vec![(900, 999)])
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=-100", 1_000),
+            ByteRangeRequest::Partial(vec![(900, 999)])
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=500-2000", 1_000),
+            ByteRangeRequest::Partial(vec![(500, 999)])
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=200-299, 0-99, 50-149", 1_000),
+            ByteRangeRequest::Partial(vec![(0, 149), (200, 299)])
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=1000-", 1_000),
+            ByteRangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=-0", 1_000),
+            ByteRangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_byte_ranges("bytes=9-3", 1_000),
+            ByteRangeRequest::Full
+        );
+        assert_eq!(
+            parse_byte_ranges("items=0-1", 1_000),
+            ByteRangeRequest::Full
+        );
+        assert_eq!(parse_byte_ranges("bytes=", 1_000), ByteRangeRequest::Full);
+        assert_eq!(
+            parse_byte_ranges("bytes= , ", 1_000),
+            ByteRangeRequest::Full
+        );
+    }
+
+    #[test]
+    fn photo_filters_use_displayed_orientation_and_location() {
+        let photo = PhotoMetadata {
+            captured_at: Some(1_622_721_909_000),
+            width: Some(6016),
+            height: Some(4016),
+            orientation: Some(6),
+            latitude: Some(60.175),
+            longitude: Some(-24.935),
+            ..PhotoMetadata::default()
+        };
+        assert!(photo_filter_matches("portrait", Some(&photo)));
+        assert!(!photo_filter_matches("landscape", Some(&photo)));
+        assert!(photo_filter_matches("located", Some(&photo)));
+        assert!(photo_filter_matches("unlocated", None));
+        assert!(!photo_filter_matches("dated", None));
+        assert_eq!(photo_pixels(Some(&photo)), 6016 * 4016);
+        assert_eq!(format_capture_date(1_622_721_909_000), "2021-06-03");
+        assert_eq!(format_capture_date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn formats_http_dates() {
+        assert_eq!(
+            http_date(SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            http_date(SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+    }
+
+    fn media_header<'a>(response: &'a MediaResponse, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    async fn media_body(response: MediaResponse) -> Vec<u8> {
+        use futures_util::StreamExt;
+
+        let MediaBody::Parts(file, parts) = response.body else {
+            panic!("response has no streamed body");
+        };
+        media_body_stream(file, parts)
+            .map(|chunk| chunk.unwrap())
+            .concat()
+            .await
+    }
+
+    #[tokio::test]
+    async fn media_response_serves_byte_ranges() {
+        let root = temporary_directory("media-range");
+        let content = (0..200_000u32).map(|byte| byte as u8).collect::<Vec<_>>();
+        fs::write(root.join("clip.mp4"), &content).unwrap();
+        let range = |value: &str| HashMap::from([("range".to_owned(), value.to_owned())]);
+
+        let single = local_media_response("clip.mp4", &root, &range("bytes=70000-70999")).unwrap();
+        assert_eq!(single.status, 206);
+        assert_eq!(
+            media_header(&single, "content-range"),
+            Some("bytes 70000-70999/200000")
+        );
+        assert_eq!(media_header(&single, "content-length"), Some("1000"));
+        assert_eq!(media_header(&single, "content-type"), Some("video/mp4"));
+        assert_eq!(media_body(single).await, &content[70_000..71_000]);
+
+        // Both ranges cross the 64 KiB read size, so parts arrive in pieces.
+        let multipart =
+            local_media_response("clip.mp4", &root, &range("bytes=0-9,100000-170000")).unwrap();
+        assert_eq!(multipart.status, 206);
+        assert_eq!(media_header(&multipart, "content-range"), None);
+        let boundary = media_header(&multipart, "content-type")
+            .and_then(|value| value.strip_prefix("multipart/byteranges; boundary="))
+            .unwrap()
+            .to_owned();
+        let mut expected = format!(
+            "\r\n--{boundary}\r\ncontent-type: video/mp4\r\ncontent-range: bytes 0-9/200000\r\n\r\n"
+        )
+        .into_bytes();
+        expected.extend_from_slice(&content[..10]);
+        expected.extend_from_slice(
+            format!(
+                "\r\n--{boundary}\r\ncontent-type: video/mp4\r\ncontent-range: bytes 100000-170000/200000\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        expected.extend_from_slice(&content[100_000..=170_000]);
+        expected.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+        assert_eq!(media_body(multipart).await, expected);
+
+        let full = local_media_response("clip.mp4", &root, &HashMap::new()).unwrap();
+        assert_eq!(full.status, 200);
+        assert_eq!(media_body(full).await, content);
+
+        let unsatisfiable =
+            local_media_response("clip.mp4", &root, &range("bytes=300000-")).unwrap();
+        assert_eq!(unsatisfiable.status, 416);
+        assert_eq!(
+            media_header(&unsatisfiable, "content-range"),
+            Some("bytes */200000")
+        );
+        let malformed = local_media_response("clip.mp4", &root, &range("bytes= ")).unwrap();
+        assert_eq!(malformed.status, 200);
+        assert_eq!(media_body(malformed).await, content);
+
+        let metadata = fs::metadata(root.join("clip.mp4")).unwrap();
+        let mut headers = range("bytes=0-1023");
+        headers.insert("if-range".to_owned(), media_etag(&metadata));
+        assert_eq!(
+            local_media_response("clip.mp4", &root, &headers)
+                .unwrap()
+                .status,
+            206
+        );
+        headers.insert("if-range".to_owned(), "\"stale\"".to_owned());
+        assert_eq!(
+            local_media_response("clip.mp4", &root, &headers)
+                .unwrap()
+                .status,
             200
         );
         let _ = fs::remove_dir_all(root);
     }
 
+    #[tokio::test]
+    async fn managed_media_response_serves_byte_ranges() {
+        let root = temporary_directory("managed-media-range");
+        let content = (0..4_096u32)
+            .map(|byte| (byte % 251) as u8)
+            .collect::<Vec<_>>();
+        fs::write(root.join("clip.mp4"), &content).unwrap();
+        let folder = ManagedFolder::open(1, &root).unwrap();
+        let headers = HashMap::from([("range".to_owned(), "bytes=-512".to_owned())]);
+        let suffix = managed_media_response("clip.mp4", &folder, &headers).unwrap();
+        assert_eq!(suffix.status, 206);
+        assert_eq!(
+            media_header(&suffix, "content-range"),
+            Some("bytes 3584-4095/4096")
+        );
+        assert_eq!(media_header(&suffix, "content-length"), Some("512"));
+        assert_eq!(media_body(suffix).await, &content[3_584..]);
+        let full = managed_media_response("clip.mp4", &folder, &HashMap::new()).unwrap();
+        assert_eq!(full.status, 200);
+        assert_eq!(media_body(full).await, content);
+        let _ = fs::remove_dir_all(root);
+    }
+
     fn upload_request(inbox: &str, filename: &str, body: &[u8]) -> wgui::HttpRequest {
         wgui::HttpRequest {
             method: "POST".to_owned(),
@@ -5953,6 +11523,259 @@ mod tests {
         let _ = fs::remove_dir_all(root);
     }
 
+    fn session_request(
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> wgui::HttpRequest {
+        wgui::HttpRequest {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            query: HashMap::from([("inbox".to_owned(), "test-inbox".to_owned())]),
+            headers: headers
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect(),
+            body: body.to_vec(),
+        }
+    }
+
+    #[test]
+    fn protected_routes_reject_missing_and_underscoped_credentials() {
+        let root = temporary_directory("authorize-request");
+        let database = Database::open(&root.join("puppydrive.db")).unwrap();
+        let auth = Auth::new(database.auth_store(), false);
+        let session = auth.set_up_admin("correct horse", None).unwrap();
+        let (_, read_only) = auth
+            .create_access_token("Viewer", AccessScope::ReadOnly)
+            .unwrap();
+        let (_, upload_only) = auth
+            .create_access_token("Camera", AccessScope::UploadOnly)
+            .unwrap();
+        let read_only = format!("Bearer {read_only}");
+        let upload_only = format!("Bearer {upload_only}");
+        let cookie = format!("{}={session}", auth::SESSION_COOKIE);
+        let status = |method: &str, path: &str, headers: &[(&str, &str)]| {
+            authorize_request(&auth, &session_request(method, path, headers, b""))
+                .map(|response| response.status)
+        };
+
+        assert_eq!(status("GET", "/media-files/1/photo.jpg", &[]), Some(401));
+        assert_eq!(status("GET", "/content/00", &[]), Some(401));
+        assert_eq!(
+            status(
+                "GET",
+                "/api/v1/files",
+                &[("authorization", "Bearer forged")]
+            ),
+            Some(401)
+        );
+        assert_eq!(
+            status("POST", "/uploads", &[("authorization", &read_only)]),
+            Some(403)
+        );
+        assert_eq!(
+            status(
+                "DELETE",
+                "/api/v1/sources/1",
+                &[("authorization", &read_only)]
+            ),
+            Some(403)
+        );
+        assert_eq!(
+            status("GET", "/api/v1/files", &[("authorization", &upload_only)]),
+            Some(403)
+        );
+
+        assert_eq!(status("GET", "/favicon.ico", &[]), None);
+        assert_eq!(
+            status("GET", "/api/v1/files", &[("authorization", &read_only)]),
+            None
+        );
+        assert_eq!(
+            status(
+                "PATCH",
+                "/uploads/sessions/abc",
+                &[("authorization", &upload_only)]
+            ),
+            None
+        );
+        assert_eq!(
+            status("DELETE", "/api/v1/tokens/1", &[("cookie", &cookie)]),
+            None
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn auth_routes_set_and_clear_session_cookies() {
+        let root = temporary_directory("auth-routes");
+        let database = Database::open(&root.join("puppydrive.db")).unwrap();
+        let auth = Auth::new(database.auth_store(), false);
+        let setup = auth_response(
+            &auth,
+            &session_request(
+                "POST",
+                "/auth/setup",
+                &[],
+                br#"{"password":"correct horse"}"#,
+            ),
+            "setup",
+        );
+        assert_eq!(setup.status, 201);
+        assert_eq!(
+            auth_response(
+                &auth,
+                &session_request("POST", "/auth/login", &[], br#"{"password":"wrong"}"#),
+                "login",
+            )
+            .status,
+            401
+        );
+        let login = auth_response(
+            &auth,
+            &session_request(
+                "POST",
+                "/auth/login",
+                &[],
+                br#"{"password":"correct horse"}"#,
+            ),
+            "login",
+        );
+        assert_eq!(login.status, 200);
+        let session = auth.log_in("correct horse").unwrap().unwrap();
+        let cookie = format!("{}={session}", auth::SESSION_COOKIE);
+        assert_eq!(
+            auth_response(
+                &auth,
+                &session_request("POST", "/auth/ticket", &[], b""),
+                "ticket"
+            )
+            .status,
+            403
+        );
+        let ticket = auth_response(
+            &auth,
+            &session_request("POST", "/auth/ticket", &[("cookie", &cookie)], b""),
+            "ticket",
+        );
+        assert_eq!(ticket.status, 200);
+        assert_eq!(
+            auth_response(
+                &auth,
+                &session_request("POST", "/auth/logout", &[("cookie", &cookie)], b""),
+                "logout",
+            )
+            .status,
+            204
+        );
+        assert_eq!(
+            authorize_request(
+                &auth,
+                &session_request("GET", "/api/v1/files", &[("cookie", &cookie)], b""),
+            )
+            .map(|response| response.status),
+            Some(401)
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn resumable_upload_session_moves_file_into_inbox() {
+        let root = temporary_directory("upload-session");
+        fs::create_dir(root.join("Inbox")).unwrap();
+        let inboxes = test_inboxes("Inbox");
+        let staging = UploadStaging::new(root.join(".staging"));
+        let respond = |request: wgui::HttpRequest| {
+            let session_path = upload_session_path(&request.path).unwrap();
+            upload_session_response(&request, session_path, &root, &inboxes, &staging)
+        };
+
+        let created = respond(session_request(
+            "POST",
+            "/uploads/sessions",
+            &[
+                ("x-puppydrive-filename", "clip.mp4"),
+                ("upload-length", "6"),
+            ],
+            b"",
+        ));
+        assert_eq!(created.status, 201);
+        let session_id = fs::read_dir(root.join(".staging"))
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .find_map(|entry| {
+                let path = entry.path();
+                (path.extension()? == "json")
+                    .then(|| path.file_stem()?.to_str().map(str::to_owned))
+                    .flatten()
+            })
+            .unwrap();
+        let session_path = format!("/uploads/sessions/{session_id}");
+
+        let patch = |offset: &str, body: &[u8]| {
+            respond(session_request(
+                "PATCH",
+                &session_path,
+                &[("upload-offset", offset)],
+                body,
+            ))
+            .status
+        };
+        assert_eq!(patch("0", b"abc"), 204);
+        assert_eq!(patch("0", b"abc"), 409);
+        assert_eq!(
+            respond(session_request("HEAD", &session_path, &[], b"")).status,
+            200
+        );
+        let finish_path = format!("{session_path}/finish");
+        assert_eq!(
+            respond(session_request("POST", &finish_path, &[], b"")).status,
+            409
+        );
+        assert_eq!(patch("3", b"def"), 204);
+        assert_eq!(
+            respond(session_request("POST", &finish_path, &[], b"")).status,
+            201
+        );
+        assert_eq!(fs::read(root.join("Inbox/clip.mp4")).unwrap(), b"abcdef");
+        assert_eq!(
+            respond(session_request("HEAD", &session_path, &[], b"")).status,
+            404
+        );
+        assert_eq!(
+            respond(session_request(
+                "POST",
+                "/uploads/sessions",
+                &[
+                    ("x-puppydrive-filename", "clip.mp4"),
+                    ("upload-length", "6")
+                ],
+                b"",
+            ))
+            .status,
+            409
+        );
+        let past_single_shot_limit = (MAX_UPLOAD_BYTES as u64 + 1).to_string();
+        assert_eq!(
+            respond(session_request(
+                "POST",
+                "/uploads/sessions",
+                &[
+                    ("x-puppydrive-filename", "huge.mp4"),
+                    ("upload-length", &past_single_shot_limit)
+                ],
+                b"",
+            ))
+            .status,
+            201
+        );
+        assert_eq!(upload_session_path("/uploads/sessions/abc"), Some("abc"));
+        assert_eq!(upload_session_path("/uploads/sessionsabc"), None);
+        let _ = fs::remove_dir_all(root);
+    }
+
     #[cfg(unix)]
     #[test]
     fn upload_rejects_symlinked_destination_outside_root() {
@@ -5986,7 +11809,9 @@ mod tests {
         fs::write(outside.join("outside.jpg"), b"outside").unwrap();
         symlink(outside.join("outside.jpg"), root.join("escape.jpg")).unwrap();
         assert_eq!(
-            local_media_response("escape.jpg", &root).unwrap().status,
+            local_media_response("escape.jpg", &root, &HashMap::new())
+                .unwrap()
+                .status,
             404
         );
         let _ = fs::remove_dir_all(root);
@@ -0,0 +1,1331 @@
+//! Versioned JSON API served under `/api/v1/`.
+//!
+//! The HTTP handler runs outside the UI event loop, so requests are forwarded
+//! to [`App::run`] over a channel and answered from the same state the UI uses.
+
+use std::path::PathBuf;
+
+use secrecy::SecretString;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::{mpsc, oneshot};
+use wgui::HttpResponse;
+
+use super::{App, FolderIndexStatus, hex_decode, hex_encode};
+use crate::auth::AccessScope;
+use crate::backup::{
+    ConflictPolicy, Manifest, PruneReport, RestoreSummary, SnapshotEntry, SnapshotSummary,
+};
+use crate::database::{
+    AccessTokenRecord, AudioMetadata, ContentSearchHit, Database, FileSearchHit, IndexedFile,
+    IndexedMediaFile, MediaScanPath, PhotoMetadata, ScanHistoryEntry, ScanTrigger, SftpAuth,
+    SftpSourceConfig, Source, VirtualDirectory, VirtualDirectoryEntry, now_millis,
+    sftp_source_config,
+};
+use crate::file_query::FileQuery;
+use crate::indexer::{find_indexer, registered_indexers};
+use crate::peers::{self, CHANGES_PAGE_LIMIT};
+
+pub(super) const API_PREFIX: &str = "/api/v1/";
+const DEFAULT_PAGE_LIMIT: usize = 100;
+const MAX_PAGE_LIMIT: usize = 1_000;
+
+pub(super) struct ApiRequest {
+    request: wgui::HttpRequest,
+    reply: oneshot::Sender<HttpResponse>,
+}
+
+/// Hands an HTTP request to the app loop and waits for its response.
+pub(super) async fn forward(
+    requests: &mpsc::Sender<ApiRequest>,
+    request: wgui::HttpRequest,
+) -> HttpResponse {
+    let (reply, response) = oneshot::channel();
+    if requests.send(ApiRequest { request, reply }).await.is_err() {
+        return ApiError::new(503, "PuppyDrive is shutting down").into_response();
+    }
+    response
+        .await
+        .unwrap_or_else(|_| ApiError::new(503, "PuppyDrive is shutting down").into_response())
+}
+
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(what: &str) -> Self {
+        Self::new(404, format!("{what} not found"))
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+
+    fn internal(error: anyhow::Error) -> Self {
+        log::error!("API request failed: {error:#}");
+        Self::new(500, "internal error")
+    }
+
+    fn into_response(self) -> HttpResponse {
+        json_response(self.status, &json!({ "error": self.message }))
+    }
+}
+
+type ApiResult = std::result::Result<(u16, Value), ApiError>;
+
+fn ok(value: Value) -> ApiResult {
+    Ok((200, value))
+}
+
+fn json_response(status: u16, value: &Value) -> HttpResponse {
+    HttpResponse::new(status, value.to_string())
+        .header("content-type", "application/json")
+        .header("cache-control", "no-store")
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(request: &wgui::HttpRequest) -> Result<T, ApiError> {
+    serde_json::from_slice(&request.body)
+        .map_err(|error| ApiError::bad_request(format!("invalid JSON body: {error}")))
+}
+
+fn parse_id(value: &str) -> Result<u32, ApiError> {
+    value
+        .parse()
+        .map_err(|_| ApiError::bad_request(format!("invalid id '{value}'")))
+}
+
+fn parse_hash(value: &str) -> Result<Vec<u8>, ApiError> {
+    hex_decode(value)
+        .filter(|hash| hash.len() == 32)
+        .ok_or_else(|| ApiError::bad_request(format!("invalid file hash '{value}'")))
+}
+
+/// Offset/limit pagination plus the filters shared by the index listings.
+#[derive(Debug, PartialEq, Eq)]
+struct ListingQuery {
+    offset: usize,
+    limit: usize,
+    mime: Option<String>,
+    folder: Option<u32>,
+    search: Option<String>,
+}
+
+impl ListingQuery {
+    fn parse(request: &wgui::HttpRequest) -> Result<Self, ApiError> {
+        let number = |name: &str| {
+            request
+                .query
+                .get(name)
+                .map(|value| {
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| ApiError::bad_request(format!("invalid {name} '{value}'")))
+                })
+                .transpose()
+        };
+        let text = |name: &str| {
+            request
+                .query
+                .get(name)
+                .map(|value| value.trim().to_lowercase())
+                .filter(|value| !value.is_empty())
+        };
+        Ok(Self {
+            offset: number("offset")?.unwrap_or(0),
+            limit: number("limit")?
+                .unwrap_or(DEFAULT_PAGE_LIMIT)
+                .clamp(1, MAX_PAGE_LIMIT),
+            mime: text("mime"),
+            folder: request
+                .query
+                .get("folder")
+                .map(|value| parse_id(value))
+                .transpose()?,
+            search: text("q"),
+        })
+    }
+
+    fn matches(
+        &self,
+        path: &std::path::Path,
+        mime_type: Option<&str>,
+        folder: Option<u32>,
+    ) -> bool {
+        let mime_matches = self.mime.as_deref().is_none_or(|filter| {
+            mime_type.is_some_and(|mime_type| {
+                let mime_type = mime_type.to_lowercase();
+                if let Some(prefix) = filter.strip_suffix("/*") {
+                    mime_type.starts_with(&format!("{prefix}/"))
+                } else {
+                    mime_type == filter
+                }
+            })
+        });
+        let folder_matches = self.folder.is_none_or(|filter| folder == Some(filter));
+        let search_matches = self
+            .search
+            .as_deref()
+            .is_none_or(|search| path.to_string_lossy().to_lowercase().contains(search));
+        mime_matches && folder_matches && search_matches
+    }
+
+    fn page(&self, items: Vec<Value>) -> Value {
+        let total = items.len();
+        let items: Vec<Value> = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .collect();
+        json!({
+            "items": items,
+            "total": total,
+            "offset": self.offset,
+            "limit": self.limit,
+        })
+    }
+}
+
+fn file_json(
+    path: &std::path::Path,
+    size: u64,
+    mime_type: Option<&str>,
+    modified_at: Option<i64>,
+    hash: Option<&[u8]>,
+    scanned_folder_id: Option<u32>,
+    replica_count: usize,
+) -> Value {
+    json!({
+        "path": path.to_string_lossy(),
+        "name": path.file_name().map(|name| name.to_string_lossy().into_owned()),
+        "size": size,
+        "mime_type": mime_type,
+        "modified_at": modified_at,
+        "hash": hash.map(hex_encode),
+        "content_url": hash.map(|hash| format!("/content/{}", hex_encode(hash))),
+        "scanned_folder_id": scanned_folder_id,
+        "replica_count": replica_count,
+    })
+}
+
+fn indexed_file_json(file: &IndexedFile) -> Value {
+    let mut value = file_json(
+        &file.path,
+        file.size,
+        file.mime_type.as_deref(),
+        file.modified_at,
+        file.hash.as_deref(),
+        file.scanned_folder_id,
+        file.replica_count,
+    );
+    value["extension_mime_type"] = json!(file.extension_mime_type);
+    value["sniffed_mime_type"] = json!(file.sniffed_mime_type);
+    value["mime_type_mismatch"] = json!(file.mime_type_mismatch());
+    value
+}
+
+fn content_search_hit_json(hit: &ContentSearchHit) -> Value {
+    let mut value = file_json(
+        &hit.path,
+        hit.size,
+        hit.mime_type.as_deref(),
+        hit.modified_at,
+        Some(&hit.hash),
+        hit.scanned_folder_id,
+        hit.replica_count,
+    );
+    value["snippet"] = json!(hit.snippet);
+    value["rank"] = json!(hit.rank);
+    value
+}
+
+fn file_search_hit_json(hit: &FileSearchHit) -> Value {
+    let mut value = file_json(
+        &hit.path,
+        hit.size,
+        hit.mime_type.as_deref(),
+        hit.modified_at,
+        hit.hash.as_deref(),
+        hit.scanned_folder_id,
+        hit.replica_count,
+    );
+    value["node_id"] = json!(hex_encode(&hit.node_id));
+    value["node_name"] = json!(hit.node_name);
+    value["is_local"] = json!(hit.is_local);
+    value
+}
+
+fn indexed_media_json(file: &IndexedMediaFile) -> Value {
+    let mut value = file_json(
+        &file.path,
+        file.size,
+        file.mime_type.as_deref(),
+        file.modified_at,
+        file.hash.as_deref(),
+        Some(file.scanned_folder_id),
+        file.replica_count,
+    );
+    value["photo"] = file.photo.as_ref().map_or(Value::Null, photo_metadata_json);
+    value["audio"] = file.audio.as_ref().map_or(Value::Null, audio_metadata_json);
+    value
+}
+
+fn audio_metadata_json(audio: &AudioMetadata) -> Value {
+    json!({
+        "title": audio.title,
+        "artist": audio.artist,
+        "album": audio.album,
+        "album_artist": audio.album_artist,
+        "track_number": audio.track_number,
+        "track_total": audio.track_total,
+        "disc_number": audio.disc_number,
+        "disc_total": audio.disc_total,
+        "year": audio.year,
+        "genre": audio.genre,
+        "duration_ms": audio.duration_ms,
+        "cover_hash": audio.cover_hash.as_deref().map(hex_encode),
+    })
+}
+
+fn photo_metadata_json(photo: &PhotoMetadata) -> Value {
+    json!({
+        "captured_at": photo.captured_at,
+        "camera_make": photo.camera_make,
+        "camera_model": photo.camera_model,
+        "lens_model": photo.lens_model,
+        "width": photo.width,
+        "height": photo.height,
+        "orientation": photo.orientation,
+        "latitude": photo.latitude,
+        "longitude": photo.longitude,
+    })
+}
+
+fn scanned_folder_json(folder: &MediaScanPath, status: Option<&FolderIndexStatus>) -> Value {
+    let indexers = serde_json::from_str::<Vec<String>>(&folder.indexers).unwrap_or_default();
+    json!({
+        "id": folder.id,
+        "path": folder.path,
+        "enabled": folder.enabled,
+        "indexers": indexers,
+        "include_globs": folder.include_glob_list(),
+        "exclude_globs": folder.exclude_glob_list(),
+        "scan": status.map(|status| json!({
+            "queued": status.queued,
+            "scanning": status.scanning,
+            "stopping": status.stopping,
+            "directories_scanned": status.directories_scanned,
+            "files_indexed": status.media_files_indexed,
+            "current_path": status.current_path,
+            "outcome": status.outcome.map(|outcome| outcome.as_str()),
+            "message": status.message,
+        })),
+    })
+}
+
+fn scan_history_json(entry: &ScanHistoryEntry) -> Value {
+    json!({
+        "scanned_folder_id": entry.scanned_folder_id,
+        "trigger": entry.trigger.as_str(),
+        "outcome": entry.outcome.as_str(),
+        "started_at": entry.started_at,
+        "finished_at": entry.finished_at,
+        "directories_scanned": entry.directories_scanned,
+        "files_indexed": entry.files_indexed,
+        "error_message": entry.error_message,
+    })
+}
+
+fn source_json(source: &Source) -> Value {
+    json!({
+        "id": source.id,
+        "source_key": source.source_key,
+        "name": source.name,
+        "source_type": source.source_type,
+        "config_schema_version": source.config_schema_version,
+        "config": serde_json::from_str::<Value>(&source.config).unwrap_or(Value::Null),
+        "enabled": source.enabled,
+    })
+}
+
+fn access_token_json(token: &AccessTokenRecord) -> Value {
+    json!({
+        "id": token.id,
+        "name": token.name,
+        "scope": token.scope,
+        "created_at": token.created_at,
+        "last_used_at": token.last_used_at,
+    })
+}
+
+fn virtual_directory_json(
+    directory: &VirtualDirectory,
+    entries: &[VirtualDirectoryEntry],
+) -> Value {
+    json!({
+        "id": directory.id,
+        "name": directory.name,
+        "entry_count": entries
+            .iter()
+            .filter(|entry| entry.virtual_directory_id == directory.id)
+            .count(),
+    })
+}
+
+fn snapshot_json(snapshot: &SnapshotSummary) -> Value {
+    json!({
+        "id": snapshot.id,
+        "device": snapshot.device,
+        "started_at": snapshot.started_at,
+        "finished_at": snapshot.finished_at,
+        "file_count": snapshot.file_count,
+        "total_bytes": snapshot.total_bytes,
+        "bytes_written": snapshot.bytes_written,
+        "error_count": snapshot.error_count,
+    })
+}
+
+fn snapshot_entry_json(entry: &SnapshotEntry) -> Value {
+    json!({
+        "name": entry.name,
+        "is_directory": entry.is_directory,
+        "size": entry.size,
+        "file_count": entry.file_count,
+    })
+}
+
+fn restore_summary_json(summary: &RestoreSummary) -> Value {
+    json!({
+        "target": summary.target.to_string_lossy(),
+        "restored": summary.restored,
+        "renamed": summary.renamed,
+        "skipped": summary.skipped,
+        "failed": summary.failed,
+        "bytes": summary.bytes,
+        "errors": summary.errors,
+    })
+}
+
+fn prune_report_json(report: &PruneReport) -> Value {
+    json!({
+        "dry_run": report.dry_run,
+        "snapshots": report.snapshots.iter().map(snapshot_json).collect::<Vec<_>>(),
+        "kept": report.kept,
+        "objects": report.objects,
+        "bytes": report.bytes,
+    })
+}
+
+fn virtual_directory_entry_json(entry: &VirtualDirectoryEntry) -> Value {
+    json!({
+        "hash": hex_encode(&entry.hash),
+        "path": entry.path.as_ref().map(|path| path.to_string_lossy()),
+        "size": entry.size,
+        "mime_type": entry.mime_type,
+        "modified_at": entry.modified_at,
+        "scanned_folder_id": entry.scanned_folder_id,
+        "replica_count": entry.replica_count,
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateScannedFolder {
+    path: PathBuf,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default = "default_indexers")]
+    indexers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpdateScannedFolder {
+    enabled: Option<bool>,
+    indexers: Option<Vec<String>>,
+    include_globs: Option<Vec<String>>,
+    exclude_globs: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateSource {
+    name: String,
+    /// `local` unless given; the fields below only apply to `sftp`.
+    source_type: Option<String>,
+    /// The folder of a local source, or the remote root of an SFTP one.
+    path: PathBuf,
+    host: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    auth: Option<SftpAuth>,
+}
+
+/// Credentials for one session's connection to an SFTP source.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConnectSource {
+    password: Option<String>,
+    private_key: Option<String>,
+    passphrase: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpdateSource {
+    name: Option<String>,
+    enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateAccessToken {
+    name: String,
+    scope: String,
+}
+
+/// Restores `path` of Scanned folder `folder` in a snapshot; an empty path
+/// restores the whole folder.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RestoreBody {
+    #[serde(default)]
+    folder: usize,
+    #[serde(default)]
+    path: PathBuf,
+    target: Option<PathBuf>,
+    conflicts: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PassphraseBody {
+    passphrase: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PruneBody {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VirtualDirectoryBody {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VirtualDirectoryEntryBody {
+    hash: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_indexers() -> Vec<String> {
+    MediaScanPath::DEFAULT_INDEXERS
+        .iter()
+        .map(|name| (*name).to_owned())
+        .collect()
+}
+
+fn validate_indexers(indexers: &[String]) -> Result<String, ApiError> {
+    if let Some(unknown) = indexers.iter().find(|name| find_indexer(name).is_none()) {
+        return Err(ApiError::bad_request(format!(
+            "unknown indexer '{unknown}'; available: {}",
+            registered_indexers()
+                .iter()
+                .map(|indexer| indexer.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    Ok(serde_json::to_string(indexers).expect("serialize indexers"))
+}
+
+fn glob_list_json(globs: &[String]) -> String {
+    let globs = globs
+        .iter()
+        .map(|glob| glob.trim())
+        .filter(|glob| !glob.is_empty())
+        .collect::<Vec<_>>();
+    serde_json::to_string(&globs).expect("serialize globs")
+}
+
+/// Answers the routes paired devices call, `node` and `index/changes`, from
+/// the database alone so the peer protocol does not depend on UI state.
+/// Returns `None` for every other request.
+pub(crate) fn peer_api_response(
+    database: &Database,
+    node_id: &[u8],
+    device_name: &str,
+    request: &wgui::HttpRequest,
+) -> Option<(u16, Value)> {
+    if request.method != "GET" {
+        return None;
+    }
+    let result = match request.path.strip_prefix(API_PREFIX)?.trim_end_matches('/') {
+        "node" => ok(peers::node_json(node_id, device_name)),
+        "index/changes" => index_changes(database, node_id, device_name, request),
+        _ => return None,
+    };
+    Some(result.unwrap_or_else(|error| (error.status, json!({ "error": error.message }))))
+}
+
+/// The local index change feed that paired devices pull, starting after
+/// sequence `since`.
+fn index_changes(
+    database: &Database,
+    node_id: &[u8],
+    device_name: &str,
+    request: &wgui::HttpRequest,
+) -> ApiResult {
+    let since = match request.query.get("since") {
+        Some(value) => value
+            .parse::<i64>()
+            .map_err(|_| ApiError::bad_request(format!("invalid since '{value}'")))?,
+        None => 0,
+    };
+    let limit = match request.query.get("limit") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| ApiError::bad_request(format!("invalid limit '{value}'")))?
+            .clamp(1, CHANGES_PAGE_LIMIT),
+        None => CHANGES_PAGE_LIMIT,
+    };
+    let changes = database
+        .local_index_changes(since, limit)
+        .map_err(ApiError::internal)?;
+    ok(peers::changes_json(
+        node_id,
+        device_name,
+        &changes,
+        since,
+        limit,
+    ))
+}
+
+impl App {
+    /// Answers one forwarded API request. Returns whether connected clients
+    /// should be re-rendered because state changed.
+    pub(super) async fn handle_api_request(&mut self, api_request: ApiRequest) -> bool {
+        let ApiRequest { request, reply } = api_request;
+        let mutating = request.method != "GET" && request.method != "HEAD";
+        let response = match self.route_api_request(&request).await {
+            Ok((status, value)) => json_response(status, &value),
+            Err(error) => error.into_response(),
+        };
+        let _ = reply.send(response);
+        mutating
+    }
+
+    async fn route_api_request(&mut self, request: &wgui::HttpRequest) -> ApiResult {
+        let Some(path) = request.path.strip_prefix(API_PREFIX) else {
+            return Err(ApiError::not_found("API endpoint"));
+        };
+        let segments: Vec<&str> = path
+            .trim_end_matches('/')
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        if let Some(response) = peer_api_response(
+            &self.database,
+            &self.local_node_id,
+            &self.config.general.device_name,
+            request,
+        ) {
+            return Ok(response);
+        }
+        let method = request.method.as_str();
+        match (method, segments.as_slice()) {
+            ("GET", ["files"]) => self.api_files(request),
+            ("GET", ["files", "search"]) => self.api_file_search(request),
+            ("GET", ["media"]) => self.api_media(request, false),
+            ("GET", ["audio"]) => self.api_media(request, true),
+            ("GET", ["search"]) => self.api_search(request),
+
+            ("GET", ["scanned-folders"]) => ok(json!(
+                self.media_paths
+                    .iter()
+                    .map(|folder| scanned_folder_json(folder, self.index_status.get(&folder.id)))
+                    .collect::<Vec<_>>()
+            )),
+            ("POST", ["scanned-folders"]) => self.api_create_scanned_folder(request).await,
+            ("GET", ["scanned-folders", id]) => {
+                let folder = self.api_scanned_folder(id)?;
+                ok(scanned_folder_json(
+                    &folder,
+                    self.index_status.get(&folder.id),
+                ))
+            }
+            ("PATCH", ["scanned-folders", id]) => self.api_update_scanned_folder(request, id).await,
+            ("DELETE", ["scanned-folders", id]) => {
+                let id = parse_id(id)?;
+                match self.forget_scanned_folder(id) {
+                    Ok(true) => Ok((204, Value::Null)),
+                    Ok(false) => Err(ApiError::not_found("Scanned folder")),
+                    Err(error) => Err(ApiError::internal(error)),
+                }
+            }
+            ("GET", ["scanned-folders", id, "scans"]) => {
+                let folder = self.api_scanned_folder(id)?;
+                let history = self
+                    .database
+                    .scanned_folder_scan_history(folder.id)
+                    .map_err(ApiError::internal)?;
+                ok(json!(
+                    history.iter().map(scan_history_json).collect::<Vec<_>>()
+                ))
+            }
+            ("POST", ["scanned-folders", id, "scan"]) => {
+                let folder = self.api_scanned_folder(id)?;
+                if !folder.enabled {
+                    return Err(ApiError::new(409, "Scanned folder is paused"));
+                }
+                if !self.queue_media_index_for(folder.id, ScanTrigger::ManualFolder) {
+                    return Err(ApiError::new(409, "a scan is already queued or running"));
+                }
+                Ok((
+                    202,
+                    scanned_folder_json(&folder, self.index_status.get(&folder.id)),
+                ))
+            }
+            ("DELETE", ["scanned-folders", id, "scan"]) => {
+                let folder = self.api_scanned_folder(id)?;
+                if !self.stop_media_index_for(folder.id) {
+                    return Err(ApiError::new(409, "no scan is running"));
+                }
+                Ok((
+                    202,
+                    scanned_folder_json(&folder, self.index_status.get(&folder.id)),
+                ))
+            }
+            ("POST", ["scans"]) => {
+                self.queue_media_index(ScanTrigger::ManualRefresh);
+                Ok((202, json!({ "queued": self.media_paths.len() })))
+            }
+
+            ("GET", ["sources"]) => ok(json!(
+                self.sources.iter().map(source_json).collect::<Vec<_>>()
+            )),
+            ("POST", ["sources"]) => {
+                let body: CreateSource = parse_body(request)?;
+                let name = body.name.trim();
+                if name.is_empty() {
+                    return Err(ApiError::bad_request("source name cannot be empty"));
+                }
+                let created = match body.source_type.as_deref().unwrap_or("local") {
+                    "local" => self.create_local_source(name, &body.path).await,
+                    "sftp" => {
+                        let config = SftpSourceConfig {
+                            host: body.host.unwrap_or_default().trim().to_owned(),
+                            port: body.port.unwrap_or(22),
+                            user: body.user.unwrap_or_default().trim().to_owned(),
+                            root: body.path.to_string_lossy().into_owned(),
+                            auth: body.auth.unwrap_or(SftpAuth::Password),
+                            host_key: None,
+                        };
+                        self.create_sftp_source(name, config).await
+                    }
+                    other => {
+                        return Err(ApiError::bad_request(format!(
+                            "unsupported source type '{other}'"
+                        )));
+                    }
+                };
+                created
+                    .map(|source| (201, source_json(&source)))
+                    .map_err(|error| ApiError::bad_request(format!("{error:#}")))
+            }
+            ("GET", ["sources", id]) => ok(source_json(&self.api_source(id)?)),
+            ("GET", ["sources", id, "connection"]) => {
+                let source = self.api_source(id)?;
+                ok(self.source_connection_json(&source))
+            }
+            ("POST", ["sources", id, "connection"]) => {
+                let source = self.api_source(id)?;
+                let config = sftp_source_config(&source)
+                    .ok_or_else(|| ApiError::bad_request("not an SFTP source"))?;
+                let body: ConnectSource = parse_body(request)?;
+                let secret = match (config.auth, body.password, body.private_key) {
+                    (SftpAuth::Password, secret, None) | (SftpAuth::PrivateKey, None, secret) => {
+                        secret.unwrap_or_default()
+                    }
+                    (SftpAuth::Password, _, Some(_)) => {
+                        return Err(ApiError::bad_request(
+                            "this source signs in with a password",
+                        ));
+                    }
+                    (SftpAuth::PrivateKey, Some(_), _) => {
+                        return Err(ApiError::bad_request(
+                            "this source signs in with a private key",
+                        ));
+                    }
+                };
+                self.connect_source(
+                    source.id,
+                    SecretString::from(secret),
+                    SecretString::from(body.passphrase.unwrap_or_default()),
+                )
+                .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                Ok((202, self.source_connection_json(&source)))
+            }
+            ("DELETE", ["sources", id, "connection"]) => {
+                let source = self.api_source(id)?;
+                self.disconnect_source(&source.source_key);
+                ok(self.source_connection_json(&source))
+            }
+            ("POST", ["sources", id, "index"]) => {
+                let source = self.api_source(id)?;
+                self.index_source(source.id)
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                Ok((202, self.source_connection_json(&source)))
+            }
+            ("PATCH", ["sources", id]) => {
+                let mut source = self.api_source(id)?;
+                let body: UpdateSource = parse_body(request)?;
+                if let Some(name) = body.name {
+                    let name = name.trim();
+                    if name.is_empty() {
+                        return Err(ApiError::bad_request("source name cannot be empty"));
+                    }
+                    source.name = name.to_owned();
+                }
+                if let Some(enabled) = body.enabled {
+                    source.enabled = enabled;
+                }
+                self.update_source(source)
+                    .await
+                    .map(|source| (200, source_json(&source)))
+                    .map_err(|error| ApiError::bad_request(format!("{error:#}")))
+            }
+            ("DELETE", ["sources", id]) => match self.remove_source(parse_id(id)?) {
+                Ok(true) => Ok((204, Value::Null)),
+                Ok(false) => Err(ApiError::not_found("source")),
+                Err(error) => Err(ApiError::internal(error)),
+            },
+
+            ("GET", ["virtual-directories"]) => ok(json!(
+                self.virtual_directories
+                    .iter()
+                    .map(|directory| virtual_directory_json(
+                        directory,
+                        &self.virtual_directory_entries
+                    ))
+                    .collect::<Vec<_>>()
+            )),
+            ("POST", ["virtual-directories"]) => {
+                let body: VirtualDirectoryBody = parse_body(request)?;
+                let directory = self
+                    .database
+                    .create_virtual_directory(&body.name)
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                self.reload_virtual_directories();
+                Ok((201, virtual_directory_json(&directory, &[])))
+            }
+            ("GET", ["virtual-directories", id]) => {
+                let directory = self.api_virtual_directory(id)?;
+                let mut value = virtual_directory_json(&directory, &self.virtual_directory_entries);
+                value["entries"] = json!(
+                    self.virtual_directory_entries
+                        .iter()
+                        .filter(|entry| entry.virtual_directory_id == directory.id)
+                        .map(virtual_directory_entry_json)
+                        .collect::<Vec<_>>()
+                );
+                ok(value)
+            }
+            ("PATCH", ["virtual-directories", id]) => {
+                let directory = self.api_virtual_directory(id)?;
+                let body: VirtualDirectoryBody = parse_body(request)?;
+                self.database
+                    .rename_virtual_directory(directory.id, &body.name)
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                self.reload_virtual_directories();
+                let directory = self.api_virtual_directory(&directory.id.to_string())?;
+                ok(virtual_directory_json(
+                    &directory,
+                    &self.virtual_directory_entries,
+                ))
+            }
+            ("DELETE", ["virtual-directories", id]) => {
+                let id = parse_id(id)?;
+                match self.database.delete_virtual_directory(id) {
+                    Ok(true) => {
+                        if self.selected_virtual_directory_id == Some(id) {
+                            self.selected_virtual_directory_id = None;
+                        }
+                        self.reload_virtual_directories();
+                        Ok((204, Value::Null))
+                    }
+                    Ok(false) => Err(ApiError::not_found("virtual directory")),
+                    Err(error) => Err(ApiError::internal(error)),
+                }
+            }
+            ("POST", ["virtual-directories", id, "entries"]) => {
+                let directory = self.api_virtual_directory(id)?;
+                let body: VirtualDirectoryEntryBody = parse_body(request)?;
+                let hash = parse_hash(&body.hash)?;
+                self.database
+                    .add_file_to_virtual_directory(directory.id, &hash)
+                    .map_err(|_| ApiError::not_found("indexed file"))?;
+                self.reload_virtual_directories();
+                Ok((201, json!({ "hash": hex_encode(&hash) })))
+            }
+            ("DELETE", ["virtual-directories", id, "entries", hash]) => {
+                let directory = self.api_virtual_directory(id)?;
+                let hash = parse_hash(hash)?;
+                match self
+                    .database
+                    .remove_file_from_virtual_directory(directory.id, &hash)
+                {
+                    Ok(true) => {
+                        self.reload_virtual_directories();
+                        Ok((204, Value::Null))
+                    }
+                    Ok(false) => Err(ApiError::not_found("virtual directory entry")),
+                    Err(error) => Err(ApiError::internal(error)),
+                }
+            }
+
+            ("GET", ["tokens"]) => {
+                let tokens = self.auth.access_tokens().map_err(ApiError::internal)?;
+                ok(json!(
+                    tokens.iter().map(access_token_json).collect::<Vec<_>>()
+                ))
+            }
+            ("POST", ["tokens"]) => {
+                let body: CreateAccessToken = parse_body(request)?;
+                let scope = AccessScope::from_str(&body.scope).ok_or_else(|| {
+                    ApiError::bad_request("scope must be read-only, upload-only or admin")
+                })?;
+                let (record, secret) = self
+                    .auth
+                    .create_access_token(&body.name, scope)
+                    .map_err(|error| ApiError::bad_request(format!("{error:#}")))?;
+                self.reload_access_tokens();
+                let mut value = access_token_json(&record);
+                value["token"] = json!(secret);
+                Ok((201, value))
+            }
+            ("DELETE", ["tokens", id]) => match self.auth.revoke_access_token(parse_id(id)?) {
+                Ok(true) => {
+                    self.reload_access_tokens();
+                    Ok((204, Value::Null))
+                }
+                Ok(false) => Err(ApiError::not_found("access token")),
+                Err(error) => Err(ApiError::internal(error)),
+            },
+
+            ("GET", ["backup"]) => ok(self.backup_status_json()),
+            ("POST", ["backup", "unlock"]) => {
+                let body: PassphraseBody = parse_body(request)?;
+                self.unlock_backup(SecretString::from(body.passphrase))
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                Ok((202, self.backup_status_json()))
+            }
+            ("DELETE", ["backup", "unlock"]) => {
+                self.lock_backup();
+                ok(self.backup_status_json())
+            }
+            ("POST", ["backup", "rotate-key"]) => {
+                let body: PassphraseBody = parse_body(request)?;
+                self.rotate_backup_key(SecretString::from(body.passphrase))
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                Ok((202, self.backup_status_json()))
+            }
+            ("GET", ["snapshots"]) => {
+                let snapshots = match self.backup_store() {
+                    Ok(store) => store.snapshots().map_err(ApiError::internal)?,
+                    Err(_) => Vec::new(),
+                };
+                ok(json!(
+                    snapshots.iter().map(snapshot_json).collect::<Vec<_>>()
+                ))
+            }
+            ("GET", ["snapshots", id]) => {
+                let manifest = self.api_snapshot(id)?;
+                let mut value = snapshot_json(&manifest.summary());
+                value["folders"] = json!(
+                    manifest
+                        .folders
+                        .iter()
+                        .map(|folder| folder.to_string_lossy())
+                        .collect::<Vec<_>>()
+                );
+                value["errors"] = json!(manifest.errors);
+                ok(value)
+            }
+            ("GET", ["snapshots", id, "entries"]) => {
+                let manifest = self.api_snapshot(id)?;
+                let folder = match request.query.get("folder") {
+                    Some(value) => value
+                        .parse::<usize>()
+                        .map_err(|_| ApiError::bad_request(format!("invalid folder '{value}'")))?,
+                    None => 0,
+                };
+                if folder >= manifest.folders.len() {
+                    return Err(ApiError::not_found("snapshot folder"));
+                }
+                let path = PathBuf::from(request.query.get("path").map_or("", String::as_str));
+                ok(json!(
+                    manifest
+                        .entries(folder, &path)
+                        .iter()
+                        .map(snapshot_entry_json)
+                        .collect::<Vec<_>>()
+                ))
+            }
+            ("POST", ["snapshots", id, "restore"]) => {
+                let manifest = self.api_snapshot(id)?;
+                let body: RestoreBody = parse_body(request)?;
+                if body.folder >= manifest.folders.len() {
+                    return Err(ApiError::not_found("snapshot folder"));
+                }
+                let conflicts = match body.conflicts.as_deref() {
+                    Some(value) => ConflictPolicy::from_str(value).ok_or_else(|| {
+                        ApiError::bad_request("conflicts must be skip, overwrite or rename")
+                    })?,
+                    None => ConflictPolicy::Skip,
+                };
+                self.start_restore(manifest.id, body.folder, body.path, body.target, conflicts)
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                Ok((202, self.restore_status_json()))
+            }
+            ("GET", ["restore"]) => ok(self.restore_status_json()),
+            ("POST", ["prune"]) => {
+                let body: PruneBody = parse_body(request)?;
+                self.start_prune(body.dry_run)
+                    .map_err(|error| ApiError::new(409, format!("{error:#}")))?;
+                Ok((202, self.prune_status_json()))
+            }
+            ("GET", ["prune"]) => ok(self.prune_status_json()),
+
+            (
+                _,
+                [
+                    "files"
+                    | "media"
+                    | "audio"
+                    | "search"
+                    | "scanned-folders"
+                    | "sources"
+                    | "virtual-directories"
+                    | "scans"
+                    | "tokens"
+                    | "backup"
+                    | "snapshots"
+                    | "restore"
+                    | "prune"
+                    | "node"
+                    | "index",
+                    ..,
+                ],
+            ) => Err(ApiError::new(405, "method not allowed")),
+            _ => Err(ApiError::not_found("API endpoint")),
+        }
+    }
+
+    fn api_files(&self, request: &wgui::HttpRequest) -> ApiResult {
+        let query = ListingQuery::parse(request)?;
+        let items = self
+            .indexed_files
+            .iter()
+            .filter(|file| {
+                query.matches(
+                    &file.path,
+                    file.mime_type.as_deref(),
+                    file.scanned_folder_id,
+                )
+            })
+            .map(indexed_file_json)
+            .collect();
+        ok(query.page(items))
+    }
+
+    /// Full-text search over indexed contents. `q` is matched by the FTS
+    /// index rather than against paths; `mime` and `folder` still filter.
+    fn api_search(&self, request: &wgui::HttpRequest) -> ApiResult {
+        let mut query = ListingQuery::parse(request)?;
+        let Some(text) = query.search.take() else {
+            return Err(ApiError::bad_request("q is required"));
+        };
+        let hits = self
+            .database
+            .search_contents(&self.local_node_id, &text, MAX_PAGE_LIMIT)
+            .map_err(ApiError::internal)?;
+        let items = hits
+            .iter()
+            .filter(|hit| query.matches(&hit.path, hit.mime_type.as_deref(), hit.scanned_folder_id))
+            .map(content_search_hit_json)
+            .collect();
+        ok(query.page(items))
+    }
+
+    /// Filename search across every node using the [`FileQuery`] syntax in
+    /// `q`, e.g. `report ext:pdf modified:2024`.
+    fn api_file_search(&self, request: &wgui::HttpRequest) -> ApiResult {
+        let mut query = ListingQuery::parse(request)?;
+        query.search = None;
+        let text = request.query.get("q").map_or("", String::as_str);
+        let file_query = FileQuery::parse(text, now_millis())
+            .map_err(|error| ApiError::bad_request(format!("{error:#}")))?;
+        if file_query.is_empty() {
+            return Err(ApiError::bad_request("q is required"));
+        }
+        let hits = self
+            .database
+            .search_files(&file_query, MAX_PAGE_LIMIT)
+            .map_err(ApiError::internal)?;
+        let items = hits
+            .iter()
+            .filter(|hit| query.matches(&hit.path, hit.mime_type.as_deref(), hit.scanned_folder_id))
+            .map(file_search_hit_json)
+            .collect();
+        ok(query.page(items))
+    }
+
+    fn api_media(&self, request: &wgui::HttpRequest, audio: bool) -> ApiResult {
+        let query = ListingQuery::parse(request)?;
+        let entries = if audio {
+            &self.audio_index_entries
+        } else {
+            &self.media_index_entries
+        };
+        let items = entries
+            .iter()
+            .filter(|file| {
+                query.matches(
+                    &file.path,
+                    file.mime_type.as_deref(),
+                    Some(file.scanned_folder_id),
+                )
+            })
+            .map(indexed_media_json)
+            .collect();
+        ok(query.page(items))
+    }
+
+    async fn api_create_scanned_folder(&mut self, request: &wgui::HttpRequest) -> ApiResult {
+        let body: CreateScannedFolder = parse_body(request)?;
+        let indexers = validate_indexers(&body.indexers)?;
+        self.insert_scanned_folder(body.path, body.enabled, indexers)
+            .await
+            .map(|folder| {
+                (
+                    201,
+                    scanned_folder_json(&folder, self.index_status.get(&folder.id)),
+                )
+            })
+            .map_err(|message| ApiError::new(409, message))
+    }
+
+    async fn api_update_scanned_folder(
+        &mut self,
+        request: &wgui::HttpRequest,
+        id: &str,
+    ) -> ApiResult {
+        let mut folder = self.api_scanned_folder(id)?;
+        let body: UpdateScannedFolder = parse_body(request)?;
+        if let Some(enabled) = body.enabled {
+            folder.enabled = enabled;
+        }
+        if let Some(indexers) = body.indexers {
+            folder.indexers = validate_indexers(&indexers)?;
+        }
+        let rules_changed = body.include_globs.is_some() || body.exclude_globs.is_some();
+        if let Some(globs) = body.include_globs {
+            folder.include_globs = glob_list_json(&globs);
+        }
+        if let Some(globs) = body.exclude_globs {
+            folder.exclude_globs = glob_list_json(&globs);
+        }
+        let folder = self
+            .update_scanned_folder(folder)
+            .await
+            .map_err(ApiError::internal)?;
+        if rules_changed {
+            self.queue_media_index_for(folder.id, ScanTrigger::ManualFolder);
+        }
+        ok(scanned_folder_json(
+            &folder,
+            self.index_status.get(&folder.id),
+        ))
+    }
+
+    fn api_scanned_folder(&self, id: &str) -> Result<MediaScanPath, ApiError> {
+        let id = parse_id(id)?;
+        self.media_paths
+            .iter()
+            .find(|folder| folder.id == id)
+            .cloned()
+            .ok_or_else(|| ApiError::not_found("Scanned folder"))
+    }
+
+    fn api_source(&self, id: &str) -> Result<Source, ApiError> {
+        let id = parse_id(id)?;
+        self.sources
+            .iter()
+            .find(|source| source.id == id)
+            .cloned()
+            .ok_or_else(|| ApiError::not_found("source"))
+    }
+
+    fn source_connection_json(&self, source: &Source) -> Value {
+        let key = &source.source_key;
+        json!({
+            "connected": self.source_connections.contains_key(key),
+            "connecting": self.connecting_sources.contains(key),
+            "indexing": self.indexing_sources.contains_key(key),
+            "message": self.source_messages.get(key),
+        })
+    }
+
+    fn api_snapshot(&self, id: &str) -> Result<Manifest, ApiError> {
+        self.backup_store()
+            .and_then(|store| store.manifest(id))
+            .map_err(|_| ApiError::not_found("snapshot"))
+    }
+
+    fn restore_status_json(&self) -> Value {
+        let (files_done, files_total) = self.restore_progress.unwrap_or_default();
+        json!({
+            "running": self.restore_running,
+            "files_done": files_done,
+            "files_total": files_total,
+            "message": self.restore_message,
+            "last": self.last_restore.as_ref().map(restore_summary_json),
+        })
+    }
+
+    fn backup_status_json(&self) -> Value {
+        json!({
+            "destination": self
+                .config
+                .backup
+                .destination
+                .as_ref()
+                .map(|destination| destination.to_string_lossy()),
+            "unlocked": self.backup_keys.is_some(),
+            "unlocking": self.backup_unlocking,
+            "rotating_key": self.backup_key_rotating,
+            "running": self.backup_running,
+            "message": self.backup_message,
+        })
+    }
+
+    fn prune_status_json(&self) -> Value {
+        json!({
+            "running": self.prune_running,
+            "message": self.prune_message,
+            "last": self.last_prune.as_ref().map(prune_report_json),
+        })
+    }
+
+    fn api_virtual_directory(&self, id: &str) -> Result<VirtualDirectory, ApiError> {
+        let id = parse_id(id)?;
+        self.virtual_directories
+            .iter()
+            .find(|directory| directory.id == id)
+            .cloned()
+            .ok_or_else(|| ApiError::not_found("virtual directory"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use super::*;
+
+    fn get(path: &str, query: &[(&str, &str)]) -> wgui::HttpRequest {
+        wgui::HttpRequest {
+            method: "GET".to_owned(),
+            path: path.to_owned(),
+            query: query
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect::<HashMap<_, _>>(),
+            headers: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn listing_query_paginates_and_filters() {
+        let query = ListingQuery::parse(&get(
+            "/api/v1/files",
+            &[
+                ("offset", "1"),
+                ("limit", "5000"),
+                ("mime", "Image/*"),
+                ("folder", "3"),
+                ("q", " Holiday "),
+            ],
+        ))
+        .unwrap();
+        assert_eq!(
+            query,
+            ListingQuery {
+                offset: 1,
+                limit: MAX_PAGE_LIMIT,
+                mime: Some("image/*".to_owned()),
+                folder: Some(3),
+                search: Some("holiday".to_owned()),
+            }
+        );
+        assert!(query.matches(
+            Path::new("/photos/Holiday/a.jpg"),
+            Some("image/jpeg"),
+            Some(3)
+        ));
+        assert!(!query.matches(
+            Path::new("/photos/Holiday/a.mp4"),
+            Some("video/mp4"),
+            Some(3)
+        ));
+        assert!(!query.matches(
+            Path::new("/photos/Holiday/a.jpg"),
+            Some("image/jpeg"),
+            Some(4)
+        ));
+        assert!(!query.matches(Path::new("/photos/work/a.jpg"), Some("image/jpeg"), Some(3)));
+
+        let page = query.page((0..4).map(|index| json!(index)).collect());
+        assert_eq!(page["total"], 4);
+        assert_eq!(page["items"], json!([1, 2, 3]));
+
+        assert!(ListingQuery::parse(&get("/api/v1/files", &[("limit", "many")])).is_err());
+    }
+
+    #[test]
+    fn parses_hashes_and_ids() {
+        assert_eq!(parse_id("42").unwrap(), 42);
+        assert_eq!(parse_id("x").unwrap_err().status, 400);
+        assert_eq!(parse_hash(&"ab".repeat(32)).unwrap(), vec![0xab; 32]);
+        assert!(parse_hash("abcd").is_err());
+        assert!(parse_hash(&"zz".repeat(32)).is_err());
+        assert_eq!(
+            validate_indexers(&["media".to_owned()]).unwrap(),
+            r#"["media"]"#
+        );
+        assert_eq!(
+            validate_indexers(&["nope".to_owned()]).unwrap_err().status,
+            400
+        );
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+
+use crate::database::{AccessTokenRecord, AuthStore};
+
+pub const SESSION_COOKIE: &str = "puppydrive_session";
+const SESSION_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+const CLIENT_TICKET_LIFETIME: Duration = Duration::from_secs(60);
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// What a bearer token may do. Browser sessions always act as [`AccessScope::Admin`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessScope {
+    ReadOnly,
+    UploadOnly,
+    Admin,
+}
+
+impl AccessScope {
+    pub const ALL: [Self; 3] = [Self::ReadOnly, Self::UploadOnly, Self::Admin];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ReadOnly => "read-only",
+            Self::UploadOnly => "upload-only",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "read-only" => Some(Self::ReadOnly),
+            "upload-only" => Some(Self::UploadOnly),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn allows(self, required: Self) -> bool {
+        self == Self::Admin || self == required
+    }
+}
+
+/// Returns the scope a daemon HTTP route requires, or `None` for public routes.
+///
+/// The wgui page and its websocket stay public; the websocket is gated per
+/// client once the browser proves it holds a session.
+pub fn required_scope(method: &str, path: &str) -> Option<AccessScope> {
+    if path == "/uploads" || path.starts_with("/uploads/") {
+        return Some(AccessScope::UploadOnly);
+    }
+    if path.starts_with("/api/v1/tokens") {
+        return Some(AccessScope::Admin);
+    }
+    if path.starts_with("/api/") {
+        return Some(if method == "GET" || method == "HEAD" {
+            AccessScope::ReadOnly
+        } else {
+            AccessScope::Admin
+        });
+    }
+    PROTECTED_READ_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+        .then_some(AccessScope::ReadOnly)
+}
+
+const PROTECTED_READ_PREFIXES: [&str; 4] = [
+    "/source-files/",
+    "/media-files/",
+    "/media-thumbnails/",
+    "/content/",
+];
+
+#[derive(Debug)]
+pub enum SetupError {
+    AlreadyConfigured,
+    InvalidSetupCode,
+    WeakPassword,
+    Failed(anyhow::Error),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyConfigured => write!(f, "an admin password is already set"),
+            Self::InvalidSetupCode => write!(f, "the setup code is not valid"),
+            Self::WeakPassword => write!(
+                f,
+                "the password must be at least {MIN_PASSWORD_LENGTH} characters"
+            ),
+            Self::Failed(error) => write!(f, "{error:#}"),
+        }
+    }
+}
+
+pub struct Auth {
+    store: AuthStore,
+    setup_code: Option<String>,
+    /// Outstanding tickets, with when they were issued and the session that
+    /// asked for them.
+    client_tickets: Mutex<HashMap<String, (Instant, ClientSession)>>,
+}
+
+/// The browser session a wgui connection signed in with. The connection stays
+/// signed in only while that session does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientSession(Vec<u8>);
+
+impl Auth {
+    /// `require_setup_code` protects first-run setup when the server is reachable
+    /// from other machines; the code is logged locally at startup.
+    pub fn new(store: AuthStore, require_setup_code: bool) -> Self {
+        Self {
+            store,
+            setup_code: require_setup_code.then(|| random_secret()[..8].to_owned()),
+            client_tickets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn setup_code(&self) -> Option<&str> {
+        self.setup_code.as_deref()
+    }
+
+    pub fn is_configured(&self) -> Result<bool> {
+        Ok(self.store.admin_password_hash()?.is_some())
+    }
+
+    /// Sets the first admin password and returns a new session token.
+    pub fn set_up_admin(
+        &self,
+        password: &str,
+        setup_code: Option<&str>,
+    ) -> std::result::Result<String, SetupError> {
+        if self.is_configured().map_err(SetupError::Failed)? {
+            return Err(SetupError::AlreadyConfigured);
+        }
+        if let Some(expected) = &self.setup_code {
+            let presented = setup_code.unwrap_or_default().trim();
+            if blake3::hash(presented.as_bytes()) != blake3::hash(expected.as_bytes()) {
+                return Err(SetupError::InvalidSetupCode);
+            }
+        }
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(SetupError::WeakPassword);
+        }
+        let password_hash = hash_password(password).map_err(SetupError::Failed)?;
+        if !self
+            .store
+            .create_admin_password_hash(&password_hash)
+            .map_err(SetupError::Failed)?
+        {
+            return Err(SetupError::AlreadyConfigured);
+        }
+        self.create_session().map_err(SetupError::Failed)
+    }
+
+    /// Returns a new session token when `password` matches the admin password.
+    pub fn log_in(&self, password: &str) -> Result<Option<String>> {
+        let Some(password_hash) = self.store.admin_password_hash()? else {
+            return Ok(None);
+        };
+        if !verify_password(password, &password_hash) {
+            return Ok(None);
+        }
+        self.create_session().map(Some)
+    }
+
+    pub fn log_out(&self, headers: &HashMap<String, String>) -> Result<()> {
+        match cookie_value(headers, SESSION_COOKIE) {
+            Some(token) => self.store.delete_session(&secret_digest(token)),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the admin password and signs out every browser session.
+    pub fn change_password(&self, current: &str, new: &str) -> Result<bool> {
+        let Some(password_hash) = self.store.admin_password_hash()? else {
+            return Ok(false);
+        };
+        if !verify_password(current, &password_hash) {
+            return Ok(false);
+        }
+        if new.chars().count() < MIN_PASSWORD_LENGTH {
+            anyhow::bail!("{}", SetupError::WeakPassword);
+        }
+        self.store
+            .update_admin_password_hash(&hash_password(new)?)?;
+        Ok(true)
+    }
+
+    /// Resolves the scope granted by a session cookie or bearer token.
+    pub fn authenticate(&self, headers: &HashMap<String, String>) -> Result<Option<AccessScope>> {
+        if let Some(token) = bearer_token(headers) {
+            let scope = self.store.use_access_token(&secret_digest(token))?;
+            return Ok(scope.as_deref().and_then(AccessScope::from_str));
+        }
+        if self.has_session(headers)? {
+            return Ok(Some(AccessScope::Admin));
+        }
+        Ok(None)
+    }
+
+    pub fn has_session(&self, headers: &HashMap<String, String>) -> Result<bool> {
+        match cookie_value(headers, SESSION_COOKIE) {
+            Some(token) => self.store.session_is_active(&secret_digest(token)),
+            None => Ok(false),
+        }
+    }
+
+    /// Creates a token and returns it together with the only copy of its secret.
+    pub fn create_access_token(
+        &self,
+        name: &str,
+        scope: AccessScope,
+    ) -> Result<(AccessTokenRecord, String)> {
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("token name cannot be empty");
+        }
+        let secret = format!("pd_{}", random_secret());
+        let record =
+            self.store
+                .create_access_token(name, scope.as_str(), &secret_digest(&secret))?;
+        Ok((record, secret))
+    }
+
+    pub fn access_tokens(&self) -> Result<Vec<AccessTokenRecord>> {
+        self.store.access_tokens()
+    }
+
+    pub fn revoke_access_token(&self, id: u32) -> Result<bool> {
+        self.store.revoke_access_token(id)
+    }
+
+    /// Issues a short-lived, single-use ticket that lets a signed-in browser
+    /// attach its wgui connection to the session. Returns `None` without an
+    /// active session cookie.
+    pub fn issue_client_ticket(&self, headers: &HashMap<String, String>) -> Result<Option<String>> {
+        let Some(token) = cookie_value(headers, SESSION_COOKIE) else {
+            return Ok(None);
+        };
+        let session = ClientSession(secret_digest(token));
+        if !self.client_session_is_active(&session)? {
+            return Ok(None);
+        }
+        let ticket = random_secret();
+        let mut tickets = self
+            .client_tickets
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        tickets.retain(|_, (issued, _)| issued.elapsed() < CLIENT_TICKET_LIFETIME);
+        tickets.insert(ticket.clone(), (Instant::now(), session));
+        Ok(Some(ticket))
+    }
+
+    pub fn redeem_client_ticket(&self, ticket: &str) -> Option<ClientSession> {
+        self.client_tickets
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .remove(ticket)
+            .filter(|(issued, _)| issued.elapsed() < CLIENT_TICKET_LIFETIME)
+            .map(|(_, session)| session)
+    }
+
+    /// False once the session has signed out, expired or been ended by a
+    /// password change.
+    pub fn client_session_is_active(&self, session: &ClientSession) -> Result<bool> {
+        self.store.session_is_active(&session.0)
+    }
+
+    fn create_session(&self) -> Result<String> {
+        let token = random_secret();
+        let expires_at = SystemTime::now()
+            .checked_add(SESSION_LIFETIME)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .context("session expiry is out of range")?
+            .as_millis() as i64;
+        self.store
+            .create_session(&secret_digest(&token), expires_at)?;
+        Ok(token)
+    }
+}
+
+pub fn session_cookie(token: &str) -> String {
+    format!(
+        "{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+        SESSION_LIFETIME.as_secs()
+    )
+}
+
+pub fn expired_session_cookie() -> String {
+    format!("{SESSION_COOKIE}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0")
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())
+        .map_err(|error| anyhow::anyhow!("unable to encode password salt: {error}"))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|error| anyhow::anyhow!("unable to hash password: {error}"))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+fn random_secret() -> String {
+    let mut bytes = Vec::with_capacity(32);
+    bytes.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+    bytes.extend_from_slice(uuid::Uuid::new_v4().as_bytes());
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn secret_digest(secret: &str) -> Vec<u8> {
+    blake3::hash(secret.as_bytes()).as_bytes().to_vec()
+}
+
+fn bearer_token(headers: &HashMap<String, String>) -> Option<&str> {
+    let value = headers.get("authorization")?.trim();
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+fn cookie_value<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers.get("cookie")?.split(';').find_map(|cookie| {
+        let (cookie_name, value) = cookie.trim().split_once('=')?;
+        (cookie_name == name).then_some(value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::database::Database;
+
+    fn temporary_database(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("puppydrive-{name}-{}.db", uuid::Uuid::new_v4()))
+    }
+
+    fn cookie_headers(token: &str) -> HashMap<String, String> {
+        HashMap::from([(
+            "cookie".to_owned(),
+            format!("theme=dark; {SESSION_COOKIE}={token}"),
+        )])
+    }
+
+    fn bearer_headers(token: &str) -> HashMap<String, String> {
+        HashMap::from([("authorization".to_owned(), format!("Bearer {token}"))])
+    }
+
+    #[test]
+    fn first_run_setup_creates_a_session_and_rejects_wrong_passwords() {
+        let path = temporary_database("auth-setup");
+        let auth = Auth::new(Database::open(&path).unwrap().auth_store(), true);
+        let code = auth.setup_code().unwrap().to_owned();
+        assert!(!auth.is_configured().unwrap());
+        assert!(matches!(
+            auth.set_up_admin("correct horse", Some("wrong")),
+            Err(SetupError::InvalidSetupCode)
+        ));
+        assert!(matches!(
+            auth.set_up_admin("short", Some(&code)),
+            Err(SetupError::WeakPassword)
+        ));
+        let session = auth.set_up_admin("correct horse", Some(&code)).unwrap();
+        assert!(matches!(
+            auth.set_up_admin("another password", Some(&code)),
+            Err(SetupError::AlreadyConfigured)
+        ));
+
+        assert_eq!(
+            auth.authenticate(&cookie_headers(&session)).unwrap(),
+            Some(AccessScope::Admin)
+        );
+        assert_eq!(auth.authenticate(&cookie_headers("forged")).unwrap(), None);
+        assert_eq!(auth.authenticate(&HashMap::new()).unwrap(), None);
+        assert!(auth.log_in("wrong password").unwrap().is_none());
+        let second = auth.log_in("correct horse").unwrap().unwrap();
+        auth.log_out(&cookie_headers(&second)).unwrap();
+        assert_eq!(auth.authenticate(&cookie_headers(&second)).unwrap(), None);
+
+        assert!(
+            auth.change_password("correct horse", "battery staple")
+                .unwrap()
+        );
+        assert_eq!(auth.authenticate(&cookie_headers(&session)).unwrap(), None);
+        assert!(auth.log_in("battery staple").unwrap().is_some());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn access_tokens_are_scoped_and_revocable() {
+        let path = temporary_database("auth-tokens");
+        let auth = Auth::new(Database::open(&path).unwrap().auth_store(), false);
+        let (record, secret) = auth
+            .create_access_token("Backup script", AccessScope::ReadOnly)
+            .unwrap();
+        assert_eq!(
+            auth.authenticate(&bearer_headers(&secret)).unwrap(),
+            Some(AccessScope::ReadOnly)
+        );
+        assert!(auth.access_tokens().unwrap()[0].last_used_at.is_some());
+        assert!(auth.revoke_access_token(record.id).unwrap());
+        assert_eq!(auth.authenticate(&bearer_headers(&secret)).unwrap(), None);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn routes_require_matching_scopes() {
+        assert_eq!(required_scope("GET", "/favicon.ico"), None);
+        assert_eq!(required_scope("POST", "/auth/login"), None);
+        assert_eq!(required_scope("GET", "/"), None);
+        assert_eq!(
+            required_scope("GET", "/media-files/1/a.jpg"),
+            Some(AccessScope::ReadOnly)
+        );
+        assert_eq!(
+            required_scope("PATCH", "/uploads/sessions/abc"),
+            Some(AccessScope::UploadOnly)
+        );
+        assert_eq!(
+            required_scope("GET", "/api/v1/files"),
+            Some(AccessScope::ReadOnly)
+        );
+        assert_eq!(
+            required_scope("DELETE", "/api/v1/sources/1"),
+            Some(AccessScope::Admin)
+        );
+        assert_eq!(
+            required_scope("GET", "/api/v1/tokens"),
+            Some(AccessScope::Admin)
+        );
+        assert!(AccessScope::Admin.allows(AccessScope::UploadOnly));
+        assert!(!AccessScope::ReadOnly.allows(AccessScope::UploadOnly));
+        assert!(!AccessScope::UploadOnly.allows(AccessScope::ReadOnly));
+    }
+
+    #[test]
+    fn client_tickets_are_single_use() {
+        let path = temporary_database("auth-tickets");
+        let auth = Auth::new(Database::open(&path).unwrap().auth_store(), false);
+        let session = auth.set_up_admin("correct horse", None).unwrap();
+        assert_eq!(auth.issue_client_ticket(&HashMap::new()).unwrap(), None);
+        assert_eq!(
+            auth.issue_client_ticket(&cookie_headers("forged")).unwrap(),
+            None
+        );
+        let ticket = auth
+            .issue_client_ticket(&cookie_headers(&session))
+            .unwrap()
+            .unwrap();
+        assert!(auth.redeem_client_ticket(&ticket).is_some());
+        assert!(auth.redeem_client_ticket(&ticket).is_none());
+        assert!(auth.redeem_client_ticket("unknown").is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn password_change_rejects_signed_in_clients() {
+        let path = temporary_database("auth-client-sessions");
+        let auth = Auth::new(Database::open(&path).unwrap().auth_store(), false);
+        let session = auth.set_up_admin("correct horse", None).unwrap();
+        let ticket = auth
+            .issue_client_ticket(&cookie_headers(&session))
+            .unwrap()
+            .unwrap();
+        let client = auth.redeem_client_ticket(&ticket).unwrap();
+        assert!(auth.client_session_is_active(&client).unwrap());
+        assert!(
+            auth.change_password("correct horse", "battery staple")
+                .unwrap()
+        );
+        assert!(!auth.client_session_is_active(&client).unwrap());
+
+        let session = auth.log_in("battery staple").unwrap().unwrap();
+        let ticket = auth
+            .issue_client_ticket(&cookie_headers(&session))
+            .unwrap()
+            .unwrap();
+        let client = auth.redeem_client_ticket(&ticket).unwrap();
+        auth.log_out(&cookie_headers(&session)).unwrap();
+        assert!(!auth.client_session_is_active(&client).unwrap());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,570 @@
+//! Versioned backups of the Scanned folders to a local directory, such as an
+//! external drive.
+//!
+//! Drives leave the house, so nothing reaches the destination unencrypted:
+//! the store is unlocked with its passphrase once per session, and the keys
+//! are held in memory only (see [`crypto`]).
+//!
+//! The destination is a content-addressed store keyed by the BLAKE3 hashes
+//! the indexer already keeps in `file_entries`: each distinct content is
+//! copied once, so unchanged files and duplicates cost nothing on later runs.
+//! Every run writes a snapshot manifest listing the files it saw and their
+//! hash. Files that were never hashed are left out until a scan hashes them.
+//!
+//! Runs, restores and prunes happen on their own worker thread, so a slow
+//! drive never holds up scans, and report through [`BackupEvent`]. One
+//! thread also means a prune never sees the objects of a run in progress.
+
+mod crypto;
+mod prune;
+mod restore;
+mod store;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender as StdSender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use secrecy::SecretString;
+use tokio::sync::mpsc::Sender;
+
+pub use crypto::StoreKeys;
+pub use prune::{PruneReport, PruneRequest};
+pub use restore::{ConflictPolicy, RestoreRequest, RestoreSummary};
+pub use store::{BackupStore, Manifest, SnapshotEntry, SnapshotSummary};
+
+use crate::app::hex_encode;
+use crate::database::{Database, ScannedFolder, now_millis};
+use crate::managed_folder::ManagedFolder;
+
+/// Errors kept in a manifest; the rest are only counted in the log.
+const MAX_MANIFEST_ERRORS: usize = 100;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub enum BackupEvent {
+    Progress {
+        files_done: usize,
+        files_total: usize,
+        bytes_written: u64,
+    },
+    Finished {
+        snapshot: SnapshotSummary,
+    },
+    Failed {
+        message: String,
+    },
+    RestoreProgress {
+        files_done: usize,
+        files_total: usize,
+    },
+    Restored {
+        summary: RestoreSummary,
+    },
+    RestoreFailed {
+        message: String,
+    },
+    Pruned {
+        report: PruneReport,
+    },
+    PruneFailed {
+        message: String,
+    },
+    Unlocked {
+        keys: Arc<StoreKeys>,
+    },
+    UnlockFailed {
+        message: String,
+    },
+    KeyRotated {
+        keys: Arc<StoreKeys>,
+    },
+    KeyRotationFailed {
+        message: String,
+    },
+}
+
+pub struct BackupRequest {
+    pub destination: PathBuf,
+    pub folders: Vec<ScannedFolder>,
+    pub node_id: Vec<u8>,
+    pub device_name: String,
+    pub keys: Arc<StoreKeys>,
+}
+
+enum WorkerRequest {
+    Backup(BackupRequest),
+    Restore(RestoreRequest),
+    Prune(PruneRequest),
+    Unlock {
+        destination: PathBuf,
+        passphrase: SecretString,
+    },
+    RotateKey {
+        destination: PathBuf,
+        keys: Arc<StoreKeys>,
+        passphrase: SecretString,
+    },
+}
+
+pub struct BackupWorker {
+    requests: StdSender<WorkerRequest>,
+}
+
+impl BackupWorker {
+    pub fn start(database_path: PathBuf, events: Sender<BackupEvent>) -> Self {
+        let (requests, receiver) = mpsc::channel::<WorkerRequest>();
+        thread::Builder::new()
+            .name("puppydrive-backup".to_owned())
+            .spawn(move || {
+                while let Ok(request) = receiver.recv() {
+                    match request {
+                        WorkerRequest::Backup(request) => {
+                            let event = match back_up(&database_path, &events, request) {
+                                Ok(snapshot) => BackupEvent::Finished { snapshot },
+                                Err(error) => {
+                                    log::error!("backup failed: {error:#}");
+                                    BackupEvent::Failed {
+                                        message: format!("{error:#}"),
+                                    }
+                                }
+                            };
+                            let _ = events.blocking_send(event);
+                        }
+                        WorkerRequest::Restore(request) => {
+                            let event = match restore::restore(&events, request) {
+                                Ok(summary) => BackupEvent::Restored { summary },
+                                Err(error) => {
+                                    log::error!("restore failed: {error:#}");
+                                    BackupEvent::RestoreFailed {
+                                        message: format!("{error:#}"),
+                                    }
+                                }
+                            };
+                            let _ = events.blocking_send(event);
+                        }
+                        WorkerRequest::Unlock {
+                            destination,
+                            passphrase,
+                        } => {
+                            let event = match BackupStore::unlock(&destination, &passphrase) {
+                                Ok(store) => BackupEvent::Unlocked { keys: store.keys() },
+                                Err(error) => {
+                                    log::warn!("unable to unlock the backup: {error:#}");
+                                    BackupEvent::UnlockFailed {
+                                        message: format!("{error:#}"),
+                                    }
+                                }
+                            };
+                            let _ = events.blocking_send(event);
+                        }
+                        WorkerRequest::RotateKey {
+                            destination,
+                            keys,
+                            passphrase,
+                        } => {
+                            let rotated = BackupStore::open(&destination, keys)
+                                .and_then(|store| store.rotate_key(&passphrase));
+                            let event = match rotated {
+                                Ok(store) => BackupEvent::KeyRotated { keys: store.keys() },
+                                Err(error) => {
+                                    log::error!("backup key rotation failed: {error:#}");
+                                    BackupEvent::KeyRotationFailed {
+                                        message: format!("{error:#}"),
+                                    }
+                                }
+                            };
+                            let _ = events.blocking_send(event);
+                        }
+                        WorkerRequest::Prune(request) => {
+                            let event = match prune::prune(&request) {
+                                Ok(report) => BackupEvent::Pruned { report },
+                                Err(error) => {
+                                    log::error!("prune failed: {error:#}");
+                                    BackupEvent::PruneFailed {
+                                        message: format!("{error:#}"),
+                                    }
+                                }
+                            };
+                            let _ = events.blocking_send(event);
+                        }
+                    }
+                }
+            })
+            .expect("failed to start PuppyDrive backup thread");
+        Self { requests }
+    }
+
+    pub fn request_backup(&self, request: BackupRequest) {
+        if self.requests.send(WorkerRequest::Backup(request)).is_err() {
+            log::error!("PuppyDrive backup thread has stopped");
+        }
+    }
+
+    pub fn request_restore(&self, request: RestoreRequest) {
+        if self.requests.send(WorkerRequest::Restore(request)).is_err() {
+            log::error!("PuppyDrive backup thread has stopped");
+        }
+    }
+
+    pub fn request_prune(&self, request: PruneRequest) {
+        if self.requests.send(WorkerRequest::Prune(request)).is_err() {
+            log::error!("PuppyDrive backup thread has stopped");
+        }
+    }
+
+    /// Derives the keys of the store in `destination` from `passphrase`,
+    /// which also sets up a new store.
+    pub fn request_unlock(&self, destination: PathBuf, passphrase: SecretString) {
+        let request = WorkerRequest::Unlock {
+            destination,
+            passphrase,
+        };
+        if self.requests.send(request).is_err() {
+            log::error!("PuppyDrive backup thread has stopped");
+        }
+    }
+
+    /// Re-encrypts the store under a fresh key wrapped by `passphrase`.
+    pub fn request_key_rotation(
+        &self,
+        destination: PathBuf,
+        keys: Arc<StoreKeys>,
+        passphrase: SecretString,
+    ) {
+        let request = WorkerRequest::RotateKey {
+            destination,
+            keys,
+            passphrase,
+        };
+        if self.requests.send(request).is_err() {
+            log::error!("PuppyDrive backup thread has stopped");
+        }
+    }
+}
+
+/// Copies the content of every hashed file in the enabled `folders` that the
+/// store lacks, then writes the snapshot manifest.
+fn back_up(
+    database_path: &Path,
+    events: &Sender<BackupEvent>,
+    request: BackupRequest,
+) -> Result<SnapshotSummary> {
+    let started_at = now_millis();
+    let store = BackupStore::open(&request.destination, request.keys)?;
+    let database = Database::open(database_path)?;
+    let mut folders = HashMap::new();
+    let mut manifest = Manifest {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        device: request.device_name,
+        started_at,
+        finished_at: started_at,
+        folders: Vec::new(),
+        files: Vec::new(),
+        bytes_written: 0,
+        errors: Vec::new(),
+    };
+    for folder in request.folders.iter().filter(|folder| folder.enabled) {
+        match ManagedFolder::open(folder.id, &folder.path) {
+            Ok(managed) => {
+                folders.insert(folder.id, (manifest.folders.len(), managed));
+                manifest.folders.push(PathBuf::from(&folder.path));
+            }
+            Err(error) => {
+                log::warn!("backup skipping scanned folder {}: {error:#}", folder.id);
+                manifest.errors.push(format!("{}: {error:#}", folder.path));
+            }
+        }
+    }
+    let files = database
+        .cached_files(&request.node_id)?
+        .into_iter()
+        .filter_map(|file| {
+            let (index, folder) = folders.get(&file.scanned_folder_id?)?;
+            let relative = file.path.strip_prefix(folder.root()).ok()?.to_path_buf();
+            Some((*index, folder, relative, file))
+        })
+        .collect::<Vec<_>>();
+    let files_total = files.len();
+    let mut unhashed = 0;
+    let mut error_count = 0;
+    let mut last_progress = Instant::now();
+    for (files_done, (index, folder, relative, file)) in files.into_iter().enumerate() {
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            let _ = events.try_send(BackupEvent::Progress {
+                files_done,
+                files_total,
+                bytes_written: manifest.bytes_written,
+            });
+        }
+        let Some(hash) = file.hash else {
+            unhashed += 1;
+            continue;
+        };
+        if !store.has_object(&hash) {
+            let written = folder
+                .open_file(&file.path)
+                .and_then(|source| store.write_object(&hash, file.size, source));
+            match written {
+                Ok(written) => manifest.bytes_written += written,
+                Err(error) => {
+                    error_count += 1;
+                    log::warn!("not backing up {}: {error:#}", file.path.display());
+                    if manifest.errors.len() < MAX_MANIFEST_ERRORS {
+                        manifest
+                            .errors
+                            .push(format!("{}: {error:#}", file.path.display()));
+                    }
+                    continue;
+                }
+            }
+        }
+        manifest.files.push(store::ManifestFile {
+            folder: index,
+            path: relative,
+            hash: hex_encode(&hash),
+            size: file.size,
+            modified_at: file.modified_at,
+        });
+    }
+    manifest.finished_at = now_millis();
+    store.write_manifest(&manifest)?;
+    log::info!(
+        "backed up {} files to {}, writing {} bytes; {unhashed} not yet hashed, {error_count} failed",
+        manifest.files.len(),
+        request.destination.display(),
+        manifest.bytes_written
+    );
+    Ok(manifest.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::database::MediaIndexObservation;
+    use crate::indexer::find_indexer;
+
+    #[tokio::test]
+    async fn stores_each_content_once_and_keeps_a_manifest_per_run() {
+        let root = std::env::temp_dir().join(format!("puppydrive-backup-{}", uuid::Uuid::new_v4()));
+        let source = root.join("source");
+        let destination = root.join("drive");
+        fs::create_dir_all(source.join("trips")).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        let source = fs::canonicalize(source).unwrap();
+        let files: [(&str, &[u8]); 3] = [
+            ("a.jpg", b"alpha"),
+            ("trips/b.jpg", b"bravo"),
+            ("trips/copy.jpg", b"alpha"),
+        ];
+        for (path, content) in files {
+            fs::write(source.join(path), content).unwrap();
+        }
+        let database_path = root.join("index.db");
+        let database = Database::open(&database_path).unwrap();
+        let node_id = database.local_node_id("Laptop").unwrap();
+        let folder = database
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: source.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let observations = files
+            .iter()
+            .map(|(path, content)| MediaIndexObservation {
+                path: source.join(path),
+                hash: Some(blake3::hash(content).as_bytes().to_vec()),
+                size: content.len() as u64,
+                mime_type: Some("image/jpeg".to_owned()),
+                extension_mime_type: Some("image/jpeg".to_owned()),
+                sniffed_mime_type: None,
+                created_at: None,
+                modified_at: Some(1),
+                accessed_at: None,
+            })
+            .collect::<Vec<_>>();
+        database
+            .sync_scan(
+                &node_id,
+                &ManagedFolder::open(folder.id, &source).unwrap(),
+                &observations,
+                &[find_indexer(ScannedFolder::MEDIA_INDEXER).unwrap()],
+                true,
+            )
+            .unwrap();
+
+        let (events, _receiver) = tokio::sync::mpsc::channel(8);
+        let passphrase = SecretString::from("correct horse");
+        let store = BackupStore::unlock_with(&destination, &passphrase, crypto::TEST_KDF).unwrap();
+        let request = || BackupRequest {
+            destination: destination.clone(),
+            folders: vec![folder.clone()],
+            node_id: node_id.clone(),
+            device_name: "Laptop".to_owned(),
+            keys: store.keys(),
+        };
+        let first = back_up(&database_path, &events, request()).unwrap();
+        assert_eq!((first.file_count, first.total_bytes), (3, 15));
+        let objects = store.objects().unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(
+            first.bytes_written,
+            objects.iter().map(|(_, size)| size).sum::<u64>()
+        );
+        let manifest = store.manifest(&first.id).unwrap();
+        assert_eq!(manifest.folders, std::slice::from_ref(&source));
+        let entries = manifest.entries(0, Path::new(""));
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.is_directory, entry.file_count))
+                .collect::<Vec<_>>(),
+            [("trips", true, 2), ("a.jpg", false, 1)]
+        );
+        assert_eq!(manifest.entries(0, Path::new("trips")).len(), 2);
+
+        // A file edited after indexing is left out rather than stored under
+        // the wrong hash, and content already stored is not written again.
+        fs::write(source.join("trips/b.jpg"), b"edited").unwrap();
+        fs::remove_dir_all(
+            destination
+                .join(store::STORE_DIRECTORY_NAME)
+                .join("objects"),
+        )
+        .unwrap();
+        let second = back_up(&database_path, &events, request()).unwrap();
+        assert_eq!((second.file_count, second.error_count), (2, 1));
+        assert_eq!(second.bytes_written, first.bytes_written / 2);
+        let third = back_up(&database_path, &events, request()).unwrap();
+        assert_eq!(third.bytes_written, 0);
+        let snapshots = store.snapshots().unwrap();
+        assert_eq!(snapshots.len(), 3);
+        assert!(snapshots[0].started_at >= snapshots[2].started_at);
+        assert!(store.manifest("../escape").is_err());
+
+        // Nothing on the drive gives away names or content hashes.
+        let mut stored = vec![store::STORE_DIRECTORY_NAME.to_owned()];
+        while let Some(relative) = stored.pop() {
+            let path = destination.join(&relative);
+            if path.is_dir() {
+                for entry in fs::read_dir(&path).unwrap() {
+                    let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+                    stored.push(format!("{relative}/{name}"));
+                }
+                continue;
+            }
+            let bytes = fs::read(&path).unwrap();
+            for secret in [
+                b"trips".as_slice(),
+                b"a.jpg",
+                blake3::hash(b"alpha").to_hex().as_bytes(),
+            ] {
+                assert!(!bytes.windows(secret.len()).any(|window| window == secret));
+                assert!(
+                    !relative
+                        .as_bytes()
+                        .windows(secret.len())
+                        .any(|window| window == secret)
+                );
+            }
+        }
+
+        // Rotation re-encrypts what is there; the first run's copy of
+        // b.jpg went with the objects directory and stays missing.
+        let new_passphrase = SecretString::from("battery staple");
+        let rotated = store.rotate_key(&new_passphrase).unwrap();
+        assert_eq!(rotated.snapshots().unwrap().len(), 3);
+        assert_eq!(rotated.objects().unwrap().len(), 1);
+        let alpha = blake3::hash(b"alpha");
+        let mut content = Vec::new();
+        store::copy_hashed(rotated.open_object(alpha.as_bytes()).unwrap(), &mut content).unwrap();
+        assert_eq!(content, b"alpha");
+        assert!(store.snapshots().unwrap().is_empty());
+        assert!(BackupStore::unlock_with(&destination, &passphrase, crypto::TEST_KDF).is_err());
+        assert!(BackupStore::unlock_with(&destination, &new_passphrase, crypto::TEST_KDF).is_ok());
+
+        fs::remove_dir_all(&destination).unwrap();
+        assert!(back_up(&database_path, &events, request()).is_err());
+        drop(database);
+        let _ = fs::remove_dir_all(root);
+    }
+    #[test]
+    fn rotation_keeps_the_old_key_while_an_object_fails_to_re_encrypt() {
+        let destination =
+            std::env::temp_dir().join(format!("puppydrive-rotate-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&destination).unwrap();
+        let passphrase = SecretString::from("correct horse");
+        let store = BackupStore::unlock_with(&destination, &passphrase, crypto::TEST_KDF).unwrap();
+        let contents: [&[u8]; 2] = [b"alpha", b"bravo"];
+        let mut files = Vec::new();
+        for (index, content) in contents.into_iter().enumerate() {
+            let hash = blake3::hash(content);
+            store
+                .write_object(hash.as_bytes(), content.len() as u64, content)
+                .unwrap();
+            files.push(store::ManifestFile {
+                folder: 0,
+                path: PathBuf::from(format!("{index}.jpg")),
+                hash: hash.to_hex().to_string(),
+                size: content.len() as u64,
+                modified_at: None,
+            });
+        }
+        store
+            .write_manifest(&Manifest {
+                id: "first".to_owned(),
+                device: "Laptop".to_owned(),
+                started_at: 1,
+                finished_at: 2,
+                folders: vec![PathBuf::from("/photos")],
+                files,
+                bytes_written: 0,
+                errors: Vec::new(),
+            })
+            .unwrap();
+        let restores = |store: &BackupStore, content: &[u8]| {
+            let mut restored = Vec::new();
+            store
+                .open_object(blake3::hash(content).as_bytes())
+                .and_then(|source| store::copy_hashed(source, &mut restored))
+                .is_ok_and(|_| restored == content)
+        };
+
+        // The drive fills up before bravo is written under the new key.
+        let new_passphrase = SecretString::from("battery staple");
+        let bravo = blake3::hash(b"bravo");
+        let failed = store.rotate_key_with(&new_passphrase, |rotated, hash, size| {
+            if hash == bravo.as_bytes() {
+                anyhow::bail!("no space left on device");
+            }
+            let source = rotated.open_object(hash)?;
+            rotated.write_object(hash, size, source).map(drop)
+        });
+        assert!(failed.is_err());
+        assert!(restores(&store, b"bravo"));
+        let interrupted =
+            BackupStore::unlock_with(&destination, &new_passphrase, crypto::TEST_KDF).unwrap();
+        assert_eq!(interrupted.snapshots().unwrap().len(), 1);
+        for content in contents {
+            assert!(restores(&interrupted, content));
+        }
+
+        let rotated = interrupted.rotate_key(&new_passphrase).unwrap();
+        assert_eq!(rotated.objects().unwrap().len(), 2);
+        for content in contents {
+            assert!(restores(&rotated, content));
+        }
+        assert!(!restores(&store, b"bravo"));
+        let _ = fs::remove_dir_all(destination);
+    }
+}
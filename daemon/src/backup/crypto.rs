@@ -0,0 +1,540 @@
+//! Client-side encryption of everything written to a backup destination.
+//!
+//! Objects and manifests are encrypted with a random data key. The data key
+//! is kept in `keys.json`, wrapped by a key that Argon2id derives from the
+//! passphrase, so the passphrase alone unlocks a store found on a drive.
+//!
+//! Contents are sealed with XChaCha20-Poly1305 in 64 KiB chunks following
+//! the STREAM construction: each chunk's nonce holds its index and whether
+//! it is the last, so chunks cannot be reordered, dropped or cut off without
+//! failing authentication. Every stream is also bound to what it holds, an
+//! object to its content hash and a manifest to its snapshot id, so files
+//! cannot be swapped on the drive either. Plaintext is length-prefixed and
+//! padded with Padmé, which reveals sizes only to within about 12%, and
+//! objects are named by a keyed hash so their names do not give away the
+//! content hashes.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadInPlace, OsRng, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::app::{hex_decode, hex_encode};
+
+pub const KEYS_FILE_NAME: &str = "keys.json";
+const MAGIC: &[u8; 4] = b"PDB1";
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+/// The rest of a chunk nonce is its big-endian index and the last flag.
+const NONCE_PREFIX_SIZE: usize = NONCE_SIZE - 5;
+const LENGTH_PREFIX_SIZE: u64 = 8;
+/// Small plaintexts are all padded to this, so they look alike.
+const MIN_PADDED_LENGTH: u64 = 512;
+const CONTENT_KEY_CONTEXT: &str = "PuppyDrive backup 2024 content key";
+const NAME_KEY_CONTEXT: &str = "PuppyDrive backup 2024 object name key";
+
+/// Argon2id cost of deriving the wrapping key from the passphrase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeysFile {
+    kdf: KdfParams,
+    /// Hex Argon2id salt.
+    salt: String,
+    /// The current key first.
+    keys: Vec<WrappedKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WrappedKey {
+    id: u32,
+    /// Hex nonce followed by the sealed data key.
+    sealed: String,
+}
+
+struct DataKey {
+    id: u32,
+    key: SecretBox<[u8; 32]>,
+}
+
+impl DataKey {
+    fn generate(id: u32) -> Self {
+        Self {
+            id,
+            key: SecretBox::init_with_mut(|key: &mut [u8; 32]| OsRng.fill_bytes(key)),
+        }
+    }
+
+    fn duplicate(&self) -> Self {
+        Self {
+            id: self.id,
+            key: SecretBox::new(Box::new(*self.key.expose_secret())),
+        }
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        let content_key = SecretBox::init_with_mut(|key: &mut [u8; 32]| {
+            *key = blake3::derive_key(CONTENT_KEY_CONTEXT, self.key.expose_secret());
+        });
+        XChaCha20Poly1305::new(content_key.expose_secret().into())
+    }
+
+    fn object_name(&self, hash: &[u8]) -> String {
+        let name_key = SecretBox::init_with_mut(|key: &mut [u8; 32]| {
+            *key = blake3::derive_key(NAME_KEY_CONTEXT, self.key.expose_secret());
+        });
+        blake3::keyed_hash(name_key.expose_secret(), hash)
+            .to_hex()
+            .to_string()
+    }
+}
+
+/// The unlocked data keys of a store, held for the session. Rotation briefly
+/// leaves more than one: the first encrypts, any of them decrypts.
+pub struct StoreKeys {
+    kdf: KdfParams,
+    keys: Vec<DataKey>,
+}
+
+impl StoreKeys {
+    pub(super) fn generate(kdf: KdfParams) -> Self {
+        Self {
+            kdf,
+            keys: vec![DataKey::generate(1)],
+        }
+    }
+
+    /// Unwraps the keys kept in `directory` with `passphrase`.
+    pub(super) fn unlock(directory: &Path, passphrase: &SecretString) -> Result<Self> {
+        let path = directory.join(KEYS_FILE_NAME);
+        let file =
+            File::open(&path).with_context(|| format!("unable to open {}", path.display()))?;
+        let keys_file: KeysFile = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("{} is not a backup key file", path.display()))?;
+        let salt = hex_decode(&keys_file.salt).context("backup key file has an invalid salt")?;
+        let wrapping = wrapping_cipher(passphrase, &salt, keys_file.kdf)?;
+        let mut keys = Vec::new();
+        for wrapped in keys_file.keys {
+            let sealed = hex_decode(&wrapped.sealed)
+                .filter(|sealed| sealed.len() > NONCE_SIZE)
+                .context("backup key file holds an invalid key")?;
+            let (nonce, sealed) = sealed.split_at(NONCE_SIZE);
+            let mut key = wrapping
+                .decrypt(
+                    XNonce::from_slice(nonce),
+                    Payload {
+                        msg: sealed,
+                        aad: &key_context(wrapped.id),
+                    },
+                )
+                .map_err(|_| anyhow!("wrong passphrase"))?;
+            let Ok(bytes) = <[u8; 32]>::try_from(key.as_slice()) else {
+                bail!("backup key file holds an invalid key");
+            };
+            key.fill(0);
+            keys.push(DataKey {
+                id: wrapped.id,
+                key: SecretBox::new(Box::new(bytes)),
+            });
+        }
+        if keys.is_empty() {
+            bail!("backup key file holds no keys");
+        }
+        Ok(Self {
+            kdf: keys_file.kdf,
+            keys,
+        })
+    }
+
+    /// Wraps the keys with `passphrase`, under a fresh salt, into
+    /// `directory`.
+    pub(super) fn write(&self, directory: &Path, passphrase: &SecretString) -> Result<()> {
+        let mut salt = [0_u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let wrapping = wrapping_cipher(passphrase, &salt, self.kdf)?;
+        let mut keys = Vec::new();
+        for key in &self.keys {
+            let mut nonce = [0_u8; NONCE_SIZE];
+            OsRng.fill_bytes(&mut nonce);
+            let sealed = wrapping
+                .encrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: key.key.expose_secret(),
+                        aad: &key_context(key.id),
+                    },
+                )
+                .map_err(|_| anyhow!("unable to wrap the backup key"))?;
+            keys.push(WrappedKey {
+                id: key.id,
+                sealed: hex_encode(&[nonce.as_slice(), sealed.as_slice()].concat()),
+            });
+        }
+        let keys_file = KeysFile {
+            kdf: self.kdf,
+            salt: hex_encode(&salt),
+            keys,
+        };
+        let path = directory.join(KEYS_FILE_NAME);
+        let temporary = path.with_extension("json.part");
+        let mut file = File::create(&temporary)
+            .with_context(|| format!("unable to create {}", temporary.display()))?;
+        serde_json::to_writer_pretty(&mut file, &keys_file)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// These keys with a fresh one in front, to re-encrypt the store under.
+    pub(super) fn rotated(&self) -> Self {
+        let id = self.keys.iter().map(|key| key.id).max().unwrap_or(0) + 1;
+        let mut keys = vec![DataKey::generate(id)];
+        keys.extend(self.keys.iter().map(DataKey::duplicate));
+        Self {
+            kdf: self.kdf,
+            keys,
+        }
+    }
+
+    /// Only the current key, once nothing is encrypted under the others.
+    pub(super) fn current_only(&self) -> Self {
+        Self {
+            kdf: self.kdf,
+            keys: vec![self.keys[0].duplicate()],
+        }
+    }
+
+    /// Names the object for content `hash` may have, the current key's
+    /// first.
+    pub(super) fn object_names(&self, hash: &[u8]) -> Vec<String> {
+        self.keys.iter().map(|key| key.object_name(hash)).collect()
+    }
+
+    /// Encrypts exactly `size` bytes of `source` into `target`, bound to
+    /// `context`, and returns the BLAKE3 hash of what was encrypted.
+    pub(super) fn encrypt(
+        &self,
+        context: &[u8],
+        size: u64,
+        mut source: impl Read,
+        target: &mut impl Write,
+    ) -> Result<blake3::Hash> {
+        let key = &self.keys[0];
+        let cipher = key.cipher();
+        let mut prefix = [0_u8; NONCE_PREFIX_SIZE];
+        OsRng.fill_bytes(&mut prefix);
+        target.write_all(MAGIC)?;
+        target.write_all(&key.id.to_be_bytes())?;
+        target.write_all(&prefix)?;
+
+        let total = padded_length(size + LENGTH_PREFIX_SIZE);
+        let mut hasher = blake3::Hasher::new();
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE + TAG_SIZE);
+        chunk.extend_from_slice(&size.to_be_bytes());
+        let mut sealed = 0_u64;
+        let mut read_so_far = 0_u64;
+        for index in 0_u32.. {
+            while chunk.len() < CHUNK_SIZE && sealed + (chunk.len() as u64) < total {
+                let room =
+                    (CHUNK_SIZE - chunk.len()).min((total - sealed - chunk.len() as u64) as usize);
+                let start = chunk.len();
+                if read_so_far < size {
+                    chunk.resize(start + room.min((size - read_so_far) as usize), 0);
+                    let read = source.read(&mut chunk[start..])?;
+                    if read == 0 {
+                        bail!("content is shorter than {size} bytes");
+                    }
+                    chunk.truncate(start + read);
+                    hasher.update(&chunk[start..]);
+                    read_so_far += read as u64;
+                } else {
+                    chunk.resize(start + room, 0);
+                }
+            }
+            sealed += chunk.len() as u64;
+            let last = sealed == total;
+            cipher
+                .encrypt_in_place(&chunk_nonce(&prefix, index, last), context, &mut chunk)
+                .map_err(|_| anyhow!("unable to encrypt backup data"))?;
+            target.write_all(&chunk)?;
+            if last {
+                break;
+            }
+            chunk.clear();
+        }
+        if source.read(&mut [0_u8; 1])? != 0 {
+            bail!("content is longer than {size} bytes");
+        }
+        Ok(hasher.finalize())
+    }
+
+    /// Reads back what [`StoreKeys::encrypt`] wrote to `source` with the
+    /// same `context`. Reads fail rather than return anything that does not
+    /// authenticate.
+    pub(super) fn decryptor<R: Read>(&self, context: &[u8], source: R) -> Result<Decryptor<R>> {
+        let mut source = BufReader::new(source);
+        let mut header = [0_u8; MAGIC.len() + 4 + NONCE_PREFIX_SIZE];
+        source
+            .read_exact(&mut header)
+            .context("backup data is truncated")?;
+        if &header[..MAGIC.len()] != MAGIC {
+            bail!("backup data is not encrypted by PuppyDrive");
+        }
+        let id = u32::from_be_bytes(header[4..8].try_into()?);
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.id == id)
+            .context("backup data is encrypted with a key this store no longer has")?;
+        Ok(Decryptor {
+            source,
+            cipher: key.cipher(),
+            context: context.to_vec(),
+            prefix: header[8..].try_into()?,
+            index: 0,
+            chunk: Vec::with_capacity(CHUNK_SIZE + TAG_SIZE),
+            position: 0,
+            remaining: None,
+            finished: false,
+        })
+    }
+}
+
+pub struct Decryptor<R> {
+    source: BufReader<R>,
+    cipher: XChaCha20Poly1305,
+    context: Vec<u8>,
+    prefix: [u8; NONCE_PREFIX_SIZE],
+    index: u32,
+    /// The decrypted chunk being handed out.
+    chunk: Vec<u8>,
+    position: usize,
+    /// Content bytes still to hand out, known once the first chunk is read.
+    remaining: Option<u64>,
+    finished: bool,
+}
+
+impl<R: Read> Decryptor<R> {
+    fn next_chunk(&mut self) -> io::Result<()> {
+        self.chunk.resize(CHUNK_SIZE + TAG_SIZE, 0);
+        let mut read = 0;
+        while read < self.chunk.len() {
+            match self.source.read(&mut self.chunk[read..])? {
+                0 => break,
+                count => read += count,
+            }
+        }
+        self.chunk.truncate(read);
+        let last = self.source.fill_buf()?.is_empty();
+        let nonce = chunk_nonce(&self.prefix, self.index, last);
+        self.cipher
+            .decrypt_in_place(&nonce, &self.context, &mut self.chunk)
+            .map_err(|_| invalid_data("backup data failed authentication"))?;
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| invalid_data("backup data is too long"))?;
+        self.finished = last;
+        self.position = 0;
+        if self.remaining.is_none() {
+            let Some(length) = self.chunk.get(..LENGTH_PREFIX_SIZE as usize) else {
+                return Err(invalid_data("backup data is truncated"));
+            };
+            self.remaining = Some(u64::from_be_bytes(length.try_into().unwrap_or_default()));
+            self.position = LENGTH_PREFIX_SIZE as usize;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let remaining = self.remaining.unwrap_or_default();
+            let available = ((self.chunk.len() - self.position) as u64).min(remaining) as usize;
+            if available > 0 {
+                let count = available.min(buffer.len());
+                buffer[..count].copy_from_slice(&self.chunk[self.position..][..count]);
+                self.position += count;
+                self.remaining = Some(remaining - count as u64);
+                return Ok(count);
+            }
+            if self.finished {
+                if remaining > 0 {
+                    return Err(invalid_data("backup data is truncated"));
+                }
+                return Ok(0);
+            }
+            // Padding chunks are read too, so the end is authenticated.
+            self.next_chunk()?;
+        }
+    }
+}
+
+/// Padmé: rounds `length` up so that only about log log of it shows.
+fn padded_length(length: u64) -> u64 {
+    let length = length.max(MIN_PADDED_LENGTH);
+    let exponent = 63 - length.leading_zeros();
+    let exponent_bits = 32 - exponent.leading_zeros();
+    let mask = (1_u64 << (exponent - exponent_bits)) - 1;
+    (length + mask) & !mask
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_SIZE], index: u32, last: bool) -> XNonce {
+    let mut nonce = XNonce::default();
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_SIZE..NONCE_SIZE - 1].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = u8::from(last);
+    nonce
+}
+
+fn wrapping_cipher(
+    passphrase: &SecretString,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<XChaCha20Poly1305> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|error| anyhow!("invalid backup key parameters: {error}"))?;
+    let mut key = SecretBox::<[u8; 32]>::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(
+            passphrase.expose_secret().as_bytes(),
+            salt,
+            key.expose_secret_mut(),
+        )
+        .map_err(|error| anyhow!("unable to derive the backup key: {error}"))?;
+    Ok(XChaCha20Poly1305::new(key.expose_secret().into()))
+}
+
+fn key_context(id: u32) -> Vec<u8> {
+    format!("PuppyDrive backup key {id}").into_bytes()
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Key derivation cheap enough for tests.
+#[cfg(test)]
+pub(super) const TEST_KDF: KdfParams = KdfParams {
+    memory_kib: 64,
+    iterations: 1,
+    parallelism: 1,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seal(keys: &StoreKeys, context: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        keys.encrypt(context, content.len() as u64, content, &mut sealed)
+            .unwrap();
+        sealed
+    }
+
+    fn open(keys: &StoreKeys, context: &[u8], sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        keys.decryptor(context, sealed)
+            .map_err(|error| invalid_data(&error.to_string()))?
+            .read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn seals_padded_streams_that_fail_when_tampered_with() {
+        assert_eq!(padded_length(9), 512);
+        assert_eq!(padded_length(1_000_000), 1_015_808);
+        assert!(padded_length(5_000_000_000) - 5_000_000_000 < 5_000_000_000 / 8);
+
+        let keys = StoreKeys::generate(TEST_KDF);
+        for length in [0, 100, CHUNK_SIZE - 8, CHUNK_SIZE, 3 * CHUNK_SIZE + 7] {
+            let content = (0..length).map(|byte| byte as u8).collect::<Vec<_>>();
+            let sealed = seal(&keys, b"object", &content);
+            let padded = padded_length(length as u64 + LENGTH_PREFIX_SIZE) as usize;
+            assert_eq!(
+                sealed.len(),
+                27 + padded + padded.div_ceil(CHUNK_SIZE) * TAG_SIZE
+            );
+            assert_eq!(open(&keys, b"object", &sealed).unwrap(), content);
+        }
+
+        let content = vec![7_u8; 2 * CHUNK_SIZE];
+        let sealed = seal(&keys, b"object", &content);
+        assert!(open(&keys, b"manifest", &sealed).is_err());
+        let mut flipped = sealed.clone();
+        flipped[40] ^= 1;
+        assert!(open(&keys, b"object", &flipped).is_err());
+        let chunk = CHUNK_SIZE + TAG_SIZE;
+        assert!(open(&keys, b"object", &sealed[..27 + 2 * chunk]).is_err());
+        assert!(open(&keys, b"object", &sealed[..27 + chunk]).is_err());
+        assert!(open(&StoreKeys::generate(TEST_KDF), b"object", &sealed).is_err());
+        let mut shorter = Vec::new();
+        assert!(
+            keys.encrypt(b"object", 10, &b"short"[..], &mut shorter)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn unlocks_with_the_passphrase_and_rotates_keys() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-keys-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        let passphrase = SecretString::from("correct horse");
+        let keys = StoreKeys::generate(TEST_KDF);
+        keys.write(&directory, &passphrase).unwrap();
+        let stored = fs::read_to_string(directory.join(KEYS_FILE_NAME)).unwrap();
+        assert!(!stored.contains(&hex_encode(keys.keys[0].key.expose_secret())));
+        assert!(
+            StoreKeys::unlock(&directory, &SecretString::from("wrong"))
+                .is_err_and(|error| error.to_string() == "wrong passphrase")
+        );
+        let unlocked = StoreKeys::unlock(&directory, &passphrase).unwrap();
+        let sealed = seal(&keys, b"object", b"content");
+        assert_eq!(open(&unlocked, b"object", &sealed).unwrap(), b"content");
+        assert_eq!(unlocked.object_names(b"hash"), keys.object_names(b"hash"));
+
+        let rotated = unlocked.rotated();
+        assert_eq!(rotated.object_names(b"hash").len(), 2);
+        assert_eq!(open(&rotated, b"object", &sealed).unwrap(), b"content");
+        let resealed = seal(&rotated, b"object", b"content");
+        let rotated = rotated.current_only();
+        rotated
+            .write(&directory, &SecretString::from("new passphrase"))
+            .unwrap();
+        assert!(StoreKeys::unlock(&directory, &passphrase).is_err());
+        let unlocked =
+            StoreKeys::unlock(&directory, &SecretString::from("new passphrase")).unwrap();
+        assert!(open(&unlocked, b"object", &sealed).is_err());
+        assert_eq!(open(&unlocked, b"object", &resealed).unwrap(), b"content");
+        let _ = fs::remove_dir_all(directory);
+    }
+}
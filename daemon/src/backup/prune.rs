@@ -0,0 +1,325 @@
+//! Retention: choosing the snapshots [`RetentionConfig`] keeps, removing the
+//! rest and collecting the content no remaining snapshot refers to.
+//!
+//! Manifests are removed before objects, so an interrupted prune leaves at
+//! worst unreferenced objects for the next prune, never a snapshot missing
+//! its content.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use super::{BackupStore, SnapshotSummary, StoreKeys};
+use crate::app::{civil_from_days, hex_decode};
+use crate::config::RetentionConfig;
+
+const HOUR_MILLIS: i64 = 3_600_000;
+const DAY_MILLIS: i64 = 24 * HOUR_MILLIS;
+
+/// Maps a start time to the hour, day, week or month it falls in.
+type BucketKey = fn(i64) -> i64;
+
+pub struct PruneRequest {
+    pub destination: PathBuf,
+    pub keys: Arc<StoreKeys>,
+    pub retention: RetentionConfig,
+    /// Only works out what would be removed.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PruneReport {
+    pub dry_run: bool,
+    /// Snapshots removed, or that a dry run would remove, newest first.
+    pub snapshots: Vec<SnapshotSummary>,
+    pub kept: usize,
+    /// Names of the objects removed.
+    pub objects: Vec<String>,
+    /// Size of those objects on the drive.
+    pub bytes: u64,
+}
+
+/// Ids of the snapshots `retention` keeps out of `snapshots`, which are
+/// newest first.
+fn retained(snapshots: &[SnapshotSummary], retention: &RetentionConfig) -> HashSet<String> {
+    if !retention.prunes() {
+        return snapshots
+            .iter()
+            .map(|snapshot| snapshot.id.clone())
+            .collect();
+    }
+    let mut kept = snapshots
+        .iter()
+        .take(retention.keep_last.max(1) as usize)
+        .map(|snapshot| snapshot.id.clone())
+        .collect::<HashSet<_>>();
+    let buckets: [(u32, BucketKey); 4] = [
+        (retention.hourly, |at| at.div_euclid(HOUR_MILLIS)),
+        (retention.daily, |at| at.div_euclid(DAY_MILLIS)),
+        // Weeks start on Monday; 1970-01-01 was a Thursday.
+        (retention.weekly, |at| {
+            (at.div_euclid(DAY_MILLIS) + 3).div_euclid(7)
+        }),
+        (retention.monthly, |at| {
+            let (year, month, _) = civil_from_days(at.div_euclid(DAY_MILLIS));
+            year * 12 + month
+        }),
+    ];
+    for (count, bucket) in buckets {
+        let mut last = None;
+        let mut filled = 0;
+        for snapshot in snapshots {
+            if filled == count {
+                break;
+            }
+            let key = bucket(snapshot.started_at);
+            if last != Some(key) {
+                last = Some(key);
+                filled += 1;
+                kept.insert(snapshot.id.clone());
+            }
+        }
+    }
+    kept
+}
+
+/// Removes the snapshots `request.retention` does not keep and every object
+/// the remaining ones do not refer to, or with `dry_run` only reports them.
+/// Refuses to run while any manifest cannot be read, since the content it
+/// refers to cannot be told apart from garbage.
+pub(super) fn prune(request: &PruneRequest) -> Result<PruneReport> {
+    let store = BackupStore::open(&request.destination, Arc::clone(&request.keys))?;
+    let mut manifests = Vec::new();
+    for id in store.manifest_ids()? {
+        let manifest = store
+            .manifest(&id)
+            .with_context(|| format!("not pruning while snapshot {id} cannot be read"))?;
+        manifests.push(manifest);
+    }
+    manifests.sort_by_key(|manifest| std::cmp::Reverse(manifest.started_at));
+    let summaries = manifests
+        .iter()
+        .map(|manifest| manifest.summary())
+        .collect::<Vec<_>>();
+    let kept = retained(&summaries, &request.retention);
+    let referenced = manifests
+        .iter()
+        .filter(|manifest| kept.contains(&manifest.id))
+        .flat_map(|manifest| &manifest.files)
+        .filter_map(|file| hex_decode(&file.hash))
+        .flat_map(|hash| store.object_names(&hash))
+        .collect::<HashSet<_>>();
+    let (objects, sizes): (Vec<_>, Vec<_>) = store
+        .objects()?
+        .into_iter()
+        .filter(|(name, _)| !referenced.contains(name))
+        .unzip();
+    let report = PruneReport {
+        dry_run: request.dry_run,
+        snapshots: summaries
+            .into_iter()
+            .filter(|snapshot| !kept.contains(&snapshot.id))
+            .collect(),
+        kept: kept.len(),
+        objects,
+        bytes: sizes.into_iter().sum(),
+    };
+    if !request.dry_run {
+        remove(&store, &report)?;
+        log::info!(
+            "pruned {} snapshots and {} objects from {}, reclaiming {} bytes",
+            report.snapshots.len(),
+            report.objects.len(),
+            request.destination.display(),
+            report.bytes
+        );
+    }
+    Ok(report)
+}
+
+fn remove(store: &BackupStore, report: &PruneReport) -> Result<()> {
+    for snapshot in &report.snapshots {
+        store.remove_manifest(&snapshot.id)?;
+    }
+    for hash in &report.objects {
+        store.remove_object(hash)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::backup::Manifest;
+    use crate::backup::crypto::TEST_KDF;
+    use crate::backup::store::ManifestFile;
+    use crate::database::days_from_civil;
+
+    fn summary(id: &str, started_at: i64) -> SnapshotSummary {
+        SnapshotSummary {
+            id: id.to_owned(),
+            device: "Laptop".to_owned(),
+            started_at,
+            finished_at: started_at,
+            file_count: 0,
+            total_bytes: 0,
+            bytes_written: 0,
+            error_count: 0,
+        }
+    }
+
+    fn off() -> RetentionConfig {
+        RetentionConfig {
+            keep_last: 0,
+            hourly: 0,
+            daily: 0,
+            weekly: 0,
+            monthly: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest_snapshot_of_each_bucket() {
+        let at =
+            |month, day, hour| days_from_civil(2024, month, day) * DAY_MILLIS + hour * HOUR_MILLIS;
+        // Newest first: two runs in one hour, then daily runs into the
+        // previous month. 2024-05-06 is a Monday.
+        let snapshots = [
+            summary("may7-10b", at(5, 7, 10) + 60_000),
+            summary("may7-10a", at(5, 7, 10)),
+            summary("may7-09", at(5, 7, 9)),
+            summary("may6", at(5, 6, 12)),
+            summary("may5", at(5, 5, 12)),
+            summary("may1", at(5, 1, 12)),
+            summary("apr30", at(4, 30, 12)),
+            summary("apr2", at(4, 2, 12)),
+        ];
+        let ids = |retention| {
+            let mut ids = retained(&snapshots, &retention)
+                .into_iter()
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(off()).len(), snapshots.len());
+        assert_eq!(
+            ids(RetentionConfig {
+                keep_last: 2,
+                ..off()
+            }),
+            ["may7-10a", "may7-10b"]
+        );
+        assert_eq!(
+            ids(RetentionConfig { hourly: 2, ..off() }),
+            ["may7-09", "may7-10b"]
+        );
+        assert_eq!(
+            ids(RetentionConfig { daily: 3, ..off() }),
+            ["may5", "may6", "may7-10b"]
+        );
+        assert_eq!(
+            ids(RetentionConfig { weekly: 2, ..off() }),
+            ["may5", "may7-10b"]
+        );
+        assert_eq!(
+            ids(RetentionConfig {
+                monthly: 3,
+                ..off()
+            }),
+            ["apr30", "may7-10b"]
+        );
+    }
+
+    #[test]
+    fn dry_run_reports_exactly_what_a_prune_removes() {
+        let destination =
+            std::env::temp_dir().join(format!("puppydrive-prune-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&destination).unwrap();
+        let passphrase = secrecy::SecretString::from("correct horse");
+        let store = BackupStore::unlock_with(&destination, &passphrase, TEST_KDF).unwrap();
+        let mut hashes = Vec::new();
+        for content in [b"old".as_slice(), b"shared", b"new"] {
+            let hash = blake3::hash(content);
+            store
+                .write_object(hash.as_bytes(), content.len() as u64, content)
+                .unwrap();
+            hashes.push(hash.to_hex().to_string());
+        }
+        let old_object = store.object_names(blake3::hash(b"old").as_bytes())[0].clone();
+        let old_size = fs::metadata(store.object_path(&old_object)).unwrap().len();
+        let manifest = |id: &str, started_at, files: &[usize]| Manifest {
+            id: id.to_owned(),
+            device: "Laptop".to_owned(),
+            started_at,
+            finished_at: started_at,
+            folders: vec![PathBuf::from("/photos")],
+            files: files
+                .iter()
+                .map(|&index| ManifestFile {
+                    folder: 0,
+                    path: PathBuf::from(format!("{index}.jpg")),
+                    hash: hashes[index].clone(),
+                    size: 3,
+                    modified_at: None,
+                })
+                .collect(),
+            bytes_written: 0,
+            errors: Vec::new(),
+        };
+        store
+            .write_manifest(&manifest("older", 1, &[0, 1]))
+            .unwrap();
+        store
+            .write_manifest(&manifest("newer", 2, &[1, 2]))
+            .unwrap();
+        let request = |dry_run| PruneRequest {
+            destination: destination.clone(),
+            keys: store.keys(),
+            retention: RetentionConfig {
+                keep_last: 1,
+                ..off()
+            },
+            dry_run,
+        };
+
+        let preview = prune(&request(true)).unwrap();
+        assert_eq!(preview.snapshots.len(), 1);
+        assert_eq!(preview.snapshots[0].id, "older");
+        assert_eq!(
+            (preview.kept, preview.objects.as_slice(), preview.bytes),
+            (1, [old_object].as_slice(), old_size)
+        );
+        assert_eq!(store.snapshots().unwrap().len(), 2);
+        assert!(store.has_object(blake3::hash(b"old").as_bytes()));
+
+        let pruned = prune(&request(false)).unwrap();
+        assert_eq!(
+            pruned,
+            PruneReport {
+                dry_run: false,
+                ..preview
+            }
+        );
+        assert_eq!(store.snapshots().unwrap().len(), 1);
+        assert!(!store.has_object(blake3::hash(b"old").as_bytes()));
+        assert!(store.has_object(blake3::hash(b"shared").as_bytes()));
+        assert!(prune(&request(false)).unwrap().objects.is_empty());
+
+        // An unreadable manifest might refer to any object, so nothing is
+        // collected until it is fixed or removed.
+        fs::write(
+            destination
+                .join(crate::backup::store::STORE_DIRECTORY_NAME)
+                .join("snapshots/broken.json"),
+            "{",
+        )
+        .unwrap();
+        assert!(prune(&request(true)).is_err());
+        let _ = fs::remove_dir_all(destination);
+    }
+}
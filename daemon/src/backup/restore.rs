@@ -0,0 +1,415 @@
+//! Restoring a subtree of a snapshot.
+//!
+//! Files go back to the Scanned folder they were backed up from, or below
+//! another folder, where the subtree keeps its own name. Each file is copied
+//! to a temporary name next to its destination, checked against the hash in
+//! the manifest and only then moved into place, so a damaged object never
+//! replaces anything.
+
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result, bail};
+use tokio::sync::mpsc::Sender;
+
+use super::store::{BackupStore, ManifestFile, copy_hashed};
+use super::{BackupEvent, PROGRESS_INTERVAL, StoreKeys};
+use crate::app::hex_decode;
+
+const MAX_SUMMARY_ERRORS: usize = 100;
+
+/// What to do when a restored file would land on an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keeps the existing file.
+    Skip,
+    /// Replaces the existing file.
+    Overwrite,
+    /// Restores next to the existing file under a new name.
+    Rename,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [Self; 3] = [Self::Skip, Self::Overwrite, Self::Rename];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Overwrite => "overwrite",
+            Self::Rename => "rename",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == value)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Skip => "Keep existing files",
+            Self::Overwrite => "Replace existing files",
+            Self::Rename => "Restore as copies",
+        }
+    }
+}
+
+pub struct RestoreRequest {
+    /// The backup destination holding the snapshot.
+    pub destination: PathBuf,
+    pub keys: Arc<StoreKeys>,
+    pub snapshot_id: String,
+    /// Index into the manifest's folders.
+    pub folder: usize,
+    /// Relative to the folder root; empty for the whole folder.
+    pub subtree: PathBuf,
+    /// Folder to restore below instead of the original location.
+    pub target: Option<PathBuf>,
+    pub conflicts: ConflictPolicy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RestoreSummary {
+    /// Where the subtree was restored to.
+    pub target: PathBuf,
+    pub restored: usize,
+    /// Restored under a new name because the path was taken.
+    pub renamed: usize,
+    /// Left alone because the path was taken.
+    pub skipped: usize,
+    pub failed: usize,
+    pub bytes: u64,
+    pub errors: Vec<String>,
+}
+
+enum Outcome {
+    Restored(u64),
+    Renamed(u64),
+    Skipped,
+}
+
+pub(super) fn restore(
+    events: &Sender<BackupEvent>,
+    request: RestoreRequest,
+) -> Result<RestoreSummary> {
+    let store = BackupStore::open(&request.destination, Arc::clone(&request.keys))?;
+    let manifest = store.manifest(&request.snapshot_id)?;
+    let root = manifest
+        .folders
+        .get(request.folder)
+        .context("the snapshot has no such folder")?;
+    if !is_relative_and_plain(&request.subtree) {
+        bail!(
+            "{} is not a path inside the snapshot",
+            request.subtree.display()
+        );
+    }
+    let files = manifest
+        .files
+        .iter()
+        .filter(|file| file.folder == request.folder && file.path.starts_with(&request.subtree))
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        bail!(
+            "the snapshot holds nothing at {}",
+            root.join(&request.subtree).display()
+        );
+    }
+    // Below another folder the subtree keeps its own name: restoring
+    // `trips` to `/tmp/restore` gives `/tmp/restore/trips/…`.
+    let (base, stripped) = match (&request.target, request.subtree.parent()) {
+        (None, _) => (root.clone(), PathBuf::new()),
+        (Some(target), Some(parent)) => (target.clone(), parent.to_path_buf()),
+        (Some(target), None) => (
+            target.join(root.file_name().unwrap_or("restore".as_ref())),
+            PathBuf::new(),
+        ),
+    };
+    if !base.is_absolute() {
+        bail!("restore to an absolute path");
+    }
+    let mut summary = RestoreSummary {
+        target: base.join(
+            request
+                .subtree
+                .strip_prefix(&stripped)
+                .unwrap_or(Path::new("")),
+        ),
+        ..RestoreSummary::default()
+    };
+    let files_total = files.len();
+    let mut last_progress = Instant::now();
+    for (files_done, file) in files.into_iter().enumerate() {
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            let _ = events.try_send(BackupEvent::RestoreProgress {
+                files_done,
+                files_total,
+            });
+        }
+        let path = match file.path.strip_prefix(&stripped) {
+            Ok(relative) if is_relative_and_plain(relative) => base.join(relative),
+            _ => {
+                summary.failed += 1;
+                push_error(
+                    &mut summary,
+                    &file.path,
+                    "is not a path inside the snapshot",
+                );
+                continue;
+            }
+        };
+        match restore_file(&store, file, &path, request.conflicts) {
+            Ok(Outcome::Restored(bytes)) => {
+                summary.restored += 1;
+                summary.bytes += bytes;
+            }
+            Ok(Outcome::Renamed(bytes)) => {
+                summary.restored += 1;
+                summary.renamed += 1;
+                summary.bytes += bytes;
+            }
+            Ok(Outcome::Skipped) => summary.skipped += 1,
+            Err(error) => {
+                log::warn!("could not restore {}: {error:#}", path.display());
+                summary.failed += 1;
+                push_error(&mut summary, &path, &format!("{error:#}"));
+            }
+        }
+    }
+    log::info!(
+        "restored {} files to {} ({} renamed, {} skipped, {} failed)",
+        summary.restored,
+        summary.target.display(),
+        summary.renamed,
+        summary.skipped,
+        summary.failed
+    );
+    Ok(summary)
+}
+
+fn push_error(summary: &mut RestoreSummary, path: &Path, error: &str) {
+    if summary.errors.len() < MAX_SUMMARY_ERRORS {
+        summary.errors.push(format!("{}: {error}", path.display()));
+    }
+}
+
+/// Manifests come from a drive that may have been tampered with, so paths
+/// that could climb out of the restore target are refused.
+fn is_relative_and_plain(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn restore_file(
+    store: &BackupStore,
+    file: &ManifestFile,
+    path: &Path,
+    conflicts: ConflictPolicy,
+) -> Result<Outcome> {
+    let hash = hex_decode(&file.hash)
+        .filter(|hash| hash.len() == 32)
+        .context("the manifest has an invalid hash")?;
+    let mut target = path.to_path_buf();
+    let taken = fs::symlink_metadata(path).is_ok();
+    if taken {
+        match conflicts {
+            ConflictPolicy::Skip => return Ok(Outcome::Skipped),
+            ConflictPolicy::Overwrite => {}
+            ConflictPolicy::Rename => target = free_name(path),
+        }
+    }
+    let directory = target.parent().context("restore path has no parent")?;
+    fs::create_dir_all(directory)
+        .with_context(|| format!("unable to create {}", directory.display()))?;
+    let temporary = directory.join(format!(
+        ".puppydrive-restore-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    let result = (|| {
+        let mut output = File::create(&temporary)?;
+        let (copied_hash, bytes) = copy_hashed(store.open_object(&hash)?, &mut output)?;
+        if copied_hash.as_bytes() != hash.as_slice() {
+            bail!("the backed up copy is damaged and does not match its hash");
+        }
+        if let Some(modified) = file
+            .modified_at
+            .and_then(|millis| u64::try_from(millis).ok())
+            .and_then(|millis| SystemTime::UNIX_EPOCH.checked_add(Duration::from_millis(millis)))
+        {
+            output.set_modified(modified)?;
+        }
+        output.sync_all()?;
+        fs::rename(&temporary, &target)
+            .with_context(|| format!("unable to move into {}", target.display()))?;
+        Ok(bytes)
+    })();
+    match result {
+        Ok(bytes) if taken && conflicts == ConflictPolicy::Rename => Ok(Outcome::Renamed(bytes)),
+        Ok(bytes) => Ok(Outcome::Restored(bytes)),
+        Err(error) => {
+            let _ = fs::remove_file(&temporary);
+            Err(error)
+        }
+    }
+}
+
+/// `photo (restored).jpg`, then `photo (restored 2).jpg` and so on, whichever
+/// is free first.
+fn free_name(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|attempt| {
+            let suffix = if attempt == 1 {
+                "restored".to_owned()
+            } else {
+                format!("restored {attempt}")
+            };
+            path.with_file_name(format!("{stem} ({suffix}){extension}"))
+        })
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .expect("some restore name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::Manifest;
+    use crate::backup::crypto::TEST_KDF;
+
+    fn manifest_file(path: &str, content: &[u8]) -> ManifestFile {
+        ManifestFile {
+            folder: 0,
+            path: PathBuf::from(path),
+            hash: crate::app::hex_encode(blake3::hash(content).as_bytes()),
+            size: content.len() as u64,
+            modified_at: Some(1_600_000_000_000),
+        }
+    }
+
+    #[test]
+    fn restores_subtrees_with_each_conflict_policy_and_verifies_content() {
+        let root =
+            std::env::temp_dir().join(format!("puppydrive-restore-{}", uuid::Uuid::new_v4()));
+        let original = root.join("photos");
+        let destination = root.join("drive");
+        fs::create_dir_all(&destination).unwrap();
+        let passphrase = secrecy::SecretString::from("correct horse");
+        let store = BackupStore::unlock_with(&destination, &passphrase, TEST_KDF).unwrap();
+        let contents: [(&str, &[u8]); 3] = [
+            ("a.txt", b"alpha"),
+            ("trips/b.txt", b"bravo"),
+            ("trips/c.txt", b"charlie"),
+        ];
+        for (_, content) in contents {
+            store
+                .write_object(
+                    blake3::hash(content).as_bytes(),
+                    content.len() as u64,
+                    content,
+                )
+                .unwrap();
+        }
+        store
+            .write_manifest(&Manifest {
+                id: "snapshot".to_owned(),
+                device: "Laptop".to_owned(),
+                started_at: 1,
+                finished_at: 2,
+                folders: vec![original.clone()],
+                files: contents
+                    .iter()
+                    .map(|(path, content)| manifest_file(path, content))
+                    .collect(),
+                bytes_written: 17,
+                errors: Vec::new(),
+            })
+            .unwrap();
+        let (events, _receiver) = tokio::sync::mpsc::channel(8);
+        let request = |subtree: &str, target: Option<PathBuf>, conflicts| RestoreRequest {
+            destination: destination.clone(),
+            keys: store.keys(),
+            snapshot_id: "snapshot".to_owned(),
+            folder: 0,
+            subtree: PathBuf::from(subtree),
+            target,
+            conflicts,
+        };
+
+        let summary = restore(&events, request("", None, ConflictPolicy::Skip)).unwrap();
+        assert_eq!((summary.restored, summary.bytes), (3, 17));
+        assert_eq!(summary.target, original);
+        assert_eq!(fs::read(original.join("trips/c.txt")).unwrap(), b"charlie");
+        let modified = fs::metadata(original.join("a.txt"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(
+            modified,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1_600_000_000_000)
+        );
+
+        fs::write(original.join("a.txt"), b"edited").unwrap();
+        let summary = restore(&events, request("", None, ConflictPolicy::Skip)).unwrap();
+        assert_eq!((summary.restored, summary.skipped), (0, 3));
+        assert_eq!(fs::read(original.join("a.txt")).unwrap(), b"edited");
+
+        let summary = restore(&events, request("trips", None, ConflictPolicy::Rename)).unwrap();
+        assert_eq!((summary.restored, summary.renamed), (2, 2));
+        assert_eq!(
+            fs::read(original.join("trips/b (restored).txt")).unwrap(),
+            b"bravo"
+        );
+        restore(&events, request("trips", None, ConflictPolicy::Rename)).unwrap();
+        assert!(original.join("trips/b (restored 2).txt").is_file());
+
+        let summary = restore(&events, request("a.txt", None, ConflictPolicy::Overwrite)).unwrap();
+        assert_eq!(summary.restored, 1);
+        assert_eq!(fs::read(original.join("a.txt")).unwrap(), b"alpha");
+
+        let elsewhere = root.join("elsewhere");
+        let summary = restore(
+            &events,
+            request("trips", Some(elsewhere.clone()), ConflictPolicy::Skip),
+        )
+        .unwrap();
+        assert_eq!(summary.target, elsewhere.join("trips"));
+        assert_eq!(fs::read(elsewhere.join("trips/b.txt")).unwrap(), b"bravo");
+        let summary = restore(
+            &events,
+            request("", Some(elsewhere.clone()), ConflictPolicy::Skip),
+        )
+        .unwrap();
+        assert_eq!(summary.target, elsewhere.join("photos"));
+        assert!(elsewhere.join("photos/trips/c.txt").is_file());
+
+        // A damaged object is refused and leaves nothing behind.
+        let object = store.object_path(&store.object_names(blake3::hash(b"bravo").as_bytes())[0]);
+        let mut sealed = fs::read(&object).unwrap();
+        sealed[40] ^= 1;
+        fs::write(&object, sealed).unwrap();
+        let damaged = root.join("damaged");
+        let summary = restore(
+            &events,
+            request("trips", Some(damaged.clone()), ConflictPolicy::Skip),
+        )
+        .unwrap();
+        assert_eq!((summary.restored, summary.failed), (1, 1));
+        assert!(summary.errors[0].contains("failed authentication"));
+        assert_eq!(fs::read_dir(damaged.join("trips")).unwrap().count(), 1);
+
+        assert!(restore(&events, request("../photos", None, ConflictPolicy::Skip)).is_err());
+        assert!(restore(&events, request("missing", None, ConflictPolicy::Skip)).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,525 @@
+//! On-disk layout of a backup destination.
+//!
+//! ```text
+//! <destination>/PuppyDrive Backup/
+//!     keys.json           data keys, wrapped by the passphrase
+//!     objects/ab/ab12…   encrypted file contents, named by keyed hash
+//!     snapshots/<id>.json one encrypted manifest per backup run
+//! ```
+//!
+//! Everything but the key file is encrypted as described in [`super::crypto`].
+//! Objects are written to a temporary name, synced, read back and only then
+//! renamed into place, so an object that exists is always complete and
+//! matches its name.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use super::crypto::{Decryptor, KEYS_FILE_NAME, KdfParams, StoreKeys};
+use crate::app::{hex_decode, hex_encode};
+
+pub const STORE_DIRECTORY_NAME: &str = "PuppyDrive Backup";
+const OBJECTS_DIRECTORY_NAME: &str = "objects";
+const SNAPSHOTS_DIRECTORY_NAME: &str = "snapshots";
+
+/// One backup run: every file it saw and the content it had.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    /// Name of the device that was backed up.
+    pub device: String,
+    /// Unix millis.
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// Roots of the Scanned folders, indexed by [`ManifestFile::folder`].
+    pub folders: Vec<PathBuf>,
+    pub files: Vec<ManifestFile>,
+    /// Bytes of new content this run added to the store.
+    pub bytes_written: u64,
+    /// Files left out of the snapshot and why.
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub folder: usize,
+    /// Relative to the folder root.
+    pub path: PathBuf,
+    /// Hex BLAKE3 hash naming the object with the content.
+    pub hash: String,
+    pub size: u64,
+    pub modified_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotSummary {
+    pub id: String,
+    pub device: String,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub bytes_written: u64,
+    pub error_count: usize,
+}
+
+/// A file or directory directly inside a directory of a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub is_directory: bool,
+    /// Total size of everything below a directory.
+    pub size: u64,
+    /// Files below a directory, or 1.
+    pub file_count: usize,
+}
+
+impl Manifest {
+    pub fn summary(&self) -> SnapshotSummary {
+        SnapshotSummary {
+            id: self.id.clone(),
+            device: self.device.clone(),
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+            file_count: self.files.len(),
+            total_bytes: self.files.iter().map(|file| file.size).sum(),
+            bytes_written: self.bytes_written,
+            error_count: self.errors.len(),
+        }
+    }
+
+    /// What `directory`, relative to the root of `folder`, held in this
+    /// snapshot. Directories come first, then files, each sorted by name.
+    pub fn entries(&self, folder: usize, directory: &Path) -> Vec<SnapshotEntry> {
+        let mut entries = Vec::<SnapshotEntry>::new();
+        for file in self.files.iter().filter(|file| file.folder == folder) {
+            let Ok(rest) = file.path.strip_prefix(directory) else {
+                continue;
+            };
+            let mut components = rest.components();
+            let Some(first) = components.next() else {
+                continue;
+            };
+            let name = first.as_os_str().to_string_lossy().into_owned();
+            let is_directory = components.next().is_some();
+            match entries
+                .iter_mut()
+                .find(|entry| entry.is_directory == is_directory && entry.name == name)
+            {
+                Some(entry) => {
+                    entry.size += file.size;
+                    entry.file_count += 1;
+                }
+                None => entries.push(SnapshotEntry {
+                    name,
+                    is_directory,
+                    size: file.size,
+                    file_count: 1,
+                }),
+            }
+        }
+        entries.sort_by(|left, right| {
+            right
+                .is_directory
+                .cmp(&left.is_directory)
+                .then_with(|| left.name.to_lowercase().cmp(&right.name.to_lowercase()))
+        });
+        entries
+    }
+}
+
+#[derive(Clone)]
+pub struct BackupStore {
+    root: PathBuf,
+    keys: Arc<StoreKeys>,
+}
+
+impl BackupStore {
+    /// Unlocks the store in `destination` with `passphrase`, setting up an
+    /// empty store protected by it on first use. The destination itself must
+    /// exist, so an unplugged drive is reported instead of being backed up
+    /// to the mount point.
+    pub fn unlock(destination: &Path, passphrase: &SecretString) -> Result<Self> {
+        Self::unlock_with(destination, passphrase, KdfParams::default())
+    }
+
+    pub(super) fn unlock_with(
+        destination: &Path,
+        passphrase: &SecretString,
+        kdf: KdfParams,
+    ) -> Result<Self> {
+        if !destination.is_dir() {
+            bail!(
+                "backup destination {} is not available",
+                destination.display()
+            );
+        }
+        let root = destination.join(STORE_DIRECTORY_NAME);
+        let keys = if root.join(KEYS_FILE_NAME).is_file() {
+            StoreKeys::unlock(&root, passphrase)?
+        } else if root.join(SNAPSHOTS_DIRECTORY_NAME).is_dir() {
+            bail!(
+                "{} holds a backup made without encryption; choose another destination",
+                destination.display()
+            );
+        } else {
+            if passphrase.expose_secret().is_empty() {
+                bail!("choose a passphrase for the backup");
+            }
+            fs::create_dir_all(&root)
+                .with_context(|| format!("unable to create {}", root.display()))?;
+            let keys = StoreKeys::generate(kdf);
+            keys.write(&root, passphrase)?;
+            keys
+        };
+        for directory in [OBJECTS_DIRECTORY_NAME, SNAPSHOTS_DIRECTORY_NAME] {
+            let path = root.join(directory);
+            fs::create_dir_all(&path)
+                .with_context(|| format!("unable to create {}", path.display()))?;
+        }
+        Ok(Self {
+            root,
+            keys: Arc::new(keys),
+        })
+    }
+
+    /// Opens a store unlocked earlier in the session.
+    pub fn open(destination: &Path, keys: Arc<StoreKeys>) -> Result<Self> {
+        let root = destination.join(STORE_DIRECTORY_NAME);
+        if !root.join(SNAPSHOTS_DIRECTORY_NAME).is_dir() {
+            bail!(
+                "backup destination {} is not available",
+                destination.display()
+            );
+        }
+        Ok(Self { root, keys })
+    }
+
+    pub fn keys(&self) -> Arc<StoreKeys> {
+        Arc::clone(&self.keys)
+    }
+
+    pub(super) fn object_path(&self, name: &str) -> PathBuf {
+        self.root
+            .join(OBJECTS_DIRECTORY_NAME)
+            .join(&name[..2])
+            .join(name)
+    }
+
+    /// Where the object for content `hash` is, under whichever key wrote it.
+    fn find_object(&self, hash: &[u8]) -> Option<PathBuf> {
+        self.keys
+            .object_names(hash)
+            .iter()
+            .map(|name| self.object_path(name))
+            .find(|path| path.is_file())
+    }
+
+    /// Names the object for content `hash` may have in the store.
+    pub(super) fn object_names(&self, hash: &[u8]) -> Vec<String> {
+        self.keys.object_names(hash)
+    }
+
+    pub fn has_object(&self, hash: &[u8]) -> bool {
+        self.find_object(hash).is_some()
+    }
+
+    /// Encrypts `size` bytes of `source` into the store as the object for
+    /// `hash` and returns the bytes written. Fails without leaving anything
+    /// behind when the content does not match `hash` or does not read back
+    /// intact.
+    pub fn write_object(&self, hash: &[u8], size: u64, source: impl Read) -> Result<u64> {
+        let target = self.object_path(&self.keys.object_names(hash)[0]);
+        let directory = target.parent().context("object path has no parent")?;
+        fs::create_dir_all(directory)
+            .with_context(|| format!("unable to create {}", directory.display()))?;
+        let temporary = target.with_extension(format!("{}.part", uuid::Uuid::new_v4().simple()));
+        let result = self
+            .write_verified(&temporary, hash, size, source)
+            .and_then(|written| {
+                fs::rename(&temporary, &target)
+                    .with_context(|| format!("unable to store {}", target.display()))?;
+                Ok(written)
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+
+    /// Decrypts the object for `hash`. Callers check the content against
+    /// the hash themselves as they copy it.
+    pub fn open_object(&self, hash: &[u8]) -> Result<Decryptor<File>> {
+        let path = self
+            .find_object(hash)
+            .with_context(|| format!("the backup is missing {}", hex_encode(hash)))?;
+        let file =
+            File::open(&path).with_context(|| format!("unable to open {}", path.display()))?;
+        self.keys.decryptor(hash, file)
+    }
+
+    pub fn write_manifest(&self, manifest: &Manifest) -> Result<()> {
+        let path = self.manifest_path(&manifest.id)?;
+        let temporary = path.with_extension("json.part");
+        let json = serde_json::to_vec(manifest)?;
+        let mut file = BufWriter::new(
+            File::create(&temporary)
+                .with_context(|| format!("unable to create {}", temporary.display()))?,
+        );
+        self.keys.encrypt(
+            &manifest_context(&manifest.id),
+            json.len() as u64,
+            json.as_slice(),
+            &mut file,
+        )?;
+        file.into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+        fs::rename(&temporary, &path)
+            .with_context(|| format!("unable to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn manifest(&self, id: &str) -> Result<Manifest> {
+        let path = self.manifest_path(id)?;
+        let file =
+            File::open(&path).with_context(|| format!("unable to open {}", path.display()))?;
+        let mut json = Vec::new();
+        self.keys
+            .decryptor(&manifest_context(id), file)
+            .and_then(|mut decryptor| Ok(decryptor.read_to_end(&mut json)?))
+            .with_context(|| format!("unable to decrypt {}", path.display()))?;
+        serde_json::from_slice(&json)
+            .with_context(|| format!("{} is not a snapshot manifest", path.display()))
+    }
+
+    /// Every snapshot in the store, newest first. Manifests that cannot be
+    /// read are logged and left out.
+    pub fn snapshots(&self) -> Result<Vec<SnapshotSummary>> {
+        let mut snapshots = Vec::new();
+        for id in self.manifest_ids()? {
+            match self.manifest(&id) {
+                Ok(manifest) => snapshots.push(manifest.summary()),
+                Err(error) => log::warn!("skipping snapshot {id}: {error:#}"),
+            }
+        }
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.started_at));
+        Ok(snapshots)
+    }
+
+    /// Ids of every manifest file, readable or not.
+    pub(super) fn manifest_ids(&self) -> Result<Vec<String>> {
+        let directory = self.root.join(SNAPSHOTS_DIRECTORY_NAME);
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&directory)
+            .with_context(|| format!("unable to read {}", directory.display()))?
+        {
+            let path = entry?.path();
+            if let Some(id) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+            {
+                ids.push(id.to_owned());
+            }
+        }
+        Ok(ids)
+    }
+
+    pub(super) fn remove_manifest(&self, id: &str) -> Result<()> {
+        let path = self.manifest_path(id)?;
+        fs::remove_file(&path).with_context(|| format!("unable to remove {}", path.display()))
+    }
+
+    /// Name and size of every complete object in the store. Partial objects
+    /// left by an interrupted run are not listed.
+    pub(super) fn objects(&self) -> Result<Vec<(String, u64)>> {
+        let directory = self.root.join(OBJECTS_DIRECTORY_NAME);
+        let mut objects = Vec::new();
+        for prefix in fs::read_dir(&directory)
+            .with_context(|| format!("unable to read {}", directory.display()))?
+        {
+            let prefix = prefix?.path();
+            if !prefix.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&prefix)
+                .with_context(|| format!("unable to read {}", prefix.display()))?
+            {
+                let entry = entry?;
+                let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                    continue;
+                };
+                if name.len() == 64 && name.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                    objects.push((name, entry.metadata()?.len()));
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Removes the object called `name`, and its prefix directory once
+    /// empty.
+    pub(super) fn remove_object(&self, name: &str) -> Result<()> {
+        let path = self.object_path(name);
+        fs::remove_file(&path).with_context(|| format!("unable to remove {}", path.display()))?;
+        if let Some(directory) = path.parent() {
+            let _ = fs::remove_dir(directory);
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts every object and manifest under a fresh data key wrapped
+    /// by `passphrase` and returns the store unlocked with it. The key file
+    /// holds both keys until the end, so an interrupted rotation leaves a
+    /// readable store for the next rotation to finish.
+    pub(super) fn rotate_key(&self, passphrase: &SecretString) -> Result<Self> {
+        self.rotate_key_with(passphrase, |rotated, hash, size| {
+            let source = rotated.open_object(hash)?;
+            rotated.write_object(hash, size, source).map(drop)
+        })
+    }
+
+    /// [`BackupStore::rotate_key`] with `reencrypt` writing each object
+    /// under the new key. When any object fails, the manifests and both
+    /// keys are left as they are, since the old copy of that object is still
+    /// the only one.
+    pub(super) fn rotate_key_with(
+        &self,
+        passphrase: &SecretString,
+        mut reencrypt: impl FnMut(&Self, &[u8], u64) -> Result<()>,
+    ) -> Result<Self> {
+        if passphrase.expose_secret().is_empty() {
+            bail!("choose a passphrase for the backup");
+        }
+        let rotated = Self {
+            root: self.root.clone(),
+            keys: Arc::new(self.keys.rotated()),
+        };
+        let mut manifests = Vec::new();
+        for id in self.manifest_ids()? {
+            let manifest = self
+                .manifest(&id)
+                .with_context(|| format!("not rotating while snapshot {id} cannot be read"))?;
+            manifests.push(manifest);
+        }
+        rotated.keys.write(&self.root, passphrase)?;
+        let mut done = HashSet::new();
+        let mut failed = Vec::new();
+        for file in manifests.iter().flat_map(|manifest| &manifest.files) {
+            if !done.insert(file.hash.as_str()) {
+                continue;
+            }
+            let Some(hash) = hex_decode(&file.hash).filter(|hash| hash.len() == 32) else {
+                continue;
+            };
+            let names = rotated.keys.object_names(&hash);
+            if !rotated.object_path(&names[0]).is_file() {
+                if !rotated.has_object(&hash) {
+                    log::warn!("not re-encrypting {}: the backup is missing it", file.hash);
+                    continue;
+                }
+                if let Err(error) = reencrypt(&rotated, &hash, file.size) {
+                    log::warn!("unable to re-encrypt {}: {error:#}", file.hash);
+                    failed.push(file.hash.as_str());
+                    continue;
+                }
+            }
+            for old in &names[1..] {
+                let _ = fs::remove_file(rotated.object_path(old));
+            }
+        }
+        if let Some(first) = failed.first() {
+            bail!(
+                "unable to re-encrypt {} objects, such as {first}; the old key was kept, so \
+                 rotating again can finish",
+                failed.len()
+            );
+        }
+        for manifest in &manifests {
+            rotated.write_manifest(manifest)?;
+        }
+        let finished = Self {
+            root: self.root.clone(),
+            keys: Arc::new(rotated.keys.current_only()),
+        };
+        finished.keys.write(&self.root, passphrase)?;
+        Ok(finished)
+    }
+
+    fn manifest_path(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty()
+            || !id
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        {
+            bail!("invalid snapshot id {id:?}");
+        }
+        Ok(self
+            .root
+            .join(SNAPSHOTS_DIRECTORY_NAME)
+            .join(format!("{id}.json")))
+    }
+
+    fn write_verified(
+        &self,
+        path: &Path,
+        hash: &[u8],
+        size: u64,
+        source: impl Read,
+    ) -> Result<u64> {
+        let mut file = BufWriter::new(
+            File::create(path).with_context(|| format!("unable to create {}", path.display()))?,
+        );
+        let encrypted_hash = self.keys.encrypt(hash, size, source, &mut file)?;
+        let file = file.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()?;
+        if encrypted_hash.as_bytes() != hash {
+            bail!("content changed since it was indexed");
+        }
+        // Reading the object back catches a failing drive now rather than at
+        // restore time.
+        let (read_back, _) = copy_hashed(
+            self.keys.decryptor(hash, File::open(path)?)?,
+            &mut io::sink(),
+        )
+        .with_context(|| format!("{} did not read back intact", path.display()))?;
+        if read_back.as_bytes() != hash {
+            bail!("{} did not read back intact", path.display());
+        }
+        Ok(file.metadata()?.len())
+    }
+}
+
+fn manifest_context(id: &str) -> Vec<u8> {
+    format!("manifest {id}").into_bytes()
+}
+
+/// Copies `source` into `target` and returns the BLAKE3 hash of what was
+/// copied along with its length.
+pub(super) fn copy_hashed(
+    mut source: impl Read,
+    target: &mut impl Write,
+) -> Result<(blake3::Hash, u64)> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0_u8; 64 * 1024];
+    let mut copied = 0_u64;
+    loop {
+        let read = source.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        target.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+    Ok((hasher.finalize(), copied))
+}
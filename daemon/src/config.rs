@@ -20,6 +20,7 @@ pub struct AppConfig {
     pub database: DatabaseConfig,
     pub general: GeneralConfig,
     pub backup: BackupConfig,
+    pub peers: PeersConfig,
     pub appearance: AppearanceConfig,
     pub media: MediaConfig,
     pub inboxes: Vec<InboxConfig>,
@@ -33,6 +34,7 @@ impl Default for AppConfig {
             database: DatabaseConfig::default(),
             general: GeneralConfig::default(),
             backup: BackupConfig::default(),
+            peers: PeersConfig::default(),
             appearance: AppearanceConfig::default(),
             media: MediaConfig::default(),
             inboxes: Vec::new(),
@@ -106,7 +108,14 @@ impl Default for GeneralConfig {
 #[serde(default)]
 pub struct BackupConfig {
     pub metered_connections: bool,
+    /// How often Scanned folders are rescanned and backed up.
     pub schedule: BackupSchedule,
+    /// Holds scheduled scans and maintenance while running on battery.
+    pub pause_on_battery: bool,
+    /// Directory backups are written to, such as an external drive. Nothing
+    /// is backed up while unset.
+    pub destination: Option<PathBuf>,
+    pub retention: RetentionConfig,
 }
 
 impl Default for BackupConfig {
@@ -114,6 +123,69 @@ impl Default for BackupConfig {
         Self {
             metered_connections: false,
             schedule: BackupSchedule::Continuous,
+            pause_on_battery: true,
+            destination: None,
+            retention: RetentionConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeersConfig {
+    /// Minutes between pulls of the paired devices' indexes. Zero leaves
+    /// syncing to the Sync button.
+    pub sync_minutes: u64,
+    /// Megabytes of content fetched from paired devices kept for reuse. Zero
+    /// keeps only the file opened last.
+    pub cache_mb: u64,
+    /// Sends access tokens to paired devices on other computers over plain
+    /// http. Off by default; devices reached over loopback always work.
+    pub allow_cleartext: bool,
+}
+
+impl Default for PeersConfig {
+    fn default() -> Self {
+        Self {
+            sync_minutes: 5,
+            cache_mb: 2_048,
+            allow_cleartext: false,
+        }
+    }
+}
+
+/// Which snapshots a prune keeps. A snapshot survives when any rule keeps
+/// it: `keep_last` keeps the newest runs, and each bucket count keeps the
+/// newest run of that many recent hours, days, weeks or months that have
+/// one. The newest snapshot is always kept, and all zeros turn pruning off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub keep_last: u32,
+    pub hourly: u32,
+    pub daily: u32,
+    pub weekly: u32,
+    pub monthly: u32,
+}
+
+impl RetentionConfig {
+    pub fn prunes(&self) -> bool {
+        self.keep_last > 0
+            || self.hourly > 0
+            || self.daily > 0
+            || self.weekly > 0
+            || self.monthly > 0
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            keep_last: 3,
+            hourly: 24,
+            daily: 7,
+            weekly: 4,
+            monthly: 12,
         }
     }
 }
@@ -127,6 +199,25 @@ pub enum BackupSchedule {
     Daily,
 }
 
+impl BackupSchedule {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Continuous => "continuous",
+            Self::Hourly => "hourly",
+            Self::Daily => "daily",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "continuous" => Some(Self::Continuous),
+            "hourly" => Some(Self::Hourly),
+            "daily" => Some(Self::Daily),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppearanceConfig {
@@ -163,6 +254,9 @@ pub struct MediaConfig {
     pub ignored_directory_names: Vec<String>,
     /// Zero disables the limit.
     pub max_file_size_mb: u64,
+    /// How often every indexed file is read back and checked against its
+    /// hash. Zero disables scrubbing.
+    pub scrub_interval_days: u64,
 }
 
 impl Default for MediaConfig {
@@ -179,6 +273,7 @@ impl Default for MediaConfig {
                 "target".to_owned(),
             ],
             max_file_size_mb: 1_024,
+            scrub_interval_days: 30,
         }
     }
 }
@@ -188,6 +283,8 @@ pub struct ConfigPaths {
     pub config_file: PathBuf,
     pub database_file: PathBuf,
     pub thumbnail_cache_dir: PathBuf,
+    pub upload_staging_dir: PathBuf,
+    pub peer_cache_dir: PathBuf,
 }
 
 impl ConfigPaths {
@@ -211,14 +308,16 @@ impl ConfigPaths {
             .transpose()?
             .or(configured_database)
             .unwrap_or_else(|| project.data_local_dir().join("puppydrive.db"));
-        let thumbnail_cache_dir = database_file
-            .parent()
-            .unwrap_or_else(|| Path::new("."))
-            .join("thumbnails");
+        let data_dir = database_file.parent().unwrap_or_else(|| Path::new("."));
+        let thumbnail_cache_dir = data_dir.join("thumbnails");
+        let upload_staging_dir = data_dir.join("uploads");
+        let peer_cache_dir = data_dir.join("peer-cache");
         Ok(Self {
             config_file,
             database_file,
             thumbnail_cache_dir,
+            upload_staging_dir,
+            peer_cache_dir,
         })
     }
 }
@@ -4,27 +4,59 @@ use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
-use rusqlite::{OptionalExtension, params};
+use rusqlite::{OptionalExtension, Transaction, params};
 use serde::{Deserialize, Serialize};
 use wgui::{DbTable, HasId, SQLLiteDB, SqliteTable, Wdb, WguiModel, apply_sqlite_migrations};
 
+use crate::file_query::{FileQuery, FolderFilter};
+use crate::indexer::{Indexer, IndexerRows, ObservedFile, registered_indexers};
+use crate::managed_folder::ManagedFolder;
+
 #[derive(Debug, Clone, Serialize, Deserialize, WguiModel)]
 pub struct ScannedFolder {
     pub id: u32,
     pub path: String,
     pub enabled: bool,
     pub indexers: String,
+    /// JSON array of globs that are indexed even where the built-in rules
+    /// would skip them. See [`crate::ignore_rules`].
+    pub include_globs: String,
+    /// JSON array of globs that are never indexed.
+    pub exclude_globs: String,
 }
 
 impl ScannedFolder {
     pub const MEDIA_INDEXER: &'static str = "media";
+    pub const EXIF_INDEXER: &'static str = "exif";
+    pub const AUDIO_TAGS_INDEXER: &'static str = "audio-tags";
+    pub const CONTENT_INDEXER: &'static str = "content";
+    pub const SIMILAR_IMAGES_INDEXER: &'static str = "similar-images";
+    /// Indexers enabled for folders added from the UI, the API or first run.
+    /// The others read or decode file contents and are opted into per folder.
+    pub const DEFAULT_INDEXERS: [&'static str; 1] = [Self::MEDIA_INDEXER];
+
+    pub fn default_indexers() -> String {
+        serde_json::to_string(&Self::DEFAULT_INDEXERS).unwrap_or_default()
+    }
+
+    pub fn indexer_names(&self) -> Vec<String> {
+        serde_json::from_str(&self.indexers).unwrap_or_default()
+    }
+
+    pub fn indexes(&self, indexer: &str) -> bool {
+        self.indexer_names().iter().any(|name| name == indexer)
+    }
 
     pub fn indexes_media(&self) -> bool {
-        serde_json::from_str::<Vec<String>>(&self.indexers).is_ok_and(|indexers| {
-            indexers
-                .iter()
-                .any(|indexer| indexer == Self::MEDIA_INDEXER)
-        })
+        self.indexes(Self::MEDIA_INDEXER)
+    }
+
+    pub fn include_glob_list(&self) -> Vec<String> {
+        serde_json::from_str(&self.include_globs).unwrap_or_default()
+    }
+
+    pub fn exclude_glob_list(&self) -> Vec<String> {
+        serde_json::from_str(&self.exclude_globs).unwrap_or_default()
     }
 }
 
@@ -36,6 +68,10 @@ pub enum ScanTrigger {
     ManualFolder,
     ManualRefresh,
     FilesystemChange,
+    /// Continues a scan that was interrupted by a daemon restart.
+    Resumed,
+    /// Started by the scheduler rather than a person or the watcher.
+    Scheduled,
 }
 
 impl ScanTrigger {
@@ -44,6 +80,8 @@ impl ScanTrigger {
             Self::ManualFolder => "manual-folder",
             Self::ManualRefresh => "manual-refresh",
             Self::FilesystemChange => "filesystem-change",
+            Self::Resumed => "resumed",
+            Self::Scheduled => "scheduled",
         }
     }
 
@@ -51,6 +89,8 @@ impl ScanTrigger {
         match value {
             "manual-folder" => Self::ManualFolder,
             "manual-refresh" => Self::ManualRefresh,
+            "resumed" => Self::Resumed,
+            "scheduled" => Self::Scheduled,
             _ => Self::FilesystemChange,
         }
     }
@@ -81,6 +121,147 @@ impl ScanOutcome {
     }
 }
 
+/// Where an unfinished scan of one Scanned folder stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanCheckpoint {
+    pub scanned_folder_id: u32,
+    pub node_id: Vec<u8>,
+    /// The `last_seen_scan` stamp the scan writes, kept so a resumed scan
+    /// still sweeps only what neither run saw.
+    pub scan_id: Vec<u8>,
+    pub started_at: i64,
+    /// Directories still to traverse, in queue order.
+    pub pending_directories: Vec<PathBuf>,
+    pub directories_scanned: usize,
+    pub files_indexed: usize,
+}
+
+/// An indexed file due for content verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrubCandidate {
+    pub scanned_folder_id: u32,
+    pub path: PathBuf,
+    pub hash: Vec<u8>,
+    pub size: u64,
+    pub created_at: Option<i64>,
+    pub modified_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScrubOutcome {
+    Healthy,
+    /// The content hashes differently although size and timestamps match.
+    Changed {
+        actual_hash: Vec<u8>,
+    },
+    Unreadable {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrubResult {
+    pub candidate: ScrubCandidate,
+    pub outcome: ScrubOutcome,
+}
+
+/// A replica that failed verification, with the replicas of the same content
+/// that are not known to be damaged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrubAlert {
+    pub path: PathBuf,
+    pub expected_hash: Vec<u8>,
+    pub actual_hash: Option<Vec<u8>>,
+    pub error: Option<String>,
+    pub size: u64,
+    pub detected_at: i64,
+    pub healthy_replicas: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScrubSummary {
+    pub hashed_files: usize,
+    /// Files verified at or after the cutoff passed to
+    /// [`Database::scrub_summary`].
+    pub verified_files: usize,
+}
+
+/// Indexed files on one node that share a hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    pub hash: Vec<u8>,
+    pub size: u64,
+    pub copies: Vec<DuplicateCopy>,
+    /// Copies that are separate files on disk. Hardlinked copies share one
+    /// file and take no extra space.
+    pub distinct_files: usize,
+}
+
+impl DuplicateGroup {
+    /// Bytes freed by keeping a single copy.
+    pub fn wasted_bytes(&self) -> u64 {
+        self.size
+            .saturating_mul(self.distinct_files.saturating_sub(1) as u64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCopy {
+    pub hash: Vec<u8>,
+    pub path: PathBuf,
+    pub scanned_folder_id: u32,
+    pub modified_at: Option<i64>,
+}
+
+/// One image content, with every copy of it, in a group of look-alikes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimilarImage {
+    pub hash: Vec<u8>,
+    pub size: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub copies: Vec<DuplicateCopy>,
+}
+
+impl SimilarImage {
+    pub fn pixels(&self) -> u64 {
+        u64::from(self.width.unwrap_or(0)) * u64::from(self.height.unwrap_or(0))
+    }
+}
+
+/// Another PuppyDrive daemon whose index is replicated into this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    pub node_id: Vec<u8>,
+    pub name: String,
+    pub url: String,
+    /// Token the peer issued to this daemon. Unlike tokens presented to this
+    /// daemon it is kept in the clear, because every pull sends it.
+    pub access_token: String,
+    /// Last change of the peer's feed that was applied here.
+    pub last_sequence: i64,
+    pub last_synced_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub file_count: usize,
+}
+
+/// The latest change of one path in an index change feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexChange {
+    pub sequence: i64,
+    pub path: String,
+    /// `None` when the path is no longer indexed.
+    pub location: Option<ChangedLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedLocation {
+    pub hash: Option<Vec<u8>>,
+    pub size: u64,
+    pub mime_type: Option<String>,
+    pub modified_at: Option<i64>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ScanHistoryEntry {
     pub scanned_folder_id: u32,
@@ -182,7 +363,9 @@ impl Database {
             id: 0,
             path: path.to_string_lossy().into_owned(),
             enabled: true,
-            indexers: r#"["media"]"#.to_owned(),
+            indexers: ScannedFolder::default_indexers(),
+            include_globs: "[]".to_owned(),
+            exclude_globs: "[]".to_owned(),
         })
     }
 
@@ -214,7 +397,7 @@ impl Database {
                 SELECT 1 FROM scanned_folder_locations membership
                 WHERE membership.node_id = file_locations.node_id
                   AND membership.path = file_locations.path
-            )",
+            ) AND node_id IN (SELECT node_id FROM nodes WHERE is_local = 1)",
             [],
         )?;
         let affected = transaction.execute("DELETE FROM ScannedFolder WHERE id = ?1", [id])?;
@@ -226,6 +409,12 @@ impl Database {
         self.delete_scanned_folder(id)
     }
 
+    pub fn delete_source(&self, id: u32) -> Result<bool> {
+        let connection = self.connection()?;
+        let affected = connection.execute("DELETE FROM Source WHERE id = ?1", [id])?;
+        Ok(affected > 0)
+    }
+
     pub fn scanned_folder_scan_history(&self, folder_id: u32) -> Result<Vec<ScanHistoryEntry>> {
         let connection = self.connection()?;
         let mut statement = connection.prepare(
@@ -273,6 +462,104 @@ impl Database {
         Ok(entry)
     }
 
+    pub fn scan_checkpoints(&self) -> Result<Vec<ScanCheckpoint>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT scanned_folder_id, node_id, scan_id, started_at, pending_directories,
+                    directories_scanned, files_indexed
+             FROM scan_checkpoints ORDER BY scanned_folder_id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let pending_directories = row.get::<_, String>(4)?;
+            Ok(ScanCheckpoint {
+                scanned_folder_id: row.get(0)?,
+                node_id: row.get(1)?,
+                scan_id: row.get(2)?,
+                started_at: row.get(3)?,
+                pending_directories: serde_json::from_str(&pending_directories).unwrap_or_default(),
+                directories_scanned: row.get::<_, i64>(5)? as usize,
+                files_indexed: row.get::<_, i64>(6)? as usize,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    pub fn save_scan_checkpoint(&self, checkpoint: &ScanCheckpoint) -> Result<()> {
+        let connection = self.connection()?;
+        connection.execute(
+            "INSERT INTO scan_checkpoints
+                (scanned_folder_id, node_id, scan_id, started_at, pending_directories,
+                 directories_scanned, files_indexed, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(scanned_folder_id) DO UPDATE SET
+                node_id = excluded.node_id, scan_id = excluded.scan_id,
+                started_at = excluded.started_at,
+                pending_directories = excluded.pending_directories,
+                directories_scanned = excluded.directories_scanned,
+                files_indexed = excluded.files_indexed, updated_at = excluded.updated_at",
+            params![
+                checkpoint.scanned_folder_id,
+                checkpoint.node_id,
+                checkpoint.scan_id,
+                checkpoint.started_at,
+                serde_json::to_string(&checkpoint.pending_directories)?,
+                checkpoint.directories_scanned as i64,
+                checkpoint.files_indexed as i64,
+                now_millis(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_scan_checkpoint(&self, folder_id: u32) -> Result<()> {
+        let connection = self.connection()?;
+        connection.execute(
+            "DELETE FROM scan_checkpoints WHERE scanned_folder_id = ?1",
+            [folder_id],
+        )?;
+        Ok(())
+    }
+
+    /// Last run times of scheduled jobs, in Unix millis by job name.
+    pub fn scheduled_job_runs(&self) -> Result<HashMap<String, i64>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare("SELECT job, last_run_at FROM scheduled_jobs")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<std::result::Result<HashMap<_, _>, _>>()
+            .map_err(Into::into)
+    }
+
+    pub fn record_scheduled_job_run(&self, job: &str, at: i64) -> Result<()> {
+        let connection = self.connection()?;
+        connection.execute(
+            "INSERT INTO scheduled_jobs (job, last_run_at) VALUES (?1, ?2)
+             ON CONFLICT(job) DO UPDATE SET last_run_at = excluded.last_run_at",
+            params![job, at],
+        )?;
+        Ok(())
+    }
+
+    /// Periodic upkeep: drops expired sessions, merges search-index segments
+    /// and refreshes the query planner's statistics.
+    pub fn run_maintenance(&self) -> Result<()> {
+        let connection = self.connection()?;
+        connection.execute(
+            "DELETE FROM auth_sessions WHERE expires_at <= ?1",
+            [now_millis()],
+        )?;
+        connection.execute(
+            "INSERT INTO content_search (content_search) VALUES ('optimize')",
+            [],
+        )?;
+        connection.execute(
+            "INSERT INTO path_search (path_search) VALUES ('optimize')",
+            [],
+        )?;
+        connection.execute_batch("PRAGMA optimize;")?;
+        Ok(())
+    }
+
     pub fn local_node_id(&self, name: &str) -> Result<Vec<u8>> {
         let connection = self.connection()?;
         let existing = connection
@@ -316,11 +603,31 @@ impl Database {
                      WHERE replica.hash = candidate.hash
                  )
              )
-             SELECT path, size, mime_type, modified_at, scanned_folder_id, hash, replica_count
-             FROM media_representatives
-             ORDER BY lower(path)",
+             SELECT media.path, media.size, media.mime_type, media.modified_at,
+                    media.scanned_folder_id, media.hash, media.replica_count,
+                    photo.hash IS NOT NULL, photo.captured_at, photo.camera_make,
+                    photo.camera_model, photo.lens_model, photo.width, photo.height,
+                    photo.orientation, photo.latitude, photo.longitude
+             FROM media_representatives media
+             LEFT JOIN photo_metadata photo ON photo.hash = media.hash
+             ORDER BY lower(media.path)",
         )?;
         let rows = statement.query_map([node_id], |row| {
+            let photo = if row.get::<_, bool>(7)? {
+                Some(PhotoMetadata {
+                    captured_at: row.get(8)?,
+                    camera_make: row.get(9)?,
+                    camera_model: row.get(10)?,
+                    lens_model: row.get(11)?,
+                    width: row.get(12)?,
+                    height: row.get(13)?,
+                    orientation: row.get(14)?,
+                    latitude: row.get(15)?,
+                    longitude: row.get(16)?,
+                })
+            } else {
+                None
+            };
             Ok(IndexedMediaFile {
                 path: PathBuf::from(row.get::<_, String>(0)?),
                 size: row.get::<_, i64>(1)? as u64,
@@ -329,6 +636,8 @@ impl Database {
                 scanned_folder_id: row.get::<_, i64>(4)? as u32,
                 hash: row.get(5)?,
                 replica_count: row.get::<_, i64>(6)? as usize,
+                photo,
+                audio: None,
             })
         })?;
         rows.collect::<std::result::Result<Vec<_>, _>>()
@@ -360,11 +669,34 @@ impl Database {
                      WHERE replica.hash = candidate.hash
                  )
              )
-             SELECT path, size, mime_type, modified_at, scanned_folder_id, hash, replica_count
-             FROM audio_representatives
-             ORDER BY lower(path)",
+             SELECT audio.path, audio.size, audio.mime_type, audio.modified_at,
+                    audio.scanned_folder_id, audio.hash, audio.replica_count,
+                    tags.hash IS NOT NULL, tags.title, tags.artist, tags.album,
+                    tags.album_artist, tags.track_number, tags.track_total, tags.disc_number,
+                    tags.disc_total, tags.year, tags.genre, tags.duration_ms, tags.cover_hash
+             FROM audio_representatives audio
+             LEFT JOIN audio_metadata tags ON tags.hash = audio.hash
+             ORDER BY lower(audio.path)",
         )?;
         let rows = statement.query_map([node_id], |row| {
+            let audio = if row.get::<_, bool>(7)? {
+                Some(AudioMetadata {
+                    title: row.get(8)?,
+                    artist: row.get(9)?,
+                    album: row.get(10)?,
+                    album_artist: row.get(11)?,
+                    track_number: row.get(12)?,
+                    track_total: row.get(13)?,
+                    disc_number: row.get(14)?,
+                    disc_total: row.get(15)?,
+                    year: row.get(16)?,
+                    genre: row.get(17)?,
+                    duration_ms: row.get(18)?,
+                    cover_hash: row.get(19)?,
+                })
+            } else {
+                None
+            };
             Ok(IndexedMediaFile {
                 path: PathBuf::from(row.get::<_, String>(0)?),
                 size: row.get::<_, i64>(1)? as u64,
@@ -373,6 +705,8 @@ impl Database {
                 scanned_folder_id: row.get::<_, i64>(4)? as u32,
                 hash: row.get(5)?,
                 replica_count: row.get::<_, i64>(6)? as usize,
+                photo: None,
+                audio,
             })
         })?;
         rows.collect::<std::result::Result<Vec<_>, _>>()
@@ -386,7 +720,8 @@ impl Database {
                     location.hash,
                     (SELECT MIN(membership.scanned_folder_id) FROM scanned_folder_locations membership
                      WHERE membership.node_id = location.node_id AND membership.path = location.path),
-                    (SELECT COUNT(*) FROM file_locations replica WHERE replica.hash = location.hash)
+                    (SELECT COUNT(*) FROM file_locations replica WHERE replica.hash = location.hash),
+                    location.extension_mime_type, location.sniffed_mime_type
              FROM file_locations location
              WHERE location.node_id = ?1
              ORDER BY lower(location.path)",
@@ -400,6 +735,192 @@ impl Database {
                 hash: row.get(4)?,
                 scanned_folder_id: row.get::<_, Option<i64>>(5)?.map(|id| id as u32),
                 replica_count: row.get::<_, i64>(6)? as usize,
+                extension_mime_type: row.get(7)?,
+                sniffed_mime_type: row.get(8)?,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Ranks files on this node whose indexed contents match `query`. Every
+    /// location of a matching hash is returned, best match first.
+    pub fn search_contents(
+        &self,
+        node_id: &[u8],
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<ContentSearchHit>> {
+        let Some(query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT location.path, location.size, location.mime_type, location.modified_at,
+                    location.hash,
+                    (SELECT MIN(membership.scanned_folder_id) FROM scanned_folder_locations membership
+                     WHERE membership.node_id = location.node_id AND membership.path = location.path),
+                    (SELECT COUNT(*) FROM file_locations replica WHERE replica.hash = location.hash),
+                    snippet(content_search, 0, '\u{ab}', '\u{bb}', '\u{2026}', 12),
+                    bm25(content_search)
+             FROM content_search
+             JOIN content_documents document ON document.id = content_search.rowid
+             JOIN file_locations location ON location.hash = document.hash
+             WHERE content_search MATCH ?1 AND location.node_id = ?2
+             ORDER BY bm25(content_search), lower(location.path)
+             LIMIT ?3",
+        )?;
+        let rows = statement.query_map(
+            params![query, node_id, limit.min(i64::MAX as usize) as i64],
+            |row| {
+                Ok(ContentSearchHit {
+                    path: PathBuf::from(row.get::<_, String>(0)?),
+                    size: row.get::<_, i64>(1)? as u64,
+                    mime_type: row.get(2)?,
+                    modified_at: row.get(3)?,
+                    hash: row.get(4)?,
+                    scanned_folder_id: row.get::<_, Option<i64>>(5)?.map(|id| id as u32),
+                    replica_count: row.get::<_, i64>(6)? as usize,
+                    snippet: row.get(7)?,
+                    rank: row.get(8)?,
+                })
+            },
+        )?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Finds locations on every known node whose path matches `query`.
+    /// Fragments of three or more characters use the trigram index; shorter
+    /// ones are checked directly against the candidate paths.
+    pub fn search_files(&self, query: &FileQuery, limit: usize) -> Result<Vec<FileSearchHit>> {
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conditions = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        let (indexed, short): (Vec<&String>, Vec<&String>) = query
+            .terms
+            .iter()
+            .partition(|term| term.chars().count() >= 3);
+        if !indexed.is_empty() {
+            conditions.push("path_search MATCH ?".to_owned());
+            values.push(
+                indexed
+                    .iter()
+                    .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .into(),
+            );
+        }
+        for term in short {
+            conditions.push("instr(lower(location.path), ?) > 0".to_owned());
+            values.push(term.clone().into());
+        }
+        let any = |conditions: Vec<&str>| format!("({})", conditions.join(" OR "));
+        if !query.extensions.is_empty() {
+            conditions.push(any(vec![
+                "substr(lower(location.path), ?) = ?";
+                query.extensions.len()
+            ]));
+            for extension in &query.extensions {
+                values.push((-(extension.chars().count() as i64)).into());
+                values.push(extension.clone().into());
+            }
+        }
+        if !query.mime_types.is_empty() {
+            let mut any_type = Vec::new();
+            for mime_type in &query.mime_types {
+                if mime_type.ends_with('/') {
+                    any_type.push("substr(lower(location.mime_type), 1, ?) = ?");
+                    values.push((mime_type.chars().count() as i64).into());
+                } else {
+                    any_type.push("lower(location.mime_type) = ?");
+                }
+                values.push(mime_type.clone().into());
+            }
+            conditions.push(any(any_type));
+        }
+        if let Some(size) = query.min_size {
+            conditions.push("location.size >= ?".to_owned());
+            values.push((size.min(i64::MAX as u64) as i64).into());
+        }
+        if let Some(size) = query.max_size {
+            conditions.push("location.size <= ?".to_owned());
+            values.push((size.min(i64::MAX as u64) as i64).into());
+        }
+        if let Some(from) = query.modified_from {
+            conditions.push("location.modified_at >= ?".to_owned());
+            values.push(from.into());
+        }
+        if let Some(until) = query.modified_until {
+            conditions.push("location.modified_at < ?".to_owned());
+            values.push(until.into());
+        }
+        if !query.folders.is_empty() {
+            conditions.push(any(query
+                .folders
+                .iter()
+                .map(|folder| match folder {
+                    FolderFilter::ScannedFolder(_) => {
+                        "EXISTS (SELECT 1 FROM scanned_folder_locations membership
+                         WHERE membership.node_id = location.node_id
+                           AND membership.path = location.path
+                           AND membership.scanned_folder_id = ?)"
+                    }
+                    // The directory is the path with its final component trimmed.
+                    FolderFilter::Directory(_) => {
+                        "instr(lower(rtrim(location.path, \
+                         replace(replace(location.path, '/', ''), '\\', ''))), ?) > 0"
+                    }
+                })
+                .collect()));
+            for folder in &query.folders {
+                values.push(match folder {
+                    FolderFilter::ScannedFolder(id) => i64::from(*id).into(),
+                    FolderFilter::Directory(fragment) => fragment.clone().into(),
+                });
+            }
+        }
+        values.push((limit.min(i64::MAX as usize) as i64).into());
+        let (join, order) = if indexed.is_empty() {
+            ("", "lower(location.path)")
+        } else {
+            (
+                "JOIN path_search_rows search_row
+                   ON search_row.node_id = location.node_id AND search_row.path = location.path
+                 JOIN path_search ON path_search.rowid = search_row.id",
+                "bm25(path_search), lower(location.path)",
+            )
+        };
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(&format!(
+            "SELECT location.node_id, node.name, node.is_local, location.path, location.size,
+                    location.mime_type, location.modified_at, location.hash,
+                    (SELECT MIN(membership.scanned_folder_id) FROM scanned_folder_locations membership
+                     WHERE membership.node_id = location.node_id AND membership.path = location.path),
+                    (SELECT COUNT(*) FROM file_locations replica WHERE replica.hash = location.hash)
+             FROM file_locations location
+             JOIN nodes node ON node.node_id = location.node_id
+             {join}
+             WHERE {}
+             ORDER BY node.is_local DESC, {order}
+             LIMIT ?",
+            conditions.join(" AND ")
+        ))?;
+        let rows = statement.query_map(rusqlite::params_from_iter(values), |row| {
+            Ok(FileSearchHit {
+                node_id: row.get(0)?,
+                node_name: row.get(1)?,
+                is_local: row.get(2)?,
+                path: PathBuf::from(row.get::<_, String>(3)?),
+                size: row.get::<_, i64>(4)? as u64,
+                mime_type: row.get(5)?,
+                modified_at: row.get(6)?,
+                hash: row.get(7)?,
+                scanned_folder_id: row.get::<_, Option<i64>>(8)?.map(|id| id as u32),
+                replica_count: row.get::<_, i64>(9)? as usize,
             })
         })?;
         rows.collect::<std::result::Result<Vec<_>, _>>()
@@ -420,32 +941,82 @@ impl Database {
             .map_err(Into::into)
     }
 
-    pub fn scanned_folder_location_metadata(
+    /// Size and type of the content with `hash`, if it is indexed anywhere.
+    pub fn file_entry_metadata(&self, hash: &[u8]) -> Result<Option<(u64, Option<String>)>> {
+        let connection = self.connection()?;
+        connection
+            .query_row(
+                "SELECT size, mime_type FROM file_entries WHERE hash = ?1",
+                [hash],
+                |row| Ok((row.get::<_, i64>(0)? as u64, row.get(1)?)),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// Paths holding `hash` on other devices, with the device name.
+    pub fn file_replica_nodes(&self, hash: &[u8]) -> Result<Vec<(String, PathBuf)>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT node.name, location.path
+             FROM file_locations location
+             JOIN nodes node ON node.node_id = location.node_id
+             WHERE node.is_local = 0 AND location.hash = ?1
+             ORDER BY lower(node.name), lower(location.path)",
+        )?;
+        let rows = statement.query_map([hash], |row| {
+            Ok((row.get(0)?, PathBuf::from(row.get::<_, String>(1)?)))
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// The indexed state of the files directly inside `directory`, used to
+    /// skip re-hashing unchanged files. The path range keeps the lookup on the
+    /// `file_locations` key instead of walking the whole folder.
+    pub fn directory_location_metadata(
         &self,
         node_id: &[u8],
         folder_id: u32,
+        directory: &Path,
     ) -> Result<HashMap<PathBuf, IndexedLocationMetadata>> {
+        let separator = std::path::MAIN_SEPARATOR;
+        let mut prefix = directory.to_string_lossy().into_owned();
+        if !prefix.ends_with(separator) {
+            prefix.push(separator);
+        }
+        // Every path below `directory` sorts between "dir/" and "dir0".
+        let mut end = prefix[..prefix.len() - separator.len_utf8()].to_owned();
+        end.push(char::from(separator as u8 + 1));
         let connection = self.connection()?;
         let mut statement = connection.prepare(
-            "SELECT location.path, location.hash, location.size, location.created_at, location.modified_at
+            "SELECT location.path, location.hash, location.size, location.created_at, location.modified_at,
+                    location.sniffed_mime_type
              FROM file_locations location
-             JOIN scanned_folder_locations membership
-               ON membership.node_id = location.node_id AND membership.path = location.path
-             WHERE membership.scanned_folder_id = ?1 AND membership.node_id = ?2
-               AND membership.indexer = 'media'",
+             WHERE location.node_id = ?2 AND location.path >= ?3 AND location.path < ?4
+               AND instr(substr(location.path, length(?3) + 1), ?5) = 0
+               AND EXISTS (
+                   SELECT 1 FROM scanned_folder_locations membership
+                   WHERE membership.scanned_folder_id = ?1 AND membership.node_id = location.node_id
+                     AND membership.path = location.path
+               )",
         )?;
         let mut locations = HashMap::new();
-        let rows = statement.query_map(params![folder_id, node_id], |row| {
-            Ok((
-                PathBuf::from(row.get::<_, String>(0)?),
-                IndexedLocationMetadata {
-                    hash: row.get(1)?,
-                    size: row.get::<_, i64>(2)? as u64,
-                    created_at: row.get(3)?,
-                    modified_at: row.get(4)?,
-                },
-            ))
-        })?;
+        let rows = statement.query_map(
+            params![folder_id, node_id, prefix, end, separator.to_string()],
+            |row| {
+                Ok((
+                    PathBuf::from(row.get::<_, String>(0)?),
+                    IndexedLocationMetadata {
+                        hash: row.get(1)?,
+                        size: row.get::<_, i64>(2)? as u64,
+                        created_at: row.get(3)?,
+                        modified_at: row.get(4)?,
+                        sniffed_mime_type: row.get(5)?,
+                    },
+                ))
+            },
+        )?;
         for row in rows {
             let (path, metadata) = row?;
             locations.insert(path, metadata);
@@ -453,6 +1024,32 @@ impl Database {
         Ok(locations)
     }
 
+    /// The indexed state of one path, used to skip re-hashing unchanged files.
+    pub fn location_metadata(
+        &self,
+        node_id: &[u8],
+        path: &Path,
+    ) -> Result<Option<IndexedLocationMetadata>> {
+        let connection = self.connection()?;
+        connection
+            .query_row(
+                "SELECT hash, size, created_at, modified_at, sniffed_mime_type
+                 FROM file_locations WHERE node_id = ?1 AND path = ?2",
+                params![node_id, path.to_string_lossy()],
+                |row| {
+                    Ok(IndexedLocationMetadata {
+                        hash: row.get(0)?,
+                        size: row.get::<_, i64>(1)? as u64,
+                        created_at: row.get(2)?,
+                        modified_at: row.get(3)?,
+                        sniffed_mime_type: row.get(4)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
     pub fn media_thumbnail_source(
         &self,
         node_id: &[u8],
@@ -479,49 +1076,694 @@ impl Database {
             .map_err(Into::into)
     }
 
-    pub fn virtual_directories(&self) -> Result<Vec<VirtualDirectory>> {
+    /// Hashed files in `folder_ids` not verified since `verified_before`,
+    /// never-verified and longest-unverified first.
+    pub fn scrub_candidates(
+        &self,
+        node_id: &[u8],
+        folder_ids: &[u32],
+        verified_before: i64,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<ScrubCandidate>> {
         let connection = self.connection()?;
-        let mut statement = connection
-            .prepare("SELECT id, name FROM virtual_directories ORDER BY lower(name), id")?;
-        let rows = statement.query_map([], |row| {
-            Ok(VirtualDirectory {
-                id: row.get::<_, i64>(0)? as u32,
-                name: row.get(1)?,
-            })
-        })?;
+        let mut statement = connection.prepare(
+            "SELECT MIN(membership.scanned_folder_id), location.path, location.hash,
+                    location.size, location.created_at, location.modified_at
+             FROM file_locations location
+             JOIN scanned_folder_locations membership
+               ON membership.node_id = location.node_id AND membership.path = location.path
+             WHERE location.node_id = ?1 AND location.hash IS NOT NULL
+               AND (location.verified_at IS NULL OR location.verified_at < ?2)
+               AND membership.scanned_folder_id IN (SELECT value FROM json_each(?3))
+             GROUP BY location.path
+             ORDER BY location.verified_at IS NOT NULL, location.verified_at, location.path
+             LIMIT ?4 OFFSET ?5",
+        )?;
+        let rows = statement.query_map(
+            params![
+                node_id,
+                verified_before,
+                serde_json::to_string(folder_ids)?,
+                limit as i64,
+                offset as i64
+            ],
+            |row| {
+                Ok(ScrubCandidate {
+                    scanned_folder_id: row.get(0)?,
+                    path: PathBuf::from(row.get::<_, String>(1)?),
+                    hash: row.get(2)?,
+                    size: row.get::<_, i64>(3)? as u64,
+                    created_at: row.get(4)?,
+                    modified_at: row.get(5)?,
+                })
+            },
+        )?;
         rows.collect::<std::result::Result<Vec<_>, _>>()
             .map_err(Into::into)
     }
 
-    pub fn create_virtual_directory(&self, name: &str) -> Result<VirtualDirectory> {
-        let name = name.trim();
-        if name.is_empty() {
-            anyhow::bail!("virtual directory name cannot be empty");
+    /// Stamps every result as verified at `verified_at`, raising an alert for
+    /// the ones that failed and clearing it for the ones that passed.
+    pub fn record_scrub_results(
+        &self,
+        node_id: &[u8],
+        results: &[ScrubResult],
+        verified_at: i64,
+    ) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        for result in results {
+            let candidate = &result.candidate;
+            let path = candidate.path.to_string_lossy();
+            transaction
+                .prepare_cached(
+                    "UPDATE file_locations SET verified_at = ?3
+                     WHERE node_id = ?1 AND path = ?2",
+                )?
+                .execute(params![node_id, path, verified_at])?;
+            let (actual_hash, error) = match &result.outcome {
+                ScrubOutcome::Healthy => {
+                    transaction
+                        .prepare_cached(
+                            "DELETE FROM scrub_alerts WHERE node_id = ?1 AND path = ?2",
+                        )?
+                        .execute(params![node_id, path])?;
+                    continue;
+                }
+                ScrubOutcome::Changed { actual_hash } => (Some(actual_hash), None),
+                ScrubOutcome::Unreadable { error } => (None, Some(error)),
+            };
+            transaction
+                .prepare_cached(
+                    "INSERT INTO scrub_alerts
+                        (node_id, path, expected_hash, actual_hash, error, size, modified_at, detected_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                     ON CONFLICT(node_id, path) DO UPDATE SET
+                        expected_hash = excluded.expected_hash, actual_hash = excluded.actual_hash,
+                        error = excluded.error, size = excluded.size,
+                        modified_at = excluded.modified_at",
+                )?
+                .execute(params![
+                    node_id,
+                    path,
+                    candidate.hash,
+                    actual_hash,
+                    error,
+                    candidate.size as i64,
+                    candidate.modified_at,
+                    verified_at
+                ])?;
         }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn scrub_alerts(&self, node_id: &[u8]) -> Result<Vec<ScrubAlert>> {
         let connection = self.connection()?;
-        connection.execute(
-            "INSERT INTO virtual_directories (name, created_at) VALUES (?1, ?2)",
-            params![name, now_millis()],
+        let mut statement = connection.prepare(
+            "SELECT path, expected_hash, actual_hash, error, size, detected_at
+             FROM scrub_alerts WHERE node_id = ?1
+             ORDER BY detected_at DESC, lower(path)",
         )?;
-        Ok(VirtualDirectory {
-            id: connection.last_insert_rowid() as u32,
-            name: name.to_owned(),
-        })
+        let mut alerts = statement
+            .query_map([node_id], |row| {
+                Ok(ScrubAlert {
+                    path: PathBuf::from(row.get::<_, String>(0)?),
+                    expected_hash: row.get(1)?,
+                    actual_hash: row.get(2)?,
+                    error: row.get(3)?,
+                    size: row.get::<_, i64>(4)? as u64,
+                    detected_at: row.get(5)?,
+                    healthy_replicas: Vec::new(),
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let mut replicas = connection.prepare(
+            "SELECT path FROM file_locations location
+             WHERE node_id = ?1 AND hash = ?2
+               AND NOT EXISTS (
+                   SELECT 1 FROM scrub_alerts alert
+                   WHERE alert.node_id = location.node_id AND alert.path = location.path
+               )
+             ORDER BY lower(path)",
+        )?;
+        for alert in &mut alerts {
+            alert.healthy_replicas = replicas
+                .query_map(params![node_id, alert.expected_hash], |row| {
+                    row.get::<_, String>(0).map(PathBuf::from)
+                })?
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+        }
+        Ok(alerts)
     }
 
-    pub fn file_hash_for_location(&self, node_id: &[u8], path: &Path) -> Result<Option<Vec<u8>>> {
+    pub fn scrub_summary(&self, node_id: &[u8], verified_since: i64) -> Result<ScrubSummary> {
         let connection = self.connection()?;
         connection
             .query_row(
-                "SELECT hash FROM file_locations WHERE node_id = ?1 AND path = ?2 AND hash IS NOT NULL",
-                params![node_id, path.to_string_lossy()],
-                |row| row.get(0),
+                "SELECT COUNT(*), COUNT(CASE WHEN verified_at >= ?2 THEN 1 END)
+                 FROM file_locations WHERE node_id = ?1 AND hash IS NOT NULL",
+                params![node_id, verified_since],
+                |row| {
+                    Ok(ScrubSummary {
+                        hashed_files: row.get::<_, i64>(0)? as usize,
+                        verified_files: row.get::<_, i64>(1)? as usize,
+                    })
+                },
             )
-            .optional()
             .map_err(Into::into)
     }
 
-    pub fn add_file_to_virtual_directory(&self, directory_id: u32, hash: &[u8]) -> Result<()> {
+    /// Groups the non-empty files in enabled Scanned folders that share a
+    /// hash with at least one other file. Every copy counts as a distinct
+    /// file; hardlinks are only visible on disk.
+    pub fn duplicate_groups(&self, node_id: &[u8]) -> Result<Vec<DuplicateGroup>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "WITH locations AS (
+                 SELECT location.path, location.hash, location.size, location.modified_at,
+                        MIN(membership.scanned_folder_id) AS scanned_folder_id
+                 FROM file_locations location
+                 JOIN scanned_folder_locations membership
+                   ON membership.node_id = location.node_id AND membership.path = location.path
+                 JOIN ScannedFolder folder ON folder.id = membership.scanned_folder_id
+                 WHERE location.node_id = ?1 AND folder.enabled = 1
+                   AND location.hash IS NOT NULL AND location.size > 0
+                 GROUP BY location.path
+             ), duplicated AS (
+                 SELECT hash FROM locations GROUP BY hash HAVING COUNT(*) > 1
+             )
+             SELECT locations.hash, locations.size, locations.path,
+                    locations.scanned_folder_id, locations.modified_at
+             FROM locations JOIN duplicated ON duplicated.hash = locations.hash
+             ORDER BY locations.hash, lower(locations.path)",
+        )?;
+        let rows = statement.query_map([node_id], |row| {
+            Ok((
+                row.get::<_, Vec<u8>>(0)?,
+                row.get::<_, i64>(1)? as u64,
+                DuplicateCopy {
+                    hash: row.get(0)?,
+                    path: PathBuf::from(row.get::<_, String>(2)?),
+                    scanned_folder_id: row.get::<_, i64>(3)? as u32,
+                    modified_at: row.get(4)?,
+                },
+            ))
+        })?;
+        let mut groups = Vec::<DuplicateGroup>::new();
+        for row in rows {
+            let (hash, size, copy) = row?;
+            match groups.last_mut() {
+                Some(group) if group.hash == hash => {
+                    group.copies.push(copy);
+                    group.distinct_files += 1;
+                }
+                _ => groups.push(DuplicateGroup {
+                    hash,
+                    size,
+                    copies: vec![copy],
+                    distinct_files: 1,
+                }),
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Groups images in enabled Scanned folders whose perceptual hashes are
+    /// at most `max_distance` bits apart, transitively. Each group lists the
+    /// largest image first, and groups that free the most space by keeping
+    /// only that image come first.
+    pub fn similar_images(
+        &self,
+        node_id: &[u8],
+        max_distance: u32,
+    ) -> Result<Vec<Vec<SimilarImage>>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT location.hash, perceptual.dhash, location.size, photo.width, photo.height,
+                    location.path, MIN(membership.scanned_folder_id), location.modified_at
+             FROM file_locations location
+             JOIN perceptual_hashes perceptual ON perceptual.hash = location.hash
+             JOIN scanned_folder_locations membership
+               ON membership.node_id = location.node_id AND membership.path = location.path
+             JOIN ScannedFolder folder ON folder.id = membership.scanned_folder_id
+             LEFT JOIN photo_metadata photo ON photo.hash = location.hash
+             WHERE location.node_id = ?1 AND folder.enabled = 1
+             GROUP BY location.path
+             ORDER BY location.hash, lower(location.path)",
+        )?;
+        let rows = statement.query_map([node_id], |row| {
+            Ok((
+                row.get::<_, i64>(1)? as u64,
+                SimilarImage {
+                    hash: row.get(0)?,
+                    size: row.get::<_, i64>(2)? as u64,
+                    width: row.get(3)?,
+                    height: row.get(4)?,
+                    copies: vec![DuplicateCopy {
+                        hash: row.get(0)?,
+                        path: PathBuf::from(row.get::<_, String>(5)?),
+                        scanned_folder_id: row.get::<_, i64>(6)? as u32,
+                        modified_at: row.get(7)?,
+                    }],
+                },
+            ))
+        })?;
+        let mut dhashes = Vec::new();
+        let mut images = Vec::<SimilarImage>::new();
+        for row in rows {
+            let (dhash, image) = row?;
+            match images.last_mut() {
+                Some(last) if last.hash == image.hash => last.copies.extend(image.copies),
+                _ => {
+                    dhashes.push(dhash);
+                    images.push(image);
+                }
+            }
+        }
+        let mut groups = crate::indexer::similar_clusters(&dhashes, max_distance)
+            .into_iter()
+            .map(|members| {
+                let mut group = members
+                    .into_iter()
+                    .map(|index| images[index].clone())
+                    .collect::<Vec<_>>();
+                group.sort_by_key(|image| std::cmp::Reverse((image.pixels(), image.size)));
+                group
+            })
+            .collect::<Vec<_>>();
+        groups.sort_by_key(|group| {
+            std::cmp::Reverse(
+                group
+                    .iter()
+                    .skip(1)
+                    .map(|image| image.size * image.copies.len() as u64)
+                    .sum::<u64>(),
+            )
+        });
+        Ok(groups)
+    }
+
+    /// Changes to this node's index after `since`, oldest first.
+    pub fn local_index_changes(&self, since: i64, limit: usize) -> Result<Vec<IndexChange>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT change.sequence, change.path, location.path, location.hash, location.size,
+                    location.mime_type, location.modified_at
+             FROM index_changes change
+             LEFT JOIN file_locations location
+               ON location.path = change.path
+              AND location.node_id IN (SELECT node_id FROM nodes WHERE is_local = 1)
+             WHERE change.sequence > ?1
+             ORDER BY change.sequence
+             LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![since, limit as i64], |row| {
+            let location = match row.get::<_, Option<String>>(2)? {
+                Some(_) => Some(ChangedLocation {
+                    hash: row.get(3)?,
+                    size: row.get::<_, i64>(4)? as u64,
+                    mime_type: row.get(5)?,
+                    modified_at: row.get(6)?,
+                }),
+                None => None,
+            };
+            Ok(IndexChange {
+                sequence: row.get(0)?,
+                path: row.get(1)?,
+                location,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    pub fn peers(&self) -> Result<Vec<Peer>> {
+        self.query_peers("1", [])
+    }
+
+    /// Paired devices with a location holding `hash`.
+    pub fn peers_holding(&self, hash: &[u8]) -> Result<Vec<Peer>> {
+        self.query_peers(
+            "EXISTS (SELECT 1 FROM file_locations location
+                     WHERE location.node_id = peer.node_id AND location.hash = ?1)",
+            [hash],
+        )
+    }
+
+    fn query_peers(&self, condition: &str, values: impl rusqlite::Params) -> Result<Vec<Peer>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(&format!(
+            "SELECT peer.node_id, node.name, peer.url, peer.access_token, peer.last_sequence,
+                    peer.last_synced_at, peer.last_error,
+                    (SELECT COUNT(*) FROM file_locations location
+                     WHERE location.node_id = peer.node_id)
+             FROM peers peer
+             JOIN nodes node ON node.node_id = peer.node_id
+             WHERE {condition}
+             ORDER BY lower(node.name), peer.paired_at"
+        ))?;
+        let rows = statement.query_map(values, |row| {
+            Ok(Peer {
+                node_id: row.get(0)?,
+                name: row.get(1)?,
+                url: row.get(2)?,
+                access_token: row.get(3)?,
+                last_sequence: row.get(4)?,
+                last_synced_at: row.get(5)?,
+                last_error: row.get(6)?,
+                file_count: row.get::<_, i64>(7)? as usize,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Pairs with a device, or updates its address and token when it is
+    /// already paired. Replication starts from the beginning of a new
+    /// device's feed.
+    pub fn save_peer(
+        &self,
+        node_id: &[u8],
+        name: &str,
+        url: &str,
+        access_token: &str,
+    ) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        let is_local = transaction
+            .query_row(
+                "SELECT is_local FROM nodes WHERE node_id = ?1",
+                [node_id],
+                |row| row.get::<_, bool>(0),
+            )
+            .optional()?;
+        if is_local == Some(true) {
+            anyhow::bail!("that address is this PuppyDrive");
+        }
+        let now = now_millis();
+        transaction.execute(
+            "INSERT INTO nodes (node_id, name, is_local, created_at) VALUES (?1, ?2, 0, ?3)
+             ON CONFLICT(node_id) DO UPDATE SET name = excluded.name",
+            params![node_id, name, now],
+        )?;
+        transaction.execute(
+            "INSERT INTO peers (node_id, url, access_token, paired_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(node_id) DO UPDATE SET
+                url = excluded.url, access_token = excluded.access_token, last_error = NULL",
+            params![node_id, url, access_token, now],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Unpairs a device and forgets every location replicated from it.
+    pub fn remove_peer(&self, node_id: &[u8]) -> Result<bool> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM file_locations WHERE node_id = ?1", [node_id])?;
+        let affected = transaction.execute("DELETE FROM peers WHERE node_id = ?1", [node_id])?;
+        transaction.execute(
+            "DELETE FROM nodes WHERE node_id = ?1 AND is_local = 0",
+            [node_id],
+        )?;
+        transaction.commit()?;
+        Ok(affected > 0)
+    }
+
+    pub fn record_peer_error(&self, node_id: &[u8], error: &str) -> Result<()> {
+        let connection = self.connection()?;
+        connection.execute(
+            "UPDATE peers SET last_error = ?2 WHERE node_id = ?1",
+            params![node_id, error],
+        )?;
+        Ok(())
+    }
+
+    /// Stores a page of a peer's change feed under its node and advances
+    /// its position in the feed, in one transaction so an interrupted sync
+    /// resumes where it stopped.
+    pub fn apply_peer_changes(
+        &self,
+        node_id: &[u8],
+        name: &str,
+        changes: &[IndexChange],
+        next_sequence: i64,
+    ) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        let now = now_millis();
+        transaction.execute(
+            "UPDATE nodes SET name = ?2 WHERE node_id = ?1 AND is_local = 0",
+            params![node_id, name],
+        )?;
+        for change in changes {
+            let Some(location) = &change.location else {
+                transaction
+                    .prepare_cached("DELETE FROM file_locations WHERE node_id = ?1 AND path = ?2")?
+                    .execute(params![node_id, change.path])?;
+                continue;
+            };
+            if let Some(hash) = &location.hash {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO file_entries
+                            (hash, size, mime_type, first_indexed_at, last_indexed_at)
+                         VALUES (?1, ?2, ?3, ?4, ?4)
+                         ON CONFLICT(hash) DO NOTHING",
+                    )?
+                    .execute(params![hash, location.size as i64, location.mime_type, now])?;
+            }
+            transaction
+                .prepare_cached(
+                    "INSERT INTO file_locations
+                        (node_id, path, hash, size, mime_type, last_indexed_at, modified_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                     ON CONFLICT(node_id, path) DO UPDATE SET
+                        hash = excluded.hash, size = excluded.size,
+                        mime_type = excluded.mime_type, last_indexed_at = excluded.last_indexed_at,
+                        modified_at = excluded.modified_at",
+                )?
+                .execute(params![
+                    node_id,
+                    change.path,
+                    location.hash,
+                    location.size as i64,
+                    location.mime_type,
+                    now,
+                    location.modified_at
+                ])?;
+        }
+        let affected = transaction.execute(
+            "UPDATE peers SET last_sequence = ?2, last_synced_at = ?3, last_error = NULL
+             WHERE node_id = ?1",
+            params![node_id, next_sequence, now],
+        )?;
+        if affected == 0 {
+            anyhow::bail!("device is no longer paired");
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Indexed locations of a remote source's node directly inside
+    /// `directory`, by path. Remote paths always use `/`.
+    pub fn source_directory_locations(
+        &self,
+        node_id: &[u8],
+        directory: &Path,
+    ) -> Result<HashMap<String, ChangedLocation>> {
+        let mut prefix = directory.to_string_lossy().into_owned();
+        if !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        // Every path below `directory` sorts between "dir/" and "dir0".
+        let end = format!("{}0", &prefix[..prefix.len() - 1]);
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(
+            "SELECT path, hash, size, mime_type, modified_at FROM file_locations
+             WHERE node_id = ?1 AND path >= ?2 AND path < ?3
+               AND instr(substr(path, length(?2) + 1), '/') = 0",
+        )?;
+        let rows = statement.query_map(params![node_id, prefix, end], |row| {
+            Ok((
+                row.get(0)?,
+                ChangedLocation {
+                    hash: row.get(1)?,
+                    size: row.get::<_, i64>(2)? as u64,
+                    mime_type: row.get(3)?,
+                    modified_at: row.get(4)?,
+                },
+            ))
+        })?;
+        rows.collect::<std::result::Result<HashMap<_, _>, _>>()
+            .map_err(Into::into)
+    }
+
+    /// Records locations of a remote source under its node as indexed at
+    /// `now`, creating the node on first use.
+    pub fn save_source_locations(
+        &self,
+        node_id: &[u8],
+        name: &str,
+        locations: &[(String, ChangedLocation)],
+        now: i64,
+    ) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        let is_local = transaction
+            .query_row(
+                "SELECT is_local FROM nodes WHERE node_id = ?1",
+                [node_id],
+                |row| row.get::<_, bool>(0),
+            )
+            .optional()?;
+        if is_local == Some(true) {
+            anyhow::bail!("source node clashes with this PuppyDrive");
+        }
+        transaction.execute(
+            "INSERT INTO nodes (node_id, name, is_local, created_at) VALUES (?1, ?2, 0, ?3)
+             ON CONFLICT(node_id) DO UPDATE SET name = excluded.name",
+            params![node_id, name, now],
+        )?;
+        for (path, location) in locations {
+            if let Some(hash) = &location.hash {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO file_entries
+                            (hash, size, mime_type, first_indexed_at, last_indexed_at)
+                         VALUES (?1, ?2, ?3, ?4, ?4)
+                         ON CONFLICT(hash) DO UPDATE SET last_indexed_at = excluded.last_indexed_at",
+                    )?
+                    .execute(params![hash, location.size as i64, location.mime_type, now])?;
+            }
+            transaction
+                .prepare_cached(
+                    "INSERT INTO file_locations
+                        (node_id, path, hash, size, mime_type, last_indexed_at, modified_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                     ON CONFLICT(node_id, path) DO UPDATE SET
+                        hash = excluded.hash, size = excluded.size,
+                        mime_type = excluded.mime_type, last_indexed_at = excluded.last_indexed_at,
+                        modified_at = excluded.modified_at",
+                )?
+                .execute(params![
+                    node_id,
+                    path,
+                    location.hash,
+                    location.size as i64,
+                    location.mime_type,
+                    now,
+                    location.modified_at
+                ])?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// A time later than every location of a remote source was recorded at,
+    /// for an index to mark what it sees with.
+    pub fn next_source_index_time(&self, node_id: &[u8]) -> Result<i64> {
+        let connection = self.connection()?;
+        let latest = connection.query_row(
+            "SELECT MAX(last_indexed_at) FROM file_locations WHERE node_id = ?1",
+            [node_id],
+            |row| row.get::<_, Option<i64>>(0),
+        )?;
+        Ok(latest.map_or(now_millis(), |latest| now_millis().max(latest + 1)))
+    }
+
+    /// Drops the locations of a remote source not recorded since
+    /// `indexed_before`, once an index has read its whole tree. Returns how
+    /// many were dropped.
+    pub fn remove_source_locations_before(
+        &self,
+        node_id: &[u8],
+        indexed_before: i64,
+    ) -> Result<usize> {
+        let connection = self.connection()?;
+        Ok(connection.execute(
+            "DELETE FROM file_locations WHERE node_id = ?1 AND last_indexed_at < ?2",
+            params![node_id, indexed_before],
+        )?)
+    }
+
+    /// Forgets every location indexed from a remote source.
+    pub fn remove_source_node(&self, node_id: &[u8]) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        transaction.execute("DELETE FROM file_locations WHERE node_id = ?1", [node_id])?;
+        transaction.execute(
+            "DELETE FROM nodes WHERE node_id = ?1 AND is_local = 0",
+            [node_id],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn virtual_directories(&self) -> Result<Vec<VirtualDirectory>> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT id, name FROM virtual_directories ORDER BY lower(name), id")?;
+        let rows = statement.query_map([], |row| {
+            Ok(VirtualDirectory {
+                id: row.get::<_, i64>(0)? as u32,
+                name: row.get(1)?,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
+    pub fn create_virtual_directory(&self, name: &str) -> Result<VirtualDirectory> {
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("virtual directory name cannot be empty");
+        }
+        let connection = self.connection()?;
+        connection.execute(
+            "INSERT INTO virtual_directories (name, created_at) VALUES (?1, ?2)",
+            params![name, now_millis()],
+        )?;
+        Ok(VirtualDirectory {
+            id: connection.last_insert_rowid() as u32,
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn rename_virtual_directory(&self, id: u32, name: &str) -> Result<bool> {
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("virtual directory name cannot be empty");
+        }
+        let connection = self.connection()?;
+        let affected = connection.execute(
+            "UPDATE virtual_directories SET name = ?2 WHERE id = ?1",
+            params![id, name],
+        )?;
+        Ok(affected > 0)
+    }
+
+    pub fn delete_virtual_directory(&self, id: u32) -> Result<bool> {
+        let connection = self.connection()?;
+        let affected = connection.execute("DELETE FROM virtual_directories WHERE id = ?1", [id])?;
+        Ok(affected > 0)
+    }
+
+    pub fn file_hash_for_location(&self, node_id: &[u8], path: &Path) -> Result<Option<Vec<u8>>> {
+        let connection = self.connection()?;
+        connection
+            .query_row(
+                "SELECT hash FROM file_locations WHERE node_id = ?1 AND path = ?2 AND hash IS NOT NULL",
+                params![node_id, path.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    pub fn add_file_to_virtual_directory(&self, directory_id: u32, hash: &[u8]) -> Result<()> {
         let connection = self.connection()?;
         connection.execute(
             "INSERT OR IGNORE INTO virtual_directory_entries
@@ -531,6 +1773,20 @@ impl Database {
         Ok(())
     }
 
+    pub fn remove_file_from_virtual_directory(
+        &self,
+        directory_id: u32,
+        hash: &[u8],
+    ) -> Result<bool> {
+        let connection = self.connection()?;
+        let affected = connection.execute(
+            "DELETE FROM virtual_directory_entries
+             WHERE virtual_directory_id = ?1 AND file_hash = ?2",
+            params![directory_id, hash],
+        )?;
+        Ok(affected > 0)
+    }
+
     pub fn virtual_directory_entries(&self, node_id: &[u8]) -> Result<Vec<VirtualDirectoryEntry>> {
         let connection = self.connection()?;
         let mut statement = connection.prepare(
@@ -570,80 +1826,472 @@ impl Database {
             .map_err(Into::into)
     }
 
-    pub fn sync_media_scan(
+    /// Records one scan of `folder` in a single transaction: the test-only
+    /// form of [`Database::write_scan_batch`] followed by
+    /// [`Database::finish_scan`].
+    #[cfg(test)]
+    pub fn sync_scan(
         &self,
         node_id: &[u8],
-        folder_id: u32,
+        folder: &ManagedFolder,
         observations: &[MediaIndexObservation],
+        indexers: &[&dyn Indexer],
         complete: bool,
+    ) -> Result<()> {
+        let scan_id = new_scan_id();
+        self.write_scan_batch(node_id, folder, &scan_id, observations, indexers)?;
+        self.finish_scan(folder.id(), &scan_id, complete)
+    }
+
+    /// Commits one batch of a scan. Every location is stamped with `scan_id`
+    /// so [`Database::finish_scan`] can tell what the scan saw.
+    pub fn write_scan_batch(
+        &self,
+        node_id: &[u8],
+        folder: &ManagedFolder,
+        scan_id: &[u8],
+        observations: &[MediaIndexObservation],
+        indexers: &[&dyn Indexer],
     ) -> Result<()> {
         let mut connection = self.connection()?;
+        let extracted = extract_index_rows(&connection, node_id, folder, observations, indexers)?;
         let transaction = connection.transaction()?;
-        let scan_id = uuid::Uuid::new_v4().as_bytes().to_vec();
         let indexed_at = now_millis();
-        for observation in observations {
-            let path = observation.path.to_string_lossy();
-            if let Some(hash) = &observation.hash {
-                transaction.execute(
-                    "INSERT INTO file_entries (hash, size, mime_type, first_indexed_at, last_indexed_at)
-                     VALUES (?1, ?2, ?3, ?4, ?4)
-                     ON CONFLICT(hash) DO UPDATE SET last_indexed_at = excluded.last_indexed_at",
-                    params![hash, observation.size as i64, observation.mime_type, indexed_at],
-                )?;
-            }
-            transaction.execute(
-                "INSERT INTO file_locations
-                    (node_id, path, hash, size, mime_type, last_indexed_at, created_at, modified_at, accessed_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
-                 ON CONFLICT(node_id, path) DO UPDATE SET
-                    hash = excluded.hash, size = excluded.size, mime_type = excluded.mime_type,
-                    last_indexed_at = excluded.last_indexed_at, created_at = excluded.created_at,
-                    modified_at = excluded.modified_at, accessed_at = excluded.accessed_at",
-                params![
-                    node_id,
-                    path,
-                    observation.hash,
-                    observation.size as i64,
-                    observation.mime_type,
-                    indexed_at,
-                    observation.created_at,
-                    observation.modified_at,
-                    observation.accessed_at,
-                ],
+        for (observation, accepted) in observations.iter().zip(extracted) {
+            upsert_observation(
+                &transaction,
+                node_id,
+                folder.id(),
+                observation,
+                accepted,
+                scan_id,
+                indexed_at,
             )?;
-            transaction.execute(
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Ends a scan of one Scanned folder. A complete scan drops every
+    /// location it did not see; an incomplete one leaves them alone.
+    pub fn finish_scan(&self, folder_id: u32, scan_id: &[u8], complete: bool) -> Result<()> {
+        if !complete {
+            return Ok(());
+        }
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM scanned_folder_locations
+             WHERE scanned_folder_id = ?1 AND last_seen_scan != ?2",
+            params![folder_id, scan_id],
+        )?;
+        transaction.execute(
+            "DELETE FROM file_locations WHERE NOT EXISTS (
+                SELECT 1 FROM scanned_folder_locations membership
+                WHERE membership.node_id = file_locations.node_id
+                  AND membership.path = file_locations.path
+            ) AND node_id IN (SELECT node_id FROM nodes WHERE is_local = 1)",
+            [],
+        )?;
+        for indexer in registered_indexers() {
+            indexer.prune(&transaction)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Applies watcher-reported changes to one Scanned folder without a full
+    /// traversal. Observations are written like a scan; removed paths drop
+    /// out of the folder together with everything indexed below them.
+    pub fn apply_path_changes(
+        &self,
+        node_id: &[u8],
+        folder: &ManagedFolder,
+        observations: &[MediaIndexObservation],
+        removed: &[PathBuf],
+        indexers: &[&dyn Indexer],
+    ) -> Result<()> {
+        let folder_id = folder.id();
+        let mut connection = self.connection()?;
+        let extracted = extract_index_rows(&connection, node_id, folder, observations, indexers)?;
+        let transaction = connection.transaction()?;
+        let scan_id = new_scan_id();
+        let indexed_at = now_millis();
+        for (observation, accepted) in observations.iter().zip(extracted) {
+            upsert_observation(
+                &transaction,
+                node_id,
+                folder_id,
+                observation,
+                accepted,
+                &scan_id,
+                indexed_at,
+            )?;
+            // Indexers that stopped accepting the file, say after its type
+            // changed, lose it here instead of at the next full scan.
+            transaction
+                .prepare_cached(
+                    "DELETE FROM scanned_folder_locations
+                     WHERE scanned_folder_id = ?1 AND node_id = ?2 AND path = ?3
+                       AND last_seen_scan != ?4",
+                )?
+                .execute(params![
+                    folder_id,
+                    node_id,
+                    observation.path.to_string_lossy(),
+                    scan_id
+                ])?;
+        }
+        for path in removed {
+            let path = path.to_string_lossy();
+            let descendants = format!("{path}{}", std::path::MAIN_SEPARATOR);
+            transaction
+                .prepare_cached(
+                    "DELETE FROM scanned_folder_locations
+                     WHERE scanned_folder_id = ?1 AND node_id = ?2
+                       AND (path = ?3 OR substr(path, 1, length(?4)) = ?4)",
+                )?
+                .execute(params![folder_id, node_id, path, descendants])?;
+            transaction
+                .prepare_cached(
+                    "DELETE FROM file_locations
+                     WHERE node_id = ?1 AND (path = ?2 OR substr(path, 1, length(?3)) = ?3)
+                       AND NOT EXISTS (
+                           SELECT 1 FROM scanned_folder_locations membership
+                           WHERE membership.node_id = file_locations.node_id
+                             AND membership.path = file_locations.path
+                       )",
+                )?
+                .execute(params![node_id, path, descendants])?;
+        }
+        if !removed.is_empty() || !observations.is_empty() {
+            for indexer in registered_indexers() {
+                indexer.prune(&transaction)?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    pub fn auth_store(&self) -> AuthStore {
+        AuthStore {
+            path: self.path.clone(),
+        }
+    }
+
+    fn connection(&self) -> Result<rusqlite::Connection> {
+        open_connection(&self.path)
+    }
+}
+
+pub fn new_scan_id() -> Vec<u8> {
+    uuid::Uuid::new_v4().as_bytes().to_vec()
+}
+
+/// The indexers that accepted one observation, with the rows each extracted.
+type AcceptedBy<'i> = Vec<(&'i dyn Indexer, Option<IndexerRows>)>;
+
+/// Reads every file an indexer still has to look at, before the write
+/// transaction opens, so parsing and decoding never hold the database lock.
+/// An indexer that already has the file in this folder with unchanged
+/// content does not read it again.
+fn extract_index_rows<'i>(
+    connection: &rusqlite::Connection,
+    node_id: &[u8],
+    folder: &ManagedFolder,
+    observations: &[MediaIndexObservation],
+    indexers: &[&'i dyn Indexer],
+) -> Result<Vec<AcceptedBy<'i>>> {
+    let mut extracted = Vec::with_capacity(observations.len());
+    for observation in observations {
+        let path = observation.path.to_string_lossy();
+        let file = ObservedFile {
+            folder,
+            observation,
+        };
+        let previous_hash = connection
+            .prepare_cached("SELECT hash FROM file_locations WHERE node_id = ?1 AND path = ?2")?
+            .query_row(params![node_id, path], |row| {
+                row.get::<_, Option<Vec<u8>>>(0)
+            })
+            .optional()?
+            .flatten();
+        let unchanged = observation.hash.is_some() && previous_hash == observation.hash;
+        let mut accepted = AcceptedBy::new();
+        for indexer in indexers {
+            if !indexer.accepts(&file) {
+                continue;
+            }
+            let already_member = connection
+                .prepare_cached(
+                    "SELECT 1 FROM scanned_folder_locations
+                     WHERE scanned_folder_id = ?1 AND node_id = ?2 AND path = ?3 AND indexer = ?4",
+                )?
+                .exists(params![folder.id(), node_id, path, indexer.name()])?;
+            // One file an indexer cannot handle must never fail the scan; the
+            // file keeps its membership without rows until it changes.
+            let rows = if unchanged && already_member {
+                None
+            } else {
+                indexer.extract(&file).unwrap_or_else(|error| {
+                    log::warn!(
+                        "{} indexer failed for {}: {error:#}",
+                        indexer.name(),
+                        observation.path.display()
+                    );
+                    None
+                })
+            };
+            accepted.push((*indexer, rows));
+        }
+        extracted.push(accepted);
+    }
+    Ok(extracted)
+}
+
+/// Writes one observed file and records it for every indexer that accepted
+/// it, stamping each membership with `scan_id` and storing the rows
+/// [`extract_index_rows`] produced.
+fn upsert_observation(
+    transaction: &Transaction<'_>,
+    node_id: &[u8],
+    folder_id: u32,
+    observation: &MediaIndexObservation,
+    accepted: AcceptedBy<'_>,
+    scan_id: &[u8],
+    indexed_at: i64,
+) -> Result<()> {
+    let path = observation.path.to_string_lossy();
+    if let Some(hash) = &observation.hash {
+        transaction.execute(
+            "INSERT INTO file_entries (hash, size, mime_type, first_indexed_at, last_indexed_at)
+             VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT(hash) DO UPDATE SET last_indexed_at = excluded.last_indexed_at",
+            params![
+                hash,
+                observation.size as i64,
+                observation.mime_type,
+                indexed_at
+            ],
+        )?;
+    }
+    // A new hash was just computed from the content, so it counts as
+    // verified; a reused hash keeps the previous verification time.
+    transaction.execute(
+        "INSERT INTO file_locations
+            (node_id, path, hash, size, mime_type, last_indexed_at, created_at, modified_at, accessed_at,
+             extension_mime_type, sniffed_mime_type, verified_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, CASE WHEN ?3 IS NULL THEN NULL ELSE ?6 END)
+         ON CONFLICT(node_id, path) DO UPDATE SET
+            hash = excluded.hash, size = excluded.size, mime_type = excluded.mime_type,
+            extension_mime_type = excluded.extension_mime_type,
+            sniffed_mime_type = excluded.sniffed_mime_type,
+            last_indexed_at = excluded.last_indexed_at, created_at = excluded.created_at,
+            modified_at = excluded.modified_at, accessed_at = excluded.accessed_at,
+            verified_at = CASE WHEN file_locations.hash IS excluded.hash
+                THEN file_locations.verified_at ELSE excluded.verified_at END",
+        params![
+            node_id,
+            path,
+            observation.hash,
+            observation.size as i64,
+            observation.mime_type,
+            indexed_at,
+            observation.created_at,
+            observation.modified_at,
+            observation.accessed_at,
+            observation.extension_mime_type,
+            observation.sniffed_mime_type,
+        ],
+    )?;
+    // A modified file was changed on purpose, so an earlier scrub alert no
+    // longer describes it.
+    transaction
+        .prepare_cached(
+            "DELETE FROM scrub_alerts
+             WHERE node_id = ?1 AND path = ?2 AND (size != ?3 OR modified_at IS NOT ?4)",
+        )?
+        .execute(params![
+            node_id,
+            path,
+            observation.size as i64,
+            observation.modified_at
+        ])?;
+    for (indexer, rows) in accepted {
+        transaction
+            .prepare_cached(
                 "INSERT INTO scanned_folder_locations
                     (scanned_folder_id, node_id, path, indexer, last_seen_scan)
-                 VALUES (?1, ?2, ?3, 'media', ?4)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
                  ON CONFLICT(scanned_folder_id, node_id, path, indexer)
                  DO UPDATE SET last_seen_scan = excluded.last_seen_scan",
-                params![folder_id, node_id, path, scan_id],
-            )?;
+            )?
+            .execute(params![folder_id, node_id, path, indexer.name(), scan_id])?;
+        if let Some(rows) = rows {
+            indexer
+                .write(transaction, observation, rows)
+                .with_context(|| {
+                    format!(
+                        "{} indexer failed to store {}",
+                        indexer.name(),
+                        observation.path.display()
+                    )
+                })?;
         }
-        if complete {
-            transaction.execute(
-                "DELETE FROM scanned_folder_locations
-                 WHERE scanned_folder_id = ?1 AND indexer = 'media' AND last_seen_scan != ?2",
-                params![folder_id, scan_id],
-            )?;
-            transaction.execute(
-                "DELETE FROM file_locations WHERE NOT EXISTS (
-                    SELECT 1 FROM scanned_folder_locations membership
-                    WHERE membership.node_id = file_locations.node_id
-                      AND membership.path = file_locations.path
-                )",
+    }
+    Ok(())
+}
+
+/// Credential storage that can be shared with the HTTP handler without the
+/// wgui table handles owned by [`Database`].
+#[derive(Debug, Clone)]
+pub struct AuthStore {
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessTokenRecord {
+    pub id: u32,
+    pub name: String,
+    pub scope: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+impl AuthStore {
+    pub fn admin_password_hash(&self) -> Result<Option<String>> {
+        let connection = open_connection(&self.path)?;
+        connection
+            .query_row(
+                "SELECT password_hash FROM auth_admin WHERE id = 1",
                 [],
-            )?;
-        }
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// Stores the first admin password; returns false when one already exists.
+    pub fn create_admin_password_hash(&self, password_hash: &str) -> Result<bool> {
+        let connection = open_connection(&self.path)?;
+        let now = now_millis();
+        let affected = connection.execute(
+            "INSERT OR IGNORE INTO auth_admin (id, password_hash, created_at, updated_at)
+             VALUES (1, ?1, ?2, ?2)",
+            params![password_hash, now],
+        )?;
+        Ok(affected > 0)
+    }
+
+    pub fn update_admin_password_hash(&self, password_hash: &str) -> Result<()> {
+        let mut connection = open_connection(&self.path)?;
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "UPDATE auth_admin SET password_hash = ?1, updated_at = ?2 WHERE id = 1",
+            params![password_hash, now_millis()],
+        )?;
+        transaction.execute("DELETE FROM auth_sessions", [])?;
         transaction.commit()?;
         Ok(())
     }
 
-    fn connection(&self) -> Result<rusqlite::Connection> {
-        let connection = rusqlite::Connection::open(&self.path)?;
-        connection.pragma_update(None, "foreign_keys", "ON")?;
-        Ok(connection)
+    pub fn create_session(&self, token_hash: &[u8], expires_at: i64) -> Result<()> {
+        let connection = open_connection(&self.path)?;
+        let now = now_millis();
+        connection.execute("DELETE FROM auth_sessions WHERE expires_at <= ?1", [now])?;
+        connection.execute(
+            "INSERT INTO auth_sessions (token_hash, created_at, expires_at) VALUES (?1, ?2, ?3)",
+            params![token_hash, now, expires_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn session_is_active(&self, token_hash: &[u8]) -> Result<bool> {
+        let connection = open_connection(&self.path)?;
+        let active = connection
+            .query_row(
+                "SELECT 1 FROM auth_sessions WHERE token_hash = ?1 AND expires_at > ?2",
+                params![token_hash, now_millis()],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(active.is_some())
+    }
+
+    pub fn delete_session(&self, token_hash: &[u8]) -> Result<()> {
+        let connection = open_connection(&self.path)?;
+        connection.execute(
+            "DELETE FROM auth_sessions WHERE token_hash = ?1",
+            [token_hash],
+        )?;
+        Ok(())
+    }
+
+    pub fn create_access_token(
+        &self,
+        name: &str,
+        scope: &str,
+        token_hash: &[u8],
+    ) -> Result<AccessTokenRecord> {
+        let connection = open_connection(&self.path)?;
+        let created_at = now_millis();
+        connection.execute(
+            "INSERT INTO access_tokens (name, scope, token_hash, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![name, scope, token_hash, created_at],
+        )?;
+        Ok(AccessTokenRecord {
+            id: connection.last_insert_rowid() as u32,
+            name: name.to_owned(),
+            scope: scope.to_owned(),
+            created_at,
+            last_used_at: None,
+        })
+    }
+
+    /// Looks up the scope of a presented token and records that it was used.
+    pub fn use_access_token(&self, token_hash: &[u8]) -> Result<Option<String>> {
+        let connection = open_connection(&self.path)?;
+        let scope = connection
+            .query_row(
+                "UPDATE access_tokens SET last_used_at = ?2 WHERE token_hash = ?1 RETURNING scope",
+                params![token_hash, now_millis()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(scope)
+    }
+
+    pub fn access_tokens(&self) -> Result<Vec<AccessTokenRecord>> {
+        let connection = open_connection(&self.path)?;
+        let mut statement = connection.prepare(
+            "SELECT id, name, scope, created_at, last_used_at FROM access_tokens
+             ORDER BY created_at DESC, id DESC",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(AccessTokenRecord {
+                id: row.get::<_, i64>(0)? as u32,
+                name: row.get(1)?,
+                scope: row.get(2)?,
+                created_at: row.get(3)?,
+                last_used_at: row.get(4)?,
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Into::into)
     }
+
+    pub fn revoke_access_token(&self, id: u32) -> Result<bool> {
+        let connection = open_connection(&self.path)?;
+        let affected = connection.execute("DELETE FROM access_tokens WHERE id = ?1", [id])?;
+        Ok(affected > 0)
+    }
+}
+
+fn open_connection(path: &Path) -> Result<rusqlite::Connection> {
+    let connection = rusqlite::Connection::open(path)?;
+    connection.pragma_update(None, "foreign_keys", "ON")?;
+    Ok(connection)
 }
 
 #[derive(Debug, Clone)]
@@ -651,7 +2299,10 @@ pub struct MediaIndexObservation {
     pub path: PathBuf,
     pub hash: Option<Vec<u8>>,
     pub size: u64,
+    /// The effective type, chosen from the two below.
     pub mime_type: Option<String>,
+    pub extension_mime_type: Option<String>,
+    pub sniffed_mime_type: Option<String>,
     pub created_at: Option<i64>,
     pub modified_at: Option<i64>,
     pub accessed_at: Option<i64>,
@@ -666,17 +2317,137 @@ pub struct IndexedMediaFile {
     pub scanned_folder_id: u32,
     pub hash: Option<Vec<u8>>,
     pub replica_count: usize,
+    pub photo: Option<PhotoMetadata>,
+    pub audio: Option<AudioMetadata>,
+}
+
+/// Photo details from EXIF, stored per content hash in `photo_metadata`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhotoMetadata {
+    /// Milliseconds since the epoch. Without a recorded offset this is the
+    /// camera's wall-clock time read as UTC.
+    pub captured_at: Option<i64>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// EXIF orientation, 1 through 8.
+    pub orientation: Option<u16>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl PhotoMetadata {
+    /// Make and model joined the way people name cameras, without repeating
+    /// the brand when the model already includes it.
+    pub fn camera(&self) -> Option<String> {
+        match (self.camera_make.as_deref(), self.camera_model.as_deref()) {
+            (Some(make), Some(model)) => {
+                let brand = make.split_whitespace().next().unwrap_or(make);
+                if model.to_lowercase().starts_with(&brand.to_lowercase()) {
+                    Some(model.to_owned())
+                } else {
+                    Some(format!("{make} {model}"))
+                }
+            }
+            (make, model) => make.or(model).map(str::to_owned),
+        }
+    }
+
+    /// Width and height as shown, with orientations 5-8 rotated a quarter turn.
+    pub fn display_dimensions(&self) -> Option<(u32, u32)> {
+        let (width, height) = (self.width?, self.height?);
+        Some(
+            if self.orientation.is_some_and(|orientation| orientation >= 5) {
+                (height, width)
+            } else {
+                (width, height)
+            },
+        )
+    }
+
+    pub fn location(&self) -> Option<(f64, f64)> {
+        Some((self.latitude?, self.longitude?))
+    }
+}
+
+/// Music tags, stored per content hash in `audio_metadata`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub duration_ms: Option<i64>,
+    /// BLAKE3 hash of the embedded cover image.
+    pub cover_hash: Option<Vec<u8>>,
+}
+
+impl AudioMetadata {
+    /// The artist an album is filed under: the album artist when tagged,
+    /// otherwise the track artist.
+    pub fn album_credit(&self) -> Option<&str> {
+        self.album_artist.as_deref().or(self.artist.as_deref())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mime_type: Option<String>,
+    pub modified_at: Option<i64>,
+    pub hash: Option<Vec<u8>>,
+    pub scanned_folder_id: Option<u32>,
+    pub replica_count: usize,
+    pub extension_mime_type: Option<String>,
+    pub sniffed_mime_type: Option<String>,
+}
+
+impl IndexedFile {
+    /// Whether the file's content contradicts the type its name suggests.
+    pub fn mime_type_mismatch(&self) -> bool {
+        crate::indexer::mime_types_conflict(
+            self.extension_mime_type.as_deref(),
+            self.sniffed_mime_type.as_deref(),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileSearchHit {
+    pub node_id: Vec<u8>,
+    pub node_name: String,
+    pub is_local: bool,
+    pub path: PathBuf,
+    pub size: u64,
+    pub mime_type: Option<String>,
+    pub modified_at: Option<i64>,
+    pub hash: Option<Vec<u8>>,
+    pub scanned_folder_id: Option<u32>,
+    pub replica_count: usize,
 }
 
 #[derive(Debug, Clone)]
-pub struct IndexedFile {
+pub struct ContentSearchHit {
     pub path: PathBuf,
     pub size: u64,
     pub mime_type: Option<String>,
     pub modified_at: Option<i64>,
-    pub hash: Option<Vec<u8>>,
+    pub hash: Vec<u8>,
     pub scanned_folder_id: Option<u32>,
     pub replica_count: usize,
+    /// Matching excerpt with hits wrapped in `«` and `»`.
+    pub snippet: String,
+    /// BM25 score; lower is a better match.
+    pub rank: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -685,6 +2456,7 @@ pub struct IndexedLocationMetadata {
     pub size: u64,
     pub created_at: Option<i64>,
     pub modified_at: Option<i64>,
+    pub sniffed_mime_type: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -705,7 +2477,33 @@ pub struct VirtualDirectoryEntry {
     pub replica_count: usize,
 }
 
-fn now_millis() -> i64 {
+/// Turns free text into an FTS5 query: every word must appear, the last one
+/// as a prefix so results follow the user while typing. Quoting each word
+/// keeps FTS5 operators and punctuation in user input from being parsed.
+fn fts_query(input: &str) -> Option<String> {
+    let mut query = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if query.is_empty() {
+        return None;
+    }
+    query.push('*');
+    Some(query)
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+pub(crate) fn now_millis() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
@@ -732,6 +2530,29 @@ pub struct LocalSourceConfig {
     pub path: String,
 }
 
+/// How an SFTP source signs in. The password or key itself is asked for each
+/// session and never stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SftpAuth {
+    Password,
+    PrivateKey,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SftpSourceConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    /// Absolute path on the server that browsing and indexing start from.
+    pub root: String,
+    pub auth: SftpAuth,
+    /// Fingerprint of the server's host key, pinned on the first connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_key: Option<String>,
+}
+
 pub fn validate_source_config(source_type: &str, version: i32, config: &str) -> Result<()> {
     match (source_type, version) {
         ("local", 1) => {
@@ -743,8 +2564,36 @@ pub fn validate_source_config(source_type: &str, version: i32, config: &str) ->
             }
             Ok(())
         }
-        (known, _) if known == "local" => {
-            anyhow::bail!("unsupported local source configuration version {version}")
+        ("sftp", 1) => {
+            let config: SftpSourceConfig =
+                serde_json::from_str(config).context("invalid SFTP source configuration")?;
+            if config.host.is_empty()
+                || config.host.contains(|character: char| {
+                    character.is_whitespace() || "@/".contains(character)
+                })
+            {
+                anyhow::bail!("SFTP host must be a host name or address");
+            }
+            if config.port == 0 {
+                anyhow::bail!("SFTP port must be between 1 and 65535");
+            }
+            if config.user.is_empty() || config.user.contains(char::is_whitespace) {
+                anyhow::bail!("SFTP user must be a user name");
+            }
+            if !config.root.starts_with('/') {
+                anyhow::bail!("SFTP root path must be absolute");
+            }
+            if config
+                .host_key
+                .as_deref()
+                .is_some_and(|host_key| !host_key.starts_with("SHA256:"))
+            {
+                anyhow::bail!("SFTP host key must be a SHA256 fingerprint");
+            }
+            Ok(())
+        }
+        (known, _) if known == "local" || known == "sftp" => {
+            anyhow::bail!("unsupported {known} source configuration version {version}")
         }
         _ => anyhow::bail!("unsupported source type '{source_type}'"),
     }
@@ -759,10 +2608,25 @@ pub fn local_source_path(source: &Source) -> Option<PathBuf> {
         .map(|config| PathBuf::from(config.path))
 }
 
+pub fn sftp_source_config(source: &Source) -> Option<SftpSourceConfig> {
+    if source.source_type != "sftp" || source.config_schema_version != 1 {
+        return None;
+    }
+    serde_json::from_str(&source.config).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn media_indexers() -> Vec<&'static dyn Indexer> {
+        vec![crate::indexer::find_indexer(ScannedFolder::MEDIA_INDEXER).unwrap()]
+    }
+
+    fn unchecked_folder(id: u32) -> ManagedFolder {
+        ManagedFolder::open(id, std::env::temp_dir()).unwrap()
+    }
+
     fn temporary_database(name: &str) -> PathBuf {
         std::env::temp_dir().join(format!("puppydrive-{name}-{}.db", uuid::Uuid::new_v4()))
     }
@@ -777,6 +2641,8 @@ mod tests {
                 path: "/photos".to_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -804,6 +2670,25 @@ mod tests {
         let _ = fs::remove_file(path);
     }
 
+    #[test]
+    fn scheduled_job_runs_persist_and_maintenance_runs() {
+        let path = temporary_database("scheduled-jobs");
+        let db = Database::open(&path).unwrap();
+        assert!(db.scheduled_job_runs().unwrap().is_empty());
+        db.record_scheduled_job_run("rescan", 1_000).unwrap();
+        db.record_scheduled_job_run("rescan", 2_000).unwrap();
+        db.run_maintenance().unwrap();
+        drop(db);
+
+        let reopened = Database::open(&path).unwrap();
+        assert_eq!(
+            reopened.scheduled_job_runs().unwrap(),
+            HashMap::from([("rescan".to_owned(), 2_000)])
+        );
+        drop(reopened);
+        let _ = fs::remove_file(path);
+    }
+
     #[tokio::test]
     async fn scanned_folder_history_is_newest_first_and_keeps_last_hundred() {
         let path = temporary_database("scan-history");
@@ -814,6 +2699,8 @@ mod tests {
                 path: "/photos".to_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -854,6 +2741,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn typed_sftp_config_rejects_secrets_and_unknown_versions() {
+        let config = r#"{"host":"nas.local","port":22,"user":"photos","root":"/srv/photos","auth":"password"}"#;
+        assert!(validate_source_config("sftp", 1, config).is_ok());
+        assert!(validate_source_config("sftp", 2, config).is_err());
+        assert!(
+            validate_source_config(
+                "sftp",
+                1,
+                r#"{"host":"nas.local","port":22,"user":"photos","root":"/srv","auth":"password","password":"plaintext"}"#
+            )
+            .is_err()
+        );
+        for invalid in [
+            r#"{"host":"","port":22,"user":"photos","root":"/srv","auth":"password"}"#,
+            r#"{"host":"nas.local","port":0,"user":"photos","root":"/srv","auth":"password"}"#,
+            r#"{"host":"nas.local","port":22,"user":"photos","root":"srv","auth":"password"}"#,
+            r#"{"host":"nas.local","port":22,"user":"photos","root":"/srv","auth":"agent"}"#,
+            r#"{"host":"nas.local","port":22,"user":"photos","root":"/srv","auth":"password","host_key":"MD5:ab"}"#,
+        ] {
+            assert!(
+                validate_source_config("sftp", 1, invalid).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
     #[tokio::test]
     async fn persistent_index_deduplicates_content_and_preserves_entries_after_forget() {
         let path = temporary_database("persistent-index");
@@ -867,6 +2781,8 @@ mod tests {
                 path: "/photos".to_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -876,6 +2792,8 @@ mod tests {
                 path: "/backup/photos".to_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -885,16 +2803,25 @@ mod tests {
             hash: Some(hash.clone()),
             size: 42,
             mime_type: Some("image/jpeg".to_owned()),
+            extension_mime_type: Some("image/jpeg".to_owned()),
+            sniffed_mime_type: None,
             created_at: None,
             modified_at: Some(1),
             accessed_at: None,
         };
-        db.sync_media_scan(&node_id, first.id, &[observation("/photos/a.jpg")], true)
-            .unwrap();
-        db.sync_media_scan(
+        db.sync_scan(
+            &node_id,
+            &unchecked_folder(first.id),
+            &[observation("/photos/a.jpg")],
+            &media_indexers(),
+            true,
+        )
+        .unwrap();
+        db.sync_scan(
             &node_id,
-            second.id,
+            &unchecked_folder(second.id),
             &[observation("/backup/photos/a.jpg")],
+            &media_indexers(),
             true,
         )
         .unwrap();
@@ -932,6 +2859,152 @@ mod tests {
         let _ = fs::remove_file(path);
     }
 
+    #[tokio::test]
+    async fn streamed_scan_batches_are_swept_only_by_a_complete_finish() {
+        let path = temporary_database("scan-batches");
+        let db = Database::open(&path).unwrap();
+        let node_id = db.local_node_id("PuppyDrive").unwrap();
+        let folder = db
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: "/photos".to_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let folder = unchecked_folder(folder.id);
+        let observation = |path: &str| MediaIndexObservation {
+            path: PathBuf::from(path),
+            hash: Some(blake3::hash(path.as_bytes()).as_bytes().to_vec()),
+            size: 42,
+            mime_type: Some("image/jpeg".to_owned()),
+            extension_mime_type: Some("image/jpeg".to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: Some(1),
+            accessed_at: None,
+        };
+        db.sync_scan(
+            &node_id,
+            &folder,
+            &[observation("/photos/old.jpg")],
+            &media_indexers(),
+            true,
+        )
+        .unwrap();
+
+        // Each batch is visible as soon as it is written.
+        let interrupted = new_scan_id();
+        db.write_scan_batch(
+            &node_id,
+            &folder,
+            &interrupted,
+            &[observation("/photos/a.jpg")],
+            &media_indexers(),
+        )
+        .unwrap();
+        assert_eq!(db.cached_media(&node_id).unwrap().len(), 2);
+        db.finish_scan(folder.id(), &interrupted, false).unwrap();
+        assert_eq!(db.cached_media(&node_id).unwrap().len(), 2);
+
+        let complete = new_scan_id();
+        for batch in ["/photos/a.jpg", "/photos/b.jpg"] {
+            db.write_scan_batch(
+                &node_id,
+                &folder,
+                &complete,
+                &[observation(batch)],
+                &media_indexers(),
+            )
+            .unwrap();
+        }
+        db.finish_scan(folder.id(), &complete, true).unwrap();
+        let mut paths = db
+            .cached_media(&node_id)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("/photos/a.jpg"),
+                PathBuf::from("/photos/b.jpg")
+            ]
+        );
+        drop(db);
+        let _ = fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn location_metadata_is_looked_up_one_directory_at_a_time() {
+        let path = temporary_database("directory-metadata");
+        let db = Database::open(&path).unwrap();
+        let node_id = db.local_node_id("PuppyDrive").unwrap();
+        let folder = db
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: "/photos".to_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let observation = |path: &str| MediaIndexObservation {
+            path: PathBuf::from(path),
+            hash: Some(blake3::hash(path.as_bytes()).as_bytes().to_vec()),
+            size: 42,
+            mime_type: Some("image/jpeg".to_owned()),
+            extension_mime_type: Some("image/jpeg".to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: Some(1),
+            accessed_at: None,
+        };
+        db.sync_scan(
+            &node_id,
+            &unchecked_folder(folder.id),
+            &[
+                observation("/photos/a.jpg"),
+                observation("/photos/trip/b.jpg"),
+                observation("/photos/trip/day 2/c.jpg"),
+                observation("/photos/trip2/d.jpg"),
+            ],
+            &media_indexers(),
+            true,
+        )
+        .unwrap();
+        let paths = |directory: &str| {
+            let mut paths = db
+                .directory_location_metadata(&node_id, folder.id, Path::new(directory))
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths("/photos"), [PathBuf::from("/photos/a.jpg")]);
+        assert_eq!(paths("/photos/trip"), [PathBuf::from("/photos/trip/b.jpg")]);
+        assert_eq!(
+            paths("/photos/trip/"),
+            [PathBuf::from("/photos/trip/b.jpg")]
+        );
+        assert!(paths("/photos/empty").is_empty());
+        assert!(
+            db.directory_location_metadata(&node_id, folder.id + 1, Path::new("/photos"))
+                .unwrap()
+                .is_empty()
+        );
+        drop(db);
+        let _ = fs::remove_file(path);
+    }
+
     #[tokio::test]
     async fn virtual_directory_links_are_idempotent_and_survive_forgotten_locations() {
         let path = temporary_database("virtual-directories");
@@ -943,22 +3016,27 @@ mod tests {
                 path: "/photos".to_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
         let hash = vec![9; 32];
-        db.sync_media_scan(
+        db.sync_scan(
             &node_id,
-            folder.id,
+            &unchecked_folder(folder.id),
             &[MediaIndexObservation {
                 path: PathBuf::from("/photos/a.jpg"),
                 hash: Some(hash.clone()),
                 size: 42,
                 mime_type: Some("image/jpeg".to_owned()),
+                extension_mime_type: Some("image/jpeg".to_owned()),
+                sniffed_mime_type: None,
                 created_at: None,
                 modified_at: Some(1),
                 accessed_at: None,
             }],
+            &media_indexers(),
             true,
         )
         .unwrap();
@@ -973,6 +3051,352 @@ mod tests {
         let entries = db.virtual_directory_entries(&node_id).unwrap();
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0].path, None);
+
+        assert!(
+            db.rename_virtual_directory(directory.id, "Keepers")
+                .unwrap()
+        );
+        assert_eq!(db.virtual_directories().unwrap()[0].name, "Keepers");
+        assert!(
+            db.remove_file_from_virtual_directory(directory.id, &hash)
+                .unwrap()
+        );
+        assert!(db.virtual_directory_entries(&node_id).unwrap().is_empty());
+        db.add_file_to_virtual_directory(directory.id, &hash)
+            .unwrap();
+        assert!(db.delete_virtual_directory(directory.id).unwrap());
+        assert!(db.virtual_directories().unwrap().is_empty());
+        assert!(db.virtual_directory_entries(&node_id).unwrap().is_empty());
+        assert!(!db.delete_virtual_directory(directory.id).unwrap());
+        drop(db);
+        let _ = fs::remove_file(path);
+    }
+
+    struct TextIndexer {
+        indexed: std::sync::Mutex<Vec<PathBuf>>,
+    }
+
+    impl Indexer for TextIndexer {
+        fn name(&self) -> &'static str {
+            "text"
+        }
+
+        fn description(&self) -> &'static str {
+            "Text files"
+        }
+
+        fn accepts(&self, file: &ObservedFile<'_>) -> bool {
+            file.observation.mime_type.as_deref() == Some("text/plain")
+        }
+
+        fn extract(&self, file: &ObservedFile<'_>) -> Result<Option<IndexerRows>> {
+            self.indexed
+                .lock()
+                .unwrap()
+                .push(file.observation.path.clone());
+            if file.observation.path.ends_with("broken.txt") {
+                anyhow::bail!("unreadable text");
+            }
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn indexers_record_their_own_membership() {
+        let path = temporary_database("indexer-membership");
+        let db = Database::open(&path).unwrap();
+        let node_id = db.local_node_id("PuppyDrive").unwrap();
+        let folder = db
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: "/notes".to_owned(),
+                enabled: true,
+                indexers: r#"["media","text"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(folder.indexer_names(), ["media", "text"]);
+        assert!(folder.indexes("text"));
+        let text = TextIndexer {
+            indexed: std::sync::Mutex::new(Vec::new()),
+        };
+        let mut indexers = media_indexers();
+        indexers.push(&text);
+        let observation = |path: &str, mime_type: &str, hash: u8| MediaIndexObservation {
+            path: PathBuf::from(path),
+            hash: Some(vec![hash; 32]),
+            size: 3,
+            mime_type: Some(mime_type.to_owned()),
+            extension_mime_type: Some(mime_type.to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: Some(1),
+            accessed_at: None,
+        };
+        let scan = |observations: &[MediaIndexObservation]| {
+            db.sync_scan(
+                &node_id,
+                &unchecked_folder(folder.id),
+                observations,
+                &indexers,
+                true,
+            )
+            .unwrap();
+        };
+        let memberships = || {
+            let connection = db.connection().unwrap();
+            let mut statement = connection
+                .prepare(
+                    "SELECT indexer, path FROM scanned_folder_locations
+                     WHERE scanned_folder_id = ?1 ORDER BY indexer, path",
+                )
+                .unwrap();
+            statement
+                .query_map([folder.id], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+                })
+                .unwrap()
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        scan(&[
+            observation("/notes/a.txt", "text/plain", 1),
+            observation("/notes/b.jpg", "image/jpeg", 2),
+        ]);
+        assert_eq!(
+            memberships(),
+            [
+                ("media".to_owned(), "/notes/a.txt".to_owned()),
+                ("media".to_owned(), "/notes/b.jpg".to_owned()),
+                ("text".to_owned(), "/notes/a.txt".to_owned()),
+            ]
+        );
+        assert_eq!(
+            *text.indexed.lock().unwrap(),
+            [PathBuf::from("/notes/a.txt")]
+        );
+
+        scan(&[observation("/notes/a.txt", "text/plain", 1)]);
+        assert_eq!(text.indexed.lock().unwrap().len(), 1);
+        assert_eq!(
+            memberships(),
+            [
+                ("media".to_owned(), "/notes/a.txt".to_owned()),
+                ("text".to_owned(), "/notes/a.txt".to_owned()),
+            ]
+        );
+
+        scan(&[observation("/notes/a.txt", "text/plain", 3)]);
+        assert_eq!(text.indexed.lock().unwrap().len(), 2);
+
+        // A file the indexer fails on is still indexed, without its rows.
+        scan(&[
+            observation("/notes/a.txt", "text/plain", 3),
+            observation("/notes/broken.txt", "text/plain", 4),
+        ]);
+        assert_eq!(text.indexed.lock().unwrap().len(), 3);
+        assert_eq!(
+            memberships(),
+            [
+                ("media".to_owned(), "/notes/a.txt".to_owned()),
+                ("media".to_owned(), "/notes/broken.txt".to_owned()),
+                ("text".to_owned(), "/notes/a.txt".to_owned()),
+                ("text".to_owned(), "/notes/broken.txt".to_owned()),
+            ]
+        );
+        drop(db);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn fts_queries_quote_words_and_prefix_the_last() {
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(fts_query("quick fo").unwrap(), r#""quick" "fo"*"#);
+        assert_eq!(
+            fts_query(r#"say "hi" OR"#).unwrap(),
+            r#""say" """hi""" "OR"*"#
+        );
+    }
+
+    #[tokio::test]
+    async fn content_search_ranks_text_files_and_forgets_removed_ones() {
+        let path = temporary_database("content-search");
+        let db = Database::open(&path).unwrap();
+        let node_id = db.local_node_id("PuppyDrive").unwrap();
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-content-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        let folder = db
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: directory.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: ScannedFolder::default_indexers(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let folder = ManagedFolder::open(folder.id, &directory).unwrap();
+        let files: [(&str, &[u8], &str); 3] = [
+            (
+                "notes.md",
+                b"The quick brown fox jumps over the lazy dog",
+                "text/markdown",
+            ),
+            (
+                "main.rs",
+                b"fn main() { println!(\"quick\"); }",
+                "text/x-rust",
+            ),
+            ("data.txt", b"quick\0\x01\x02", "text/plain"),
+        ];
+        let observations = files
+            .iter()
+            .map(|(name, bytes, mime_type)| {
+                fs::write(directory.join(name), bytes).unwrap();
+                MediaIndexObservation {
+                    path: folder.root().join(name),
+                    hash: Some(blake3::hash(bytes).as_bytes().to_vec()),
+                    size: bytes.len() as u64,
+                    mime_type: Some((*mime_type).to_owned()),
+                    extension_mime_type: Some((*mime_type).to_owned()),
+                    sniffed_mime_type: None,
+                    created_at: None,
+                    modified_at: Some(1),
+                    accessed_at: None,
+                }
+            })
+            .collect::<Vec<_>>();
+        let indexers = [crate::indexer::find_indexer(ScannedFolder::CONTENT_INDEXER).unwrap()];
+        db.sync_scan(&node_id, &folder, &observations, &indexers, true)
+            .unwrap();
+
+        let hits = db.search_contents(&node_id, "quic", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|hit| !hit.path.ends_with("data.txt")));
+        let fox = db.search_contents(&node_id, "brown fo", 10).unwrap();
+        assert_eq!(fox.len(), 1);
+        assert!(fox[0].path.ends_with("notes.md"));
+        assert!(fox[0].snippet.contains("«brown»"));
+        assert!(db.search_contents(&node_id, "OR", 10).unwrap().is_empty());
+
+        db.sync_scan(&node_id, &folder, &observations[1..], &indexers, true)
+            .unwrap();
+        assert!(db.search_contents(&node_id, "fox", 10).unwrap().is_empty());
+        assert_eq!(db.search_contents(&node_id, "quick", 10).unwrap().len(), 1);
+        drop(db);
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_dir_all(directory);
+    }
+
+    #[tokio::test]
+    async fn file_search_combines_path_fragments_and_filters() {
+        let path = temporary_database("file-search");
+        let db = Database::open(&path).unwrap();
+        let node_id = db.local_node_id("PuppyDrive").unwrap();
+        let folder = db
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: "/archive".to_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let observation =
+            |path: &str, mime_type: &str, size: u64, modified_at: i64| MediaIndexObservation {
+                path: PathBuf::from(path),
+                hash: Some(blake3::hash(path.as_bytes()).as_bytes().to_vec()),
+                size,
+                mime_type: Some(mime_type.to_owned()),
+                extension_mime_type: Some(mime_type.to_owned()),
+                sniffed_mime_type: None,
+                created_at: None,
+                modified_at: Some(modified_at),
+                accessed_at: None,
+            };
+        let may = days_from_civil(2024, 5, 3) * 86_400_000;
+        db.sync_scan(
+            &node_id,
+            &unchecked_folder(folder.id),
+            &[
+                observation(
+                    "/archive/Reports/2024 budget.pdf",
+                    "application/pdf",
+                    2_000_000,
+                    may,
+                ),
+                observation("/archive/Holiday/beach.jpg", "image/jpeg", 4_000_000, may),
+                observation("/archive/Holiday/report.txt", "text/plain", 10, 0),
+                observation("/archive/ab.md", "text/markdown", 10, 0),
+            ],
+            &media_indexers(),
+            true,
+        )
+        .unwrap();
+        let search = |input: &str| {
+            db.search_files(&FileQuery::parse(input, may).unwrap(), 10)
+                .unwrap()
+                .into_iter()
+                .map(|hit| hit.path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            search("REPORT"),
+            [
+                "/archive/Holiday/report.txt",
+                "/archive/Reports/2024 budget.pdf"
+            ]
+        );
+        assert_eq!(
+            search("report ext:pdf"),
+            ["/archive/Reports/2024 budget.pdf"]
+        );
+        assert_eq!(search("type:image"), ["/archive/Holiday/beach.jpg"]);
+        assert_eq!(
+            search("folder:holiday size>1mb"),
+            ["/archive/Holiday/beach.jpg"]
+        );
+        assert_eq!(search("holiday folder:reports").len(), 0);
+        assert_eq!(
+            search(&format!("ab folder:{}", folder.id)),
+            ["/archive/ab.md"]
+        );
+        assert_eq!(search("modified:2024-05 type:application/pdf").len(), 1);
+        assert!(search("").is_empty());
+
+        db.sync_scan(
+            &node_id,
+            &unchecked_folder(folder.id),
+            &[observation("/archive/ab.md", "text/markdown", 10, 0)],
+            &media_indexers(),
+            true,
+        )
+        .unwrap();
+        assert!(search("report").is_empty());
+
+        // A VACUUM may renumber file_locations, which hits must survive.
+        db.sync_scan(
+            &node_id,
+            &unchecked_folder(folder.id),
+            &[
+                observation("/archive/ab.md", "text/markdown", 10, 0),
+                observation("/archive/Notes/meeting.txt", "text/plain", 10, 0),
+            ],
+            &media_indexers(),
+            true,
+        )
+        .unwrap();
+        db.connection().unwrap().execute_batch("VACUUM").unwrap();
+        assert_eq!(search("meeting"), ["/archive/Notes/meeting.txt"]);
+        assert_eq!(search("archive").len(), 2);
         drop(db);
         let _ = fs::remove_file(path);
     }
@@ -0,0 +1,324 @@
+//! Filename search syntax shared by the sidebar search box and the API.
+//!
+//! Free words (or `"quoted phrases"`) must all appear in the path. Filters
+//! narrow the result further:
+//!
+//! - `ext:jpg` or `ext:jpg,png`
+//! - `type:image` (any `image/*`) or `type:image/png`
+//! - `size>10mb`, `size>=1k`, `size<2gb`, `size<=500b`
+//! - `modified:2024`, `modified:2024-05`, `modified:2024-05-03`, with an
+//!   optional `>`, `>=`, `<` or `<=` prefix, plus `modified:today` and
+//!   `modified:7d` for the last seven days. Dates are in UTC.
+//! - `folder:12` for a Scanned folder id, otherwise a directory name or path
+//!   fragment such as `folder:holiday`.
+//!
+//! Repeating a filter, or listing comma separated values, matches any of them.
+
+use anyhow::{Result, bail};
+
+use crate::database::days_from_civil;
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileQuery {
+    /// Lowercase fragments that must all appear in the path.
+    pub terms: Vec<String>,
+    /// Lowercase extensions including the leading dot.
+    pub extensions: Vec<String>,
+    /// Exact MIME types, or major types ending in `/` that match as a prefix.
+    pub mime_types: Vec<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Inclusive lower bound in Unix milliseconds.
+    pub modified_from: Option<i64>,
+    /// Exclusive upper bound in Unix milliseconds.
+    pub modified_until: Option<i64>,
+    pub folders: Vec<FolderFilter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FolderFilter {
+    ScannedFolder(u32),
+    /// Lowercase fragment of the directory part of a path.
+    Directory(String),
+}
+
+impl FileQuery {
+    /// Parses `input`, resolving relative dates against `now` (Unix millis).
+    pub fn parse(input: &str, now: i64) -> Result<Self> {
+        let mut query = Self::default();
+        for token in tokens(input) {
+            if token.quoted {
+                query.terms.push(token.text.to_lowercase());
+                continue;
+            }
+            let text = token.text.as_str();
+            if let Some(value) = filter_value(text, "ext:") {
+                query.extensions.extend(
+                    values(value)
+                        .map(|extension| format!(".{}", extension.trim_start_matches('.'))),
+                );
+            } else if let Some(value) = filter_value(text, "type:") {
+                query.mime_types.extend(values(value).map(|mime_type| {
+                    if mime_type.contains('/') {
+                        mime_type
+                    } else {
+                        format!("{mime_type}/")
+                    }
+                }));
+            } else if let Some(value) =
+                filter_value(text, "size").filter(|value| value.starts_with(['>', '<', '=', ':']))
+            {
+                query.parse_size(text, value)?;
+            } else if let Some(value) = filter_value(text, "modified:") {
+                query.parse_modified(text, value, now)?;
+            } else if let Some(value) = filter_value(text, "folder:") {
+                query.folders.extend(values(value).map(|folder| {
+                    folder.parse().map_or_else(
+                        |_| FolderFilter::Directory(folder.clone()),
+                        FolderFilter::ScannedFolder,
+                    )
+                }));
+            } else {
+                query.terms.push(text.to_lowercase());
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn parse_size(&mut self, token: &str, value: &str) -> Result<()> {
+        let (comparison, amount) = comparison(value);
+        let Some(bytes) = parse_size(amount) else {
+            bail!("invalid size filter '{token}', expected something like size>10mb");
+        };
+        match comparison {
+            Comparison::Greater => self.min_size = Some(bytes.saturating_add(1)),
+            Comparison::GreaterOrEqual => self.min_size = Some(bytes),
+            Comparison::Less if bytes > 0 => self.max_size = Some(bytes - 1),
+            Comparison::LessOrEqual => self.max_size = Some(bytes),
+            Comparison::Equal => {
+                self.min_size = Some(bytes);
+                self.max_size = Some(bytes);
+            }
+            Comparison::Less => bail!("size<0 can never match"),
+        }
+        Ok(())
+    }
+
+    fn parse_modified(&mut self, token: &str, value: &str, now: i64) -> Result<()> {
+        let (comparison, period) = comparison(value);
+        let Some((start, end)) = parse_period(&period.to_lowercase(), now) else {
+            bail!(
+                "invalid modified filter '{token}', expected a date like modified:2024-05-03, \
+                 modified:today or modified:7d"
+            );
+        };
+        match comparison {
+            Comparison::Equal => {
+                self.modified_from = Some(start);
+                self.modified_until = Some(end);
+            }
+            Comparison::Greater => self.modified_from = Some(end),
+            Comparison::GreaterOrEqual => self.modified_from = Some(start),
+            Comparison::Less => self.modified_until = Some(start),
+            Comparison::LessOrEqual => self.modified_until = Some(end),
+        }
+        Ok(())
+    }
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+/// Splits on whitespace, keeping `"quoted phrases"` together.
+fn tokens(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut was_quoted = false;
+    for character in input.chars() {
+        match character {
+            '"' => {
+                quoted = !quoted;
+                was_quoted = true;
+            }
+            character if character.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut current),
+                        quoted: was_quoted,
+                    });
+                }
+                was_quoted = false;
+            }
+            character => current.push(character),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(Token {
+            text: current,
+            quoted: was_quoted,
+        });
+    }
+    tokens
+}
+
+fn filter_value<'a>(token: &'a str, name: &str) -> Option<&'a str> {
+    let prefix = token.get(..name.len())?;
+    prefix
+        .eq_ignore_ascii_case(name)
+        .then(|| &token[name.len()..])
+        .filter(|value| !value.is_empty())
+}
+
+fn values(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(|value| value.trim().to_lowercase())
+        .filter(|value| !value.is_empty())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+fn comparison(value: &str) -> (Comparison, &str) {
+    for (prefix, comparison) in [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        (":", Comparison::Equal),
+        ("=", Comparison::Equal),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (comparison, rest);
+        }
+    }
+    (Comparison::Equal, value)
+}
+
+/// Parses `500`, `500b`, `10k`, `1.5mb` or `2gb`, using 1024-byte units like
+/// the size column.
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim().to_lowercase();
+    let split = value
+        .find(|character: char| !character.is_ascii_digit() && character != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.parse::<f64>().ok().filter(|number| *number >= 0.0)?;
+    let multiplier = match unit {
+        "" | "b" => 1_u64,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        "t" | "tb" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Resolves a date, `today` or a day count into a `[start, end)` range.
+fn parse_period(value: &str, now: i64) -> Option<(i64, i64)> {
+    if value == "today" {
+        let start = now.div_euclid(DAY_MILLIS) * DAY_MILLIS;
+        return Some((start, start + DAY_MILLIS));
+    }
+    if let Some(days) = value.strip_suffix('d') {
+        let days = days.parse::<i64>().ok().filter(|days| *days > 0)?;
+        return Some((now - days * DAY_MILLIS, now + 1));
+    }
+    let parts = value
+        .split('-')
+        .map(|part| part.parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let day_millis = |year, month, day| days_from_civil(year, month, day) * DAY_MILLIS;
+    match parts.as_slice() {
+        [year] if value.len() == 4 => Some((day_millis(*year, 1, 1), day_millis(year + 1, 1, 1))),
+        [year, month] if (1..=12).contains(month) => {
+            let (next_year, next_month) = if *month == 12 {
+                (year + 1, 1)
+            } else {
+                (*year, month + 1)
+            };
+            Some((
+                day_millis(*year, *month, 1),
+                day_millis(next_year, next_month, 1),
+            ))
+        }
+        [year, month, day] if (1..=12).contains(month) && (1..=31).contains(day) => {
+            let start = day_millis(*year, *month, *day);
+            Some((start, start + DAY_MILLIS))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_words_phrases_and_filters() {
+        let now = days_from_civil(2024, 5, 10) * DAY_MILLIS + 3_600_000;
+        let query = FileQuery::parse(
+            r#"Report "tax return" ext:PDF,.docx type:image type:text/plain size>=1.5mb size<2g folder:12 folder:Holiday"#,
+            now,
+        )
+        .unwrap();
+        assert_eq!(query.terms, ["report", "tax return"]);
+        assert_eq!(query.extensions, [".pdf", ".docx"]);
+        assert_eq!(query.mime_types, ["image/", "text/plain"]);
+        assert_eq!(query.min_size, Some(1_572_864));
+        assert_eq!(query.max_size, Some((2 << 30) - 1));
+        assert_eq!(
+            query.folders,
+            [
+                FolderFilter::ScannedFolder(12),
+                FolderFilter::Directory("holiday".to_owned())
+            ]
+        );
+
+        let may = FileQuery::parse("modified:2024-05", now).unwrap();
+        assert_eq!(
+            may.modified_from,
+            Some(days_from_civil(2024, 5, 1) * DAY_MILLIS)
+        );
+        assert_eq!(
+            may.modified_until,
+            Some(days_from_civil(2024, 6, 1) * DAY_MILLIS)
+        );
+        let after = FileQuery::parse("modified:>2023", now).unwrap();
+        assert_eq!(
+            after.modified_from,
+            Some(days_from_civil(2024, 1, 1) * DAY_MILLIS)
+        );
+        assert_eq!(after.modified_until, None);
+        let today = FileQuery::parse("modified:today", now).unwrap();
+        assert_eq!(
+            today.modified_from,
+            Some(days_from_civil(2024, 5, 10) * DAY_MILLIS)
+        );
+        let week = FileQuery::parse("modified:7d", now).unwrap();
+        assert_eq!(week.modified_from, Some(now - 7 * DAY_MILLIS));
+
+        assert!(FileQuery::parse("  ", now).unwrap().is_empty());
+        assert!(FileQuery::parse("size>lots", now).is_err());
+        assert_eq!(FileQuery::parse("sizes", now).unwrap().terms, ["sizes"]);
+        assert!(FileQuery::parse("modified:2024-13", now).is_err());
+        // A bare filter name without a value is just a word.
+        assert_eq!(FileQuery::parse("ext:", now).unwrap().terms, ["ext:"]);
+    }
+}
@@ -0,0 +1,447 @@
+//! Which paths inside a Scanned folder are left out of the index.
+//!
+//! Rules are checked in this order, and the last one that matches a path
+//! decides:
+//!
+//! 1. Built in: hidden directories (names starting with `.`) and
+//!    directories named in `MediaConfig.ignored_directory_names` are
+//!    skipped. Hidden files are indexed like any other file.
+//! 2. The folder's include globs, which bring back paths the built-in rules
+//!    skip, e.g. `.config/` to index one hidden directory.
+//! 3. The folder's exclude globs.
+//! 4. `.puppyignore` files, from the folder root down to the path's own
+//!    directory, so deeper files override shallower ones.
+//!
+//! Globs and `.puppyignore` lines use gitignore syntax: `#` comments, `!` to
+//! re-include, a trailing `/` for directories only, a leading or inner `/` to
+//! anchor the pattern to the directory it is defined in, and `*`, `?`,
+//! `[a-z]` and `**`. As with gitignore, nothing inside a skipped directory is
+//! indexed, whatever later rules say about it.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::database::ScannedFolder;
+
+pub const IGNORE_FILE_NAME: &str = ".puppyignore";
+
+pub struct IgnoreRules {
+    root: PathBuf,
+    ignored_directory_names: Vec<String>,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// Parsed `.puppyignore` files by the directory they are in.
+    ignore_files: Mutex<HashMap<PathBuf, Arc<[Pattern]>>>,
+}
+
+impl IgnoreRules {
+    pub fn new(
+        root: impl Into<PathBuf>,
+        include: &[String],
+        exclude: &[String],
+        ignored_directory_names: &[String],
+    ) -> Self {
+        let parse = |globs: &[String]| {
+            globs
+                .iter()
+                .filter_map(|glob| Pattern::parse(glob))
+                .collect::<Vec<_>>()
+        };
+        Self {
+            root: root.into(),
+            ignored_directory_names: ignored_directory_names.to_vec(),
+            include: parse(include),
+            exclude: parse(exclude),
+            ignore_files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Rules for `folder`, matched against paths below `root`, which may be
+    /// the configured folder path or its canonical form.
+    pub fn for_folder(
+        folder: &ScannedFolder,
+        root: impl Into<PathBuf>,
+        ignored_directory_names: &[String],
+    ) -> Self {
+        Self::new(
+            root,
+            &folder.include_glob_list(),
+            &folder.exclude_glob_list(),
+            ignored_directory_names,
+        )
+    }
+
+    /// Whether `path` or any directory between the root and it is skipped.
+    /// Paths outside the root are never ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(names) = self.relative_names(path) else {
+            return false;
+        };
+        (1..=names.len()).any(|depth| {
+            let entry_is_dir = depth < names.len() || is_dir;
+            self.decide(&names[..depth], entry_is_dir)
+        })
+    }
+
+    /// Like [`IgnoreRules::is_ignored`] for an entry whose parent directory
+    /// is already known not to be skipped, as during a traversal.
+    pub fn is_entry_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.relative_names(path)
+            .is_some_and(|names| !names.is_empty() && self.decide(&names, is_dir))
+    }
+
+    /// Drops the cached `.puppyignore` of `directory` after it changed.
+    pub fn forget_ignore_file(&self, directory: &Path) {
+        self.ignore_files
+            .lock()
+            .expect("ignore file cache lock poisoned")
+            .remove(directory);
+    }
+
+    /// Uses `contents` as the `.puppyignore` of `directory` instead of
+    /// reading it from disk, for folders that are not on this computer.
+    pub fn set_ignore_file(&self, directory: &Path, contents: &str) {
+        self.ignore_files
+            .lock()
+            .expect("ignore file cache lock poisoned")
+            .insert(
+                directory.to_path_buf(),
+                contents.lines().filter_map(Pattern::parse).collect(),
+            );
+    }
+
+    fn relative_names(&self, path: &Path) -> Option<Vec<String>> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        Some(
+            relative
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                    _ => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Applies every rule to the entry at `names` below the root.
+    fn decide(&self, names: &[String], is_dir: bool) -> bool {
+        let name = names.last().map(String::as_str).unwrap_or_default();
+        let mut ignored = is_dir
+            && (name.starts_with('.')
+                || self
+                    .ignored_directory_names
+                    .iter()
+                    .any(|ignored| ignored.eq_ignore_ascii_case(name)));
+        let relative = names.join("/");
+        if self
+            .include
+            .iter()
+            .any(|pattern| pattern.matches(&relative, is_dir))
+        {
+            ignored = false;
+        }
+        if self
+            .exclude
+            .iter()
+            .any(|pattern| pattern.matches(&relative, is_dir))
+        {
+            ignored = true;
+        }
+        let mut directory = self.root.clone();
+        for depth in 0..names.len() {
+            if depth > 0 {
+                directory.push(&names[depth - 1]);
+            }
+            let relative = names[depth..].join("/");
+            for pattern in self.ignore_file(&directory).iter() {
+                if pattern.matches(&relative, is_dir) {
+                    ignored = !pattern.negated;
+                }
+            }
+        }
+        ignored
+    }
+
+    fn ignore_file(&self, directory: &Path) -> Arc<[Pattern]> {
+        let mut ignore_files = self
+            .ignore_files
+            .lock()
+            .expect("ignore file cache lock poisoned");
+        ignore_files
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| {
+                let path = directory.join(IGNORE_FILE_NAME);
+                // A symlinked ignore file could point outside the folder.
+                let is_file = std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.is_file());
+                let contents = is_file
+                    .then(|| std::fs::read_to_string(&path).ok())
+                    .flatten()
+                    .unwrap_or_default();
+                contents.lines().filter_map(Pattern::parse).collect()
+            })
+            .clone()
+    }
+}
+
+pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == std::ffi::OsStr::new(IGNORE_FILE_NAME))
+}
+
+/// Splits a comma-separated list of globs, as typed into Settings.
+pub fn parse_glob_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|glob| !glob.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    tokens: Vec<Token>,
+    negated: bool,
+    directory_only: bool,
+    /// Matched against the whole relative path instead of just the name.
+    anchored: bool,
+}
+
+impl Pattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            // `\!` and `\#` start patterns with a literal `!` or `#`.
+            None if line.starts_with("\\!") || line.starts_with("\\#") => (false, &line[1..]),
+            None => (false, line),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        Some(Self {
+            tokens: compile(line),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    /// `relative` uses `/` separators and is relative to the directory the
+    /// pattern was defined in.
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        let text = if self.anchored {
+            relative
+        } else {
+            relative.rsplit('/').next().unwrap_or(relative)
+        };
+        matches(&self.tokens, &text.chars().collect::<Vec<_>>())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`, which stops at `/`.
+    Star,
+    /// A trailing `**`: everything below.
+    AnyPath,
+    /// `**/`: zero or more whole directories.
+    AnyDirectories,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+fn compile(glob: &str) -> Vec<Token> {
+    let chars = glob.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            '\\' if index + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[index + 1]));
+                index += 2;
+                continue;
+            }
+            '*' if chars.get(index + 1) == Some(&'*') => {
+                let at_boundary = index == 0 || chars[index - 1] == '/';
+                match chars.get(index + 2) {
+                    Some('/') if at_boundary => {
+                        tokens.push(Token::AnyDirectories);
+                        index += 3;
+                    }
+                    None if at_boundary => {
+                        tokens.push(Token::AnyPath);
+                        index += 2;
+                    }
+                    _ => {
+                        tokens.push(Token::Star);
+                        index += 2;
+                    }
+                }
+                continue;
+            }
+            '*' => tokens.push(Token::Star),
+            '?' => tokens.push(Token::AnyChar),
+            '[' => {
+                if let Some((class, end)) = compile_class(&chars, index) {
+                    tokens.push(class);
+                    index = end + 1;
+                    continue;
+                }
+                tokens.push(Token::Char('['));
+            }
+            character => tokens.push(Token::Char(character)),
+        }
+        index += 1;
+    }
+    tokens
+}
+
+/// Parses the `[...]` class starting at `start`, returning it with the index
+/// of its closing bracket, or `None` when it is never closed.
+fn compile_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut index = start + 1;
+    let negated = matches!(chars.get(index), Some('!' | '^'));
+    if negated {
+        index += 1;
+    }
+    let mut ranges = Vec::new();
+    let first = index;
+    while index < chars.len() {
+        let character = chars[index];
+        if character == ']' && index > first {
+            return Some((Token::Class { negated, ranges }, index));
+        }
+        if chars.get(index + 1) == Some(&'-') && chars.get(index + 2).is_some_and(|c| *c != ']') {
+            ranges.push((character, chars[index + 2]));
+            index += 3;
+        } else {
+            ranges.push((character, character));
+            index += 1;
+        }
+    }
+    None
+}
+
+fn matches(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Star => {
+            for skip in 0..=text.len() {
+                if skip > 0 && text[skip - 1] == '/' {
+                    break;
+                }
+                if matches(rest, &text[skip..]) {
+                    return true;
+                }
+            }
+            false
+        }
+        Token::AnyPath => (0..=text.len()).any(|skip| matches(rest, &text[skip..])),
+        Token::AnyDirectories => (0..=text.len())
+            .filter(|skip| *skip == 0 || text[skip - 1] == '/')
+            .any(|skip| matches(rest, &text[skip..])),
+        Token::AnyChar => text
+            .split_first()
+            .is_some_and(|(first, tail)| *first != '/' && matches(rest, tail)),
+        Token::Char(character) => text
+            .split_first()
+            .is_some_and(|(first, tail)| first == character && matches(rest, tail)),
+        Token::Class { negated, ranges } => text.split_first().is_some_and(|(first, tail)| {
+            *first != '/'
+                && ranges
+                    .iter()
+                    .any(|(low, high)| (*low..=*high).contains(first))
+                    != *negated
+                && matches(rest, tail)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern_matches(pattern: &str, relative: &str, is_dir: bool) -> bool {
+        Pattern::parse(pattern).unwrap().matches(relative, is_dir)
+    }
+
+    #[test]
+    fn matches_gitignore_globs() {
+        assert!(pattern_matches("*.log", "logs/today.log", false));
+        assert!(!pattern_matches("*.log", "today.log.gz", false));
+        assert!(pattern_matches("/build", "build", true));
+        assert!(!pattern_matches("/build", "src/build", true));
+        assert!(pattern_matches("cache/", "a/cache", true));
+        assert!(!pattern_matches("cache/", "a/cache", false));
+        assert!(pattern_matches("docs/*.md", "docs/readme.md", false));
+        assert!(!pattern_matches("docs/*.md", "docs/old/readme.md", false));
+        assert!(pattern_matches("**/raw", "photos/2024/raw", true));
+        assert!(pattern_matches("**/raw", "raw", true));
+        assert!(pattern_matches("a/**/b", "a/b", false));
+        assert!(pattern_matches("a/**/b", "a/x/y/b", false));
+        assert!(pattern_matches("tmp/**", "tmp/x/y", false));
+        assert!(pattern_matches("IMG_[0-9]?.jpg", "IMG_12.jpg", false));
+        assert!(!pattern_matches("IMG_[!0-9]?.jpg", "IMG_12.jpg", false));
+        assert!(pattern_matches(r"\#notes", "#notes", false));
+        assert!(pattern_matches(r"\*.txt", "*.txt", false));
+        assert!(!pattern_matches(r"\*.txt", "a.txt", false));
+        assert!(Pattern::parse("# comment").is_none());
+        assert!(Pattern::parse("   ").is_none());
+        assert!(Pattern::parse("!keep.log").unwrap().negated);
+    }
+
+    #[test]
+    fn layers_defaults_folder_globs_and_ignore_files() {
+        let root = std::env::temp_dir().join(format!("puppydrive-ignore-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("photos/raw")).unwrap();
+        std::fs::write(root.join(IGNORE_FILE_NAME), "*.tmp\nraw/\n").unwrap();
+        std::fs::write(root.join("photos").join(IGNORE_FILE_NAME), "!keep.tmp\n").unwrap();
+        let rules = IgnoreRules::new(
+            &root,
+            &[".config/".to_owned()],
+            &["*.bak".to_owned()],
+            &["node_modules".to_owned()],
+        );
+        assert!(!rules.is_ignored(&root.join("photos/a.jpg"), false));
+        assert!(rules.is_ignored(&root.join(".cache/a.jpg"), false));
+        assert!(rules.is_ignored(&root.join("Node_Modules"), true));
+        assert!(!rules.is_ignored(&root.join(".config/app.toml"), false));
+        assert!(!rules.is_ignored(&root.join(".config/.secret"), false));
+        assert!(!rules.is_ignored(&root.join("photos/.picasa.ini"), false));
+        assert!(rules.is_ignored(&root.join("photos/.thumbnails"), true));
+        assert!(rules.is_ignored(&root.join("old.bak"), false));
+        assert!(rules.is_ignored(&root.join("scratch.tmp"), false));
+        assert!(!rules.is_ignored(&root.join("photos/keep.tmp"), false));
+        assert!(rules.is_ignored(&root.join("photos/other.tmp"), false));
+        assert!(rules.is_ignored(&root.join("photos/raw/a.dng"), false));
+        assert!(!rules.is_entry_ignored(&root.join("photos/raw/a.dng"), false));
+        assert!(!rules.is_ignored(Path::new("/elsewhere/.hidden"), false));
+
+        std::fs::write(root.join(IGNORE_FILE_NAME), "").unwrap();
+        assert!(rules.is_ignored(&root.join("scratch.tmp"), false));
+        rules.forget_ignore_file(&root);
+        assert!(!rules.is_ignored(&root.join("scratch.tmp"), false));
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -1,3 +1,4 @@
+use std::any::Any;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -6,14 +7,145 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant, SystemTime};
 
+use rusqlite::Transaction;
 use tokio::sync::mpsc::Sender;
 
 use crate::database::{
-    Database, IndexedLocationMetadata, MediaIndexObservation, ScanHistoryEntry, ScanOutcome,
-    ScanTrigger, ScannedFolder,
+    Database, DuplicateCopy, DuplicateGroup, IndexedLocationMetadata, MediaIndexObservation,
+    ScanCheckpoint, ScanHistoryEntry, ScanOutcome, ScanTrigger, ScannedFolder,
 };
+use crate::ignore_rules::IgnoreRules;
 use crate::managed_folder::{Blake3Hash, ManagedFolder};
 
+mod audio;
+mod content;
+mod duplicates;
+mod media;
+mod perceptual;
+mod photo;
+mod scrub;
+pub(crate) mod sniff;
+
+pub use duplicates::{DuplicateAction, TRASH_DIRECTORY_NAME, collapse_hardlinks};
+pub use perceptual::similar_clusters;
+pub use sniff::mime_types_conflict;
+
+/// A named pass over scanned files.
+///
+/// Scanned folders opt into indexers by listing their names in
+/// `ScannedFolder.indexers`. Every file an indexer accepts is recorded in
+/// `scanned_folder_locations` under the indexer's name. Indexers that keep
+/// their own rows read the file in [`Indexer::extract`] before the scan's
+/// write transaction opens and store the result in [`Indexer::write`].
+pub trait Indexer: Send + Sync {
+    /// Stable name stored in `ScannedFolder.indexers` and the membership table.
+    fn name(&self) -> &'static str;
+
+    /// Short label shown next to the indexer in Settings.
+    fn description(&self) -> &'static str;
+
+    fn accepts(&self, _file: &ObservedFile<'_>) -> bool {
+        true
+    }
+
+    /// Reads what the indexer stores about an accepted file. Only called when
+    /// the file is new to this indexer in the folder or its content changed,
+    /// and never while the database is locked for writing.
+    fn extract(&self, _file: &ObservedFile<'_>) -> anyhow::Result<Option<IndexerRows>> {
+        Ok(None)
+    }
+
+    /// Stores rows returned by [`Indexer::extract`] for `observation`. Runs
+    /// inside the scan transaction, so it must not touch the file.
+    fn write(
+        &self,
+        _transaction: &Transaction<'_>,
+        _observation: &MediaIndexObservation,
+        _rows: IndexerRows,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Removes rows for files that no longer belong to any Scanned folder.
+    /// Called after a complete scan has dropped stale memberships.
+    fn prune(&self, _transaction: &Transaction<'_>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// What an indexer extracted from one file, handed back to the same
+/// indexer's [`Indexer::write`].
+pub type IndexerRows = Box<dyn Any + Send>;
+
+/// Recovers the concrete rows an indexer extracted.
+pub fn downcast_rows<T: 'static>(indexer: &dyn Indexer, rows: IndexerRows) -> anyhow::Result<T> {
+    rows.downcast::<T>().map(|rows| *rows).map_err(|_| {
+        anyhow::anyhow!(
+            "{} indexer was given rows it did not extract",
+            indexer.name()
+        )
+    })
+}
+
+/// A file seen during a scan, with read access through its Scanned folder.
+pub struct ObservedFile<'a> {
+    pub folder: &'a ManagedFolder,
+    pub observation: &'a MediaIndexObservation,
+}
+
+impl ObservedFile<'_> {
+    pub fn read(&self, limit: Option<u64>) -> anyhow::Result<Vec<u8>> {
+        self.folder.read(&self.observation.path, limit)
+    }
+}
+
+static INDEXERS: [&dyn Indexer; 5] = [
+    &media::MediaIndexer,
+    &photo::PhotoIndexer,
+    &audio::AudioTagIndexer,
+    &content::ContentIndexer,
+    &perceptual::PerceptualHashIndexer,
+];
+
+pub fn registered_indexers() -> &'static [&'static dyn Indexer] {
+    &INDEXERS
+}
+
+pub fn find_indexer(name: &str) -> Option<&'static dyn Indexer> {
+    INDEXERS
+        .iter()
+        .copied()
+        .find(|indexer| indexer.name() == name)
+}
+
+/// Resolves the indexers a folder opted into, skipping names this build does not know.
+pub fn folder_indexers(folder: &ScannedFolder) -> Vec<&'static dyn Indexer> {
+    folder
+        .indexer_names()
+        .iter()
+        .filter_map(|name| {
+            let indexer = find_indexer(name);
+            if indexer.is_none() {
+                log::warn!(
+                    "Scanned folder {} uses unknown indexer '{name}'",
+                    folder.path
+                );
+            }
+            indexer
+        })
+        .collect()
+}
+
+/// Whether scans and the watcher look at `folder` at all: it is enabled and
+/// opted into at least one indexer this build knows.
+pub fn is_indexed(folder: &ScannedFolder) -> bool {
+    folder.enabled
+        && folder
+            .indexer_names()
+            .iter()
+            .any(|name| find_indexer(name).is_some())
+}
+
 #[derive(Debug, Clone)]
 pub enum IndexerEvent {
     Started {
@@ -35,6 +167,44 @@ pub enum IndexerEvent {
     Failed {
         message: String,
     },
+    /// Watcher-reported paths were applied without a folder traversal.
+    PathsIndexed {
+        folder_id: u32,
+        updated: usize,
+        removed: usize,
+    },
+    /// Targeted indexing failed or covered too many paths, so only a full
+    /// scan can catch up.
+    RescanNeeded {
+        folder_id: u32,
+    },
+    /// A scrub run read files back; `failed` of them no longer match their
+    /// hash or could not be read.
+    ScrubFinished {
+        verified: usize,
+        failed: usize,
+    },
+    /// Duplicate copies were trashed or hardlinked. `changed` lists the
+    /// Scanned folder and path of every copy that was replaced or moved.
+    DuplicatesResolved {
+        action: DuplicateAction,
+        changed: Vec<(u32, PathBuf)>,
+        reclaimed_bytes: u64,
+        errors: Vec<String>,
+    },
+    /// Duplicate groups of a node, with hardlinked copies counted once.
+    DuplicatesLoaded {
+        groups: Vec<DuplicateGroup>,
+    },
+}
+
+enum WorkerRequest {
+    Scan(IndexRequest),
+    Paths(PathsRequest),
+    Maintenance,
+    Scrub(scrub::ScrubRequest),
+    Dedupe(duplicates::DedupeRequest),
+    Duplicates { node_id: Vec<u8> },
 }
 
 #[derive(Clone)]
@@ -49,23 +219,79 @@ struct IndexRequest {
     cancellations: HashMap<u32, Arc<AtomicBool>>,
 }
 
+/// Paths the filesystem watcher saw change inside one Scanned folder.
+struct PathsRequest {
+    folder: ScannedFolder,
+    node_id: Vec<u8>,
+    paths: Vec<PathBuf>,
+    ignored_directory_names: Vec<String>,
+    max_file_size_bytes: u64,
+}
+
 /// Runs all blocking filesystem traversal, hashing, and SQLite index writes away
 /// from the UI task. Requests are processed in order so explicit user scans are
 /// never lost behind a watcher-triggered scan.
 pub struct IndexerWorker {
-    requests: StdSender<IndexRequest>,
+    requests: StdSender<WorkerRequest>,
     cancellations: Arc<Mutex<HashMap<u32, Arc<AtomicBool>>>>,
 }
 
 impl IndexerWorker {
     pub fn start(database_path: PathBuf, events: Sender<IndexerEvent>) -> Self {
-        let (requests, receiver) = mpsc::channel::<IndexRequest>();
+        let (requests, receiver) = mpsc::channel::<WorkerRequest>();
         let cancellations = Arc::new(Mutex::new(HashMap::<u32, Arc<AtomicBool>>::new()));
         let worker_cancellations = cancellations.clone();
         thread::Builder::new()
             .name("puppydrive-indexer".to_owned())
             .spawn(move || {
                 while let Ok(request) = receiver.recv() {
+                    let request = match request {
+                        WorkerRequest::Scan(request) => request,
+                        WorkerRequest::Paths(request) => {
+                            let folder_id = request.folder.id;
+                            if let Err(error) = index_paths(&database_path, &events, request) {
+                                log::warn!(
+                                    "incremental indexing failed for scanned folder {folder_id}: {error:#}"
+                                );
+                                let _ = events.try_send(IndexerEvent::RescanNeeded { folder_id });
+                            }
+                            continue;
+                        }
+                        WorkerRequest::Maintenance => {
+                            let result = Database::open(&database_path)
+                                .and_then(|database| database.run_maintenance());
+                            match result {
+                                Ok(()) => log::info!("database maintenance finished"),
+                                Err(error) => {
+                                    log::warn!("database maintenance failed: {error:#}")
+                                }
+                            }
+                            continue;
+                        }
+                        WorkerRequest::Scrub(request) => {
+                            if let Err(error) = scrub::scrub(&database_path, &events, request) {
+                                log::error!("scrub failed: {error:#}");
+                                let _ = events.try_send(IndexerEvent::Failed {
+                                    message: format!("{error:#}"),
+                                });
+                            }
+                            continue;
+                        }
+                        WorkerRequest::Dedupe(request) => {
+                            let _ = events.try_send(duplicates::resolve(request));
+                            continue;
+                        }
+                        WorkerRequest::Duplicates { node_id } => {
+                            match duplicates::load(&database_path, &node_id) {
+                                Ok(groups) => {
+                                    let _ =
+                                        events.try_send(IndexerEvent::DuplicatesLoaded { groups });
+                                }
+                                Err(error) => log::error!("failed loading duplicates: {error:#}"),
+                            }
+                            continue;
+                        }
+                    };
                     log::info!(
                         "indexer worker received scan request for folders {:?}",
                         request
@@ -131,7 +357,111 @@ impl IndexerWorker {
             trigger,
             cancellations,
         };
-        if self.requests.send(request).is_err() {
+        if self.requests.send(WorkerRequest::Scan(request)).is_err() {
+            log::error!("PuppyDrive indexer thread has stopped");
+        }
+    }
+
+    /// Re-indexes only `paths` in `folder`. Removed paths drop out of the
+    /// index together with everything below them, and new directories are
+    /// walked so a directory moved into place is picked up whole. Changes
+    /// covering more than one write batch fall back to a full scan.
+    pub fn request_paths(
+        &self,
+        folder: ScannedFolder,
+        node_id: Vec<u8>,
+        paths: Vec<PathBuf>,
+        ignored_directory_names: Vec<String>,
+        max_file_size_bytes: u64,
+    ) {
+        let request = PathsRequest {
+            folder,
+            node_id,
+            paths,
+            ignored_directory_names,
+            max_file_size_bytes,
+        };
+        if self.requests.send(WorkerRequest::Paths(request)).is_err() {
+            log::error!("PuppyDrive indexer thread has stopped");
+        }
+    }
+
+    /// Runs database upkeep on the worker thread, after any scans already
+    /// queued so it never competes with them for the write lock.
+    pub fn request_maintenance(&self) {
+        if self.requests.send(WorkerRequest::Maintenance).is_err() {
+            log::error!("PuppyDrive indexer thread has stopped");
+        }
+    }
+
+    /// Reads back files in `folders` not verified since `verified_before`
+    /// and checks them against their indexed hash.
+    pub fn request_scrub(
+        &self,
+        folders: Vec<ScannedFolder>,
+        node_id: Vec<u8>,
+        verified_before: i64,
+    ) {
+        let request = scrub::ScrubRequest {
+            folders,
+            node_id,
+            verified_before,
+            byte_budget: scrub::SCRUB_BYTES_PER_RUN,
+        };
+        if self.requests.send(WorkerRequest::Scrub(request)).is_err() {
+            log::error!("PuppyDrive indexer thread has stopped");
+        }
+    }
+
+    /// Lists the duplicate groups of `node_id` on the worker thread, where
+    /// checking copies for hardlinks cannot hold up the UI.
+    pub fn request_duplicates(&self, node_id: Vec<u8>) {
+        if self
+            .requests
+            .send(WorkerRequest::Duplicates { node_id })
+            .is_err()
+        {
+            log::error!("PuppyDrive indexer thread has stopped");
+        }
+    }
+
+    /// Keeps `group.copies[keep]` and applies `action` to the other copies.
+    pub fn request_dedupe(
+        &self,
+        folders: Vec<ScannedFolder>,
+        group: &DuplicateGroup,
+        keep: usize,
+        action: DuplicateAction,
+    ) {
+        let Some(kept) = group.copies.get(keep) else {
+            return;
+        };
+        let others = group
+            .copies
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != keep)
+            .map(|(_, copy)| copy.clone())
+            .collect();
+        self.request_dedupe_copies(folders, kept.clone(), others, action);
+    }
+
+    /// Keeps `keep` and applies `action` to `others`. Only copies with the
+    /// same hash as `keep` can be hardlinked.
+    pub fn request_dedupe_copies(
+        &self,
+        folders: Vec<ScannedFolder>,
+        keep: DuplicateCopy,
+        others: Vec<DuplicateCopy>,
+        action: DuplicateAction,
+    ) {
+        let request = duplicates::DedupeRequest {
+            folders,
+            keep,
+            others,
+            action,
+        };
+        if self.requests.send(WorkerRequest::Dedupe(request)).is_err() {
             log::error!("PuppyDrive indexer thread has stopped");
         }
     }
@@ -161,7 +491,7 @@ fn index(
     let active = request
         .folders
         .iter()
-        .filter(|folder| folder.enabled && folder.indexes_media())
+        .filter(|folder| is_indexed(folder))
         .cloned()
         .collect::<Vec<_>>();
     log::info!(
@@ -177,16 +507,6 @@ fn index(
         folder_ids: active.iter().map(|folder| folder.id).collect(),
     });
     let database = Database::open(database_path)?;
-    let metadata_lookup_started = Instant::now();
-    let previous_locations = active
-        .iter()
-        .map(|folder| {
-            database
-                .scanned_folder_location_metadata(&request.node_id, folder.id)
-                .map(|locations| (folder.id, locations))
-        })
-        .collect::<anyhow::Result<HashMap<_, _>>>()?;
-    let metadata_lookup_duration = metadata_lookup_started.elapsed();
     let folders = active
         .iter()
         .filter_map(
@@ -206,6 +526,26 @@ fn index(
             },
         )
         .collect::<HashMap<_, _>>();
+    // Only a resumed request continues from checkpoints; any other scan
+    // starts over and replaces them.
+    let checkpoints = if request.trigger == ScanTrigger::Resumed {
+        database
+            .scan_checkpoints()?
+            .into_iter()
+            .filter(|checkpoint| checkpoint.node_id == request.node_id)
+            .map(|checkpoint| (checkpoint.scanned_folder_id, checkpoint))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let mut writer = ScanWriter::new(
+        &database,
+        &request.node_id,
+        &active,
+        &folders,
+        started_at,
+        &checkpoints,
+    )?;
     let scan = scan_media(
         &active,
         &folders,
@@ -213,35 +553,26 @@ fn index(
         request.max_directories,
         &request.ignored_directory_names,
         request.max_file_size_bytes,
-        &previous_locations,
         &request.cancellations,
+        &checkpoints,
+        &mut writer,
         events,
-    );
+    )?;
     log::info!(
-        "indexer traversal finished: {} folder observations, truncated: {}, errors: {}",
-        scan.observations.len(),
+        "indexer traversal finished: {} files, truncated: {}, errors: {}",
+        scan.folder_files.values().sum::<usize>(),
         scan.truncated,
         scan.errors.len()
     );
     let finished_at = system_time_millis(SystemTime::now());
-    let database_write_started = Instant::now();
     for folder in &active {
         let folder_id = folder.id;
         let available = folders.contains_key(&folder_id);
-        let observations = scan
-            .observations
-            .get(&folder_id)
-            .map_or(&[][..], Vec::as_slice);
-        if available {
-            log::debug!(
-                "indexer writing {} observations for scanned folder {folder_id}",
-                observations.len()
-            );
-            let folder_complete = !scan.truncated
-                && !scan.cancelled_folders.contains(&folder_id)
-                && scan.folder_errors.get(&folder_id).is_none_or(Vec::is_empty);
-            database.sync_media_scan(&request.node_id, folder_id, observations, folder_complete)?;
-        }
+        let folder_complete = available
+            && !scan.truncated
+            && !scan.cancelled_folders.contains(&folder_id)
+            && scan.folder_errors.get(&folder_id).is_none_or(Vec::is_empty);
+        writer.finish(folder_id, folder_complete)?;
         let mut messages = scan
             .folder_errors
             .get(&folder_id)
@@ -264,7 +595,7 @@ fn index(
             scanned_folder_id: folder_id,
             trigger: request.trigger,
             outcome,
-            started_at,
+            started_at: writer.started_at(folder_id),
             finished_at,
             directories_scanned: *scan.folder_directories.get(&folder_id).unwrap_or(&0),
             files_indexed: *scan.folder_files.get(&folder_id).unwrap_or(&0),
@@ -272,11 +603,11 @@ fn index(
         })?;
         let _ = events.try_send(IndexerEvent::FolderFinished { history });
     }
-    let database_write_duration = database_write_started.elapsed();
+    let database_write_duration = writer.write_duration;
     log::info!(
         "indexer scan telemetry: total={:?}, metadata_lookup={:?}, traversal={:?}, hashing_wall={:?} (read_total={:?}, hash_update_total={:?}, open_total={:?}; {} files, {} bytes; workers peak={}, final={}), reused_hashes={}, database_write={:?}",
         scan_started.elapsed(),
-        metadata_lookup_duration,
+        scan.metadata_lookup_duration,
         scan.traversal_duration,
         scan.hashing_duration,
         scan.file_read_duration,
@@ -296,14 +627,336 @@ fn index(
     Ok(())
 }
 
+fn index_paths(
+    database_path: &Path,
+    events: &Sender<IndexerEvent>,
+    request: PathsRequest,
+) -> anyhow::Result<()> {
+    let indexers = folder_indexers(&request.folder);
+    if !request.folder.enabled || indexers.is_empty() {
+        return Ok(());
+    }
+    let folder_id = request.folder.id;
+    let folder = ManagedFolder::open(folder_id, &request.folder.path)?;
+    let database = Database::open(database_path)?;
+    // The watcher reports paths below the configured root, which may differ
+    // from the canonical root that indexed paths are stored under.
+    let configured_root = Path::new(&request.folder.path);
+    let mut pending = request
+        .paths
+        .iter()
+        .filter_map(|path| match path.strip_prefix(configured_root) {
+            Ok(relative) => Some(folder.root().join(relative)),
+            Err(_) => folder.contains(path).then(|| path.clone()),
+        })
+        .collect::<VecDeque<_>>();
+    let rules = IgnoreRules::for_folder(
+        &request.folder,
+        folder.root(),
+        &request.ignored_directory_names,
+    );
+    let mut visited = HashSet::new();
+    let mut observations = Vec::new();
+    let mut removed = Vec::new();
+    while let Some(path) = pending.pop_front() {
+        if !visited.insert(path.clone()) {
+            continue;
+        }
+        // A large tree moved into the folder is left to a full scan, which
+        // writes in bounded batches, rather than held here all at once.
+        if visited.len() > SCAN_WRITE_BATCH_SIZE {
+            log::info!(
+                "changes in scanned folder {folder_id} cover more than {SCAN_WRITE_BATCH_SIZE} paths; rescanning it"
+            );
+            let _ = events.try_send(IndexerEvent::RescanNeeded { folder_id });
+            return Ok(());
+        }
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            removed.push(path);
+            continue;
+        };
+        // Anything indexed before the rules skipped it drops out.
+        if rules.is_ignored(&path, metadata.is_dir()) {
+            removed.push(path);
+            continue;
+        }
+        if metadata.is_dir() {
+            match folder.read_dir(&path) {
+                Ok(entries) => pending.extend(entries.iter().map(|entry| entry.path())),
+                Err(error) => log::debug!("unable to read {}: {error:#}", path.display()),
+            }
+            continue;
+        }
+        if !metadata.is_file() {
+            continue;
+        }
+        let Some(mut observation) =
+            observe_file(&folder, path.clone(), request.max_file_size_bytes)
+        else {
+            // Grown past the size limit, or gone again since the event.
+            removed.push(path);
+            continue;
+        };
+        let previous = database.location_metadata(&request.node_id, &observation.path)?;
+        if !reuse_hash(&folder, &mut observation, previous.as_ref()) {
+            match folder.blake3(&observation.path) {
+                Ok(hash) => {
+                    observation.hash = Some(hash);
+                    sniff_observation(&folder, &mut observation);
+                }
+                Err(error) => {
+                    log::debug!("unable to hash {}: {error:#}", observation.path.display())
+                }
+            }
+        }
+        observations.push(observation);
+    }
+    database.apply_path_changes(
+        &request.node_id,
+        &folder,
+        &observations,
+        &removed,
+        &indexers,
+    )?;
+    log::debug!(
+        "indexer applied {} updated and {} removed paths in scanned folder {folder_id}",
+        observations.len(),
+        removed.len()
+    );
+    let _ = events.try_send(IndexerEvent::PathsIndexed {
+        folder_id,
+        updated: observations.len(),
+        removed: removed.len(),
+    });
+    Ok(())
+}
+
+/// Observations buffered before a batch is committed. Small enough that a
+/// crash or cancel loses little work, large enough to keep transactions
+/// from dominating the scan.
+const SCAN_WRITE_BATCH_SIZE: usize = 500;
+
+/// Streams a scan's observations to SQLite in bounded batches, so indexed
+/// files survive a crash or cancel. At most one batch per folder is held
+/// in memory; hashes still being computed are buffered separately.
+struct ScanWriter<'a> {
+    database: &'a Database,
+    node_id: &'a [u8],
+    folders: &'a HashMap<u32, ManagedFolder>,
+    indexers: HashMap<u32, Vec<&'static dyn Indexer>>,
+    scan_ids: HashMap<u32, Vec<u8>>,
+    started_at: HashMap<u32, i64>,
+    pending: HashMap<u32, Vec<MediaIndexObservation>>,
+    write_duration: Duration,
+}
+
+impl<'a> ScanWriter<'a> {
+    /// Starts a scan of `roots`, continuing the ones with a checkpoint. Each
+    /// available folder is checkpointed straight away so even an early crash
+    /// leaves something to resume.
+    fn new(
+        database: &'a Database,
+        node_id: &'a [u8],
+        roots: &[ScannedFolder],
+        folders: &'a HashMap<u32, ManagedFolder>,
+        started_at: i64,
+        checkpoints: &HashMap<u32, ScanCheckpoint>,
+    ) -> anyhow::Result<Self> {
+        let writer = Self {
+            database,
+            node_id,
+            folders,
+            indexers: roots
+                .iter()
+                .map(|root| (root.id, folder_indexers(root)))
+                .collect(),
+            scan_ids: roots
+                .iter()
+                .map(|root| {
+                    let scan_id = checkpoints
+                        .get(&root.id)
+                        .map_or_else(crate::database::new_scan_id, |checkpoint| {
+                            checkpoint.scan_id.clone()
+                        });
+                    (root.id, scan_id)
+                })
+                .collect(),
+            started_at: roots
+                .iter()
+                .map(|root| {
+                    let started_at = checkpoints
+                        .get(&root.id)
+                        .map_or(started_at, |checkpoint| checkpoint.started_at);
+                    (root.id, started_at)
+                })
+                .collect(),
+            pending: HashMap::new(),
+            write_duration: Duration::ZERO,
+        };
+        for (folder_id, folder) in folders {
+            match checkpoints.get(folder_id) {
+                Some(checkpoint) => writer.save_checkpoint(
+                    *folder_id,
+                    checkpoint.pending_directories.clone(),
+                    checkpoint.directories_scanned,
+                    checkpoint.files_indexed,
+                )?,
+                None => {
+                    writer.save_checkpoint(*folder_id, vec![folder.root().to_path_buf()], 0, 0)?
+                }
+            }
+        }
+        Ok(writer)
+    }
+
+    /// The indexed state of the files directly inside `directory`.
+    fn directory_location_metadata(
+        &self,
+        folder_id: u32,
+        directory: &Path,
+    ) -> anyhow::Result<HashMap<PathBuf, IndexedLocationMetadata>> {
+        self.database
+            .directory_location_metadata(self.node_id, folder_id, directory)
+    }
+
+    fn started_at(&self, folder_id: u32) -> i64 {
+        self.started_at.get(&folder_id).copied().unwrap_or_default()
+    }
+
+    fn push(&mut self, folder_id: u32, observation: MediaIndexObservation) -> anyhow::Result<()> {
+        let pending = self.pending.entry(folder_id).or_default();
+        pending.push(observation);
+        if pending.len() >= SCAN_WRITE_BATCH_SIZE {
+            self.flush(folder_id)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, folder_id: u32) -> anyhow::Result<()> {
+        let observations = self.pending.remove(&folder_id).unwrap_or_default();
+        let (Some(folder), Some(scan_id)) =
+            (self.folders.get(&folder_id), self.scan_ids.get(&folder_id))
+        else {
+            return Ok(());
+        };
+        if observations.is_empty() {
+            return Ok(());
+        }
+        log::debug!(
+            "indexer writing {} observations for scanned folder {folder_id}",
+            observations.len()
+        );
+        let started = Instant::now();
+        self.database.write_scan_batch(
+            self.node_id,
+            folder,
+            scan_id,
+            &observations,
+            self.indexers.get(&folder_id).map_or(&[][..], Vec::as_slice),
+        )?;
+        self.write_duration += started.elapsed();
+        Ok(())
+    }
+
+    /// Commits everything buffered and records which directories each
+    /// folder still has queued. Pending hashes must be flushed first, so that
+    /// every directory already taken off the queue is fully written.
+    fn checkpoint(
+        &mut self,
+        queue: &VecDeque<(ManagedFolder, PathBuf)>,
+        folder_directories: &HashMap<u32, usize>,
+        folder_files: &HashMap<u32, usize>,
+    ) -> anyhow::Result<()> {
+        let folder_ids = self.folders.keys().copied().collect::<Vec<_>>();
+        for folder_id in folder_ids {
+            self.flush(folder_id)?;
+            let pending_directories = queue
+                .iter()
+                .filter(|(folder, _)| folder.id() == folder_id)
+                .map(|(_, directory)| directory.clone())
+                .collect();
+            self.save_checkpoint(
+                folder_id,
+                pending_directories,
+                *folder_directories.get(&folder_id).unwrap_or(&0),
+                *folder_files.get(&folder_id).unwrap_or(&0),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn save_checkpoint(
+        &self,
+        folder_id: u32,
+        pending_directories: Vec<PathBuf>,
+        directories_scanned: usize,
+        files_indexed: usize,
+    ) -> anyhow::Result<()> {
+        let Some(scan_id) = self.scan_ids.get(&folder_id) else {
+            return Ok(());
+        };
+        self.database.save_scan_checkpoint(&ScanCheckpoint {
+            scanned_folder_id: folder_id,
+            node_id: self.node_id.to_vec(),
+            scan_id: scan_id.clone(),
+            started_at: self.started_at(folder_id),
+            pending_directories,
+            directories_scanned,
+            files_indexed,
+        })
+    }
+
+    /// Commits what is left for `folder_id` and closes its scan. The
+    /// checkpoint goes too: a stopped, truncated or failed scan is not
+    /// resumed, only one the daemon never got to finish.
+    fn finish(&mut self, folder_id: u32, complete: bool) -> anyhow::Result<()> {
+        self.flush(folder_id)?;
+        let started = Instant::now();
+        if let Some(scan_id) = self
+            .scan_ids
+            .get(&folder_id)
+            .filter(|_| self.folders.contains_key(&folder_id))
+        {
+            self.database.finish_scan(folder_id, scan_id, complete)?;
+        }
+        self.database.delete_scan_checkpoint(folder_id)?;
+        self.write_duration += started.elapsed();
+        Ok(())
+    }
+}
+
+/// How often a scan records where it is, bounding the work a restart repeats.
+const SCAN_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct HashTotals {
+    duration: Duration,
+    files: usize,
+    bytes: u64,
+    open_duration: Duration,
+    read_duration: Duration,
+    update_duration: Duration,
+}
+
+impl HashTotals {
+    fn add(&mut self, batch: &HashBatchResult) {
+        self.duration += batch.duration;
+        self.files += batch.files_hashed;
+        self.bytes += batch.bytes_hashed;
+        self.open_duration += batch.file_open_duration;
+        self.read_duration += batch.file_read_duration;
+        self.update_duration += batch.hash_update_duration;
+    }
+}
+
 struct ScanResult {
-    observations: HashMap<u32, Vec<MediaIndexObservation>>,
     folder_directories: HashMap<u32, usize>,
     folder_files: HashMap<u32, usize>,
     cancelled_folders: HashSet<u32>,
     truncated: bool,
     errors: Vec<String>,
     folder_errors: HashMap<u32, Vec<String>>,
+    metadata_lookup_duration: Duration,
     traversal_duration: Duration,
     hashing_duration: Duration,
     files_hashed: usize,
@@ -381,29 +1034,34 @@ fn scan_media(
     max_directories: usize,
     ignored_directory_names: &[String],
     max_file_size_bytes: u64,
-    previous_locations: &HashMap<u32, HashMap<PathBuf, IndexedLocationMetadata>>,
     cancellations: &HashMap<u32, Arc<AtomicBool>>,
+    checkpoints: &HashMap<u32, ScanCheckpoint>,
+    writer: &mut ScanWriter<'_>,
     events: &Sender<IndexerEvent>,
-) -> ScanResult {
+) -> anyhow::Result<ScanResult> {
     let traversal_started = Instant::now();
+    let mut metadata_lookup_duration = Duration::ZERO;
     let mut queue = VecDeque::new();
-    let mut observations: HashMap<u32, Vec<MediaIndexObservation>> = HashMap::new();
     let mut errors = Vec::new();
     let mut folder_errors = HashMap::<u32, Vec<String>>::new();
     let mut truncated = false;
-    let mut hashing_duration = Duration::ZERO;
-    let mut files_hashed = 0;
-    let mut bytes_hashed = 0;
+    let mut hashing = HashTotals::default();
     let mut reused_hashes = 0;
-    let mut file_open_duration = Duration::ZERO;
-    let mut file_read_duration = Duration::ZERO;
-    let mut hash_update_duration = Duration::ZERO;
     let mut accepted_files = 0;
     let mut pending_hashes = Vec::new();
     let mut hash_pool = AdaptiveHashPool::new();
     let mut cancelled_folders = HashSet::new();
+    let mut folder_directories = HashMap::<u32, usize>::new();
+    let mut folder_files = HashMap::<u32, usize>::new();
+    let rules = roots
+        .iter()
+        .filter_map(|root| {
+            let folder = folders.get(&root.id)?;
+            let rules = IgnoreRules::for_folder(root, folder.root(), ignored_directory_names);
+            Some((root.id, rules))
+        })
+        .collect::<HashMap<_, _>>();
     for root in roots {
-        observations.entry(root.id).or_default();
         if is_cancelled(cancellations, root.id) {
             cancelled_folders.insert(root.id);
             continue;
@@ -414,11 +1072,24 @@ fn scan_media(
             folder_errors.entry(root.id).or_default().push(error);
             continue;
         };
-        queue.push_back((folder.clone(), folder.root().to_path_buf()));
+        match checkpoints.get(&root.id) {
+            Some(checkpoint) => {
+                queue.extend(
+                    checkpoint
+                        .pending_directories
+                        .iter()
+                        .filter(|directory| folder.contains(directory))
+                        .map(|directory| (folder.clone(), directory.clone())),
+                );
+                folder_directories.insert(root.id, checkpoint.directories_scanned);
+                folder_files.insert(root.id, checkpoint.files_indexed);
+                accepted_files += checkpoint.files_indexed;
+            }
+            None => queue.push_back((folder.clone(), folder.root().to_path_buf())),
+        }
     }
     let mut visited = HashSet::new();
-    let mut folder_directories = HashMap::<u32, usize>::new();
-    let mut folder_files = HashMap::<u32, usize>::new();
+    let mut last_checkpoint = Instant::now();
     while let Some((folder, directory)) = queue.pop_front() {
         if is_cancelled(cancellations, folder.id()) {
             cancelled_folders.insert(folder.id());
@@ -445,6 +1116,11 @@ fn scan_media(
             continue;
         };
         entries.sort_by_key(|entry| entry.file_name().to_string_lossy().to_lowercase());
+        // Reuse metadata is fetched one directory at a time so memory follows
+        // the largest directory rather than the whole library.
+        let lookup_started = Instant::now();
+        let previous_locations = writer.directory_location_metadata(folder.id(), &directory)?;
+        metadata_lookup_duration += lookup_started.elapsed();
         for entry in entries {
             if is_cancelled(cancellations, folder.id()) {
                 cancelled_folders.insert(folder.id());
@@ -461,15 +1137,14 @@ fn scan_media(
                 continue;
             }
             let path = entry.path();
+            if rules
+                .get(&folder.id())
+                .is_some_and(|rules| rules.is_entry_ignored(&path, file_type.is_dir()))
+            {
+                continue;
+            }
             if file_type.is_dir() {
-                let name = entry.file_name().to_string_lossy().into_owned();
-                let ignored = name.starts_with('.')
-                    || ignored_directory_names
-                        .iter()
-                        .any(|ignored| ignored.eq_ignore_ascii_case(&name));
-                if !ignored
-                    && (max_directories == 0 || visited.len() + queue.len() < max_directories)
-                {
+                if max_directories == 0 || visited.len() + queue.len() < max_directories {
                     queue.push_back((folder.clone(), path));
                 }
                 continue;
@@ -477,55 +1152,23 @@ fn scan_media(
             if !file_type.is_file() {
                 continue;
             }
-            let mime_type = file_mime_type(&path).to_owned();
-            let Ok(path) = folder.canonicalize(path) else {
-                continue;
-            };
-            let Ok(metadata) = folder.metadata(&path) else {
-                continue;
-            };
-            if max_file_size_bytes > 0 && metadata.len() > max_file_size_bytes {
-                log::debug!(
-                    "indexer skipping {} because it exceeds the {} byte size limit",
-                    path.display(),
-                    max_file_size_bytes
-                );
+            let Some(mut observation) = observe_file(&folder, path, max_file_size_bytes) else {
                 continue;
-            }
-            let current_path = path.display().to_string();
-            let created_at = metadata.created().ok().map(system_time_millis);
-            let modified_at = metadata.modified().ok().map(system_time_millis);
-            let reused_hash = reusable_hash(
-                previous_locations
-                    .get(&folder.id())
-                    .and_then(|locations| locations.get(&path)),
-                metadata.len(),
-                created_at,
-                modified_at,
-            );
-            let observation = MediaIndexObservation {
-                hash: None,
-                path,
-                size: metadata.len(),
-                mime_type: Some(mime_type),
-                created_at,
-                modified_at,
-                accessed_at: metadata.accessed().ok().map(system_time_millis),
             };
+            let current_path = observation.path.display().to_string();
+            let previous = previous_locations.get(&observation.path);
             accepted_files += 1;
-            if let Some(hash) = reused_hash {
+            if reuse_hash(&folder, &mut observation, previous) {
                 reused_hashes += 1;
-                let mut observation = observation;
-                observation.hash = Some(hash);
                 record_observation(
                     folder.id(),
                     observation,
                     current_path,
-                    &mut observations,
+                    writer,
                     &folder_directories,
                     &mut folder_files,
                     events,
-                );
+                )?;
             } else {
                 pending_hashes.push(HashCandidate {
                     folder: folder.clone(),
@@ -536,19 +1179,14 @@ fn scan_media(
                     let batch = flush_hashes(
                         &mut pending_hashes,
                         &mut hash_pool,
-                        &mut observations,
+                        writer,
                         &folder_directories,
                         &mut folder_files,
                         cancellations,
                         &mut cancelled_folders,
                         events,
-                    );
-                    hashing_duration += batch.duration;
-                    files_hashed += batch.files_hashed;
-                    bytes_hashed += batch.bytes_hashed;
-                    file_open_duration += batch.file_open_duration;
-                    file_read_duration += batch.file_read_duration;
-                    hash_update_duration += batch.hash_update_duration;
+                    )?;
+                    hashing.add(&batch);
                 }
             }
         }
@@ -560,54 +1198,64 @@ fn scan_media(
                 current_path: Some(directory.display().to_string()),
             });
         }
+        if last_checkpoint.elapsed() >= SCAN_CHECKPOINT_INTERVAL {
+            let batch = flush_hashes(
+                &mut pending_hashes,
+                &mut hash_pool,
+                writer,
+                &folder_directories,
+                &mut folder_files,
+                cancellations,
+                &mut cancelled_folders,
+                events,
+            )?;
+            hashing.add(&batch);
+            writer.checkpoint(&queue, &folder_directories, &folder_files)?;
+            last_checkpoint = Instant::now();
+        }
     }
     let batch = flush_hashes(
         &mut pending_hashes,
         &mut hash_pool,
-        &mut observations,
+        writer,
         &folder_directories,
         &mut folder_files,
         cancellations,
         &mut cancelled_folders,
         events,
-    );
-    hashing_duration += batch.duration;
-    files_hashed += batch.files_hashed;
-    bytes_hashed += batch.bytes_hashed;
-    file_open_duration += batch.file_open_duration;
-    file_read_duration += batch.file_read_duration;
-    hash_update_duration += batch.hash_update_duration;
-    ScanResult {
-        observations,
+    )?;
+    hashing.add(&batch);
+    Ok(ScanResult {
         folder_directories,
         folder_files,
         cancelled_folders,
         truncated,
         errors,
         folder_errors,
+        metadata_lookup_duration,
         traversal_duration: traversal_started.elapsed(),
-        hashing_duration,
-        files_hashed,
-        bytes_hashed,
+        hashing_duration: hashing.duration,
+        files_hashed: hashing.files,
+        bytes_hashed: hashing.bytes,
         reused_hashes,
         hash_workers_peak: hash_pool.peak_workers,
         hash_workers_final: hash_pool.workers,
-        file_open_duration,
-        file_read_duration,
-        hash_update_duration,
-    }
+        file_open_duration: hashing.open_duration,
+        file_read_duration: hashing.read_duration,
+        hash_update_duration: hashing.update_duration,
+    })
 }
 
 fn flush_hashes(
     pending: &mut Vec<HashCandidate>,
     pool: &mut AdaptiveHashPool,
-    observations: &mut HashMap<u32, Vec<MediaIndexObservation>>,
+    writer: &mut ScanWriter<'_>,
     folder_directories: &HashMap<u32, usize>,
     folder_files: &mut HashMap<u32, usize>,
     cancellations: &HashMap<u32, Arc<AtomicBool>>,
     cancelled_folders: &mut HashSet<u32>,
     events: &Sender<IndexerEvent>,
-) -> HashBatchResult {
+) -> anyhow::Result<HashBatchResult> {
     pending.retain(|candidate| {
         let cancelled = is_cancelled(cancellations, candidate.folder.id());
         if cancelled {
@@ -616,7 +1264,7 @@ fn flush_hashes(
         !cancelled
     });
     if pending.is_empty() {
-        return HashBatchResult {
+        return Ok(HashBatchResult {
             candidates: Vec::new(),
             duration: Duration::ZERO,
             bytes_hashed: 0,
@@ -624,7 +1272,7 @@ fn flush_hashes(
             file_open_duration: Duration::ZERO,
             file_read_duration: Duration::ZERO,
             hash_update_duration: Duration::ZERO,
-        };
+        });
     }
     let batch = hash_candidates(std::mem::take(pending), pool.workers);
     for (candidate, hash) in batch.candidates.iter() {
@@ -634,14 +1282,14 @@ fn flush_hashes(
             candidate.folder.id(),
             observation,
             candidate.current_path.clone(),
-            observations,
+            writer,
             folder_directories,
             folder_files,
             events,
-        );
+        )?;
     }
     pool.observe(&batch);
-    batch
+    Ok(batch)
 }
 
 fn is_cancelled(cancellations: &HashMap<u32, Arc<AtomicBool>>, folder_id: u32) -> bool {
@@ -659,7 +1307,7 @@ fn hash_candidates(candidates: Vec<HashCandidate>, workers: usize) -> HashBatchR
         for _ in 0..workers {
             scope.spawn(|| {
                 loop {
-                    let Some(candidate) =
+                    let Some(mut candidate) =
                         queue.lock().expect("hash queue lock poisoned").pop_front()
                     else {
                         break;
@@ -668,6 +1316,9 @@ fn hash_candidates(candidates: Vec<HashCandidate>, workers: usize) -> HashBatchR
                         .folder
                         .blake3_timed(&candidate.observation.path)
                         .ok();
+                    if hash.is_some() {
+                        sniff_observation(&candidate.folder, &mut candidate.observation);
+                    }
                     results
                         .lock()
                         .expect("hash results lock poisoned")
@@ -706,12 +1357,12 @@ fn record_observation(
     folder_id: u32,
     observation: MediaIndexObservation,
     current_path: String,
-    observations: &mut HashMap<u32, Vec<MediaIndexObservation>>,
+    writer: &mut ScanWriter<'_>,
     folder_directories: &HashMap<u32, usize>,
     folder_files: &mut HashMap<u32, usize>,
     events: &Sender<IndexerEvent>,
-) {
-    observations.entry(folder_id).or_default().push(observation);
+) -> anyhow::Result<()> {
+    writer.push(folder_id, observation)?;
     let files_indexed = folder_files.entry(folder_id).or_default();
     *files_indexed += 1;
     if *files_indexed == 1 || files_indexed.is_multiple_of(10) {
@@ -722,6 +1373,63 @@ fn record_observation(
             current_path: Some(current_path),
         });
     }
+    Ok(())
+}
+
+/// Reads a file's metadata into an unhashed observation, or `None` when it
+/// is unreadable, outside `folder` or above the size limit.
+fn observe_file(
+    folder: &ManagedFolder,
+    path: PathBuf,
+    max_file_size_bytes: u64,
+) -> Option<MediaIndexObservation> {
+    let extension_mime_type = file_mime_type(&path).to_owned();
+    let path = folder.canonicalize(path).ok()?;
+    let metadata = folder.metadata(&path).ok()?;
+    if max_file_size_bytes > 0 && metadata.len() > max_file_size_bytes {
+        log::debug!(
+            "indexer skipping {} because it exceeds the {} byte size limit",
+            path.display(),
+            max_file_size_bytes
+        );
+        return None;
+    }
+    Some(MediaIndexObservation {
+        hash: None,
+        path,
+        size: metadata.len(),
+        mime_type: Some(extension_mime_type.clone()),
+        extension_mime_type: Some(extension_mime_type),
+        sniffed_mime_type: None,
+        created_at: metadata.created().ok().map(system_time_millis),
+        modified_at: metadata.modified().ok().map(system_time_millis),
+        accessed_at: metadata.accessed().ok().map(system_time_millis),
+    })
+}
+
+/// Copies the previous hash onto an unchanged file, returning false when it
+/// has to be hashed again.
+fn reuse_hash(
+    folder: &ManagedFolder,
+    observation: &mut MediaIndexObservation,
+    previous: Option<&IndexedLocationMetadata>,
+) -> bool {
+    let Some(hash) = reusable_hash(
+        previous,
+        observation.size,
+        observation.created_at,
+        observation.modified_at,
+    ) else {
+        return false;
+    };
+    observation.hash = Some(hash);
+    // Unchanged content keeps its sniffed type; locations indexed before
+    // sniffing existed are read once to fill it in.
+    match previous.and_then(|previous| previous.sniffed_mime_type.clone()) {
+        Some(sniffed) => classify_content(observation, Some(sniffed)),
+        None => sniff_observation(folder, observation),
+    }
+    true
 }
 
 fn reusable_hash(
@@ -738,7 +1446,23 @@ fn reusable_hash(
         .flatten()
 }
 
-fn file_mime_type(path: &Path) -> &'static str {
+fn sniff_observation(folder: &ManagedFolder, observation: &mut MediaIndexObservation) {
+    let sniffed = folder
+        .read(&observation.path, Some(sniff::SNIFF_BYTES))
+        .ok()
+        .and_then(|bytes| sniff::sniff_mime_type(&bytes));
+    classify_content(observation, sniffed.map(str::to_owned));
+}
+
+fn classify_content(observation: &mut MediaIndexObservation, sniffed: Option<String>) {
+    if let Some(extension) = observation.extension_mime_type.as_deref() {
+        observation.mime_type =
+            Some(sniff::effective_mime_type(extension, sniffed.as_deref()).to_owned());
+    }
+    observation.sniffed_mime_type = sniffed;
+}
+
+pub fn file_mime_type(path: &Path) -> &'static str {
     let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
         return "application/octet-stream";
     };
@@ -752,6 +1476,8 @@ fn file_mime_type(path: &Path) -> &'static str {
         "image/webp"
     } else if extension.eq_ignore_ascii_case("bmp") {
         "image/bmp"
+    } else if extension.eq_ignore_ascii_case("tif") || extension.eq_ignore_ascii_case("tiff") {
+        "image/tiff"
     } else if extension.eq_ignore_ascii_case("avif") {
         "image/avif"
     } else if extension.eq_ignore_ascii_case("svg") {
@@ -787,17 +1513,81 @@ fn file_mime_type(path: &Path) -> &'static str {
         "audio/ogg"
     } else if extension.eq_ignore_ascii_case("aif") || extension.eq_ignore_ascii_case("aiff") {
         "audio/aiff"
-    } else if extension.eq_ignore_ascii_case("txt") || extension.eq_ignore_ascii_case("md") {
+    } else if extension.eq_ignore_ascii_case("txt") || extension.eq_ignore_ascii_case("log") {
         "text/plain"
+    } else if extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown") {
+        "text/markdown"
     } else if extension.eq_ignore_ascii_case("json") {
         "application/json"
     } else if extension.eq_ignore_ascii_case("pdf") {
         "application/pdf"
+    } else if let Some(mime_type) = source_mime_type(extension) {
+        mime_type
+    } else if let Some(mime_type) = document_mime_type(extension) {
+        mime_type
     } else {
         "application/octet-stream"
     }
 }
 
+fn source_mime_type(extension: &str) -> Option<&'static str> {
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "rs" => "text/x-rust",
+        "py" => "text/x-python",
+        "js" | "mjs" | "cjs" => "text/javascript",
+        "ts" | "tsx" | "jsx" => "text/x-typescript",
+        "go" => "text/x-go",
+        "c" | "h" => "text/x-c",
+        "cc" | "cpp" | "cxx" | "hpp" => "text/x-c++",
+        "java" => "text/x-java",
+        "kt" | "kts" => "text/x-kotlin",
+        "swift" => "text/x-swift",
+        "rb" => "text/x-ruby",
+        "php" => "text/x-php",
+        "cs" => "text/x-csharp",
+        "sh" | "bash" | "zsh" => "text/x-shellscript",
+        "sql" => "text/x-sql",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "ini" | "cfg" | "conf" => "text/plain",
+        _ => return None,
+    })
+}
+
+fn document_mime_type(extension: &str) -> Option<&'static str> {
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "avi" => "video/x-msvideo",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "bz2" => "application/x-bzip2",
+        "xz" => "application/x-xz",
+        "zst" => "application/zstd",
+        "7z" => "application/x-7z-compressed",
+        "rar" => "application/vnd.rar",
+        "epub" => "application/epub+zip",
+        "jar" => "application/java-archive",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "odp" => "application/vnd.oasis.opendocument.presentation",
+        "doc" => "application/msword",
+        "xls" => "application/vnd.ms-excel",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "rtf" => "application/rtf",
+        "sqlite" | "sqlite3" => "application/vnd.sqlite3",
+        _ => return None,
+    })
+}
+
 fn system_time_millis(time: SystemTime) -> i64 {
     time.duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
@@ -816,6 +1606,7 @@ mod tests {
             size: 128,
             created_at: Some(10),
             modified_at: Some(20),
+            sniffed_mime_type: None,
         };
         assert_eq!(
             reusable_hash(Some(&metadata), 128, Some(10), Some(20)),
@@ -833,6 +1624,7 @@ mod tests {
         std::fs::write(directory.join("photo.jpg"), b"photo").unwrap();
         std::fs::write(directory.join("song.mp3"), b"audio").unwrap();
         std::fs::write(directory.join("notes.txt"), b"notes").unwrap();
+        std::fs::write(directory.join("scan"), b"\xFF\xD8\xFF\xE0jpeg").unwrap();
         std::fs::create_dir_all(directory.join("node_modules")).unwrap();
         std::fs::write(directory.join("node_modules/skip.txt"), b"skip").unwrap();
         let database_path =
@@ -844,6 +1636,8 @@ mod tests {
                 path: directory.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -872,15 +1666,485 @@ mod tests {
         .await
         .unwrap();
         assert!(finished);
-        assert_eq!(database.cached_media(&node_id).unwrap().len(), 1);
+        let media = database.cached_media(&node_id).unwrap();
+        assert_eq!(media.len(), 2);
+        assert!(media.iter().any(|file| file.path.ends_with("scan")));
         assert_eq!(database.cached_audio(&node_id).unwrap().len(), 1);
-        assert_eq!(database.cached_files(&node_id).unwrap().len(), 3);
+        let files = database.cached_files(&node_id).unwrap();
+        assert_eq!(files.len(), 4);
+        let photo = files
+            .iter()
+            .find(|file| file.path.ends_with("photo.jpg"))
+            .unwrap();
+        assert_eq!(photo.mime_type.as_deref(), Some("image/jpeg"));
+        assert!(photo.mime_type_mismatch());
+        let scan = files
+            .iter()
+            .find(|file| file.path.ends_with("scan"))
+            .unwrap();
+        assert_eq!(scan.mime_type.as_deref(), Some("image/jpeg"));
+        assert!(!scan.mime_type_mismatch());
         let history = database.scanned_folder_scan_history(folder_id).unwrap();
         assert_eq!(history.len(), 1);
         assert_eq!(history[0].trigger, ScanTrigger::ManualFolder);
         assert_eq!(history[0].outcome, ScanOutcome::Completed);
         assert_eq!(history[0].directories_scanned, 1);
-        assert_eq!(history[0].files_indexed, 3);
+        assert_eq!(history[0].files_indexed, 4);
+        drop(worker);
+        drop(database);
+        let _ = std::fs::remove_dir_all(directory);
+        let _ = std::fs::remove_file(database_path);
+    }
+
+    async fn wait_for_event(
+        events_rx: &mut tokio::sync::mpsc::Receiver<IndexerEvent>,
+        wanted: impl Fn(&IndexerEvent) -> bool,
+    ) -> bool {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(event) = events_rx.recv().await {
+                if wanted(&event) {
+                    return true;
+                }
+            }
+            false
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    #[tokio::test]
+    async fn worker_applies_changed_paths_without_a_full_scan() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(directory.join("album")).unwrap();
+        std::fs::write(directory.join("keep.txt"), b"keep").unwrap();
+        std::fs::write(directory.join("gone.txt"), b"gone").unwrap();
+        std::fs::write(directory.join("album/one.jpg"), b"one").unwrap();
+        let database_path =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}.db", uuid::Uuid::new_v4()));
+        let database = Database::open(&database_path).unwrap();
+        let folder = database
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: directory.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let node_id = database.local_node_id("PuppyDrive").unwrap();
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::channel(32);
+        let worker = IndexerWorker::start(database_path.clone(), events_tx);
+        worker.request_scan(
+            vec![folder.clone()],
+            node_id.clone(),
+            0,
+            0,
+            Vec::new(),
+            0,
+            ScanTrigger::ManualFolder,
+        );
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::Finished { .. }
+            ))
+            .await
+        );
+        assert_eq!(database.cached_files(&node_id).unwrap().len(), 3);
+
+        std::fs::remove_file(directory.join("gone.txt")).unwrap();
+        std::fs::remove_dir_all(directory.join("album")).unwrap();
+        std::fs::create_dir_all(directory.join("moved")).unwrap();
+        std::fs::write(directory.join("moved/two.jpg"), b"two").unwrap();
+        std::fs::write(directory.join("keep.txt"), b"kept").unwrap();
+        worker.request_paths(
+            folder.clone(),
+            node_id.clone(),
+            vec![
+                directory.join("gone.txt"),
+                directory.join("album"),
+                directory.join("moved"),
+                directory.join("keep.txt"),
+            ],
+            Vec::new(),
+            0,
+        );
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::PathsIndexed { .. }
+            ))
+            .await
+        );
+        let files = database.cached_files(&node_id).unwrap();
+        let mut names = files
+            .iter()
+            .map(|file| {
+                file.path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["keep.txt", "two.jpg"]);
+        let media = database.cached_media(&node_id).unwrap();
+        assert_eq!(media.len(), 1);
+        assert!(media[0].path.ends_with("moved/two.jpg"));
+        // Only the initial scan is recorded; targeted updates are not scans.
+        assert_eq!(
+            database
+                .scanned_folder_scan_history(folder.id)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // A tree too large for one write batch is left to a full scan.
+        std::fs::create_dir_all(directory.join("dump")).unwrap();
+        for index in 0..SCAN_WRITE_BATCH_SIZE {
+            std::fs::write(directory.join(format!("dump/{index}.jpg")), b"x").unwrap();
+        }
+        worker.request_paths(
+            folder.clone(),
+            node_id.clone(),
+            vec![directory.join("dump")],
+            Vec::new(),
+            0,
+        );
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::RescanNeeded { folder_id } if *folder_id == folder.id
+            ))
+            .await
+        );
+        assert_eq!(database.cached_files(&node_id).unwrap().len(), 2);
+        drop(worker);
+        drop(database);
+        let _ = std::fs::remove_dir_all(directory);
+        let _ = std::fs::remove_file(database_path);
+    }
+
+    #[tokio::test]
+    async fn worker_scrub_flags_content_changed_without_an_mtime_change() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("original.jpg"), b"archived bytes").unwrap();
+        std::fs::write(directory.join("replica.jpg"), b"archived bytes").unwrap();
+        let database_path =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}.db", uuid::Uuid::new_v4()));
+        let database = Database::open(&database_path).unwrap();
+        let folder = database
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: directory.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let node_id = database.local_node_id("PuppyDrive").unwrap();
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::channel(32);
+        let worker = IndexerWorker::start(database_path.clone(), events_tx);
+        worker.request_scan(
+            vec![folder.clone()],
+            node_id.clone(),
+            0,
+            0,
+            Vec::new(),
+            0,
+            ScanTrigger::ManualFolder,
+        );
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::Finished { .. }
+            ))
+            .await
+        );
+
+        // Flip bytes in place and put the modification time back, the way
+        // a failing disk would leave the file.
+        let original = directory.join("original.jpg");
+        let modified = std::fs::metadata(&original).unwrap().modified().unwrap();
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&original)
+            .unwrap();
+        std::io::Write::write_all(&mut &file, b"archived byt3s").unwrap();
+        file.set_modified(modified).unwrap();
+        drop(file);
+        worker.request_scrub(vec![folder.clone()], node_id.clone(), i64::MAX);
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::ScrubFinished {
+                    verified: 1,
+                    failed: 1
+                }
+            ))
+            .await
+        );
+        let alerts = database.scrub_alerts(&node_id).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].path.ends_with("original.jpg"));
+        assert_eq!(
+            alerts[0].actual_hash.as_deref(),
+            Some(blake3::hash(b"archived byt3s").as_bytes().as_slice())
+        );
+        assert_eq!(alerts[0].healthy_replicas.len(), 1);
+        assert!(alerts[0].healthy_replicas[0].ends_with("replica.jpg"));
+        let summary = database.scrub_summary(&node_id, 0).unwrap();
+        assert_eq!((summary.hashed_files, summary.verified_files), (2, 2));
+
+        // Saving the file on purpose changes its modification time, which
+        // clears the alert.
+        std::fs::write(&original, b"edited on purpose").unwrap();
+        worker.request_paths(folder, node_id.clone(), vec![original], Vec::new(), 0);
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::PathsIndexed { .. }
+            ))
+            .await
+        );
+        assert!(database.scrub_alerts(&node_id).unwrap().is_empty());
+        drop(worker);
+        drop(database);
+        let _ = std::fs::remove_dir_all(directory);
+        let _ = std::fs::remove_file(database_path);
+    }
+
+    #[tokio::test]
+    async fn worker_trashes_and_hardlinks_duplicate_copies() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(directory.join("album")).unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg", "album/d.jpg"] {
+            std::fs::write(directory.join(name), b"same bytes").unwrap();
+        }
+        std::fs::write(directory.join("unique.jpg"), b"other bytes").unwrap();
+        let database_path =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}.db", uuid::Uuid::new_v4()));
+        let database = Database::open(&database_path).unwrap();
+        let folder = database
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: directory.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let node_id = database.local_node_id("PuppyDrive").unwrap();
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::channel(32);
+        let worker = IndexerWorker::start(database_path.clone(), events_tx);
+        worker.request_scan(
+            vec![folder.clone()],
+            node_id.clone(),
+            0,
+            0,
+            Vec::new(),
+            0,
+            ScanTrigger::ManualFolder,
+        );
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::Finished { .. }
+            ))
+            .await
+        );
+        let groups = database.duplicate_groups(&node_id).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].copies.len(), 4);
+        assert_eq!(groups[0].wasted_bytes(), 3 * 10);
+        assert!(groups[0].copies[0].path.ends_with("a.jpg"));
+
+        // An edit the index has not seen yet protects the copy.
+        std::fs::write(directory.join("c.jpg"), b"edited!!!!").unwrap();
+        async fn resolved(
+            events_rx: &mut tokio::sync::mpsc::Receiver<IndexerEvent>,
+        ) -> (usize, u64, usize) {
+            tokio::time::timeout(std::time::Duration::from_secs(5), async {
+                while let Some(event) = events_rx.recv().await {
+                    if let IndexerEvent::DuplicatesResolved {
+                        changed,
+                        reclaimed_bytes,
+                        errors,
+                        ..
+                    } = event
+                    {
+                        return (changed.len(), reclaimed_bytes, errors.len());
+                    }
+                }
+                panic!("indexer event channel closed");
+            })
+            .await
+            .expect("duplicates were not resolved")
+        }
+        worker.request_dedupe(
+            vec![folder.clone()],
+            &groups[0],
+            0,
+            DuplicateAction::Hardlink,
+        );
+        assert_eq!(resolved(&mut events_rx).await, (2, 20, 1));
+        assert_eq!(
+            std::fs::read(directory.join("c.jpg")).unwrap(),
+            b"edited!!!!"
+        );
+        worker.request_duplicates(node_id.clone());
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::DuplicatesLoaded { groups } if groups[0].distinct_files == 2
+            ))
+            .await
+        );
+
+        worker.request_dedupe(vec![folder.clone()], &groups[0], 0, DuplicateAction::Trash);
+        // The copies left are hardlinks of a.jpg, so trashing them frees nothing.
+        assert_eq!(resolved(&mut events_rx).await, (2, 0, 1));
+        assert!(!directory.join("album/d.jpg").exists());
+        let trash = std::fs::read_dir(directory.join(TRASH_DIRECTORY_NAME))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        assert_eq!(
+            std::fs::read(trash.join("album/d.jpg")).unwrap(),
+            b"same bytes"
+        );
+        assert_eq!(
+            std::fs::read(directory.join("a.jpg")).unwrap(),
+            b"same bytes"
+        );
+        drop(worker);
+        drop(database);
+        let _ = std::fs::remove_dir_all(directory);
+        let _ = std::fs::remove_file(database_path);
+    }
+
+    #[tokio::test]
+    async fn worker_resumes_an_interrupted_scan_from_its_checkpoint() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(directory.join("done")).unwrap();
+        std::fs::create_dir_all(directory.join("todo")).unwrap();
+        std::fs::write(directory.join("done/written.jpg"), b"written").unwrap();
+        // Added after the interrupted run finished this directory, so a
+        // resumed scan that re-walked it would pick it up.
+        std::fs::write(directory.join("done/late.jpg"), b"late").unwrap();
+        std::fs::write(directory.join("todo/pending.jpg"), b"pending").unwrap();
+        let database_path =
+            std::env::temp_dir().join(format!("puppydrive-indexer-{}.db", uuid::Uuid::new_v4()));
+        let database = Database::open(&database_path).unwrap();
+        let folder = database
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: directory.to_string_lossy().into_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap();
+        let node_id = database.local_node_id("PuppyDrive").unwrap();
+        let managed = ManagedFolder::open(folder.id, &folder.path).unwrap();
+        let observation = |path: PathBuf| MediaIndexObservation {
+            hash: Some(vec![1; 32]),
+            path,
+            size: 7,
+            mime_type: Some("image/jpeg".to_owned()),
+            extension_mime_type: Some("image/jpeg".to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: None,
+            accessed_at: None,
+        };
+        database
+            .sync_scan(
+                &node_id,
+                &managed,
+                &[observation(managed.root().join("deleted.jpg"))],
+                &folder_indexers(&folder),
+                true,
+            )
+            .unwrap();
+        let scan_id = crate::database::new_scan_id();
+        database
+            .write_scan_batch(
+                &node_id,
+                &managed,
+                &scan_id,
+                &[observation(managed.root().join("done/written.jpg"))],
+                &folder_indexers(&folder),
+            )
+            .unwrap();
+        database
+            .save_scan_checkpoint(&ScanCheckpoint {
+                scanned_folder_id: folder.id,
+                node_id: node_id.clone(),
+                scan_id,
+                started_at: 1_000,
+                pending_directories: vec![managed.root().join("todo")],
+                directories_scanned: 2,
+                files_indexed: 1,
+            })
+            .unwrap();
+
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::channel(32);
+        let worker = IndexerWorker::start(database_path.clone(), events_tx);
+        worker.request_scan(
+            vec![folder.clone()],
+            node_id.clone(),
+            0,
+            0,
+            Vec::new(),
+            0,
+            ScanTrigger::Resumed,
+        );
+        assert!(
+            wait_for_event(&mut events_rx, |event| matches!(
+                event,
+                IndexerEvent::Finished { .. }
+            ))
+            .await
+        );
+        let mut names = database
+            .cached_files(&node_id)
+            .unwrap()
+            .iter()
+            .map(|file| {
+                file.path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["pending.jpg", "written.jpg"]);
+        assert!(database.scan_checkpoints().unwrap().is_empty());
+        let history = database.scanned_folder_scan_history(folder.id).unwrap();
+        assert_eq!(history[0].trigger, ScanTrigger::Resumed);
+        assert_eq!(history[0].outcome, ScanOutcome::Completed);
+        assert_eq!(history[0].started_at, 1_000);
+        assert_eq!(history[0].directories_scanned, 3);
+        assert_eq!(history[0].files_indexed, 2);
         drop(worker);
         drop(database);
         let _ = std::fs::remove_dir_all(directory);
@@ -904,6 +2168,8 @@ mod tests {
                 path: directory.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -913,6 +2179,8 @@ mod tests {
                 path: missing_directory.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -984,6 +2252,8 @@ mod tests {
                 path: root.to_string_lossy().into_owned(),
                 enabled: true,
                 indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
             })
             .await
             .unwrap();
@@ -0,0 +1,317 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::Result;
+use rusqlite::{Transaction, params};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{
+    MetadataBuilder, MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey, Tag,
+};
+use symphonia::core::probe::Hint;
+
+use super::{Indexer, IndexerRows, ObservedFile, downcast_rows};
+use crate::database::{AudioMetadata, MediaIndexObservation, ScannedFolder};
+
+const ID3V1_LENGTH: u64 = 128;
+
+/// Reads ID3v2/ID3v1 frames, FLAC and Ogg Vorbis comments and MP4 metadata
+/// atoms into `audio_metadata`, keyed by content hash like photo metadata.
+pub struct AudioTagIndexer;
+
+impl Indexer for AudioTagIndexer {
+    fn name(&self) -> &'static str {
+        ScannedFolder::AUDIO_TAGS_INDEXER
+    }
+
+    fn description(&self) -> &'static str {
+        "Music tags, duration and cover art"
+    }
+
+    fn accepts(&self, file: &ObservedFile<'_>) -> bool {
+        file.observation.hash.is_some()
+            && file
+                .observation
+                .mime_type
+                .as_deref()
+                .is_some_and(|mime_type| mime_type.starts_with("audio/"))
+    }
+
+    fn extract(&self, file: &ObservedFile<'_>) -> Result<Option<IndexerRows>> {
+        // Like photos, an unreadable track is skipped until its content changes.
+        match read_audio_metadata(file) {
+            Ok(metadata) => Ok(Some(Box::new(metadata))),
+            Err(error) => {
+                log::debug!(
+                    "unable to read audio tags from {}: {error:#}",
+                    file.observation.path.display()
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    fn write(
+        &self,
+        transaction: &Transaction<'_>,
+        observation: &MediaIndexObservation,
+        rows: IndexerRows,
+    ) -> Result<()> {
+        let Some(hash) = &observation.hash else {
+            return Ok(());
+        };
+        let metadata: AudioMetadata = downcast_rows(self, rows)?;
+        transaction
+            .prepare_cached(
+                "INSERT INTO audio_metadata
+                    (hash, title, artist, album, album_artist, track_number, track_total,
+                     disc_number, disc_total, year, genre, duration_ms, cover_hash, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+                 ON CONFLICT(hash) DO UPDATE SET
+                    title = excluded.title, artist = excluded.artist, album = excluded.album,
+                    album_artist = excluded.album_artist, track_number = excluded.track_number,
+                    track_total = excluded.track_total, disc_number = excluded.disc_number,
+                    disc_total = excluded.disc_total, year = excluded.year,
+                    genre = excluded.genre, duration_ms = excluded.duration_ms,
+                    cover_hash = excluded.cover_hash, indexed_at = excluded.indexed_at",
+            )?
+            .execute(params![
+                hash,
+                metadata.title,
+                metadata.artist,
+                metadata.album,
+                metadata.album_artist,
+                metadata.track_number,
+                metadata.track_total,
+                metadata.disc_number,
+                metadata.disc_total,
+                metadata.year,
+                metadata.genre,
+                metadata.duration_ms,
+                metadata.cover_hash,
+                crate::database::now_millis(),
+            ])?;
+        Ok(())
+    }
+
+    fn prune(&self, transaction: &Transaction<'_>) -> Result<()> {
+        transaction.execute(
+            "DELETE FROM audio_metadata WHERE NOT EXISTS (
+                SELECT 1 FROM file_locations location WHERE location.hash = audio_metadata.hash
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+}
+
+fn read_audio_metadata(file: &ObservedFile<'_>) -> Result<AudioMetadata> {
+    let path = &file.observation.path;
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+    let source = MediaSourceStream::new(Box::new(file.folder.open_file(path)?), Default::default());
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut metadata = AudioMetadata::default();
+    // Tags ahead of the container (ID3v2 on MP3) come from the probe; the
+    // container's own tags (Vorbis comments, MP4 atoms) take precedence.
+    if let Some(revision) = probed.format.metadata().skip_to_latest() {
+        apply_revision(&mut metadata, revision);
+    }
+    if let Some(revision) = probed
+        .metadata
+        .get()
+        .as_mut()
+        .and_then(|metadata| metadata.skip_to_latest())
+    {
+        apply_revision(&mut metadata, revision);
+    }
+    if let Some(revision) = read_id3v1(file)? {
+        apply_revision(&mut metadata, &revision);
+    }
+    metadata.duration_ms = probed.format.default_track().and_then(|track| {
+        let params = &track.codec_params;
+        let frames = params.n_frames?;
+        let time = match (params.time_base, params.sample_rate) {
+            (Some(time_base), _) => time_base.calc_time(frames),
+            (None, Some(sample_rate)) if sample_rate > 0 => {
+                symphonia::core::units::TimeBase::new(1, sample_rate).calc_time(frames)
+            }
+            _ => return None,
+        };
+        i64::try_from(time.seconds.saturating_mul(1_000))
+            .ok()
+            .map(|millis| millis + (time.frac * 1_000.0) as i64)
+    });
+    Ok(metadata)
+}
+
+/// ID3v1 lives in the last 128 bytes of an MP3 and is only used to fill in
+/// fields missing from the richer formats.
+fn read_id3v1(file: &ObservedFile<'_>) -> Result<Option<MetadataRevision>> {
+    if file.observation.mime_type.as_deref() != Some("audio/mpeg")
+        || file.observation.size < ID3V1_LENGTH
+    {
+        return Ok(None);
+    }
+    let mut reader = file.folder.open_file(&file.observation.path)?;
+    reader.seek(SeekFrom::End(-(ID3V1_LENGTH as i64)))?;
+    let mut tail = Vec::with_capacity(ID3V1_LENGTH as usize);
+    reader.take(ID3V1_LENGTH).read_to_end(&mut tail)?;
+    if !tail.starts_with(b"TAG") {
+        return Ok(None);
+    }
+    let mut builder = MetadataBuilder::new();
+    symphonia_metadata::id3v1::read_id3v1(
+        &mut symphonia::core::io::BufReader::new(&tail),
+        &mut builder,
+    )?;
+    Ok(Some(builder.metadata()))
+}
+
+/// Fills fields that are still empty, so the first revision applied wins.
+fn apply_revision(metadata: &mut AudioMetadata, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        apply_tag(metadata, tag);
+    }
+    if metadata.cover_hash.is_none() {
+        let cover = revision
+            .visuals()
+            .iter()
+            .find(|visual| visual.usage == Some(StandardVisualKey::FrontCover))
+            .or_else(|| revision.visuals().first());
+        metadata.cover_hash = cover.map(|visual| blake3::hash(&visual.data).as_bytes().to_vec());
+    }
+}
+
+fn apply_tag(metadata: &mut AudioMetadata, tag: &Tag) {
+    let Some(key) = tag.std_key else {
+        return;
+    };
+    let value = tag.value.to_string();
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    let text = || Some(value.to_owned());
+    match key {
+        StandardTagKey::TrackTitle => fill(&mut metadata.title, text()),
+        StandardTagKey::Artist => fill(&mut metadata.artist, text()),
+        StandardTagKey::Album => fill(&mut metadata.album, text()),
+        StandardTagKey::AlbumArtist => fill(&mut metadata.album_artist, text()),
+        StandardTagKey::Genre => fill(&mut metadata.genre, text()),
+        StandardTagKey::TrackNumber => {
+            let (number, total) = number_and_total(value);
+            fill(&mut metadata.track_number, number);
+            fill(&mut metadata.track_total, total);
+        }
+        StandardTagKey::TrackTotal => fill(&mut metadata.track_total, number_and_total(value).0),
+        StandardTagKey::DiscNumber => {
+            let (number, total) = number_and_total(value);
+            fill(&mut metadata.disc_number, number);
+            fill(&mut metadata.disc_total, total);
+        }
+        StandardTagKey::DiscTotal => fill(&mut metadata.disc_total, number_and_total(value).0),
+        StandardTagKey::Date | StandardTagKey::ReleaseDate | StandardTagKey::OriginalDate => {
+            fill(&mut metadata.year, leading_year(value));
+        }
+        _ => {}
+    }
+}
+
+fn fill<T>(field: &mut Option<T>, value: Option<T>) {
+    if field.is_none() {
+        *field = value;
+    }
+}
+
+/// Parses `"3"`, `"3/12"` and zero-padded variants; zero means unset.
+fn number_and_total(value: &str) -> (Option<u32>, Option<u32>) {
+    let mut parts = value.splitn(2, '/');
+    let mut number = || {
+        parts
+            .next()
+            .and_then(|part| part.trim().parse::<u32>().ok())
+            .filter(|number| *number > 0)
+    };
+    (number(), number())
+}
+
+fn leading_year(value: &str) -> Option<i32> {
+    let digits = value.get(..4)?;
+    digits
+        .bytes()
+        .all(|byte| byte.is_ascii_digit())
+        .then(|| digits.parse().ok())
+        .flatten()
+        .filter(|year| *year > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MediaIndexObservation;
+    use crate::managed_folder::ManagedFolder;
+
+    #[test]
+    fn parses_track_numbers_and_years() {
+        assert_eq!(number_and_total("3/12"), (Some(3), Some(12)));
+        assert_eq!(number_and_total("07"), (Some(7), None));
+        assert_eq!(number_and_total("0/0"), (None, None));
+        assert_eq!(leading_year("2019-05-03"), Some(2019));
+        assert_eq!(leading_year("19"), None);
+    }
+
+    #[test]
+    fn reads_id3v1_tags_from_the_end_of_an_mp3() {
+        let mut tag = b"TAG".to_vec();
+        for (text, width) in [("Song", 30), ("Band", 30), ("Record", 30), ("1999", 4)] {
+            let mut field = text.as_bytes().to_vec();
+            field.resize(width, 0);
+            tag.extend(field);
+        }
+        tag.extend([0; 28]);
+        tag.extend([0, 5, 17]);
+        let mut bytes = vec![0_u8; 512];
+        bytes.extend(&tag);
+
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-id3-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("song.mp3"), &bytes).unwrap();
+        let folder = ManagedFolder::open(1, &directory).unwrap();
+        let observation = MediaIndexObservation {
+            path: folder.root().join("song.mp3"),
+            hash: Some(vec![1; 32]),
+            size: bytes.len() as u64,
+            mime_type: Some("audio/mpeg".to_owned()),
+            extension_mime_type: Some("audio/mpeg".to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: None,
+            accessed_at: None,
+        };
+        let file = ObservedFile {
+            folder: &folder,
+            observation: &observation,
+        };
+        assert!(AudioTagIndexer.accepts(&file));
+
+        let mut metadata = AudioMetadata::default();
+        apply_revision(&mut metadata, &read_id3v1(&file).unwrap().unwrap());
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.artist.as_deref(), Some("Band"));
+        assert_eq!(metadata.album.as_deref(), Some("Record"));
+        assert_eq!(metadata.year, Some(1999));
+        assert_eq!(metadata.track_number, Some(5));
+        assert_eq!(metadata.genre.as_deref(), Some("Rock"));
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
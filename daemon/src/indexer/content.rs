@@ -0,0 +1,121 @@
+use anyhow::Result;
+use rusqlite::{OptionalExtension, Transaction, params};
+
+use super::{Indexer, IndexerRows, ObservedFile, downcast_rows};
+use crate::database::{MediaIndexObservation, ScannedFolder};
+
+/// Only the start of very large text files is searchable. Files above the
+/// configured `max_file_size_mb` never reach indexers at all.
+const MAX_CONTENT_BYTES: u64 = 8 * 1024 * 1024;
+/// A NUL byte this early means the file is binary despite its extension.
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+const TEXT_APPLICATION_TYPES: [&str; 4] = [
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/toml",
+];
+
+/// Feeds text, Markdown, JSON and source files into the `content_search`
+/// FTS5 table, one document per content hash.
+pub struct ContentIndexer;
+
+impl Indexer for ContentIndexer {
+    fn name(&self) -> &'static str {
+        ScannedFolder::CONTENT_INDEXER
+    }
+
+    fn description(&self) -> &'static str {
+        "Full-text search in text, Markdown, JSON and source files"
+    }
+
+    fn accepts(&self, file: &ObservedFile<'_>) -> bool {
+        file.observation.hash.is_some()
+            && file
+                .observation
+                .mime_type
+                .as_deref()
+                .is_some_and(is_searchable_mime_type)
+    }
+
+    fn extract(&self, file: &ObservedFile<'_>) -> Result<Option<IndexerRows>> {
+        let bytes = match file.read(Some(MAX_CONTENT_BYTES)) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                log::debug!(
+                    "unable to read {} for content search: {error:#}",
+                    file.observation.path.display()
+                );
+                return Ok(None);
+            }
+        };
+        if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+            return Ok(None);
+        }
+        let content = String::from_utf8_lossy(&bytes).into_owned();
+        Ok(Some(Box::new(content)))
+    }
+
+    fn write(
+        &self,
+        transaction: &Transaction<'_>,
+        observation: &MediaIndexObservation,
+        rows: IndexerRows,
+    ) -> Result<()> {
+        let Some(hash) = &observation.hash else {
+            return Ok(());
+        };
+        let content: String = downcast_rows(self, rows)?;
+        let existing = transaction
+            .prepare_cached("SELECT id FROM content_documents WHERE hash = ?1")?
+            .query_row([hash], |row| row.get::<_, i64>(0))
+            .optional()?;
+        let document_id = match existing {
+            Some(id) => {
+                transaction
+                    .prepare_cached("DELETE FROM content_search WHERE rowid = ?1")?
+                    .execute([id])?;
+                transaction
+                    .prepare_cached("UPDATE content_documents SET indexed_at = ?2 WHERE id = ?1")?
+                    .execute(params![id, crate::database::now_millis()])?;
+                id
+            }
+            None => {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO content_documents (hash, indexed_at) VALUES (?1, ?2)",
+                    )?
+                    .execute(params![hash, crate::database::now_millis()])?;
+                transaction.last_insert_rowid()
+            }
+        };
+        transaction
+            .prepare_cached("INSERT INTO content_search (rowid, content) VALUES (?1, ?2)")?
+            .execute(params![document_id, content])?;
+        Ok(())
+    }
+
+    fn prune(&self, transaction: &Transaction<'_>) -> Result<()> {
+        transaction.execute(
+            "DELETE FROM content_search WHERE rowid IN (
+                SELECT document.id FROM content_documents document
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM file_locations location WHERE location.hash = document.hash
+                )
+            )",
+            [],
+        )?;
+        transaction.execute(
+            "DELETE FROM content_documents WHERE NOT EXISTS (
+                SELECT 1 FROM file_locations location
+                WHERE location.hash = content_documents.hash
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+}
+
+fn is_searchable_mime_type(mime_type: &str) -> bool {
+    mime_type.starts_with("text/") || TEXT_APPLICATION_TYPES.contains(&mime_type)
+}
@@ -0,0 +1,195 @@
+//! Reclaiming the space taken by duplicate files.
+//!
+//! The index already knows every copy of a hash. Resolving a group keeps one
+//! copy and either moves the others into the trash directory of their Scanned
+//! folder, from where they can be put back by hand, or replaces them with
+//! hardlinks to the kept copy. Every file is hashed again first, so a copy
+//! edited since the last scan is never thrown away.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use super::IndexerEvent;
+use crate::database::{Database, DuplicateCopy, DuplicateGroup, ScannedFolder, now_millis};
+use crate::managed_folder::ManagedFolder;
+
+/// Directory in each Scanned folder root that trashed copies are moved to.
+/// It is hidden, so scans skip it.
+pub const TRASH_DIRECTORY_NAME: &str = ".puppydrive-trash";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Moves the other copies into the trash directory of their Scanned folder.
+    Trash,
+    /// Replaces the other copies with hardlinks to the kept copy, which only
+    /// works within one filesystem.
+    Hardlink,
+}
+
+pub(super) struct DedupeRequest {
+    pub folders: Vec<ScannedFolder>,
+    pub keep: DuplicateCopy,
+    pub others: Vec<DuplicateCopy>,
+    pub action: DuplicateAction,
+}
+
+pub(super) fn resolve(request: DedupeRequest) -> IndexerEvent {
+    let mut folders = HashMap::new();
+    let mut changed = Vec::new();
+    let mut errors = Vec::new();
+    let mut reclaimed_bytes = 0_u64;
+    let keep = match open_folder(
+        &request.folders,
+        &mut folders,
+        request.keep.scanned_folder_id,
+    )
+    .and_then(|folder| verified_path(folder, &request.keep.path, &request.keep.hash))
+    {
+        Ok(keep) => Some(keep),
+        Err(error) => {
+            errors.push(format!("{}: {error:#}", request.keep.path.display()));
+            None
+        }
+    };
+    if let Some((keep_path, keep_metadata)) = keep {
+        let keep_identity = file_identity(&keep_metadata);
+        // One trash directory per run keeps copies trashed together apart
+        // from earlier runs.
+        let batch = now_millis().to_string();
+        for copy in &request.others {
+            let result = open_folder(&request.folders, &mut folders, copy.scanned_folder_id)
+                .and_then(|folder| {
+                    let (path, metadata) = verified_path(folder, &copy.path, &copy.hash)?;
+                    if path == keep_path {
+                        bail!("is the copy being kept");
+                    }
+                    let linked =
+                        keep_identity.is_some() && file_identity(&metadata) == keep_identity;
+                    match request.action {
+                        DuplicateAction::Trash => move_to_trash(folder.root(), &path, &batch)?,
+                        DuplicateAction::Hardlink if copy.hash != request.keep.hash => {
+                            bail!("differs from the copy being kept")
+                        }
+                        DuplicateAction::Hardlink if linked => {}
+                        DuplicateAction::Hardlink => replace_with_hardlink(&keep_path, &path)?,
+                    }
+                    Ok(if linked { 0 } else { metadata.len() })
+                });
+            match result {
+                Ok(bytes) => {
+                    reclaimed_bytes = reclaimed_bytes.saturating_add(bytes);
+                    changed.push((copy.scanned_folder_id, copy.path.clone()));
+                }
+                Err(error) => errors.push(format!("{}: {error:#}", copy.path.display())),
+            }
+        }
+    }
+    for error in &errors {
+        log::warn!("unable to resolve duplicate {error}");
+    }
+    IndexerEvent::DuplicatesResolved {
+        action: request.action,
+        changed,
+        reclaimed_bytes,
+        errors,
+    }
+}
+
+fn open_folder<'a>(
+    folders: &[ScannedFolder],
+    opened: &'a mut HashMap<u32, ManagedFolder>,
+    folder_id: u32,
+) -> Result<&'a ManagedFolder> {
+    match opened.entry(folder_id) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let folder = folders
+                .iter()
+                .find(|folder| folder.id == folder_id && folder.enabled)
+                .context("Scanned folder is no longer available")?;
+            Ok(entry.insert(ManagedFolder::open(folder.id, &folder.path)?))
+        }
+    }
+}
+
+/// Resolves `path` inside `folder` and checks it still holds `hash`.
+fn verified_path(folder: &ManagedFolder, path: &Path, hash: &[u8]) -> Result<(PathBuf, Metadata)> {
+    let path = folder.canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    if !metadata.is_file() {
+        bail!("is not a file");
+    }
+    if folder.blake3(&path)? != hash {
+        bail!("changed since it was indexed");
+    }
+    Ok((path, metadata))
+}
+
+fn move_to_trash(root: &Path, path: &Path, batch: &str) -> Result<()> {
+    let relative = path.strip_prefix(root)?;
+    let target = root.join(TRASH_DIRECTORY_NAME).join(batch).join(relative);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(path, &target).with_context(|| format!("unable to move to {}", target.display()))
+}
+
+/// Links `keep` next to `path` first and renames the link over it, so `path`
+/// never goes missing.
+fn replace_with_hardlink(keep: &Path, path: &Path) -> Result<()> {
+    let name = path.file_name().context("has no file name")?;
+    let staging = path.with_file_name(format!(".{}.puppydrive-link", name.to_string_lossy()));
+    fs::hard_link(keep, &staging)
+        .context("unable to hardlink; both copies must be on the same filesystem")?;
+    if let Err(error) = fs::rename(&staging, path) {
+        let _ = fs::remove_file(&staging);
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Loads the duplicate groups of `node_id` and collapses hardlinks, which
+/// reads the metadata of every copy.
+pub(super) fn load(database_path: &Path, node_id: &[u8]) -> Result<Vec<DuplicateGroup>> {
+    let mut groups = Database::open(database_path)?.duplicate_groups(node_id)?;
+    collapse_hardlinks(&mut groups);
+    Ok(groups)
+}
+
+/// Counts hardlinked copies once and drops groups that turn out to be a
+/// single file on disk.
+pub fn collapse_hardlinks(groups: &mut Vec<DuplicateGroup>) {
+    for group in groups.iter_mut() {
+        let mut identities = HashSet::new();
+        group.distinct_files = group
+            .copies
+            .iter()
+            .filter(|copy| {
+                match fs::metadata(&copy.path)
+                    .ok()
+                    .as_ref()
+                    .and_then(file_identity)
+                {
+                    Some(identity) => identities.insert(identity),
+                    None => true,
+                }
+            })
+            .count();
+    }
+    groups.retain(|group| group.distinct_files > 1);
+}
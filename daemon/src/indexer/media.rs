@@ -0,0 +1,16 @@
+use super::Indexer;
+use crate::database::ScannedFolder;
+
+/// Records every regular file under the `media` membership. The Files, Media
+/// and Audio views narrow that membership down by MIME type.
+pub struct MediaIndexer;
+
+impl Indexer for MediaIndexer {
+    fn name(&self) -> &'static str {
+        ScannedFolder::MEDIA_INDEXER
+    }
+
+    fn description(&self) -> &'static str {
+        "Files, photos, videos and audio"
+    }
+}
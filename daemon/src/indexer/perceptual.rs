@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Seek};
+
+use anyhow::Result;
+use image::imageops::FilterType;
+use image::{DynamicImage, Limits};
+use rusqlite::{Transaction, params};
+
+use super::{Indexer, IndexerRows, ObservedFile, downcast_rows};
+use crate::database::{MediaIndexObservation, ScannedFolder};
+
+const IMAGE_MIME_TYPES: [&str; 5] = [
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "image/bmp",
+];
+
+/// The hash only needs a 9×8 sample, so images beyond this are not worth
+/// decoding at all.
+const MAX_DECODE_DIMENSION: u32 = 16_384;
+const MAX_DECODE_ALLOCATION: u64 = 256 * 1024 * 1024;
+
+/// Stores a difference hash of every image in `perceptual_hashes`, so resized,
+/// re-encoded or re-exported copies can be found even though their content
+/// hashes differ.
+pub struct PerceptualHashIndexer;
+
+impl Indexer for PerceptualHashIndexer {
+    fn name(&self) -> &'static str {
+        ScannedFolder::SIMILAR_IMAGES_INDEXER
+    }
+
+    fn description(&self) -> &'static str {
+        "Perceptual hashes for finding similar photos"
+    }
+
+    fn accepts(&self, file: &ObservedFile<'_>) -> bool {
+        file.observation.hash.is_some()
+            && file
+                .observation
+                .mime_type
+                .as_deref()
+                .is_some_and(|mime_type| IMAGE_MIME_TYPES.contains(&mime_type))
+    }
+
+    fn extract(&self, file: &ObservedFile<'_>) -> Result<Option<IndexerRows>> {
+        // Like an image that cannot be decoded, one that vanished or cannot
+        // be opened is skipped rather than failing the scan.
+        let reader = match file.folder.open_file(&file.observation.path) {
+            Ok(opened) => BufReader::new(opened),
+            Err(error) => {
+                log::debug!(
+                    "unable to open {} for a perceptual hash: {error:#}",
+                    file.observation.path.display()
+                );
+                return Ok(None);
+            }
+        };
+        let image = match decode_limited(reader) {
+            Ok(image) => image,
+            Err(error) => {
+                log::debug!(
+                    "unable to decode {} for a perceptual hash: {error:#}",
+                    file.observation.path.display()
+                );
+                return Ok(None);
+            }
+        };
+        Ok(Some(Box::new(dhash(&image))))
+    }
+
+    fn write(
+        &self,
+        transaction: &Transaction<'_>,
+        observation: &MediaIndexObservation,
+        rows: IndexerRows,
+    ) -> Result<()> {
+        let Some(hash) = &observation.hash else {
+            return Ok(());
+        };
+        let dhash: u64 = downcast_rows(self, rows)?;
+        transaction
+            .prepare_cached(
+                "INSERT INTO perceptual_hashes (hash, dhash, indexed_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(hash) DO UPDATE SET
+                    dhash = excluded.dhash, indexed_at = excluded.indexed_at",
+            )?
+            .execute(params![hash, dhash as i64, crate::database::now_millis(),])?;
+        Ok(())
+    }
+
+    fn prune(&self, transaction: &Transaction<'_>) -> Result<()> {
+        transaction.execute(
+            "DELETE FROM perceptual_hashes WHERE NOT EXISTS (
+                SELECT 1 FROM file_locations location
+                WHERE location.hash = perceptual_hashes.hash
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+}
+
+/// Decodes an image for hashing, refusing ones whose dimensions or decoder
+/// allocations exceed the limits instead of exhausting memory.
+fn decode_limited(reader: impl BufRead + Seek) -> Result<DynamicImage> {
+    let mut reader = image::ImageReader::new(reader).with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOCATION);
+    reader.limits(limits);
+    Ok(reader.decode()?)
+}
+
+/// 64-bit difference hash: each bit records whether a pixel of a 9×8
+/// grayscale rendering is darker than its right neighbour.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    // Shrinking in two steps keeps large photos fast without aliasing the
+    // final 9×8 sample.
+    let small = image
+        .thumbnail(64, 64)
+        .resize_exact(9, 8, FilterType::Triangle)
+        .into_luma8();
+    let mut bits = 0_u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            bits <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                bits |= 1;
+            }
+        }
+    }
+    bits
+}
+
+/// Groups the positions of `hashes` that are within `max_distance` bits of
+/// another member, transitively. Only groups of two or more are returned.
+pub fn similar_clusters(hashes: &[u64], max_distance: u32) -> Vec<Vec<usize>> {
+    let mut tree = BkTree::default();
+    for (index, hash) in hashes.iter().enumerate() {
+        tree.insert(*hash, index);
+    }
+    let mut parents = (0..hashes.len()).collect::<Vec<_>>();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for (index, hash) in hashes.iter().enumerate() {
+        for neighbour in tree.within(*hash, max_distance) {
+            let (a, b) = (root(&mut parents, index), root(&mut parents, neighbour));
+            if a != b {
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    let mut clusters = HashMap::<usize, Vec<usize>>::new();
+    for index in 0..hashes.len() {
+        let cluster = root(&mut parents, index);
+        clusters.entry(cluster).or_default().push(index);
+    }
+    let mut clusters = clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .collect::<Vec<_>>();
+    clusters.sort_by_key(|members| members[0]);
+    clusters
+}
+
+/// Burkhard-Keller tree over Hamming distance, so neighbour lookups skip
+/// most of the index instead of comparing every pair.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    hash: u64,
+    index: usize,
+    children: HashMap<u32, usize>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, index: usize) {
+        let new = self.nodes.len();
+        self.nodes.push(BkNode {
+            hash,
+            index,
+            children: HashMap::new(),
+        });
+        if new == 0 {
+            return;
+        }
+        let mut current = 0;
+        loop {
+            let distance = (self.nodes[current].hash ^ hash).count_ones();
+            match self.nodes[current].children.get(&distance) {
+                Some(&child) => current = child,
+                None => {
+                    self.nodes[current].children.insert(distance, new);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn within(&self, hash: u64, max_distance: u32) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut pending = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(current) = pending.pop() {
+            let node = &self.nodes[current];
+            let distance = (node.hash ^ hash).count_ones();
+            if distance <= max_distance {
+                found.push(node.index);
+            }
+            pending.extend(node.children.iter().filter_map(|(edge, child)| {
+                (edge.abs_diff(distance) <= max_distance).then_some(*child)
+            }));
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wave_image(width: u32, height: u32, flip: bool) -> DynamicImage {
+        DynamicImage::ImageLuma8(image::GrayImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as f32 / width as f32, y as f32 / height as f32);
+            let wave = (x * 9.0).sin() * (y * 5.0).cos();
+            let value = (128.0 + 100.0 * wave) as u8;
+            image::Luma([if flip { 255 - value } else { value }])
+        }))
+    }
+
+    #[test]
+    fn resized_copies_hash_alike() {
+        let original = dhash(&wave_image(640, 480, false));
+        let resized =
+            dhash(&wave_image(640, 480, false).resize_exact(320, 240, FilterType::Lanczos3));
+        let inverted = dhash(&wave_image(640, 480, true));
+        assert!((original ^ resized).count_ones() <= 6);
+        assert!((original ^ inverted).count_ones() > 20);
+    }
+
+    #[test]
+    fn oversized_images_are_not_decoded() {
+        let encode = |image: DynamicImage| {
+            let mut bytes = std::io::Cursor::new(Vec::new());
+            image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+            bytes.set_position(0);
+            bytes
+        };
+        assert!(decode_limited(encode(wave_image(64, 48, false))).is_ok());
+        let tall = DynamicImage::ImageLuma8(image::GrayImage::new(1, MAX_DECODE_DIMENSION + 1));
+        assert!(decode_limited(encode(tall)).is_err());
+    }
+
+    #[test]
+    fn vanished_images_are_skipped() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-perceptual-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let folder = crate::managed_folder::ManagedFolder::open(1, &directory).unwrap();
+        let observation = MediaIndexObservation {
+            path: folder.root().join("gone.png"),
+            hash: Some(vec![1; 32]),
+            size: 10,
+            mime_type: Some("image/png".to_owned()),
+            extension_mime_type: Some("image/png".to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: Some(1),
+            accessed_at: None,
+        };
+        let file = ObservedFile {
+            folder: &folder,
+            observation: &observation,
+        };
+        assert!(PerceptualHashIndexer.extract(&file).unwrap().is_none());
+        let _ = std::fs::remove_dir_all(directory);
+    }
+
+    #[test]
+    fn clusters_hashes_within_the_distance() {
+        let hashes = [0b0000, 0xffff_0000, 0b0011, 0xffff_0001, 0b1111_0000_0000];
+        assert_eq!(similar_clusters(&hashes, 2), [vec![0, 2], vec![1, 3]]);
+        assert_eq!(similar_clusters(&hashes, 4), [vec![0, 2, 4], vec![1, 3]]);
+        assert!(similar_clusters(&hashes, 0).is_empty());
+    }
+}
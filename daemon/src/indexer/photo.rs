@@ -0,0 +1,327 @@
+use std::io::{BufReader, Seek, SeekFrom};
+
+use anyhow::Result;
+use exif::{Exif, In, Reader, Tag, Value};
+use rusqlite::{Transaction, params};
+
+use super::{Indexer, IndexerRows, ObservedFile, downcast_rows};
+use crate::database::{MediaIndexObservation, PhotoMetadata, ScannedFolder, days_from_civil};
+
+const PHOTO_MIME_TYPES: [&str; 4] = ["image/jpeg", "image/tiff", "image/webp", "image/png"];
+
+/// Reads capture time, camera, lens, dimensions, orientation and GPS position
+/// from photos into `photo_metadata`. Rows are keyed by content hash, so a
+/// photo copied into several folders is only parsed once per change.
+pub struct PhotoIndexer;
+
+impl Indexer for PhotoIndexer {
+    fn name(&self) -> &'static str {
+        ScannedFolder::EXIF_INDEXER
+    }
+
+    fn description(&self) -> &'static str {
+        "Photo capture date, camera and location"
+    }
+
+    fn accepts(&self, file: &ObservedFile<'_>) -> bool {
+        file.observation.hash.is_some()
+            && file
+                .observation
+                .mime_type
+                .as_deref()
+                .is_some_and(|mime_type| PHOTO_MIME_TYPES.contains(&mime_type))
+    }
+
+    fn extract(&self, file: &ObservedFile<'_>) -> Result<Option<IndexerRows>> {
+        // A photo that cannot be parsed should not fail the whole scan; it is
+        // retried when its content changes.
+        match read_photo_metadata(file) {
+            Ok(metadata) => Ok(Some(Box::new(metadata))),
+            Err(error) => {
+                log::debug!(
+                    "unable to read photo metadata from {}: {error:#}",
+                    file.observation.path.display()
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    fn write(
+        &self,
+        transaction: &Transaction<'_>,
+        observation: &MediaIndexObservation,
+        rows: IndexerRows,
+    ) -> Result<()> {
+        let Some(hash) = &observation.hash else {
+            return Ok(());
+        };
+        let metadata: PhotoMetadata = downcast_rows(self, rows)?;
+        transaction
+            .prepare_cached(
+                "INSERT INTO photo_metadata
+                    (hash, captured_at, camera_make, camera_model, lens_model, width, height,
+                     orientation, latitude, longitude, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                 ON CONFLICT(hash) DO UPDATE SET
+                    captured_at = excluded.captured_at, camera_make = excluded.camera_make,
+                    camera_model = excluded.camera_model, lens_model = excluded.lens_model,
+                    width = excluded.width, height = excluded.height,
+                    orientation = excluded.orientation, latitude = excluded.latitude,
+                    longitude = excluded.longitude, indexed_at = excluded.indexed_at",
+            )?
+            .execute(params![
+                hash,
+                metadata.captured_at,
+                metadata.camera_make,
+                metadata.camera_model,
+                metadata.lens_model,
+                metadata.width,
+                metadata.height,
+                metadata.orientation,
+                metadata.latitude,
+                metadata.longitude,
+                crate::database::now_millis(),
+            ])?;
+        Ok(())
+    }
+
+    fn prune(&self, transaction: &Transaction<'_>) -> Result<()> {
+        transaction.execute(
+            "DELETE FROM photo_metadata WHERE NOT EXISTS (
+                SELECT 1 FROM file_locations location WHERE location.hash = photo_metadata.hash
+            )",
+            [],
+        )?;
+        Ok(())
+    }
+}
+
+/// Parses EXIF when present and falls back to the image header for
+/// dimensions, so photos without EXIF still sort by resolution.
+fn read_photo_metadata(file: &ObservedFile<'_>) -> Result<PhotoMetadata> {
+    let mut reader = BufReader::new(file.folder.open_file(&file.observation.path)?);
+    let mut metadata = match Reader::new().read_from_container(&mut reader) {
+        Ok(exif) => photo_metadata(&exif),
+        Err(exif::Error::NotFound(_)) => PhotoMetadata::default(),
+        Err(error) => {
+            log::debug!(
+                "ignoring unreadable EXIF in {}: {error}",
+                file.observation.path.display()
+            );
+            PhotoMetadata::default()
+        }
+    };
+    if metadata.width.is_none() || metadata.height.is_none() {
+        reader.seek(SeekFrom::Start(0))?;
+        if let Ok((width, height)) = image::ImageReader::new(reader)
+            .with_guessed_format()
+            .map_err(anyhow::Error::from)
+            .and_then(|reader| reader.into_dimensions().map_err(Into::into))
+        {
+            metadata.width = Some(width);
+            metadata.height = Some(height);
+        }
+    }
+    Ok(metadata)
+}
+
+fn photo_metadata(exif: &Exif) -> PhotoMetadata {
+    let (width, height) = match (
+        uint_field(exif, Tag::PixelXDimension),
+        uint_field(exif, Tag::PixelYDimension),
+    ) {
+        (Some(width), Some(height)) => (Some(width), Some(height)),
+        _ => (
+            uint_field(exif, Tag::ImageWidth),
+            uint_field(exif, Tag::ImageLength),
+        ),
+    };
+    PhotoMetadata {
+        captured_at: [
+            (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+            (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+            (Tag::DateTime, Tag::OffsetTime),
+        ]
+        .into_iter()
+        .find_map(|(time, offset)| captured_at(exif, time, offset)),
+        camera_make: text_field(exif, Tag::Make),
+        camera_model: text_field(exif, Tag::Model),
+        lens_model: text_field(exif, Tag::LensModel),
+        width,
+        height,
+        orientation: uint_field(exif, Tag::Orientation)
+            .and_then(|orientation| u16::try_from(orientation).ok())
+            .filter(|orientation| (1..=8).contains(orientation)),
+        latitude: coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S', 90.0),
+        longitude: coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W', 180.0),
+    }
+}
+
+fn ascii_field(exif: &Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(Vec::as_slice),
+        _ => None,
+    }
+}
+
+fn text_field(exif: &Exif, tag: Tag) -> Option<String> {
+    let text = String::from_utf8_lossy(ascii_field(exif, tag)?);
+    let text = text.trim_matches(|character: char| character == '\0' || character.is_whitespace());
+    (!text.is_empty()).then(|| text.to_owned())
+}
+
+fn uint_field(exif: &Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY)?.value.get_uint(0)
+}
+
+fn captured_at(exif: &Exif, time: Tag, offset: Tag) -> Option<i64> {
+    let mut date_time = exif::DateTime::from_ascii(ascii_field(exif, time)?).ok()?;
+    if let Some(offset) = ascii_field(exif, offset) {
+        // A malformed offset leaves the wall-clock reading, which is still useful.
+        let _ = date_time.parse_offset(offset);
+    }
+    if !(1..=12).contains(&date_time.month)
+        || !(1..=31).contains(&date_time.day)
+        || date_time.hour > 23
+        || date_time.minute > 59
+        || date_time.second > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(
+        i64::from(date_time.year),
+        i64::from(date_time.month),
+        i64::from(date_time.day),
+    );
+    let seconds = days * 86_400
+        + i64::from(date_time.hour) * 3_600
+        + i64::from(date_time.minute) * 60
+        + i64::from(date_time.second)
+        - i64::from(date_time.offset.unwrap_or(0)) * 60;
+    Some(seconds * 1_000)
+}
+
+fn coordinate(exif: &Exif, tag: Tag, reference: Tag, negative: u8, limit: f64) -> Option<f64> {
+    let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let mut degrees = 0.0;
+    for (part, scale) in parts.iter().zip([1.0, 60.0, 3_600.0]) {
+        if part.denom == 0 {
+            return None;
+        }
+        degrees += part.to_f64() / scale;
+    }
+    if ascii_field(exif, reference).and_then(|reference| reference.first()) == Some(&negative) {
+        degrees = -degrees;
+    }
+    (degrees.is_finite() && degrees.abs() <= limit).then_some(degrees)
+}
+
+#[cfg(test)]
+mod tests {
+    use exif::experimental::Writer;
+    use exif::{Field, Rational};
+
+    use super::*;
+    use crate::database::MediaIndexObservation;
+    use crate::managed_folder::ManagedFolder;
+
+    fn ascii(tag: Tag, value: &str) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        }
+    }
+
+    fn rationals(tag: Tag, values: &[(u32, u32)]) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(
+                values
+                    .iter()
+                    .map(|&(num, denom)| Rational { num, denom })
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn reads_capture_time_camera_and_location_from_exif() {
+        let fields = [
+            ascii(Tag::Make, "NIKON CORPORATION"),
+            ascii(Tag::Model, "NIKON D750"),
+            ascii(Tag::LensModel, "24.0-120.0 mm f/4.0"),
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            ascii(Tag::DateTimeOriginal, "2021:06:03 14:05:09"),
+            ascii(Tag::OffsetTimeOriginal, "+02:00"),
+            Field {
+                tag: Tag::PixelXDimension,
+                ifd_num: In::PRIMARY,
+                value: Value::Long(vec![6016]),
+            },
+            Field {
+                tag: Tag::PixelYDimension,
+                ifd_num: In::PRIMARY,
+                value: Value::Long(vec![4016]),
+            },
+            ascii(Tag::GPSLatitudeRef, "N"),
+            rationals(Tag::GPSLatitude, &[(60, 1), (10, 1), (30, 1)]),
+            ascii(Tag::GPSLongitudeRef, "W"),
+            rationals(Tag::GPSLongitude, &[(24, 1), (561, 10), (0, 1)]),
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-exif-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("photo.tif");
+        std::fs::write(&path, tiff.into_inner()).unwrap();
+        let folder = ManagedFolder::open(1, &directory).unwrap();
+        let observation = MediaIndexObservation {
+            path: folder.root().join("photo.tif"),
+            hash: Some(vec![1; 32]),
+            size: 0,
+            mime_type: Some("image/tiff".to_owned()),
+            extension_mime_type: Some("image/tiff".to_owned()),
+            sniffed_mime_type: None,
+            created_at: None,
+            modified_at: None,
+            accessed_at: None,
+        };
+        let file = ObservedFile {
+            folder: &folder,
+            observation: &observation,
+        };
+        assert!(PhotoIndexer.accepts(&file));
+
+        let metadata = read_photo_metadata(&file).unwrap();
+        assert_eq!(metadata.captured_at, Some(1_622_721_909_000));
+        assert_eq!(metadata.camera().as_deref(), Some("NIKON D750"));
+        assert_eq!(metadata.lens_model.as_deref(), Some("24.0-120.0 mm f/4.0"));
+        assert_eq!(metadata.display_dimensions(), Some((4016, 6016)));
+        let (latitude, longitude) = metadata.location().unwrap();
+        assert!((latitude - 60.175).abs() < 1e-9);
+        assert!((longitude + 24.935).abs() < 1e-9);
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn civil_dates_convert_to_unix_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+    }
+}
@@ -0,0 +1,129 @@
+//! Bit-rot scrubbing: reads indexed files back and compares them with their
+//! stored hash.
+//!
+//! Scans reuse a hash while size and timestamps are unchanged, so damage
+//! that leaves the metadata alone would otherwise never be noticed. Each run
+//! re-hashes the files verified longest ago, within a byte budget, so the
+//! whole index is covered over successive runs.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use tokio::sync::mpsc::Sender;
+
+use super::{IndexerEvent, observe_file};
+use crate::database::{
+    Database, ScannedFolder, ScrubCandidate, ScrubOutcome, ScrubResult, now_millis,
+};
+use crate::managed_folder::ManagedFolder;
+
+/// Bytes read back per run.
+pub(super) const SCRUB_BYTES_PER_RUN: u64 = 2 * 1024 * 1024 * 1024;
+const CANDIDATE_BATCH_SIZE: usize = 256;
+
+pub(super) struct ScrubRequest {
+    pub folders: Vec<ScannedFolder>,
+    pub node_id: Vec<u8>,
+    /// Files verified at or after this Unix millis time are left alone.
+    pub verified_before: i64,
+    pub byte_budget: u64,
+}
+
+pub(super) fn scrub(
+    database_path: &Path,
+    events: &Sender<IndexerEvent>,
+    request: ScrubRequest,
+) -> anyhow::Result<()> {
+    let database = Database::open(database_path)?;
+    let folders = request
+        .folders
+        .iter()
+        .filter(|folder| folder.enabled)
+        .filter_map(
+            |folder| match ManagedFolder::open(folder.id, &folder.path) {
+                Ok(managed) => Some((folder.id, managed)),
+                Err(error) => {
+                    log::debug!("scrub skipping scanned folder {}: {error:#}", folder.id);
+                    None
+                }
+            },
+        )
+        .collect::<HashMap<_, _>>();
+    let folder_ids = folders.keys().copied().collect::<Vec<_>>();
+    // Files verified during this run must not come round again.
+    let verified_before = request.verified_before.min(now_millis());
+    let mut bytes_read = 0_u64;
+    let mut verified = 0;
+    let mut failed = 0;
+    // Files that changed or vanished since they were indexed are left to the
+    // next scan and stay at the front of the queue, so they are skipped over.
+    let mut skipped = 0;
+    while bytes_read < request.byte_budget && !folder_ids.is_empty() {
+        let candidates = database.scrub_candidates(
+            &request.node_id,
+            &folder_ids,
+            verified_before,
+            skipped,
+            CANDIDATE_BATCH_SIZE,
+        )?;
+        if candidates.is_empty() {
+            break;
+        }
+        let mut results = Vec::new();
+        for candidate in candidates {
+            if bytes_read >= request.byte_budget {
+                break;
+            }
+            let Some(outcome) = verify(&folders[&candidate.scanned_folder_id], &candidate) else {
+                skipped += 1;
+                continue;
+            };
+            bytes_read = bytes_read.saturating_add(candidate.size);
+            match &outcome {
+                ScrubOutcome::Healthy => verified += 1,
+                ScrubOutcome::Changed { .. } => {
+                    failed += 1;
+                    log::warn!(
+                        "{} changed content without a modification time change",
+                        candidate.path.display()
+                    );
+                }
+                ScrubOutcome::Unreadable { error } => {
+                    failed += 1;
+                    log::warn!(
+                        "{} could not be read back: {error}",
+                        candidate.path.display()
+                    );
+                }
+            }
+            results.push(ScrubResult { candidate, outcome });
+        }
+        database.record_scrub_results(&request.node_id, &results, now_millis())?;
+    }
+    log::info!(
+        "scrub verified {verified} files and found {failed} damaged, reading {bytes_read} bytes"
+    );
+    let _ = events.try_send(IndexerEvent::ScrubFinished { verified, failed });
+    Ok(())
+}
+
+/// Re-hashes one file, or returns `None` when it no longer looks like the
+/// indexed file and so cannot be judged against its hash.
+fn verify(folder: &ManagedFolder, candidate: &ScrubCandidate) -> Option<ScrubOutcome> {
+    let observation = observe_file(folder, candidate.path.clone(), 0)?;
+    if observation.size != candidate.size
+        || observation.created_at != candidate.created_at
+        || observation.modified_at != candidate.modified_at
+    {
+        return None;
+    }
+    Some(match folder.blake3_timed(&candidate.path) {
+        Ok(hash) if hash.hash == candidate.hash => ScrubOutcome::Healthy,
+        Ok(hash) => ScrubOutcome::Changed {
+            actual_hash: hash.hash,
+        },
+        Err(error) => ScrubOutcome::Unreadable {
+            error: format!("{error:#}"),
+        },
+    })
+}
@@ -0,0 +1,330 @@
+//! Magic-byte MIME detection for the start of a file.
+//!
+//! Extensions stay the first opinion: they are usually right and carry more
+//! detail than a signature (a `.docx` is only a zip archive to the sniffer).
+//! A sniffed type replaces the extension's when the extension says nothing
+//! (`application/octet-stream`) or when a specific signature disagrees with
+//! it, such as a JPEG saved as `.png`.
+
+/// Enough for every signature below; tar's `ustar` marker ends at 262.
+pub const SNIFF_BYTES: u64 = 512;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+const PLAIN_TEXT: &str = "text/plain";
+
+/// Types that can legitimately stand in for one another, because the
+/// signature only identifies the container they share.
+const FAMILIES: [&[&str]; 7] = [
+    &["video/mp4", "audio/mp4", "video/quicktime", "video/x-m4v"],
+    &["image/heic", "image/heif"],
+    &["audio/ogg", "video/ogg"],
+    &["video/webm", "video/x-matroska", "audio/webm"],
+    &["application/gzip", "application/x-gzip"],
+    &[
+        "application/zip",
+        "application/epub+zip",
+        "application/java-archive",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "application/vnd.oasis.opendocument.text",
+        "application/vnd.oasis.opendocument.spreadsheet",
+        "application/vnd.oasis.opendocument.presentation",
+    ],
+    &[
+        "application/x-ole-storage",
+        "application/msword",
+        "application/vnd.ms-excel",
+        "application/vnd.ms-powerpoint",
+    ],
+];
+
+/// Text formats whose content only sniffs as plain text or XML.
+const TEXTUAL_APPLICATION_TYPES: [&str; 6] = [
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/toml",
+    "application/rtf",
+    "image/svg+xml",
+];
+
+/// Identifies `bytes`, the start of a file. Returns `None` for an empty file
+/// and `application/octet-stream` for binary data without a known signature.
+pub fn sniff_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let at = |offset: usize, signature: &[u8]| {
+        bytes
+            .get(offset..offset + signature.len())
+            .is_some_and(|window| window == signature)
+    };
+    let mime_type = if at(0, b"\xFF\xD8\xFF") {
+        "image/jpeg"
+    } else if at(0, b"\x89PNG\r\n\x1A\n") {
+        "image/png"
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        "image/gif"
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        "image/webp"
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        "audio/wav"
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        "video/x-msvideo"
+    } else if at(0, b"II*\0") || at(0, b"MM\0*") {
+        "image/tiff"
+    } else if at(0, b"BM") && at(6, b"\0\0\0\0") {
+        "image/bmp"
+    } else if at(0, b"\0\0\x01\0") && bytes.get(4).is_some_and(|count| *count > 0) {
+        "image/x-icon"
+    } else if at(4, b"ftyp") {
+        iso_media_type(bytes.get(8..12).unwrap_or_default())
+    } else if at(0, b"\x1A\x45\xDF\xA3") {
+        if contains(bytes, b"webm") {
+            "video/webm"
+        } else {
+            "video/x-matroska"
+        }
+    } else if at(0, b"OggS") {
+        if contains(bytes, b"\x80theora") {
+            "video/ogg"
+        } else {
+            "audio/ogg"
+        }
+    } else if at(0, b"fLaC") {
+        "audio/flac"
+    } else if at(0, b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) {
+        "audio/aiff"
+    } else if at(0, b"ID3") {
+        "audio/mpeg"
+    } else if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xF6 == 0xF0 {
+        "audio/aac"
+    } else if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0 {
+        "audio/mpeg"
+    } else if at(0, b"%PDF-") {
+        "application/pdf"
+    } else if at(0, b"{\\rtf") {
+        "application/rtf"
+    } else if at(0, b"%!PS") {
+        "application/postscript"
+    } else if at(0, b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
+        "application/x-ole-storage"
+    } else if at(0, b"PK\x03\x04") {
+        zip_media_type(bytes)
+    } else if at(0, b"PK\x05\x06") {
+        "application/zip"
+    } else if at(0, b"\x1F\x8B") {
+        "application/gzip"
+    } else if at(0, b"BZh") {
+        "application/x-bzip2"
+    } else if at(0, b"\xFD7zXZ\0") {
+        "application/x-xz"
+    } else if at(0, b"\x28\xB5\x2F\xFD") {
+        "application/zstd"
+    } else if at(0, b"7z\xBC\xAF\x27\x1C") {
+        "application/x-7z-compressed"
+    } else if at(0, b"Rar!\x1A\x07") {
+        "application/vnd.rar"
+    } else if at(257, b"ustar") {
+        "application/x-tar"
+    } else if at(0, b"SQLite format 3\0") {
+        "application/vnd.sqlite3"
+    } else if at(0, b"\x7FELF") {
+        "application/x-executable"
+    } else if at(0, b"MZ") {
+        "application/x-msdownload"
+    } else if at(0, b"\xEF\xBB\xBF") || at(0, b"\xFF\xFE") || at(0, b"\xFE\xFF") {
+        PLAIN_TEXT
+    } else if looks_like_text(bytes) {
+        markup_type(bytes).unwrap_or(PLAIN_TEXT)
+    } else {
+        OCTET_STREAM
+    };
+    Some(mime_type)
+}
+
+/// The type a file is indexed under, given its extension and content.
+pub fn effective_mime_type<'a>(extension: &'a str, sniffed: Option<&'a str>) -> &'a str {
+    match sniffed {
+        Some(sniffed) if extension == OCTET_STREAM => sniffed,
+        // Plain text and unknown binary are too weak to overrule a name.
+        Some(sniffed)
+            if !mime_types_agree(extension, sniffed)
+                && sniffed != PLAIN_TEXT
+                && sniffed != OCTET_STREAM =>
+        {
+            sniffed
+        }
+        _ => extension,
+    }
+}
+
+/// Whether a file's content contradicts the type its extension claims.
+pub fn mime_types_conflict(extension: Option<&str>, sniffed: Option<&str>) -> bool {
+    match (extension, sniffed) {
+        (Some(extension), Some(sniffed)) => {
+            extension != OCTET_STREAM && !mime_types_agree(extension, sniffed)
+        }
+        _ => false,
+    }
+}
+
+fn mime_types_agree(extension: &str, sniffed: &str) -> bool {
+    extension == sniffed
+        || FAMILIES
+            .iter()
+            .any(|family| family.contains(&extension) && family.contains(&sniffed))
+        || (matches!(sniffed, PLAIN_TEXT | "application/xml" | "text/html")
+            && (extension.starts_with("text/") || TEXTUAL_APPLICATION_TYPES.contains(&extension)))
+}
+
+/// ISO base media files share `ftyp`; the major brand tells them apart.
+fn iso_media_type(brand: &[u8]) -> &'static str {
+    match brand {
+        b"avif" | b"avis" => "image/avif",
+        b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" => "image/heic",
+        b"mif1" | b"msf1" => "image/heif",
+        b"M4A " | b"M4B " | b"M4P " => "audio/mp4",
+        b"M4V " | b"M4VH" | b"M4VP" => "video/x-m4v",
+        b"qt  " => "video/quicktime",
+        _ => "video/mp4",
+    }
+}
+
+/// EPUB and OpenDocument store their type uncompressed as the first entry.
+fn zip_media_type(bytes: &[u8]) -> &'static str {
+    const MIMETYPE_ENTRY: &[u8] = b"mimetype";
+    let name_length = bytes.get(26..28).map_or(0, |length| {
+        u16::from_le_bytes([length[0], length[1]]) as usize
+    });
+    let extra_length = bytes.get(28..30).map_or(0, |length| {
+        u16::from_le_bytes([length[0], length[1]]) as usize
+    });
+    if bytes.get(30..30 + name_length) != Some(MIMETYPE_ENTRY) {
+        return "application/zip";
+    }
+    let content = bytes
+        .get(30 + name_length + extra_length..)
+        .unwrap_or_default();
+    [
+        "application/epub+zip",
+        "application/vnd.oasis.opendocument.text",
+        "application/vnd.oasis.opendocument.spreadsheet",
+        "application/vnd.oasis.opendocument.presentation",
+    ]
+    .into_iter()
+    .find(|mime_type| content.starts_with(mime_type.as_bytes()))
+    .unwrap_or("application/zip")
+}
+
+fn markup_type(bytes: &[u8]) -> Option<&'static str> {
+    let start = String::from_utf8_lossy(&bytes[..bytes.len().min(256)])
+        .trim_start()
+        .to_ascii_lowercase();
+    if start.starts_with("<svg") || (start.starts_with("<?xml") && start.contains("<svg")) {
+        Some("image/svg+xml")
+    } else if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        Some("text/html")
+    } else if start.starts_with("<?xml") {
+        Some("application/xml")
+    } else {
+        None
+    }
+}
+
+/// UTF-8 without NUL or other control bytes, allowing the sample to end in
+/// the middle of a multi-byte character.
+fn looks_like_text(bytes: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(error) => error.error_len().is_none() && error.valid_up_to() + 4 > bytes.len(),
+    };
+    valid
+        && !bytes
+            .iter()
+            .any(|byte| *byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C))
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_signatures_and_text() {
+        let mut tar = vec![0_u8; 300];
+        tar[257..262].copy_from_slice(b"ustar");
+        let mut epub = b"PK\x03\x04".to_vec();
+        epub.extend([0; 22]);
+        epub.extend(8_u16.to_le_bytes());
+        epub.extend(0_u16.to_le_bytes());
+        epub.extend(b"mimetypeapplication/epub+zip");
+        for (bytes, expected) in [
+            (&b"\xFF\xD8\xFF\xE0\0\x10JFIF"[..], "image/jpeg"),
+            (b"\x89PNG\r\n\x1A\n\0\0", "image/png"),
+            (b"RIFF\0\0\0\0WEBPVP8 ", "image/webp"),
+            (b"\0\0\0\x18ftypheic\0\0\0\0", "image/heic"),
+            (b"\0\0\0\x18ftypisom\0\0\0\0", "video/mp4"),
+            (b"\0\0\0\x18ftypM4A \0\0\0\0", "audio/mp4"),
+            (b"ID3\x04\0\0\0\0\0\0", "audio/mpeg"),
+            (b"fLaC\0\0\0\x22", "audio/flac"),
+            (b"%PDF-1.7\n", "application/pdf"),
+            (&tar, "application/x-tar"),
+            (&epub, "application/epub+zip"),
+            (b"PK\x03\x04\x14\0\0\0\x08\0", "application/zip"),
+            (b"# Notes\n\nCaf\xC3\xA9", "text/plain"),
+            (b"  <?xml version=\"1.0\"?><svg xmlns=", "image/svg+xml"),
+            (b"\0\x01\x02\x03binary", "application/octet-stream"),
+        ] {
+            assert_eq!(sniff_mime_type(bytes), Some(expected));
+        }
+        assert_eq!(sniff_mime_type(b""), None);
+        // A multi-byte character cut off by the sample size is still text.
+        assert_eq!(sniff_mime_type(b"na\xC3"), Some("text/plain"));
+    }
+
+    #[test]
+    fn resolves_extension_and_content_disagreements() {
+        assert_eq!(
+            effective_mime_type("application/octet-stream", Some("image/jpeg")),
+            "image/jpeg"
+        );
+        assert_eq!(
+            effective_mime_type("image/png", Some("image/jpeg")),
+            "image/jpeg"
+        );
+        assert_eq!(
+            effective_mime_type("audio/mp4", Some("video/mp4")),
+            "audio/mp4"
+        );
+        assert_eq!(
+            effective_mime_type("text/x-rust", Some("text/plain")),
+            "text/x-rust"
+        );
+        assert_eq!(
+            effective_mime_type("image/jpeg", Some("text/plain")),
+            "image/jpeg"
+        );
+        assert_eq!(effective_mime_type("image/jpeg", None), "image/jpeg");
+
+        assert!(mime_types_conflict(Some("image/png"), Some("image/jpeg")));
+        assert!(mime_types_conflict(Some("image/jpeg"), Some("text/plain")));
+        assert!(!mime_types_conflict(
+            Some("application/json"),
+            Some("text/plain")
+        ));
+        assert!(!mime_types_conflict(
+            Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+            Some("application/zip")
+        ));
+        assert!(!mime_types_conflict(
+            Some("application/octet-stream"),
+            Some("image/jpeg")
+        ));
+        assert!(!mime_types_conflict(Some("image/jpeg"), None));
+    }
+}
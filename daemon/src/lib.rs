@@ -1,8 +1,16 @@
 mod app;
+mod auth;
+mod backup;
 mod config;
 mod database;
+mod file_query;
+mod ignore_rules;
 mod indexer;
 mod managed_folder;
+mod peers;
+mod scheduler;
 mod session_secrets;
+mod sftp;
+mod upload_sessions;
 
 pub use app::App;
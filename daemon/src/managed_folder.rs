@@ -78,6 +78,10 @@ impl ManagedFolder {
         Ok(fs::metadata(self.canonicalize(path)?)?)
     }
 
+    pub fn open_file(&self, path: &Path) -> Result<fs::File> {
+        Ok(fs::File::open(self.canonicalize(path)?)?)
+    }
+
     pub fn read(&self, path: &Path, limit: Option<u64>) -> Result<Vec<u8>> {
         let path = self.canonicalize(path)?;
         let mut file = fs::File::open(path)?;
@@ -89,7 +93,6 @@ impl ManagedFolder {
         Ok(bytes)
     }
 
-    #[allow(dead_code)]
     pub fn blake3(&self, path: &Path) -> Result<Vec<u8>> {
         Ok(self.blake3_timed(path)?.hash)
     }
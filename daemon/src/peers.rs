@@ -0,0 +1,851 @@
+//! Index replication between paired PuppyDrive daemons.
+//!
+//! Every daemon records the local paths whose indexed state changed in
+//! `index_changes` and serves that feed at `GET /api/v1/index/changes`.
+//! Pairing stores another daemon's address together with one of its
+//! read-only access tokens; syncing pulls the feed from where the previous
+//! sync stopped and stores the paths under the peer's node, so the UI can
+//! tell which devices hold a replica. Replication is pulled, so two devices
+//! that should see each other both pair.
+//!
+//! File content is addressed by hash: `GET /content/<hash>` serves any local
+//! replica, and a file with no replica here is fetched from a device that has
+//! one. The hash is checked while the file streams in, and the copy is kept
+//! in a size-bounded cache so it opens straight away next time.
+//!
+//! Requests are plain http with the access token as a bearer header, so by
+//! default only devices reached over loopback, such as through an SSH
+//! tunnel, are contacted. `peers.allow_cleartext` opts into sending the
+//! token across the local network.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+
+use crate::app::{hex_decode, hex_encode};
+use crate::database::{ChangedLocation, Database, IndexChange, Peer};
+
+/// Changes served per request of the feed.
+pub const CHANGES_PAGE_LIMIT: usize = 1_000;
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How content may be fetched from paired devices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchPolicy {
+    /// Size the content cache is trimmed to after a fetch.
+    pub cache_limit_bytes: u64,
+    /// Whether devices on other computers may be reached over plain http.
+    pub allow_cleartext: bool,
+}
+
+/// What the app loop hears back from pairing and sync runs.
+#[derive(Debug)]
+pub enum PeerEvent {
+    Paired(Result<Peer, String>),
+    Synced {
+        node_id: Vec<u8>,
+        name: String,
+        result: Result<usize, String>,
+    },
+    /// Content requested for the file viewer arrived in the cache.
+    Fetched {
+        hash: Vec<u8>,
+        result: Result<PathBuf, String>,
+    },
+}
+
+pub fn node_json(node_id: &[u8], name: &str) -> Value {
+    json!({ "node_id": hex_encode(node_id), "name": name })
+}
+
+/// One page of the change feed. `next` is the sequence to ask for next and
+/// `more` says whether the page was cut short at `limit`.
+pub fn changes_json(
+    node_id: &[u8],
+    name: &str,
+    changes: &[IndexChange],
+    since: i64,
+    limit: usize,
+) -> Value {
+    let mut value = node_json(node_id, name);
+    value["changes"] = json!(
+        changes
+            .iter()
+            .map(|change| match &change.location {
+                Some(location) => json!({
+                    "sequence": change.sequence,
+                    "path": change.path,
+                    "hash": location.hash.as_deref().map(hex_encode),
+                    "size": location.size,
+                    "mime_type": location.mime_type,
+                    "modified_at": location.modified_at,
+                    "deleted": false,
+                }),
+                None => json!({
+                    "sequence": change.sequence,
+                    "path": change.path,
+                    "deleted": true,
+                }),
+            })
+            .collect::<Vec<_>>()
+    );
+    value["next"] = json!(changes.last().map_or(since, |change| change.sequence));
+    value["more"] = json!(changes.len() >= limit);
+    value
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ChangesPage {
+    node_id: Vec<u8>,
+    name: String,
+    changes: Vec<IndexChange>,
+    next: i64,
+    more: bool,
+}
+
+fn parse_node(value: &Value) -> Result<(Vec<u8>, String)> {
+    let node_id = value["node_id"]
+        .as_str()
+        .and_then(hex_decode)
+        .filter(|node_id| !node_id.is_empty())
+        .context("response has no node_id")?;
+    let name = value["name"]
+        .as_str()
+        .context("response has no device name")?;
+    Ok((node_id, name.to_owned()))
+}
+
+fn parse_changes(value: &Value) -> Result<ChangesPage> {
+    let (node_id, name) = parse_node(value)?;
+    let changes = value["changes"]
+        .as_array()
+        .context("response has no changes")?
+        .iter()
+        .map(|change| {
+            let path = change["path"]
+                .as_str()
+                .context("change has no path")?
+                .to_owned();
+            let location = if change["deleted"].as_bool().unwrap_or(false) {
+                None
+            } else {
+                Some(ChangedLocation {
+                    hash: match change["hash"].as_str() {
+                        Some(hash) => Some(
+                            hex_decode(hash)
+                                .filter(|hash| hash.len() == 32)
+                                .with_context(|| format!("invalid hash for {path}"))?,
+                        ),
+                        None => None,
+                    },
+                    size: change["size"]
+                        .as_u64()
+                        .with_context(|| format!("invalid size for {path}"))?,
+                    mime_type: change["mime_type"].as_str().map(str::to_owned),
+                    modified_at: change["modified_at"].as_i64(),
+                })
+            };
+            Ok(IndexChange {
+                sequence: change["sequence"].as_i64().unwrap_or_default(),
+                path,
+                location,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ChangesPage {
+        node_id,
+        name,
+        changes,
+        next: value["next"].as_i64().context("response has no next")?,
+        more: value["more"].as_bool().unwrap_or(false),
+    })
+}
+
+/// Asks the daemon at `url` who it is and pairs with it.
+pub fn pair(
+    database: &Database,
+    url: &str,
+    access_token: &str,
+    allow_cleartext: bool,
+) -> Result<Peer> {
+    let url = normalize_url(url)?;
+    let (node_id, name) = parse_node(&get_json(
+        &url,
+        "/api/v1/node",
+        access_token,
+        allow_cleartext,
+    )?)?;
+    database.save_peer(&node_id, &name, &url, access_token)?;
+    database
+        .peers()?
+        .into_iter()
+        .find(|peer| peer.node_id == node_id)
+        .context("paired device disappeared")
+}
+
+/// Pulls every change the peer made since the last sync and returns how many
+/// were applied.
+pub fn sync(database: &Database, peer: &Peer, allow_cleartext: bool) -> Result<usize> {
+    let mut since = peer.last_sequence;
+    let mut applied = 0;
+    loop {
+        let page = parse_changes(&get_json(
+            &peer.url,
+            &format!("/api/v1/index/changes?since={since}&limit={CHANGES_PAGE_LIMIT}"),
+            &peer.access_token,
+            allow_cleartext,
+        )?)?;
+        if page.node_id != peer.node_id {
+            bail!("{} is now a different PuppyDrive", peer.url);
+        }
+        database.apply_peer_changes(&peer.node_id, &page.name, &page.changes, page.next)?;
+        applied += page.changes.len();
+        if !page.more || page.next <= since {
+            return Ok(applied);
+        }
+        since = page.next;
+    }
+}
+
+/// The cached copy of the content with `hash`, if one was fetched before.
+pub fn cached_content(cache_dir: &Path, hash: &[u8]) -> Option<PathBuf> {
+    let path = cache_dir.join(hex_encode(hash));
+    path.is_file().then_some(path)
+}
+
+/// Returns a local copy of the content with `hash`, downloading it from a
+/// paired device that holds it unless it is cached already. The cache is then
+/// trimmed to the policy's limit, least recently used first; the file just
+/// fetched always stays.
+pub fn fetch(
+    database: &Database,
+    cache_dir: &Path,
+    hash: &[u8],
+    policy: FetchPolicy,
+) -> Result<PathBuf> {
+    if let Some(path) = cached_content(cache_dir, hash) {
+        // The modification time doubles as the last use for trimming.
+        if let Err(error) = File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            log::debug!("unable to mark {} as used: {error}", path.display());
+        }
+        return Ok(path);
+    }
+    let (size, _) = database
+        .file_entry_metadata(hash)?
+        .context("file is not in the index")?;
+    let peers = database.peers_holding(hash)?;
+    if peers.is_empty() {
+        bail!("no paired device has this file");
+    }
+    fs::create_dir_all(cache_dir)
+        .with_context(|| format!("unable to create {}", cache_dir.display()))?;
+    let target = cache_dir.join(hex_encode(hash));
+    let mut errors = Vec::new();
+    for peer in &peers {
+        match fetch_from(peer, hash, size, &target, policy.allow_cleartext) {
+            Ok(()) => {
+                trim_cache(cache_dir, policy.cache_limit_bytes, &target);
+                return Ok(target);
+            }
+            Err(error) => {
+                log::warn!("fetching from {} failed: {error:#}", peer.name);
+                errors.push(format!("{}: {error:#}", peer.name));
+            }
+        }
+    }
+    bail!("unable to fetch the file ({})", errors.join("; "))
+}
+
+fn fetch_from(
+    peer: &Peer,
+    hash: &[u8],
+    size: u64,
+    target: &Path,
+    allow_cleartext: bool,
+) -> Result<()> {
+    // `local=1` stops the peer from fetching in turn, so two devices that
+    // both lack the file cannot ask each other forever.
+    let (authority, status, framing, mut reader) = get(
+        &peer.url,
+        &format!("/content/{}?local=1", hex_encode(hash)),
+        &peer.access_token,
+        allow_cleartext,
+    )?;
+    if !(200..300).contains(&status) {
+        let mut body = Vec::new();
+        let _ = read_body(&mut reader, framing, &mut body, 64 * 1024);
+        return Err(status_error(&authority, status, &body));
+    }
+    let partial = target.with_extension(format!("{}.part", uuid::Uuid::new_v4().simple()));
+    let mut write = || -> Result<()> {
+        let mut writer = HashingWriter {
+            inner: BufWriter::new(File::create(&partial)?),
+            hasher: blake3::Hasher::new(),
+        };
+        read_body(&mut reader, framing, &mut writer, size)?;
+        writer.inner.flush()?;
+        if writer.hasher.finalize().as_bytes() != hash {
+            bail!("{authority} sent content that does not match its hash");
+        }
+        fs::rename(&partial, target)?;
+        Ok(())
+    };
+    let result = write();
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+struct HashingWriter<W> {
+    inner: W,
+    hasher: blake3::Hasher,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(bytes)?;
+        self.hasher.update(&bytes[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Removes cached files, least recently used first, until the cache fits in
+/// `limit_bytes`. `keep` stays regardless.
+pub fn trim_cache(cache_dir: &Path, limit_bytes: u64, keep: &Path) {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return;
+    };
+    let mut files = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let metadata = entry.metadata().ok()?;
+            // Downloads still in progress carry an extension.
+            (metadata.is_file() && path.extension().is_none())
+                .then(|| (metadata.modified().ok(), metadata.len(), path))
+        })
+        .collect::<Vec<_>>();
+    let mut total = files.iter().map(|(_, size, _)| size).sum::<u64>();
+    files.sort();
+    for (_, size, path) in files {
+        if total <= limit_bytes {
+            break;
+        }
+        if path != keep && fs::remove_file(&path).is_ok() {
+            total -= size;
+        }
+    }
+}
+
+/// Accepts `host:port` or an `http://` URL and drops any trailing slash.
+fn normalize_url(url: &str) -> Result<String> {
+    let url = url.trim().trim_end_matches('/');
+    if url.is_empty() {
+        bail!("enter the address of the other PuppyDrive");
+    }
+    if url.starts_with("https://") {
+        bail!("https addresses are not supported yet; use http://");
+    }
+    let url = if url.contains("://") {
+        url.to_owned()
+    } else {
+        format!("http://{url}")
+    };
+    split_url(&url)?;
+    Ok(url)
+}
+
+/// Splits an `http://host[:port][/base]` URL into its authority and base path.
+fn split_url(url: &str) -> Result<(&str, &str)> {
+    let rest = url
+        .strip_prefix("http://")
+        .with_context(|| format!("{url} is not an http:// address"))?;
+    let (authority, base) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if authority.is_empty() {
+        bail!("{url} has no host");
+    }
+    Ok((authority, base.trim_end_matches('/')))
+}
+
+/// How the body of a response is delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    Length(u64),
+    Chunked,
+    UntilClose,
+}
+
+/// Minimal blocking HTTP/1.1 GET for the peer protocol. Returns the host for
+/// error messages, the status and the connection positioned at the body.
+/// Unless `allow_cleartext` is set, anything but a loopback address is
+/// refused before the token is sent.
+fn get(
+    url: &str,
+    path: &str,
+    access_token: &str,
+    allow_cleartext: bool,
+) -> Result<(String, u16, Framing, BufReader<TcpStream>)> {
+    let (authority, base) = split_url(url)?;
+    let address = if authority.contains(':') {
+        authority.to_owned()
+    } else {
+        format!("{authority}:80")
+    };
+    let socket = address
+        .to_socket_addrs()
+        .with_context(|| format!("unable to resolve {authority}"))?
+        .next()
+        .with_context(|| format!("unable to resolve {authority}"))?;
+    if !allow_cleartext && !socket.ip().is_loopback() {
+        bail!(
+            "{authority} is not on this computer, and its access token would cross the network \
+             unencrypted; reach it through an SSH tunnel or set peers.allow_cleartext"
+        );
+    }
+    let mut stream = TcpStream::connect_timeout(&socket, TIMEOUT)
+        .with_context(|| format!("unable to connect to {authority}"))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "GET {base}{path} HTTP/1.1\r\nHost: {authority}\r\nAuthorization: Bearer {access_token}\r\nConnection: close\r\n\r\n"
+    )?;
+    stream.flush()?;
+    let mut reader = BufReader::new(stream);
+    let (status, framing) = read_head(&mut reader)?;
+    Ok((authority.to_owned(), status, framing, reader))
+}
+
+/// Turns a failed response into an error, using the API's JSON message when
+/// there is one.
+fn status_error(authority: &str, status: u16, body: &[u8]) -> anyhow::Error {
+    let value = serde_json::from_slice::<Value>(body).ok();
+    let message = value
+        .as_ref()
+        .and_then(|value| value["error"].as_str())
+        .unwrap_or("request failed");
+    match status {
+        401 | 403 => anyhow::anyhow!("{authority} rejected the access token ({status}): {message}"),
+        _ => anyhow::anyhow!("{authority} answered {status}: {message}"),
+    }
+}
+
+fn get_json(url: &str, path: &str, access_token: &str, allow_cleartext: bool) -> Result<Value> {
+    let (authority, status, framing, mut reader) = get(url, path, access_token, allow_cleartext)?;
+    let mut body = Vec::new();
+    read_body(&mut reader, framing, &mut body, MAX_RESPONSE_BYTES)?;
+    if !(200..300).contains(&status) {
+        return Err(status_error(&authority, status, &body));
+    }
+    serde_json::from_slice(&body).with_context(|| format!("{authority} did not answer with JSON"))
+}
+
+fn read_head(reader: &mut impl BufRead) -> Result<(u16, Framing)> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .with_context(|| format!("invalid HTTP status line '{}'", line.trim()))?;
+    let mut framing = Framing::UntilClose;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("connection closed inside the response headers");
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") && framing != Framing::Chunked {
+            framing = Framing::Length(value.parse().context("invalid content-length")?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding")
+            && value.to_ascii_lowercase().contains("chunked")
+        {
+            framing = Framing::Chunked;
+        }
+    }
+    Ok((status, framing))
+}
+
+/// Copies the body to `writer`, failing once it grows past `max_bytes`.
+fn read_body(
+    reader: &mut impl BufRead,
+    framing: Framing,
+    writer: &mut impl Write,
+    max_bytes: u64,
+) -> Result<u64> {
+    let mut copy_exactly = |reader: &mut dyn Read, length: u64, written: u64| -> Result<u64> {
+        if written.saturating_add(length) > max_bytes {
+            bail!("response is larger than expected");
+        }
+        if std::io::copy(&mut reader.take(length), writer)? < length {
+            bail!("connection closed inside the response body");
+        }
+        Ok(length)
+    };
+    match framing {
+        Framing::Length(length) => copy_exactly(reader, length, 0),
+        Framing::Chunked => {
+            let mut written = 0;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line)?;
+                let size = line.trim().split(';').next().unwrap_or_default();
+                let size = u64::from_str_radix(size, 16)
+                    .with_context(|| format!("invalid chunk size '{size}'"))?;
+                if size == 0 {
+                    return Ok(written);
+                }
+                written += copy_exactly(reader, size, written)?;
+                line.clear();
+                reader.read_line(&mut line)?;
+            }
+        }
+        Framing::UntilClose => {
+            let copied = std::io::copy(&mut reader.take(max_bytes.saturating_add(1)), writer)?;
+            if copied > max_bytes {
+                bail!("response is larger than expected");
+            }
+            Ok(copied)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::app::api::peer_api_response;
+    use crate::auth::{AccessScope, Auth, required_scope};
+    use crate::database::{MediaIndexObservation, ScannedFolder};
+    use crate::indexer::find_indexer;
+    use crate::managed_folder::ManagedFolder;
+
+    fn temporary_database(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("puppydrive-{name}-{}.db", uuid::Uuid::new_v4()))
+    }
+
+    /// Serves `database` to a paired device the way the daemon does: bearer
+    /// tokens are checked by [`Auth`] and the feed is answered by the API's own
+    /// handler. Content comes from `contents` by hash so a test can make the
+    /// peer lie, and every response is chunked so both body framings get
+    /// exercised.
+    fn serve(
+        listener: TcpListener,
+        database: PathBuf,
+        contents: Vec<(Vec<u8>, Vec<u8>)>,
+        requests: usize,
+    ) {
+        let database = Database::open(&database).unwrap();
+        let node_id = database.local_node_id("Laptop").unwrap();
+        let auth = Auth::new(database.auth_store(), false);
+        for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut request = read_request(&mut BufReader::new(stream.try_clone().unwrap()));
+            let required = required_scope(&request.method, &request.path).unwrap();
+            let authorized = auth
+                .authenticate(&request.headers)
+                .unwrap()
+                .is_some_and(|scope| scope.allows(required));
+            let (status, body) = if !authorized {
+                (401, json!({ "error": "authentication required" }))
+            } else if let Some(hash) = request.path.strip_prefix("/content/") {
+                assert_eq!(request.query.get("local").map(String::as_str), Some("1"));
+                let (_, content) = contents
+                    .iter()
+                    .find(|(content_hash, _)| hex_encode(content_hash) == hash)
+                    .unwrap();
+                write_chunked(&mut stream, 200, content);
+                continue;
+            } else {
+                // Pages of two so the sync has to follow `next`.
+                request.query.insert("limit".to_owned(), "2".to_owned());
+                peer_api_response(&database, &node_id, "Laptop", &request).unwrap()
+            };
+            write_chunked(&mut stream, status, body.to_string().as_bytes());
+        }
+    }
+
+    fn read_request(reader: &mut impl BufRead) -> wgui::HttpRequest {
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap().to_owned();
+        let target = parts.next().unwrap();
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let Some((name, value)) = line.split_once(':') else {
+                break;
+            };
+            headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+        }
+        wgui::HttpRequest {
+            method,
+            path: path.to_owned(),
+            query: query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect(),
+            headers,
+            body: Vec::new(),
+        }
+    }
+
+    fn write_chunked(stream: &mut TcpStream, status: u16, body: &[u8]) {
+        let (first, second) = body.split_at(body.len() / 2);
+        write!(
+            stream,
+            "HTTP/1.1 {status} OK\r\ntransfer-encoding: chunked\r\n\r\n{:x}\r\n",
+            first.len()
+        )
+        .unwrap();
+        stream.write_all(first).unwrap();
+        write!(stream, "\r\n{:x}\r\n", second.len()).unwrap();
+        stream.write_all(second).unwrap();
+        stream.write_all(b"\r\n0\r\n\r\n").unwrap();
+    }
+
+    /// A read-only token for `database`, as the Settings page would create.
+    fn access_token(database: &Database) -> String {
+        Auth::new(database.auth_store(), false)
+            .create_access_token("Desktop", AccessScope::ReadOnly)
+            .unwrap()
+            .1
+    }
+
+    async fn scanned_folder(database: &Database) -> u32 {
+        database
+            .save_scanned_folder(ScannedFolder {
+                id: 0,
+                path: "/photos".to_owned(),
+                enabled: true,
+                indexers: r#"["media"]"#.to_owned(),
+                include_globs: "[]".to_owned(),
+                exclude_globs: "[]".to_owned(),
+            })
+            .await
+            .unwrap()
+            .id
+    }
+
+    fn hash(content: &[u8]) -> Vec<u8> {
+        blake3::hash(content).as_bytes().to_vec()
+    }
+
+    fn index(database: &Database, node_id: &[u8], folder_id: u32, files: &[(&str, &[u8])]) {
+        let observations = files
+            .iter()
+            .map(|(path, content)| MediaIndexObservation {
+                path: PathBuf::from(path),
+                hash: Some(hash(content)),
+                size: content.len() as u64,
+                mime_type: Some("image/jpeg".to_owned()),
+                extension_mime_type: Some("image/jpeg".to_owned()),
+                sniffed_mime_type: None,
+                created_at: None,
+                modified_at: Some(1),
+                accessed_at: None,
+            })
+            .collect::<Vec<_>>();
+        database
+            .sync_scan(
+                node_id,
+                &ManagedFolder::open(folder_id, std::env::temp_dir()).unwrap(),
+                &observations,
+                &[find_indexer(ScannedFolder::MEDIA_INDEXER).unwrap()],
+                true,
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn pairs_and_pulls_the_index_of_another_daemon_over_loopback() {
+        let laptop_path = temporary_database("peer-laptop");
+        let desktop_path = temporary_database("peer-desktop");
+        let laptop = Database::open(&laptop_path).unwrap();
+        let laptop_node = laptop.local_node_id("Laptop").unwrap();
+        let laptop_folder = scanned_folder(&laptop).await;
+        index(
+            &laptop,
+            &laptop_node,
+            laptop_folder,
+            &[
+                ("/photos/a.jpg", b"a"),
+                ("/photos/b.jpg", b"b"),
+                ("/photos/c.jpg", b"c"),
+            ],
+        );
+        let desktop = Database::open(&desktop_path).unwrap();
+        let desktop_node = desktop.local_node_id("Desktop").unwrap();
+        let desktop_folder = scanned_folder(&desktop).await;
+        let token = access_token(&laptop);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("{}/", listener.local_addr().unwrap());
+        let server_database = laptop_path.clone();
+        let server = std::thread::spawn(move || serve(listener, server_database, Vec::new(), 6));
+
+        let error = pair(&desktop, &url, "wrong", false).unwrap_err();
+        assert!(format!("{error:#}").contains("rejected the access token (401)"));
+        let peer = pair(&desktop, &url, &token, false).unwrap();
+        assert_eq!(peer.node_id, laptop_node);
+        assert_eq!(peer.name, "Laptop");
+        assert!(peer.url.starts_with("http://127.0.0.1:"));
+        assert_eq!(sync(&desktop, &peer, false).unwrap(), 3);
+        // A local scan leaves the replicated locations alone.
+        index(
+            &desktop,
+            &desktop_node,
+            desktop_folder,
+            &[("/photos/b.jpg", b"b")],
+        );
+        assert_eq!(
+            desktop.file_replica_nodes(&hash(b"b")).unwrap(),
+            [("Laptop".to_owned(), PathBuf::from("/photos/b.jpg"))]
+        );
+        assert_eq!(
+            desktop
+                .file_replica_paths(&desktop_node, &hash(b"b"))
+                .unwrap(),
+            [PathBuf::from("/photos/b.jpg")]
+        );
+
+        // Only what changed since the last sync comes over again.
+        laptop.delete_scanned_folder(laptop_folder).unwrap();
+        let peer = desktop.peers().unwrap().remove(0);
+        assert_eq!(peer.file_count, 3);
+        assert_eq!(sync(&desktop, &peer, false).unwrap(), 3);
+        assert_eq!(desktop.peers().unwrap()[0].file_count, 0);
+        assert!(desktop.file_replica_nodes(&hash(b"b")).unwrap().is_empty());
+        server.join().unwrap();
+
+        assert!(desktop.remove_peer(&laptop_node).unwrap());
+        assert!(desktop.peers().unwrap().is_empty());
+        drop((laptop, desktop));
+        let _ = std::fs::remove_file(laptop_path);
+        let _ = std::fs::remove_file(desktop_path);
+    }
+
+    #[tokio::test]
+    async fn fetches_content_from_a_peer_and_verifies_its_hash() {
+        let laptop_path = temporary_database("fetch-laptop");
+        let desktop_path = temporary_database("fetch-desktop");
+        let cache_dir =
+            std::env::temp_dir().join(format!("puppydrive-peer-cache-{}", uuid::Uuid::new_v4()));
+        let laptop = Database::open(&laptop_path).unwrap();
+        let laptop_node = laptop.local_node_id("Laptop").unwrap();
+        let laptop_folder = scanned_folder(&laptop).await;
+        index(
+            &laptop,
+            &laptop_node,
+            laptop_folder,
+            &[
+                ("/photos/note.jpg", b"hello peer"),
+                ("/photos/bad.jpg", b"original"),
+            ],
+        );
+        let desktop = Database::open(&desktop_path).unwrap();
+        desktop.local_node_id("Desktop").unwrap();
+        let token = access_token(&laptop);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = listener.local_addr().unwrap().to_string();
+        let contents = vec![
+            (hash(b"hello peer"), b"hello peer".to_vec()),
+            (hash(b"original"), b"tampered".to_vec()),
+        ];
+        let server_database = laptop_path.clone();
+        let server = std::thread::spawn(move || serve(listener, server_database, contents, 5));
+        let peer = pair(&desktop, &url, &token, false).unwrap();
+        assert_eq!(sync(&desktop, &peer, false).unwrap(), 2);
+        let policy = FetchPolicy {
+            cache_limit_bytes: 0,
+            allow_cleartext: false,
+        };
+
+        // A stale cached file makes way for the new one.
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join(hex_encode(&[0; 32])), b"old").unwrap();
+        let fetched = fetch(&desktop, &cache_dir, &hash(b"hello peer"), policy).unwrap();
+        assert_eq!(fs::read(&fetched).unwrap(), b"hello peer");
+        assert_eq!(
+            cached_content(&cache_dir, &hash(b"hello peer")),
+            Some(fetched.clone())
+        );
+        assert_eq!(cached_content(&cache_dir, &[0; 32]), None);
+        // Cached content is served without asking the peer again.
+        assert_eq!(
+            fetch(&desktop, &cache_dir, &hash(b"hello peer"), policy).unwrap(),
+            fetched
+        );
+
+        let error = fetch(&desktop, &cache_dir, &hash(b"original"), policy).unwrap_err();
+        assert!(format!("{error:#}").contains("does not match its hash"));
+        assert_eq!(cached_content(&cache_dir, &hash(b"original")), None);
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+        assert!(fetch(&desktop, &cache_dir, &[9; 32], policy).is_err());
+        server.join().unwrap();
+
+        drop((laptop, desktop));
+        let _ = fs::remove_dir_all(cache_dir);
+        let _ = fs::remove_file(laptop_path);
+        let _ = fs::remove_file(desktop_path);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_send_tokens_to_other_computers_over_cleartext() {
+        let path = temporary_database("peer-cleartext");
+        let database = Database::open(&path).unwrap();
+        database.local_node_id("Desktop").unwrap();
+        let error = pair(&database, "192.0.2.7:5777", "token", false).unwrap_err();
+        assert!(format!("{error:#}").contains("192.0.2.7:5777 is not on this computer"));
+        assert!(database.peers().unwrap().is_empty());
+        drop(database);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn reads_content_length_responses_and_rejects_https() {
+        let mut response = &b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}trailing"[..];
+        let (status, framing) = read_head(&mut response).unwrap();
+        assert_eq!((status, framing), (200, Framing::Length(2)));
+        let mut body = Vec::new();
+        assert!(read_body(&mut &b"{}"[..], framing, &mut body, 1).is_err());
+        read_body(&mut response, framing, &mut body, 2).unwrap();
+        assert_eq!(body, b"{}");
+        assert!(normalize_url("https://example.com").is_err());
+        assert_eq!(
+            normalize_url(" 192.168.1.2:8080/ ").unwrap(),
+            "http://192.168.1.2:8080"
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Periodic jobs run from the daemon event loop.
+//!
+//! `BackupConfig.schedule` decides how often every Scanned folder is
+//! rescanned: never while Continuous, because the filesystem watcher keeps
+//! the index current, otherwise hourly or daily. Folders the watcher could
+//! not watch are rescanned every `MediaConfig.fallback_rescan_seconds`
+//! whatever the schedule, and database maintenance runs daily. The same
+//! schedule paces backups once a destination is set: hourly, daily, or while
+//! Continuous soon after the index changes, at most every
+//! [`CONTINUOUS_BACKUP_MILLIS`]. Snapshots past `BackupConfig.retention` are
+//! pruned daily. Scrubbing runs hourly, each run verifying a slice of the
+//! index, so that every file is read back about once per
+//! `MediaConfig.scrub_interval_days`. Paired devices are synced every
+//! `PeersConfig.sync_minutes`. Last runs are stored, so restarting the
+//! daemon does not reset the clock.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::{AppConfig, BackupSchedule};
+
+const HOUR_MILLIS: i64 = 3_600_000;
+const DAY_MILLIS: i64 = 24 * HOUR_MILLIS;
+/// Shortest gap between continuous backups, so a burst of changes is backed
+/// up once rather than file by file.
+pub const CONTINUOUS_BACKUP_MILLIS: i64 = 15 * 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScheduledJob {
+    /// Full rescan of every Scanned folder.
+    Rescan,
+    /// Rescan of the folders the filesystem watcher is not watching.
+    FallbackRescan,
+    /// SQLite and search-index upkeep.
+    Maintenance,
+    /// Reads indexed files back to detect silent corruption.
+    Scrub,
+    /// Pulls index changes from paired devices.
+    PeerSync,
+    /// Copies the Scanned folders to the backup destination.
+    Backup,
+    /// Removes snapshots past their retention and the content only they used.
+    Prune,
+}
+
+impl ScheduledJob {
+    pub const ALL: [Self; 7] = [
+        Self::Rescan,
+        Self::FallbackRescan,
+        Self::Maintenance,
+        Self::Scrub,
+        Self::PeerSync,
+        Self::Backup,
+        Self::Prune,
+    ];
+
+    /// Stable name the last run is stored under.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Rescan => "rescan",
+            Self::FallbackRescan => "fallback-rescan",
+            Self::Maintenance => "maintenance",
+            Self::Scrub => "scrub",
+            Self::PeerSync => "peer-sync",
+            Self::Backup => "backup",
+            Self::Prune => "prune",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Rescan => "Rescan all Scanned folders",
+            Self::FallbackRescan => "Rescan unwatched folders",
+            Self::Maintenance => "Database maintenance",
+            Self::Scrub => "Verify file contents",
+            Self::PeerSync => "Sync paired devices",
+            Self::Backup => "Back up Scanned folders",
+            Self::Prune => "Prune old snapshots",
+        }
+    }
+
+    /// Milliseconds between runs, or `None` when the configuration turns
+    /// the job off.
+    pub fn interval(self, config: &AppConfig) -> Option<i64> {
+        match self {
+            Self::Rescan => match config.backup.schedule {
+                BackupSchedule::Continuous => None,
+                BackupSchedule::Hourly => Some(HOUR_MILLIS),
+                BackupSchedule::Daily => Some(DAY_MILLIS),
+            },
+            Self::FallbackRescan => (config.media.fallback_rescan_seconds > 0)
+                .then(|| config.media.fallback_rescan_seconds.saturating_mul(1_000) as i64),
+            Self::Maintenance => Some(DAY_MILLIS),
+            Self::Scrub => (config.media.scrub_interval_days > 0).then_some(HOUR_MILLIS),
+            Self::PeerSync => (config.peers.sync_minutes > 0)
+                .then(|| config.peers.sync_minutes.saturating_mul(60_000) as i64),
+            Self::Backup => {
+                config
+                    .backup
+                    .destination
+                    .is_some()
+                    .then_some(match config.backup.schedule {
+                        BackupSchedule::Continuous => CONTINUOUS_BACKUP_MILLIS,
+                        BackupSchedule::Hourly => HOUR_MILLIS,
+                        BackupSchedule::Daily => DAY_MILLIS,
+                    })
+            }
+            Self::Prune => (config.backup.destination.is_some()
+                && config.backup.retention.prunes())
+            .then_some(DAY_MILLIS),
+        }
+    }
+
+    /// Jobs that move data over the network wait for an unmetered
+    /// connection unless `BackupConfig.metered_connections` allows them.
+    fn uses_network(self) -> bool {
+        match self {
+            Self::Rescan
+            | Self::FallbackRescan
+            | Self::Maintenance
+            | Self::Scrub
+            | Self::Backup
+            | Self::Prune => false,
+            Self::PeerSync => true,
+        }
+    }
+}
+
+/// Power and network state that scheduled work may wait for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Conditions {
+    pub on_battery: bool,
+    pub metered: bool,
+}
+
+/// Where [`Conditions`] come from, so other platforms can plug in their own
+/// power and network detection.
+pub trait ConditionProbe: Send + Sync {
+    fn conditions(&self) -> Conditions;
+}
+
+/// Reads battery state from Linux sysfs. Metered connections are not
+/// detected yet and are reported as unmetered.
+pub struct SystemConditions;
+
+impl ConditionProbe for SystemConditions {
+    fn conditions(&self) -> Conditions {
+        Conditions {
+            on_battery: on_battery(Path::new("/sys/class/power_supply")),
+            metered: false,
+        }
+    }
+}
+
+/// A discharging battery with no mains supply online. Machines without a
+/// battery, or without sysfs, are never on battery.
+fn on_battery(power_supplies: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(power_supplies) else {
+        return false;
+    };
+    let mut mains_online = false;
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let read = |name: &str| {
+            std::fs::read_to_string(entry.path().join(name))
+                .map(|value| value.trim().to_owned())
+                .unwrap_or_default()
+        };
+        match read("type").as_str() {
+            "Mains" => mains_online |= read("online") == "1",
+            "Battery" => discharging |= read("status") == "Discharging",
+            _ => {}
+        }
+    }
+    discharging && !mains_online
+}
+
+fn blocked_reason(
+    uses_network: bool,
+    config: &AppConfig,
+    conditions: Conditions,
+) -> Option<&'static str> {
+    if conditions.on_battery && config.backup.pause_on_battery {
+        Some("Waiting for mains power")
+    } else if conditions.metered && uses_network && !config.backup.metered_connections {
+        Some("Waiting for an unmetered connection")
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    last_runs: HashMap<ScheduledJob, i64>,
+    blocked: HashMap<ScheduledJob, &'static str>,
+    /// Whether the index changed since the last backup started.
+    index_changed: bool,
+}
+
+impl Scheduler {
+    /// `last_runs` maps stored job names to Unix millis; jobs that never ran
+    /// count from `now`. Changes made while the daemon was stopped are not
+    /// known, so a continuous backup is due again after its interval.
+    pub fn new(last_runs: &HashMap<String, i64>, now: i64) -> Self {
+        Self {
+            last_runs: ScheduledJob::ALL
+                .into_iter()
+                .map(|job| (job, last_runs.get(job.as_str()).copied().unwrap_or(now)))
+                .collect(),
+            blocked: HashMap::new(),
+            index_changed: true,
+        }
+    }
+
+    /// Records that the index changed, which a continuous backup waits for.
+    pub fn note_index_change(&mut self) {
+        self.index_changed = true;
+    }
+
+    /// When `job` runs next, or `None` while it is off or, for a continuous
+    /// backup, waiting for the index to change.
+    pub fn next_run(&self, job: ScheduledJob, config: &AppConfig) -> Option<i64> {
+        if self.waiting_for_changes(job, config) {
+            return None;
+        }
+        let last_run = self.last_runs.get(&job).copied().unwrap_or_default();
+        Some(last_run.saturating_add(job.interval(config)?))
+    }
+
+    /// Whether `job` is a continuous backup with nothing new to back up.
+    pub fn waiting_for_changes(&self, job: ScheduledJob, config: &AppConfig) -> bool {
+        job == ScheduledJob::Backup
+            && config.backup.schedule == BackupSchedule::Continuous
+            && job.interval(config).is_some()
+            && !self.index_changed
+    }
+
+    /// Why a due job is being held back, as of the last [`Scheduler::take_due`].
+    pub fn blocked_reason(&self, job: ScheduledJob) -> Option<&'static str> {
+        self.blocked.get(&job).copied()
+    }
+
+    /// Returns the jobs due at `now` that `conditions` allow and marks them
+    /// as run. Held-back jobs stay due and run as soon as conditions allow.
+    pub fn take_due(
+        &mut self,
+        config: &AppConfig,
+        now: i64,
+        conditions: Conditions,
+    ) -> Vec<ScheduledJob> {
+        self.blocked.clear();
+        let mut due = Vec::new();
+        for job in ScheduledJob::ALL {
+            if self.next_run(job, config).is_none_or(|at| at > now) {
+                continue;
+            }
+            if let Some(reason) = blocked_reason(job.uses_network(), config, conditions) {
+                self.blocked.insert(job, reason);
+                continue;
+            }
+            self.last_runs.insert(job, now);
+            if job == ScheduledJob::Backup {
+                self.index_changed = false;
+            }
+            due.push(job);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn runs_due_jobs_per_schedule_and_holds_them_on_battery() {
+        let mut config = AppConfig::default();
+        config.media.fallback_rescan_seconds = 0;
+        config.media.scrub_interval_days = 0;
+        config.peers.sync_minutes = 0;
+        let stored = HashMap::from([("maintenance".to_owned(), 0)]);
+        let mut scheduler = Scheduler::new(&stored, 1_000);
+        assert_eq!(scheduler.next_run(ScheduledJob::Rescan, &config), None);
+        assert_eq!(scheduler.next_run(ScheduledJob::Backup, &config), None);
+        assert_eq!(
+            scheduler.next_run(ScheduledJob::Maintenance, &config),
+            Some(DAY_MILLIS)
+        );
+        assert!(
+            scheduler
+                .take_due(&config, 2_000, Conditions::default())
+                .is_empty()
+        );
+
+        config.backup.destination = Some(PathBuf::from("/backups"));
+        config.backup.schedule = BackupSchedule::Hourly;
+        let later = DAY_MILLIS + 1_000;
+        let on_battery = Conditions {
+            on_battery: true,
+            metered: false,
+        };
+        assert!(scheduler.take_due(&config, later, on_battery).is_empty());
+        assert_eq!(
+            scheduler.blocked_reason(ScheduledJob::Backup),
+            Some("Waiting for mains power")
+        );
+        assert_eq!(
+            scheduler.take_due(&config, later, Conditions::default()),
+            [
+                ScheduledJob::Rescan,
+                ScheduledJob::Maintenance,
+                ScheduledJob::Backup,
+                ScheduledJob::Prune
+            ]
+        );
+        assert_eq!(scheduler.blocked_reason(ScheduledJob::Backup), None);
+        for job in [ScheduledJob::Rescan, ScheduledJob::Backup] {
+            assert_eq!(scheduler.next_run(job, &config), Some(later + HOUR_MILLIS));
+        }
+        config.backup.schedule = BackupSchedule::Daily;
+        for job in [ScheduledJob::Rescan, ScheduledJob::Backup] {
+            assert_eq!(scheduler.next_run(job, &config), Some(later + DAY_MILLIS));
+        }
+
+        config.backup.pause_on_battery = false;
+        assert_eq!(blocked_reason(false, &config, on_battery), None);
+        let metered = Conditions {
+            on_battery: false,
+            metered: true,
+        };
+        assert_eq!(
+            blocked_reason(true, &config, metered),
+            Some("Waiting for an unmetered connection")
+        );
+        assert_eq!(blocked_reason(false, &config, metered), None);
+        config.backup.metered_connections = true;
+        assert_eq!(blocked_reason(true, &config, metered), None);
+    }
+
+    #[test]
+    fn continuous_backups_follow_index_changes() {
+        let mut config = AppConfig::default();
+        config.backup.destination = Some(PathBuf::from("/backups"));
+        let backs_up = |scheduler: &mut Scheduler, config: &AppConfig, now| {
+            scheduler
+                .take_due(config, now, Conditions::default())
+                .contains(&ScheduledJob::Backup)
+        };
+        // Changes made while the daemon was stopped are unknown, so the first
+        // interval ends in a backup.
+        let mut scheduler = Scheduler::new(&HashMap::new(), 0);
+        assert_eq!(
+            scheduler.next_run(ScheduledJob::Backup, &config),
+            Some(CONTINUOUS_BACKUP_MILLIS)
+        );
+        assert!(!backs_up(
+            &mut scheduler,
+            &config,
+            CONTINUOUS_BACKUP_MILLIS - 1
+        ));
+        assert!(backs_up(&mut scheduler, &config, CONTINUOUS_BACKUP_MILLIS));
+
+        assert!(scheduler.waiting_for_changes(ScheduledJob::Backup, &config));
+        assert_eq!(scheduler.next_run(ScheduledJob::Backup, &config), None);
+        assert!(!backs_up(
+            &mut scheduler,
+            &config,
+            3 * CONTINUOUS_BACKUP_MILLIS
+        ));
+
+        // A change waits out the minimum gap after the previous backup.
+        let mut scheduler = Scheduler::new(&HashMap::from([("backup".to_owned(), 0)]), 0);
+        assert!(backs_up(&mut scheduler, &config, CONTINUOUS_BACKUP_MILLIS));
+        scheduler.note_index_change();
+        assert!(!scheduler.waiting_for_changes(ScheduledJob::Backup, &config));
+        assert!(!backs_up(
+            &mut scheduler,
+            &config,
+            CONTINUOUS_BACKUP_MILLIS + 1
+        ));
+        assert!(backs_up(
+            &mut scheduler,
+            &config,
+            2 * CONTINUOUS_BACKUP_MILLIS
+        ));
+
+        config.backup.destination = None;
+        scheduler.note_index_change();
+        assert!(!scheduler.waiting_for_changes(ScheduledJob::Backup, &config));
+        assert_eq!(scheduler.next_run(ScheduledJob::Backup, &config), None);
+    }
+
+    #[test]
+    fn reads_battery_state_from_power_supplies() {
+        let directory =
+            std::env::temp_dir().join(format!("puppydrive-power-{}", uuid::Uuid::new_v4()));
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let path = directory.join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            for (file, value) in files {
+                std::fs::write(path.join(file), format!("{value}\n")).unwrap();
+            }
+        };
+        assert!(!on_battery(&directory));
+        supply("BAT0", &[("type", "Battery"), ("status", "Discharging")]);
+        assert!(on_battery(&directory));
+        supply("AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(!on_battery(&directory));
+        let _ = std::fs::remove_dir_all(directory);
+    }
+}
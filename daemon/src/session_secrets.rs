@@ -7,13 +7,16 @@ pub struct SessionSecretStore {
     secrets: HashMap<(String, String), SecretString>,
 }
 
-#[allow(dead_code)] // Used by remote source providers once those transports are enabled.
 impl SessionSecretStore {
     pub fn set(&mut self, source_key: &str, slot: &str, value: SecretString) {
         self.secrets
             .insert((source_key.to_owned(), slot.to_owned()), value);
     }
 
+    pub fn get(&self, source_key: &str, slot: &str) -> Option<&SecretString> {
+        self.secrets.get(&(source_key.to_owned(), slot.to_owned()))
+    }
+
     pub fn is_available(&self, source_key: &str, slot: &str) -> bool {
         self.secrets
             .contains_key(&(source_key.to_owned(), slot.to_owned()))
@@ -37,6 +40,8 @@ impl Drop for SessionSecretStore {
 
 #[cfg(test)]
 mod tests {
+    use secrecy::ExposeSecret;
+
     use super::*;
 
     #[test]
@@ -45,6 +50,12 @@ mod tests {
         store.set("source-a", "password", SecretString::from("secret"));
         assert!(store.is_available("source-a", "password"));
         assert!(!store.is_available("source-b", "password"));
+        assert_eq!(
+            store
+                .get("source-a", "password")
+                .map(ExposeSecret::expose_secret),
+            Some("secret")
+        );
         store.clear_source("source-a");
         assert!(!store.is_available("source-a", "password"));
     }
@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Staging area for resumable Inbox uploads.
+///
+/// Every session is a `<id>.json` descriptor plus a `<id>.part` file holding
+/// the bytes received so far, so the current offset survives daemon restarts.
+pub struct UploadStaging {
+    dir: PathBuf,
+    active: Mutex<HashSet<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadSession {
+    pub id: String,
+    pub inbox_id: String,
+    pub filename: PathBuf,
+    pub length: u64,
+    pub created_at: i64,
+}
+
+#[derive(Debug)]
+pub enum UploadError {
+    NotFound,
+    Busy,
+    OffsetMismatch { expected: u64 },
+    ExceedsLength,
+    Incomplete { offset: u64 },
+    AlreadyExists,
+    Io(io::Error),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "upload session not found"),
+            Self::Busy => write!(f, "upload session is already receiving data"),
+            Self::OffsetMismatch { expected } => {
+                write!(f, "upload offset does not match; expected {expected}")
+            }
+            Self::ExceedsLength => write!(f, "chunk exceeds the declared upload length"),
+            Self::Incomplete { offset } => write!(f, "upload is incomplete at offset {offset}"),
+            Self::AlreadyExists => write!(f, "a file with that name already exists"),
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl From<io::Error> for UploadError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl UploadStaging {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            active: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn create(&self, inbox_id: &str, filename: &Path, length: u64) -> Result<UploadSession> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("unable to create upload staging {}", self.dir.display()))?;
+        let session = UploadSession {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            inbox_id: inbox_id.to_owned(),
+            filename: filename.to_path_buf(),
+            length,
+            created_at: now_millis(),
+        };
+        fs::File::create(self.part_path(&session.id))?;
+        fs::write(
+            self.descriptor_path(&session.id),
+            serde_json::to_vec(&session)?,
+        )?;
+        Ok(session)
+    }
+
+    pub fn session(&self, id: &str) -> Result<UploadSession, UploadError> {
+        if !valid_session_id(id) {
+            return Err(UploadError::NotFound);
+        }
+        let raw = match fs::read(self.descriptor_path(id)) {
+            Ok(raw) => raw,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Err(UploadError::NotFound);
+            }
+            Err(error) => return Err(error.into()),
+        };
+        serde_json::from_slice(&raw).map_err(|error| UploadError::Io(error.into()))
+    }
+
+    pub fn offset(&self, session: &UploadSession) -> Result<u64, UploadError> {
+        match fs::metadata(self.part_path(&session.id)) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Err(UploadError::NotFound),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Appends `bytes` when `offset` matches the bytes already staged and
+    /// returns the new offset.
+    pub fn append(
+        &self,
+        session: &UploadSession,
+        offset: u64,
+        bytes: &[u8],
+    ) -> Result<u64, UploadError> {
+        let _guard = self.lock(&session.id)?;
+        let current = self.offset(session)?;
+        if offset != current {
+            return Err(UploadError::OffsetMismatch { expected: current });
+        }
+        if current.saturating_add(bytes.len() as u64) > session.length {
+            return Err(UploadError::ExceedsLength);
+        }
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.part_path(&session.id))?;
+        if let Err(error) = file.write_all(bytes).and_then(|_| file.sync_data()) {
+            // Drop any torn write so the client can resume from the last good offset.
+            let _ = file.set_len(current);
+            return Err(error.into());
+        }
+        Ok(current + bytes.len() as u64)
+    }
+
+    /// Moves a fully received upload to `target` without replacing an existing file.
+    pub fn finish(&self, session: &UploadSession, target: &Path) -> Result<(), UploadError> {
+        let _guard = self.lock(&session.id)?;
+        let offset = self.offset(session)?;
+        if offset != session.length {
+            return Err(UploadError::Incomplete { offset });
+        }
+        let part = self.part_path(&session.id);
+        match fs::hard_link(&part, target) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(UploadError::AlreadyExists);
+            }
+            // Staging lives next to the database, which is often on another
+            // filesystem than the Inbox, so fall back to a copy beside the target.
+            Err(_) => copy_into_place(&part, target, &session.id)?,
+        }
+        let _ = fs::remove_file(&part);
+        let _ = fs::remove_file(self.descriptor_path(&session.id));
+        Ok(())
+    }
+
+    pub fn abort(&self, session: &UploadSession) -> Result<(), UploadError> {
+        let _guard = self.lock(&session.id)?;
+        let _ = fs::remove_file(self.part_path(&session.id));
+        fs::remove_file(self.descriptor_path(&session.id))?;
+        Ok(())
+    }
+
+    /// Removes sessions that have not received data for longer than `max_age`.
+    pub fn prune(&self, max_age: Duration) -> Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error.into()),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let part = self.part_path(id);
+            let last_write = fs::metadata(&part)
+                .or_else(|_| fs::metadata(&path))
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            let stale = last_write
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|elapsed| elapsed > max_age);
+            if stale && !self.is_active(id) {
+                let _ = fs::remove_file(&part);
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn lock(&self, id: &str) -> Result<SessionGuard<'_>, UploadError> {
+        let mut active = self
+            .active
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if !active.insert(id.to_owned()) {
+            return Err(UploadError::Busy);
+        }
+        Ok(SessionGuard {
+            staging: self,
+            id: id.to_owned(),
+        })
+    }
+
+    fn is_active(&self, id: &str) -> bool {
+        self.active
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .contains(id)
+    }
+
+    fn part_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.part"))
+    }
+
+    fn descriptor_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+}
+
+struct SessionGuard<'a> {
+    staging: &'a UploadStaging,
+    id: String,
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        self.staging
+            .active
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .remove(&self.id);
+    }
+}
+
+fn copy_into_place(part: &Path, target: &Path, id: &str) -> Result<(), UploadError> {
+    let Some(filename) = target.file_name() else {
+        return Err(UploadError::NotFound);
+    };
+    let staged = target.with_file_name(format!(".{}.{id}.upload", filename.to_string_lossy()));
+    let result = fs::copy(part, &staged)
+        .and_then(|_| fs::File::open(&staged)?.sync_all())
+        .map_err(UploadError::from)
+        .and_then(|_| publish(&staged, target));
+    let _ = fs::remove_file(&staged);
+    result
+}
+
+/// Puts `staged` in place at `target` without replacing anything created
+/// there since the upload started. `staged` is left for the caller.
+fn publish(staged: &Path, target: &Path) -> Result<(), UploadError> {
+    match fs::hard_link(staged, target) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            Err(UploadError::AlreadyExists)
+        }
+        // Filesystems without hardlinks, such as FAT drives: claim the name
+        // first so the rename only ever replaces this placeholder.
+        Err(_) => {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)
+            {
+                Ok(_) => {}
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    return Err(UploadError::AlreadyExists);
+                }
+                Err(error) => return Err(error.into()),
+            }
+            fs::rename(staged, target).map_err(|error| {
+                let _ = fs::remove_file(target);
+                UploadError::from(error)
+            })
+        }
+    }
+}
+
+fn valid_session_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+        .min(i64::MAX as u128) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("puppydrive-{name}-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn resumes_and_finishes_uploads() {
+        let root = temporary_directory("upload-staging");
+        let staging = UploadStaging::new(root.join("staging"));
+        let session = staging.create("inbox", Path::new("video.mp4"), 10).unwrap();
+        assert_eq!(staging.session(&session.id).unwrap(), session);
+        assert_eq!(staging.append(&session, 0, b"hello").unwrap(), 5);
+        assert!(matches!(
+            staging.append(&session, 0, b"hello"),
+            Err(UploadError::OffsetMismatch { expected: 5 })
+        ));
+        assert!(matches!(
+            staging.append(&session, 5, b"too many bytes"),
+            Err(UploadError::ExceedsLength)
+        ));
+        let target = root.join("video.mp4");
+        assert!(matches!(
+            staging.finish(&session, &target),
+            Err(UploadError::Incomplete { offset: 5 })
+        ));
+        assert_eq!(staging.offset(&session).unwrap(), 5);
+        assert_eq!(staging.append(&session, 5, b"world").unwrap(), 10);
+        staging.finish(&session, &target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"helloworld");
+        assert!(matches!(
+            staging.session(&session.id),
+            Err(UploadError::NotFound)
+        ));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn finish_never_replaces_existing_files() {
+        let root = temporary_directory("upload-staging-existing");
+        let staging = UploadStaging::new(root.join("staging"));
+        let session = staging.create("inbox", Path::new("notes.txt"), 3).unwrap();
+        staging.append(&session, 0, b"new").unwrap();
+        fs::write(root.join("notes.txt"), b"old").unwrap();
+        assert!(matches!(
+            staging.finish(&session, &root.join("notes.txt")),
+            Err(UploadError::AlreadyExists)
+        ));
+        assert_eq!(fs::read(root.join("notes.txt")).unwrap(), b"old");
+        assert_eq!(staging.offset(&session).unwrap(), 3);
+        // Nor does the copy used when staging is on another filesystem.
+        assert!(matches!(
+            copy_into_place(
+                &staging.part_path(&session.id),
+                &root.join("notes.txt"),
+                &session.id
+            ),
+            Err(UploadError::AlreadyExists)
+        ));
+        assert_eq!(fs::read(root.join("notes.txt")).unwrap(), b"old");
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+        copy_into_place(
+            &staging.part_path(&session.id),
+            &root.join("copied.txt"),
+            &session.id,
+        )
+        .unwrap();
+        assert_eq!(fs::read(root.join("copied.txt")).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 3);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn rejects_malformed_session_ids() {
+        let root = temporary_directory("upload-staging-ids");
+        let staging = UploadStaging::new(&root);
+        assert!(matches!(
+            staging.session("../config"),
+            Err(UploadError::NotFound)
+        ));
+        let _ = fs::remove_dir_all(root);
+    }
+}
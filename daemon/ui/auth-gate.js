@@ -0,0 +1,184 @@
+async function responseError(response) {
+  try {
+    const body = await response.json();
+    if (body && body.error) return new Error(String(body.error));
+  } catch {
+    // Fall through to the status code.
+  }
+  return new Error(`request failed (${response.status})`);
+}
+
+async function postJson(url, body) {
+  return fetch(url, {
+    method: "POST",
+    cache: "no-store",
+    headers: { "content-type": "application/json" },
+    body: JSON.stringify(body || {}),
+  });
+}
+
+export default class AuthGate {
+  constructor(element, ctx) {
+    this.element = element;
+    this.ctx = ctx;
+    this.props = {};
+    this.error = "";
+    this.busy = false;
+    this.signedOut = false;
+  }
+
+  mount(props) {
+    this.setProps(props);
+    if (this.props.signOut) {
+      this.signOut();
+    } else {
+      this.attachSession();
+    }
+  }
+
+  setProps(props) {
+    this.props = props || {};
+    this.render();
+  }
+
+  async signOut() {
+    if (this.signedOut) return;
+    this.signedOut = true;
+    try {
+      await postJson("/auth/logout");
+    } catch {
+      // The session is already gone from this client; the server drops it on expiry.
+    }
+  }
+
+  async attachSession() {
+    try {
+      const response = await postJson("/auth/ticket");
+      if (!response.ok) return false;
+      const { ticket } = await response.json();
+      this.ctx.emit("authenticated", { ticket });
+      return true;
+    } catch {
+      return false;
+    }
+  }
+
+  async submit(url, body) {
+    if (this.busy) return;
+    this.busy = true;
+    this.error = "";
+    this.render();
+    try {
+      const response = await postJson(url, body);
+      if (!response.ok) throw await responseError(response);
+      if (!(await this.attachSession())) throw new Error("unable to start a session");
+    } catch (error) {
+      this.error = error.message || String(error);
+    } finally {
+      this.busy = false;
+      this.render();
+    }
+  }
+
+  field(labelText, type, autocomplete) {
+    const label = document.createElement("label");
+    label.textContent = labelText;
+    label.style.display = "grid";
+    label.style.gap = "6px";
+    label.style.color = "#374151";
+    label.style.fontWeight = "600";
+    const input = document.createElement("input");
+    input.type = type;
+    input.autocomplete = autocomplete;
+    input.style.boxSizing = "border-box";
+    input.style.width = "100%";
+    input.style.padding = "8px";
+    input.style.border = "1px solid #cbd5e1";
+    input.style.borderRadius = "4px";
+    input.style.fontWeight = "400";
+    label.append(input);
+    return { label, input };
+  }
+
+  render() {
+    const configured = Boolean(this.props.configured);
+    const form = document.createElement("form");
+    form.style.display = "grid";
+    form.style.gap = "14px";
+    form.style.width = "100%";
+    form.style.maxWidth = "380px";
+    form.style.margin = "10vh auto 0";
+    form.style.padding = "22px";
+    form.style.border = "1px solid #dce5e8";
+    form.style.borderRadius = "6px";
+    form.style.background = "#ffffff";
+
+    const title = document.createElement("strong");
+    title.textContent = configured ? "Sign in to PuppyDrive" : "Set up PuppyDrive";
+    title.style.color = "#0f6175";
+    const hint = document.createElement("p");
+    hint.style.margin = "0";
+    hint.style.color = "#6b7280";
+    hint.textContent = configured
+      ? "Enter the admin password for this PuppyDrive."
+      : "Choose an admin password. It protects the web interface and the HTTP API.";
+    form.append(title, hint);
+
+    const password = this.field(
+      configured ? "Password" : "New password",
+      "password",
+      configured ? "current-password" : "new-password",
+    );
+    form.append(password.label);
+    let confirmation = null;
+    let setupCode = null;
+    if (!configured) {
+      confirmation = this.field("Repeat password", "password", "new-password");
+      form.append(confirmation.label);
+      if (this.props.setupCodeRequired) {
+        setupCode = this.field("Setup code from the server log", "text", "one-time-code");
+        form.append(setupCode.label);
+      }
+    }
+
+    const status = document.createElement("div");
+    status.style.color = "#b42318";
+    status.textContent = this.error;
+
+    const submit = document.createElement("button");
+    submit.type = "submit";
+    submit.textContent = this.busy ? "Please wait…" : configured ? "Sign in" : "Create password";
+    submit.disabled = this.busy;
+    submit.style.padding = "9px 14px";
+    submit.style.border = "1px solid #0f7892";
+    submit.style.borderRadius = "4px";
+    submit.style.background = "#0f7892";
+    submit.style.color = "#ffffff";
+    submit.style.cursor = "pointer";
+    form.append(status, submit);
+
+    form.addEventListener("submit", (event) => {
+      event.preventDefault();
+      if (configured) {
+        this.submit("/auth/login", { password: password.input.value });
+        return;
+      }
+      if (password.input.value !== confirmation.input.value) {
+        this.error = "The passwords do not match.";
+        this.render();
+        return;
+      }
+      this.submit("/auth/setup", {
+        password: password.input.value,
+        setup_code: setupCode ? setupCode.input.value : null,
+      });
+    });
+
+    this.element.replaceChildren(form);
+    password.input.focus();
+  }
+
+  dispose() {
+    this.element.replaceChildren();
+  }
+}
@@ -9,10 +9,40 @@ const COLUMNS = [
   { key: "modified", label: "Modified", width: 120, min: 90, sort: "modifiedValue" },
 ];
 
+// Extra columns for the Media page, filled from EXIF when a photo has it.
+const PHOTO_COLUMNS = [
+  { key: "captured", label: "Taken", width: 110, min: 90, sort: "capturedValue" },
+  { key: "camera", label: "Camera", width: 160, min: 100, sort: "camera" },
+  { key: "lens", label: "Lens", width: 180, min: 100, sort: "lens" },
+  { key: "dimensions", label: "Dimensions", width: 120, min: 90, sort: "pixelsValue" },
+  { key: "location", label: "Location", width: 170, min: 110 },
+];
+// Extra columns for the Audio page, filled from audio tags.
+const AUDIO_COLUMNS = [
+  { key: "title", label: "Title", width: 220, min: 120, sort: "title" },
+  { key: "artist", label: "Artist", width: 170, min: 100, sort: "artist" },
+  { key: "album", label: "Album", width: 190, min: 100, sort: "albumValue" },
+  { key: "track", label: "#", width: 60, min: 45 },
+  { key: "duration", label: "Length", width: 80, min: 60, sort: "durationValue" },
+  { key: "year", label: "Year", width: 70, min: 55 },
+  { key: "genre", label: "Genre", width: 120, min: 80 },
+];
+const SEARCH_COLUMNS = [
+  { key: "folder", label: "Folder", width: 280, min: 120, sort: "folder" },
+  { key: "node", label: "Node", width: 120, min: 80, sort: "node" },
+];
+const COLUMN_SETS = {
+  files: COLUMNS,
+  photo: [...COLUMNS, ...PHOTO_COLUMNS],
+  audio: [...COLUMNS, ...AUDIO_COLUMNS],
+  search: [...COLUMNS.slice(0, 2), ...SEARCH_COLUMNS, ...COLUMNS.slice(2)],
+};
+
 export default class FileTable {
   constructor(element, ctx) {
     this.element = element;
     this.ctx = ctx;
+    this.columns = COLUMNS;
     this.widths = COLUMNS.map((column) => column.width);
     this.sortKey = null;
     this.sortDescending = false;
@@ -21,12 +51,17 @@ export default class FileTable {
   mount(props) { this.setProps(props); }
 
   setProps(props) {
+    const columns = COLUMN_SETS[props.columns] || COLUMNS;
+    if (columns !== this.columns) {
+      this.columns = columns;
+      this.widths = columns.map((column) => column.width);
+    }
     const serverSort = Boolean(props.serverSort);
     if (serverSort) {
       this.sortKey = String(props.sortKey || "name");
       this.sortDescending = Boolean(props.sortDescending);
     }
-    const grid = () => this.widths.map((width, index) => `${Math.max(width, COLUMNS[index].min)}px`).join(" ");
+    const grid = () => this.widths.map((width, index) => `${Math.max(width, columns[index].min)}px`).join(" ");
     const root = document.createElement("div");
     root.style.width = "100%";
     root.style.minWidth = "0";
@@ -41,7 +76,7 @@ export default class FileTable {
       header.style.minWidth = "max-content";
       header.style.background = "#f8fafb";
       header.style.color = "#6b7280";
-      COLUMNS.forEach((column, index) => {
+      columns.forEach((column, index) => {
         const cell = document.createElement("div");
         cell.textContent = column.label + (this.sortKey === column.sort ? (this.sortDescending ? " ↓" : " ↑") : "");
         cell.style.position = "relative";
@@ -59,7 +94,7 @@ export default class FileTable {
             render();
           };
         }
-        if (index < COLUMNS.length - 1) {
+        if (index < columns.length - 1) {
           const handle = document.createElement("div");
           handle.style.position = "absolute";
           handle.style.top = "0";
@@ -77,7 +112,7 @@ export default class FileTable {
             const startX = event.clientX;
             const startWidth = this.widths[index];
             const move = (moveEvent) => {
-              this.widths[index] = Math.max(COLUMNS[index].min, startWidth + moveEvent.clientX - startX);
+              this.widths[index] = Math.max(columns[index].min, startWidth + moveEvent.clientX - startX);
               render();
             };
             window.addEventListener("pointermove", move);
@@ -116,7 +151,7 @@ export default class FileTable {
         line.style.textAlign = "left";
         line.style.cursor = "pointer";
         line.onclick = () => this.ctx.emit("open", { index: Number(row.index) });
-        for (const column of COLUMNS) {
+        for (const column of columns) {
           const cell = document.createElement("div");
           if (column.key === "online") {
             const online = Boolean(row.online);
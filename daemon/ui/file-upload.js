@@ -1,3 +1,19 @@
+const CHUNK_SIZE = 8 * 1024 * 1024;
+const MAX_RETRIES = 5;
+
+function sessionKey(inbox, file) {
+  return `puppydrive-upload:${inbox}:${file.name}:${file.size}:${file.lastModified}`;
+}
+
+function wait(ms) {
+  return new Promise((resolve) => setTimeout(resolve, ms));
+}
+
+async function responseError(response) {
+  const message = (await response.text()).trim();
+  return new Error(message || `upload failed (${response.status})`);
+}
+
 export default class FileUpload {
   constructor(element, ctx) {
     this.element = element;
@@ -107,17 +123,11 @@ export default class FileUpload {
         status.textContent = `Uploading ${index + 1} of ${files.length}: ${file.name}`;
         status.style.color = "#4b5563";
         try {
-          const response = await fetch(`/uploads?inbox=${encodeURIComponent(inbox.value)}`, {
-            method: "POST",
-            headers: {
-              "x-puppydrive-filename": encodeURIComponent(file.name),
-              "content-type": file.type || "application/octet-stream",
-            },
-            body: file,
+          await this.uploadFile(inbox.value, file, (sent) => {
+            const percent = file.size ? Math.floor((sent / file.size) * 100) : 100;
+            status.textContent = `Uploading ${index + 1} of ${files.length}: ${file.name} (${percent}%)`;
           });
-          const message = (await response.text()).trim();
-          if (response.ok) uploaded.push(file.name);
-          else failed.push(`${file.name}: ${message || `upload failed (${response.status})`}`);
+          uploaded.push(file.name);
         } catch (error) {
           failed.push(`${file.name}: ${error instanceof Error ? error.message : "network error"}`);
         }
@@ -141,6 +151,65 @@ export default class FileUpload {
     this.element.replaceChildren(root);
   }
 
+  async uploadFile(inboxId, file, onProgress) {
+    const key = sessionKey(inboxId, file);
+    let sessionUrl = localStorage.getItem(key);
+    let offset = sessionUrl ? await this.sessionOffset(sessionUrl) : null;
+    if (offset === null) {
+      const created = await fetch(`/uploads/sessions?inbox=${encodeURIComponent(inboxId)}`, {
+        method: "POST",
+        headers: {
+          "x-puppydrive-filename": encodeURIComponent(file.name),
+          "upload-length": String(file.size),
+        },
+      });
+      if (!created.ok) throw await responseError(created);
+      sessionUrl = created.headers.get("location");
+      offset = 0;
+      localStorage.setItem(key, sessionUrl);
+    }
+
+    let retries = 0;
+    while (offset < file.size) {
+      onProgress(offset);
+      try {
+        const response = await fetch(sessionUrl, {
+          method: "PATCH",
+          headers: {
+            "upload-offset": String(offset),
+            "content-type": "application/offset+octet-stream",
+          },
+          body: file.slice(offset, offset + CHUNK_SIZE),
+        });
+        if (response.status === 404) localStorage.removeItem(key);
+        if (!response.ok && response.status !== 409) throw await responseError(response);
+        offset = Number(response.headers.get("upload-offset") ?? offset);
+        retries = 0;
+      } catch (error) {
+        if (++retries > MAX_RETRIES) throw error;
+        await wait(1000 * retries);
+        const resumed = await this.sessionOffset(sessionUrl);
+        if (resumed === null) throw error;
+        offset = resumed;
+      }
+    }
+    onProgress(file.size);
+
+    const finished = await fetch(`${sessionUrl}/finish`, { method: "POST" });
+    if (!finished.ok) throw await responseError(finished);
+    localStorage.removeItem(key);
+  }
+
+  async sessionOffset(sessionUrl) {
+    try {
+      const response = await fetch(sessionUrl, { method: "HEAD", cache: "no-store" });
+      if (!response.ok) return null;
+      return Number(response.headers.get("upload-offset"));
+    } catch {
+      return null;
+    }
+  }
+
   dispose() {
     this.element.replaceChildren();
   }